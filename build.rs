@@ -0,0 +1,18 @@
+//! Embeds `resources/Info.plist` into the niri binary on macOS.
+//!
+//! niri runs as a bare binary rather than from an app bundle, and macOS reads the Info.plist of
+//! such binaries from their `__info_plist` section. It declares that the GL contexts can run on
+//! the integrated GPU of dual-GPU Macs; without it, macOS switches to the discrete GPU for as long
+//! as niri has a context.
+
+fn main() {
+    println!("cargo:rerun-if-changed=resources/Info.plist");
+
+    if std::env::var("CARGO_CFG_TARGET_OS").as_deref() != Ok("macos") {
+        return;
+    }
+
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let plist = format!("{manifest_dir}/resources/Info.plist");
+    println!("cargo:rustc-link-arg-bins=-Wl,-sectcreate,__TEXT,__info_plist,{plist}");
+}
@@ -1,5 +1,9 @@
 use std::path::PathBuf;
+use std::str::FromStr;
 
+use miette::miette;
+
+use crate::appearance::Color;
 use crate::utils::{Flag, MergeWith};
 
 #[derive(Debug, Default, PartialEq)]
@@ -24,6 +28,20 @@ pub struct Debug {
     pub honor_xdg_activation_with_invalid_serial: bool,
     pub deactivate_unfocused_windows: bool,
     pub skip_cursor_only_updates_during_vrr: bool,
+    pub winit_disable_vsync: bool,
+    pub winit_transparent: bool,
+    pub winit_borderless: bool,
+    pub winit_window_title: Option<String>,
+    pub winit_window_size: Option<WinitWindowSize>,
+    pub winit_gpu: Option<WinitGpu>,
+    pub winit_msaa_samples: Option<u8>,
+    pub winit_key_debounce_ms: Option<u32>,
+    pub winit_dwt_timeout_ms: Option<u32>,
+    pub winit_system_shortcuts: Option<Vec<String>>,
+    pub winit_three_finger_drag: Option<WinitThreeFingerDrag>,
+    pub winit_movable_background: bool,
+    pub clear_color: Option<Color>,
+    pub screencast_capture_path: Option<PathBuf>,
 }
 
 #[derive(knuffel::Decode, Debug, Default, PartialEq)]
@@ -68,6 +86,34 @@ pub struct DebugPart {
     pub deactivate_unfocused_windows: Option<Flag>,
     #[knuffel(child)]
     pub skip_cursor_only_updates_during_vrr: Option<Flag>,
+    #[knuffel(child)]
+    pub winit_disable_vsync: Option<Flag>,
+    #[knuffel(child)]
+    pub winit_transparent: Option<Flag>,
+    #[knuffel(child)]
+    pub winit_borderless: Option<Flag>,
+    #[knuffel(child, unwrap(argument))]
+    pub winit_window_title: Option<String>,
+    #[knuffel(child, unwrap(argument, str))]
+    pub winit_window_size: Option<WinitWindowSize>,
+    #[knuffel(child, unwrap(argument))]
+    pub winit_gpu: Option<WinitGpu>,
+    #[knuffel(child, unwrap(argument))]
+    pub winit_msaa_samples: Option<u8>,
+    #[knuffel(child, unwrap(argument))]
+    pub winit_key_debounce_ms: Option<u32>,
+    #[knuffel(child, unwrap(argument))]
+    pub winit_dwt_timeout_ms: Option<u32>,
+    #[knuffel(child, unwrap(arguments))]
+    pub winit_system_shortcuts: Option<Vec<String>>,
+    #[knuffel(child, unwrap(argument))]
+    pub winit_three_finger_drag: Option<WinitThreeFingerDrag>,
+    #[knuffel(child)]
+    pub winit_movable_background: Option<Flag>,
+    #[knuffel(child)]
+    pub clear_color: Option<Color>,
+    #[knuffel(child, unwrap(argument))]
+    pub screencast_capture_path: Option<PathBuf>,
 }
 
 impl MergeWith<DebugPart> for Debug {
@@ -91,9 +137,27 @@ impl MergeWith<DebugPart> for Debug {
             honor_xdg_activation_with_invalid_serial,
             deactivate_unfocused_windows,
             skip_cursor_only_updates_during_vrr,
+            winit_disable_vsync,
+            winit_transparent,
+            winit_borderless,
+            winit_movable_background,
         );
 
-        merge_clone_opt!((self, part), preview_render, render_drm_device);
+        merge_clone_opt!(
+            (self, part),
+            preview_render,
+            render_drm_device,
+            winit_window_title,
+            winit_window_size,
+            winit_gpu,
+            winit_msaa_samples,
+            winit_key_debounce_ms,
+            winit_dwt_timeout_ms,
+            winit_system_shortcuts,
+            winit_three_finger_drag,
+            clear_color,
+            screencast_capture_path
+        );
 
         self.ignored_drm_devices
             .extend(part.ignored_drm_devices.iter().cloned());
@@ -105,3 +169,41 @@ pub enum PreviewRender {
     Screencast,
     ScreenCapture,
 }
+
+/// Size of the winit window in logical pixels, written as `WxH`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WinitWindowSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// GPU that the winit windows render on, on Macs with two.
+#[derive(knuffel::DecodeScalar, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WinitGpu {
+    /// Stay on the integrated GPU unless something else needs the discrete one.
+    Integrated,
+    /// Keep the discrete GPU on.
+    Discrete,
+}
+
+/// What a three-finger drag on the trackpad does in the winit backend.
+#[derive(knuffel::DecodeScalar, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WinitThreeFingerDrag {
+    /// Swipe like on a libinput touchpad, switching workspaces and scrolling the view.
+    Swipe,
+    /// Move the window under the cursor, like dragging it with Mod and the left button.
+    MoveWindow,
+}
+
+impl FromStr for WinitWindowSize {
+    type Err = miette::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let size = s.trim().split_once(['x', 'X']).and_then(|(width, height)| {
+            let width: u32 = width.parse().ok()?;
+            let height: u32 = height.parse().ok()?;
+            (width > 0 && height > 0).then_some(Self { width, height })
+        });
+        size.ok_or_else(|| miette!("invalid window size: {s}, expected WxH, like 1280x800"))
+    }
+}
@@ -90,6 +90,8 @@ pub struct Keyboard {
     pub xkb: Xkb,
     pub repeat_delay: u16,
     pub repeat_rate: u8,
+    /// Whether the system keyboard settings take the place of `repeat_delay` and `repeat_rate`.
+    pub repeat_follow_system: bool,
     pub track_layout: TrackLayout,
     pub numlock: bool,
 }
@@ -101,6 +103,7 @@ impl Default for Keyboard {
             // The defaults were chosen to match wlroots and sway.
             repeat_delay: 600,
             repeat_rate: 25,
+            repeat_follow_system: false,
             track_layout: Default::default(),
             numlock: Default::default(),
         }
@@ -115,6 +118,8 @@ pub struct KeyboardPart {
     pub repeat_delay: Option<u16>,
     #[knuffel(child, unwrap(argument))]
     pub repeat_rate: Option<u8>,
+    #[knuffel(child)]
+    pub repeat_follow_system: Option<Flag>,
     #[knuffel(child, unwrap(argument))]
     pub track_layout: Option<TrackLayout>,
     #[knuffel(child)]
@@ -124,7 +129,7 @@ pub struct KeyboardPart {
 impl MergeWith<KeyboardPart> for Keyboard {
     fn merge_with(&mut self, part: &KeyboardPart) {
         merge_clone!((self, part), xkb, repeat_delay, repeat_rate, track_layout);
-        merge!((self, part), numlock);
+        merge!((self, part), repeat_follow_system, numlock);
     }
 }
 
@@ -222,6 +227,8 @@ pub struct Touchpad {
     pub middle_emulation: bool,
     #[knuffel(child)]
     pub scroll_factor: Option<ScrollFactor>,
+    #[knuffel(child)]
+    pub no_scroll_momentum: bool,
 }
 
 #[derive(knuffel::Decode, Debug, Default, Clone, PartialEq)]
@@ -246,6 +253,8 @@ pub struct Mouse {
     pub middle_emulation: bool,
     #[knuffel(child)]
     pub scroll_factor: Option<ScrollFactor>,
+    #[knuffel(child)]
+    pub no_shift_horizontal_scroll: bool,
 }
 
 #[derive(knuffel::Decode, Debug, Default, Clone, PartialEq)]
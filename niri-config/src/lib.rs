@@ -578,6 +578,19 @@ mod tests {
         let config = Config::parse_mem("").unwrap();
         assert_eq!(config.input.keyboard.repeat_delay, 600);
         assert_eq!(config.input.keyboard.repeat_rate, 25);
+        assert!(!config.input.keyboard.repeat_follow_system);
+    }
+
+    #[test]
+    fn parse_winit_window_size() {
+        let config = do_parse(r#"debug { winit-window-size " 800X600 "; }"#);
+        let size = config.debug.winit_window_size.unwrap();
+        assert_eq!((size.width, size.height), (800, 600));
+
+        for size in ["1280", "0x600", "-1x600", "widexhigh"] {
+            let text = format!(r#"debug {{ winit-window-size "{size}"; }}"#);
+            assert!(Config::parse_mem(&text).is_err(), "{size} parsed");
+        }
     }
 
     #[track_caller]
@@ -595,6 +608,7 @@ mod tests {
                 keyboard {
                     repeat-delay 600
                     repeat-rate 25
+                    repeat-follow-system
                     track-layout "window"
                     xkb {
                         layout "us,ru"
@@ -913,6 +927,7 @@ mod tests {
                     },
                     repeat_delay: 600,
                     repeat_rate: 25,
+                    repeat_follow_system: true,
                     track_layout: Window,
                     numlock: false,
                 },
@@ -959,6 +974,7 @@ mod tests {
                             vertical: None,
                         },
                     ),
+                    no_scroll_momentum: false,
                 },
                 mouse: Mouse {
                     off: false,
@@ -989,6 +1005,7 @@ mod tests {
                             vertical: None,
                         },
                     ),
+                    no_shift_horizontal_scroll: false,
                 },
                 trackpoint: Trackpoint {
                     off: true,
@@ -2151,6 +2168,20 @@ mod tests {
                 honor_xdg_activation_with_invalid_serial: false,
                 deactivate_unfocused_windows: false,
                 skip_cursor_only_updates_during_vrr: false,
+                winit_disable_vsync: false,
+                winit_transparent: false,
+                winit_borderless: false,
+                winit_window_title: None,
+                winit_window_size: None,
+                winit_gpu: None,
+                winit_msaa_samples: None,
+                winit_key_debounce_ms: None,
+                winit_dwt_timeout_ms: None,
+                winit_system_shortcuts: None,
+                winit_three_finger_drag: None,
+                winit_movable_background: false,
+                clear_color: None,
+                screencast_capture_path: None,
             },
             workspaces: [
                 Workspace {
@@ -50,6 +50,7 @@
 //! - `json-schema`: derives the [schemars](https://lib.rs/crates/schemars) `JsonSchema` trait for
 //!   the types.
 //! - `clap`: derives the clap CLI parsing traits for some types. Used internally by niri itself.
+//! - `inject-input`: adds [`Request::InjectInput`] for driving niri with synthetic input in tests.
 #![warn(missing_docs)]
 
 use std::collections::HashMap;
@@ -117,6 +118,13 @@ pub enum Request {
     ReturnError,
     /// Request information about the overview.
     OverviewState,
+    /// Inject a synthetic input event, for scripted testing.
+    ///
+    /// niri handles it like input from a real device, then replies with
+    /// `Reply::Ok(Response::Handled)`. Only niri built with the `inject-input` feature accepts
+    /// this request.
+    #[cfg(feature = "inject-input")]
+    InjectInput(InjectedInput),
 }
 
 /// Reply from niri to client.
@@ -796,6 +804,12 @@ pub enum Action {
     DebugToggleOpaqueRegions {},
     /// Toggle visualization of output damage.
     DebugToggleDamage {},
+    /// Toggle the frame rate overlay of the compositor windows.
+    DebugToggleFpsOverlay {},
+    /// Recompile the built-in shaders from their source files.
+    DebugReloadShaders {},
+    /// Toggle native fullscreen of the compositor windows.
+    ToggleCompositorFullscreen {},
     /// Move the focused window between the floating and the tiling layout.
     ToggleWindowFloating {
         /// Id of the window to move.
@@ -1072,6 +1086,43 @@ pub enum OutputAction {
     },
 }
 
+/// Synthetic input event to inject.
+#[cfg(feature = "inject-input")]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "clap", derive(clap::Subcommand))]
+#[cfg_attr(feature = "clap", command(subcommand_value_name = "INPUT"))]
+#[cfg_attr(feature = "clap", command(subcommand_help_heading = "Inputs"))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub enum InjectedInput {
+    /// Press or release a key.
+    Key {
+        /// Evdev code of the key, like 30 for A.
+        #[cfg_attr(feature = "clap", arg())]
+        code: u32,
+        /// Whether the key goes down or up.
+        #[cfg_attr(feature = "clap", arg(action = clap::ArgAction::Set))]
+        pressed: bool,
+    },
+    /// Move the pointer to a position.
+    PointerMotion {
+        /// Logical X position in the global coordinate space.
+        #[cfg_attr(feature = "clap", arg(allow_hyphen_values = true))]
+        x: f64,
+        /// Logical Y position in the global coordinate space.
+        #[cfg_attr(feature = "clap", arg(allow_hyphen_values = true))]
+        y: f64,
+    },
+    /// Press or release a mouse button.
+    Button {
+        /// Evdev code of the button, like 272 (0x110) for the left button.
+        #[cfg_attr(feature = "clap", arg())]
+        code: u32,
+        /// Whether the button goes down or up.
+        #[cfg_attr(feature = "clap", arg(action = clap::ArgAction::Set))]
+        pressed: bool,
+    },
+}
+
 /// Output mode to set.
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 #[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
@@ -1172,7 +1223,7 @@ pub struct VrrToSet {
 }
 
 /// Connected output.
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Output {
     /// Name of the output.
@@ -1580,6 +1631,17 @@ pub enum Event {
         /// For example, the config file couldn't be parsed.
         failed: bool,
     },
+    /// The output configuration has changed.
+    ///
+    /// Sent when outputs are connected or disconnected, and when their modes, scale or logical
+    /// geometry change.
+    OutputsChanged {
+        /// The new output configuration, keyed by output name.
+        ///
+        /// This configuration completely replaces the previous configuration. I.e. if any outputs
+        /// are missing from here, then they were disconnected.
+        outputs: HashMap<String, Output>,
+    },
     /// A screenshot was captured.
     ScreenshotCaptured {
         /// The file path where the screenshot was saved, if it was written to disk.
@@ -1975,4 +2037,43 @@ mod tests {
         assert!("-".parse::<PositionChange>().is_err());
         assert!("10% ".parse::<PositionChange>().is_err());
     }
+
+    #[test]
+    fn outputs_changed_round_trip() {
+        let output = Output {
+            name: "Built-in Display".to_owned(),
+            make: "Apple".to_owned(),
+            model: "Color LCD".to_owned(),
+            serial: None,
+            physical_size: Some((302, 196)),
+            modes: vec![Mode {
+                width: 3024,
+                height: 1964,
+                refresh_rate: 120_000,
+                is_preferred: true,
+            }],
+            current_mode: Some(0),
+            is_custom_mode: false,
+            vrr_supported: false,
+            vrr_enabled: false,
+            logical: Some(LogicalOutput {
+                x: 0,
+                y: 0,
+                width: 1512,
+                height: 982,
+                scale: 2.,
+                transform: Transform::Normal,
+            }),
+        };
+        let event = Event::OutputsChanged {
+            outputs: HashMap::from([(output.name.clone(), output.clone())]),
+        };
+
+        let json = serde_json::to_string(&event).unwrap();
+        let Event::OutputsChanged { outputs } = serde_json::from_str(&json).unwrap() else {
+            panic!("expected OutputsChanged in {json}");
+        };
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs["Built-in Display"], output);
+    }
 }
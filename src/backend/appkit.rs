@@ -0,0 +1,1679 @@
+//! Small AppKit helpers for the winit backend.
+//!
+//! winit doesn't expose everything we need from Cocoa, so these talk to AppKit directly through
+//! the Objective-C runtime.
+
+use std::ffi::{c_char, c_void, CStr};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, Once};
+use std::time::Duration;
+
+use core_foundation::base::TCFType;
+use core_foundation::boolean::CFBoolean;
+use core_foundation::string::CFString;
+use core_foundation_sys::array::{CFArrayGetCount, CFArrayGetValueAtIndex, CFArrayRef};
+use core_foundation_sys::base::{CFRelease, CFTypeRef};
+use core_foundation_sys::dictionary::CFDictionaryRef;
+use core_foundation_sys::string::CFStringRef;
+use objc::declare::ClassDecl;
+use objc::runtime::{class_addMethod, object_getClass, Class, Imp, Object, Sel, BOOL, NO};
+use objc::{msg_send, sel, sel_impl};
+use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+use winit::platform::macos::MonitorHandleExtMacOS;
+use winit::window::Window;
+
+use crate::utils::get_monotonic_time;
+
+// NSTouchPhaseBegan | NSTouchPhaseMoved | NSTouchPhaseStationary.
+const NS_TOUCH_PHASE_TOUCHING: usize = 0b111;
+
+#[repr(C)]
+struct NSPoint {
+    x: f64,
+    y: f64,
+}
+
+/// Returns the `NSView` backing the window.
+pub fn ns_view(window: &Window) -> Option<*mut Object> {
+    let handle = window.window_handle().ok()?;
+    let RawWindowHandle::AppKit(handle) = handle.as_raw() else {
+        return None;
+    };
+    Some(handle.ns_view.as_ptr() as *mut Object)
+}
+
+/// Returns the `NSWindow` containing the window's view.
+pub fn ns_window(window: &Window) -> Option<*mut Object> {
+    let view = ns_view(window)?;
+    let ns_window: *mut Object = unsafe { msg_send![view, window] };
+    (!ns_window.is_null()).then_some(ns_window)
+}
+
+/// Returns the current `NSEvent.modifierFlags`, including the device-dependent bits.
+pub fn device_modifier_flags() -> Option<usize> {
+    let class = Class::get("NSEvent")?;
+    let flags: usize = unsafe { msg_send![class, modifierFlags] };
+    Some(flags)
+}
+
+/// Lets the window's view receive indirect (trackpad) touches.
+///
+/// AppKit only attaches touches to events for views that opt in, and we need them to count the
+/// fingers of a swipe.
+pub fn enable_trackpad_touches(window: &Window) {
+    const NS_TOUCH_TYPE_MASK_INDIRECT: usize = 1 << 1;
+
+    let Some(view) = ns_view(window) else {
+        return;
+    };
+
+    unsafe {
+        let _: () = msg_send![view, setAllowedTouchTypes: NS_TOUCH_TYPE_MASK_INDIRECT];
+    }
+}
+
+/// Returns the number of fingers currently resting on the trackpad for the event being handled.
+pub fn trackpad_touch_count() -> u32 {
+    let Some(event) = current_event() else {
+        return 0;
+    };
+
+    unsafe {
+        let view: *mut Object = std::ptr::null_mut();
+        let touches: *mut Object =
+            msg_send![event, touchesMatchingPhase: NS_TOUCH_PHASE_TOUCHING inView: view];
+        if touches.is_null() {
+            return 0;
+        }
+
+        let count: usize = msg_send![touches, count];
+        count as u32
+    }
+}
+
+/// Longest a touch can last to count as a tap.
+const TAP_TIMEOUT: Duration = Duration::from_millis(180);
+/// How far the fingers can move during a tap, in normalized trackpad coordinates.
+const TAP_MOVE_THRESHOLD: f64 = 0.02;
+/// How long after a tap macOS can send its own click for it.
+const SYSTEM_TAP_CLICK_DELAY: Duration = Duration::from_millis(300);
+
+/// Tells taps apart from the other touches of the trackpad.
+///
+/// A tap is a touch that ends quickly, without the fingers moving and without a click.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct TapDetector {
+    /// When the first finger of the touch came down.
+    start: Option<Duration>,
+    /// Most fingers down at once during the touch.
+    fingers: u32,
+    /// Fingers down at the last update.
+    last_count: u32,
+    /// Center of the fingers when their number last changed.
+    origin: (f64, f64),
+    /// Whether the touch moved or clicked, so it is no tap.
+    cancelled: bool,
+}
+
+impl TapDetector {
+    pub const fn new() -> Self {
+        Self {
+            start: None,
+            fingers: 0,
+            last_count: 0,
+            origin: (0., 0.),
+            cancelled: false,
+        }
+    }
+
+    /// Takes the number of fingers on the trackpad at `time`, and where their center is.
+    ///
+    /// Returns the number of fingers that tapped when the last of them lifts at the end of a tap.
+    pub fn update(&mut self, count: u32, center: (f64, f64), time: Duration) -> Option<u32> {
+        if count == 0 {
+            let start = self.start?;
+            let tap = !self.cancelled && time.saturating_sub(start) <= TAP_TIMEOUT;
+            let fingers = self.fingers;
+            *self = Self::new();
+            return tap.then_some(fingers);
+        }
+
+        if self.start.is_none() {
+            self.start = Some(time);
+        }
+
+        // Fingers coming down or lifting one by one move the center, which is no movement.
+        if count != self.last_count {
+            self.last_count = count;
+            self.fingers = self.fingers.max(count);
+            self.origin = center;
+        } else {
+            let (dx, dy) = (center.0 - self.origin.0, center.1 - self.origin.1);
+            if dx.hypot(dy) > TAP_MOVE_THRESHOLD {
+                self.cancelled = true;
+            }
+        }
+
+        None
+    }
+
+    /// Makes the touch in progress no tap, like when the trackpad clicked during it.
+    pub fn cancel(&mut self) {
+        if self.start.is_some() {
+            self.cancelled = true;
+        }
+    }
+}
+
+static TAP_DETECTOR: Mutex<TapDetector> = Mutex::new(TapDetector::new());
+/// Fingers of each tap since the last call to [`take_trackpad_taps()`].
+static TRACKPAD_TAPS: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+/// When the last tap happened, on the clock of the `NSEvent` timestamps.
+static LAST_TAP: Mutex<Option<Duration>> = Mutex::new(None);
+/// Set while the release of a dropped macOS tap click is still to come.
+static SYSTEM_TAP_CLICK_HELD: AtomicBool = AtomicBool::new(false);
+
+/// Watches the trackpad for taps, see [`take_trackpad_taps()`].
+///
+/// The clicks that the macOS <kbd>Tap to click</kbd> setting makes out of taps are dropped, so
+/// that the niri `tap` setting alone decides what taps do. Call it after creating the winit event
+/// loop.
+pub fn track_trackpad_taps() {
+    // NSEventTypeLeftMouseDown, NSEventTypeRightMouseDown and NSEventTypeOtherMouseDown.
+    const MOUSE_DOWN_TYPES: [usize; 3] = [1, 3, 25];
+    // The matching NSEventTypes for the releases.
+    const MOUSE_UP_TYPES: [usize; 3] = [2, 4, 26];
+    // NSEventTypeGesture, which carries the touches.
+    const GESTURE_TYPE: usize = 29;
+
+    extern "C" fn handle_event(_block: *mut GlobalBlock, event: *mut Object) -> *mut Object {
+        unsafe {
+            let ty: usize = msg_send![event, type];
+            let timestamp: f64 = msg_send![event, timestamp];
+            let time = Duration::try_from_secs_f64(timestamp.max(0.)).unwrap_or_default();
+            let (count, center) = touching_fingers(event);
+
+            if ty == GESTURE_TYPE {
+                let tap = TAP_DETECTOR.lock().unwrap().update(count, center, time);
+                if let Some(fingers) = tap {
+                    TRACKPAD_TAPS.lock().unwrap().push(fingers);
+                    *LAST_TAP.lock().unwrap() = Some(time);
+                }
+                return event;
+            }
+
+            if MOUSE_UP_TYPES.contains(&ty) {
+                if SYSTEM_TAP_CLICK_HELD.swap(false, Ordering::Relaxed) {
+                    return std::ptr::null_mut();
+                }
+                return event;
+            }
+
+            if !MOUSE_DOWN_TYPES.contains(&ty) {
+                return event;
+            }
+
+            // A click with the fingers down is a real one.
+            if count > 0 {
+                TAP_DETECTOR.lock().unwrap().cancel();
+                return event;
+            }
+
+            let last_tap = LAST_TAP.lock().unwrap().take();
+            let after_tap =
+                last_tap.is_some_and(|tap| time.saturating_sub(tap) <= SYSTEM_TAP_CLICK_DELAY);
+            if after_tap && system_tap_to_click_enabled() {
+                SYSTEM_TAP_CLICK_HELD.store(true, Ordering::Relaxed);
+                return std::ptr::null_mut();
+            }
+        }
+        event
+    }
+
+    static REGISTER: Once = Once::new();
+    REGISTER.call_once(|| unsafe {
+        let Some(class) = Class::get("NSEvent") else {
+            return;
+        };
+
+        let mask = MOUSE_DOWN_TYPES
+            .iter()
+            .chain(&MOUSE_UP_TYPES)
+            .chain(&[GESTURE_TYPE])
+            .fold(0u64, |mask, ty| mask | 1 << ty);
+
+        // AppKit keeps the monitor, and the block, for as long as the app runs.
+        let block = Box::leak(Box::new(GlobalBlock::new(handle_event)));
+        let block = block as *mut GlobalBlock as *mut c_void;
+        let monitor: *mut Object =
+            msg_send![class, addLocalMonitorForEventsMatchingMask: mask handler: block];
+        if monitor.is_null() {
+            warn!("error adding the trackpad tap monitor");
+        }
+    });
+}
+
+/// Returns the number of fingers of each trackpad tap since the last call.
+pub fn take_trackpad_taps() -> Vec<u32> {
+    std::mem::take(&mut *TRACKPAD_TAPS.lock().unwrap())
+}
+
+/// Returns the number of fingers touching the trackpad in `event`, and where their center is in
+/// normalized trackpad coordinates.
+unsafe fn touching_fingers(event: *mut Object) -> (u32, (f64, f64)) {
+    let view: *mut Object = std::ptr::null_mut();
+    let touches: *mut Object =
+        msg_send![event, touchesMatchingPhase: NS_TOUCH_PHASE_TOUCHING inView: view];
+    if touches.is_null() {
+        return (0, (0., 0.));
+    }
+
+    let touches: *mut Object = msg_send![touches, allObjects];
+    let count: usize = msg_send![touches, count];
+    if count == 0 {
+        return (0, (0., 0.));
+    }
+
+    let mut center = (0., 0.);
+    for i in 0..count {
+        let touch: *mut Object = msg_send![touches, objectAtIndex: i];
+        let position: NSPoint = msg_send![touch, normalizedPosition];
+        center.0 += position.x / count as f64;
+        center.1 += position.y / count as f64;
+    }
+    (count as u32, center)
+}
+
+/// Returns whether <kbd>Tap to click</kbd> is on in the macOS trackpad settings.
+fn system_tap_to_click_enabled() -> bool {
+    let Some(class) = Class::get("NSUserDefaults") else {
+        return false;
+    };
+
+    unsafe {
+        let defaults: *mut Object = msg_send![class, standardUserDefaults];
+        let key = ns_string("com.apple.mouse.tapBehavior");
+        let behavior: isize = msg_send![defaults, integerForKey: key];
+        behavior == 1
+    }
+}
+
+/// Returns whether the deltas of the scroll event being handled are inverted by natural scrolling.
+pub fn current_event_direction_inverted() -> bool {
+    let Some(event) = current_event() else {
+        return false;
+    };
+
+    let inverted: BOOL = unsafe { msg_send![event, isDirectionInvertedFromDevice] };
+    inverted != NO
+}
+
+/// Where the scroll event being handled is in a scroll gesture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrollPhase {
+    /// Not part of a gesture, like events from a mouse wheel.
+    None,
+    /// Fingers are on the trackpad or the Magic Mouse.
+    Touching,
+    /// The fingers lifted and macOS keeps scrolling with momentum.
+    Momentum,
+}
+
+/// Returns the gesture phase of the scroll event being handled.
+pub fn current_event_scroll_phase() -> ScrollPhase {
+    let Some(event) = current_event() else {
+        return ScrollPhase::None;
+    };
+
+    unsafe {
+        // Regular mouse wheels have no gestures.
+        let precise: BOOL = msg_send![event, hasPreciseScrollingDeltas];
+        if precise == NO {
+            return ScrollPhase::None;
+        }
+
+        // NSEventPhaseNone is 0 for both.
+        let phase: usize = msg_send![event, phase];
+        let momentum_phase: usize = msg_send![event, momentumPhase];
+        if phase != 0 {
+            ScrollPhase::Touching
+        } else if momentum_phase != 0 {
+            ScrollPhase::Momentum
+        } else {
+            ScrollPhase::None
+        }
+    }
+}
+
+/// Returns where the mouse event being handled happened, in points from the bottom left of its
+/// window.
+pub fn current_event_location_in_window() -> Option<(f64, f64)> {
+    let event = current_event()?;
+    let location: NSPoint = unsafe { msg_send![event, locationInWindow] };
+    Some((location.x, location.y))
+}
+
+/// Kind of tool on a graphics tablet, from `NSEvent.pointingDeviceType`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TabletToolKind {
+    Pen,
+    Eraser,
+    /// A puck or a tablet mouse.
+    Cursor,
+}
+
+/// Tool that came close to a graphics tablet, see [`track_tablet_proximity()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TabletTool {
+    pub kind: TabletToolKind,
+    /// `NSEvent.uniqueID`, the serial number of the tool.
+    pub serial: u64,
+    /// `NSEvent.vendorPointingDeviceType`, the model of the tool.
+    pub hardware_id: u64,
+}
+
+/// Pen state of a mouse event that came from a graphics tablet.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TabletPoint {
+    pub tool: TabletTool,
+    /// From 0 to 1.
+    pub pressure: f64,
+    /// In degrees from upright, positive to the right and towards the user.
+    pub tilt: (f64, f64),
+}
+
+/// Tool that last entered proximity, see [`track_tablet_proximity()`].
+static TABLET_TOOL: Mutex<Option<TabletTool>> = Mutex::new(None);
+/// Set when a tool leaves proximity, see [`take_tablet_left_proximity()`].
+static TABLET_LEFT_PROXIMITY: AtomicBool = AtomicBool::new(false);
+
+/// Watches tablet tools entering and leaving proximity.
+///
+/// winit doesn't handle the proximity events, and only they tell what the tool is. Call it after
+/// creating the winit event loop.
+pub fn track_tablet_proximity() {
+    // NSEventMaskTabletProximity.
+    const TABLET_PROXIMITY_MASK: u64 = 1 << 24;
+
+    extern "C" fn handle_proximity(_block: *mut GlobalBlock, event: *mut Object) -> *mut Object {
+        unsafe {
+            let entering: BOOL = msg_send![event, isEnteringProximity];
+            if entering == NO {
+                TABLET_LEFT_PROXIMITY.store(true, Ordering::Relaxed);
+                return event;
+            }
+
+            let kind: usize = msg_send![event, pointingDeviceType];
+            let serial: u64 = msg_send![event, uniqueID];
+            let hardware_id: usize = msg_send![event, vendorPointingDeviceType];
+            *TABLET_TOOL.lock().unwrap() = Some(TabletTool {
+                kind: tablet_tool_kind(kind),
+                serial,
+                hardware_id: hardware_id as u64,
+            });
+        }
+        event
+    }
+
+    static REGISTER: Once = Once::new();
+    REGISTER.call_once(|| unsafe {
+        let Some(class) = Class::get("NSEvent") else {
+            return;
+        };
+
+        // AppKit keeps the monitor, and the block, for as long as the app runs.
+        let block = Box::leak(Box::new(GlobalBlock::new(handle_proximity)));
+        let block = block as *mut GlobalBlock as *mut c_void;
+        let monitor: *mut Object = msg_send![
+            class,
+            addLocalMonitorForEventsMatchingMask: TABLET_PROXIMITY_MASK
+            handler: block
+        ];
+        if monitor.is_null() {
+            warn!("error adding the tablet proximity monitor");
+        }
+    });
+}
+
+/// Returns whether a tablet tool left proximity since the last call.
+pub fn take_tablet_left_proximity() -> bool {
+    TABLET_LEFT_PROXIMITY.swap(false, Ordering::Relaxed)
+}
+
+/// Returns the pen state of the mouse event being handled, or `None` if a mouse or trackpad
+/// generated it rather than a graphics tablet.
+pub fn current_event_tablet_point() -> Option<TabletPoint> {
+    // NSEventTypeLeftMouseDown to NSEventTypeRightMouseDragged, and the NSEventTypeOtherMouse
+    // ones. Only mouse events have a subtype.
+    const MOUSE_EVENT_TYPES: [usize; 10] = [1, 2, 3, 4, 5, 6, 7, 25, 26, 27];
+    // NSEventSubtypeTabletPoint.
+    const SUBTYPE_TABLET_POINT: i16 = 1;
+
+    let event = current_event()?;
+
+    unsafe {
+        let ty: usize = msg_send![event, type];
+        if !MOUSE_EVENT_TYPES.contains(&ty) {
+            return None;
+        }
+        let subtype: i16 = msg_send![event, subtype];
+        if subtype != SUBTYPE_TABLET_POINT {
+            return None;
+        }
+
+        let pressure: f32 = msg_send![event, pressure];
+        let tilt: NSPoint = msg_send![event, tilt];
+
+        // A tool that was already close when niri started never entered proximity.
+        let tool = TABLET_TOOL.lock().unwrap().unwrap_or(TabletTool {
+            kind: TabletToolKind::Pen,
+            serial: 0,
+            hardware_id: 0,
+        });
+        Some(TabletPoint {
+            tool,
+            pressure: f64::from(pressure).clamp(0., 1.),
+            tilt: tilt_degrees(tilt.x, tilt.y),
+        })
+    }
+}
+
+/// Converts an `NSPointingDeviceType` into a tool kind.
+fn tablet_tool_kind(kind: usize) -> TabletToolKind {
+    match kind {
+        2 => TabletToolKind::Cursor,
+        3 => TabletToolKind::Eraser,
+        // NSPointingDeviceTypePen, and unknown tools, which are most likely pens too.
+        _ => TabletToolKind::Pen,
+    }
+}
+
+/// Converts an `NSEvent.tilt`, from -1 to 1 with positive y towards the top of the tablet, into
+/// degrees with positive y towards the user.
+fn tilt_degrees(x: f64, y: f64) -> (f64, f64) {
+    (x.clamp(-1., 1.) * 90., -y.clamp(-1., 1.) * 90.)
+}
+
+/// Returns when the event being handled happened, on the clock of [`get_monotonic_time`].
+pub fn current_event_time() -> Option<Duration> {
+    let event = current_event()?;
+    let class = Class::get("NSProcessInfo")?;
+
+    unsafe {
+        // Both count seconds since the Mac started, without the time it spent asleep.
+        let timestamp: f64 = msg_send![event, timestamp];
+        let info: *mut Object = msg_send![class, processInfo];
+        let uptime: f64 = msg_send![info, systemUptime];
+        monotonic_event_time(timestamp, uptime, get_monotonic_time())
+    }
+}
+
+/// Moves an `NSEvent` timestamp over to the monotonic clock, going by how long before `uptime`
+/// the event happened.
+fn monotonic_event_time(timestamp: f64, uptime: f64, now: Duration) -> Option<Duration> {
+    let age = Duration::try_from_secs_f64((uptime - timestamp).max(0.)).ok()?;
+    Some(now.saturating_sub(age))
+}
+
+/// Returns whether natural scrolling is enabled in System Settings.
+pub fn natural_scroll_enabled() -> bool {
+    let Some(class) = Class::get("NSUserDefaults") else {
+        return true;
+    };
+
+    unsafe {
+        let defaults: *mut Object = msg_send![class, standardUserDefaults];
+        let key = ns_string("com.apple.swipescrolldirection");
+        let value: *mut Object = msg_send![defaults, objectForKey: key];
+        // Natural scrolling is the default when the user never changed the setting.
+        if value.is_null() {
+            return true;
+        }
+
+        let enabled: BOOL = msg_send![value, boolValue];
+        enabled != NO
+    }
+}
+
+/// Key repeat settings from the macOS keyboard settings.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct KeyRepeat {
+    /// Delay before keys start repeating, in milliseconds.
+    pub delay: Option<u16>,
+    /// Repeats per second, zero when key repeat is off.
+    pub rate: Option<u8>,
+}
+
+/// Length in milliseconds of the unit that `InitialKeyRepeat` and `KeyRepeat` are in.
+const KEY_REPEAT_TICK_MS: f64 = 15.;
+
+static KEY_REPEAT: Mutex<KeyRepeat> = Mutex::new(KeyRepeat {
+    delay: None,
+    rate: None,
+});
+
+/// Returns the key repeat settings as of the last [`refresh_key_repeat()`].
+///
+/// Settings that the user never changed are missing, just like before the first refresh.
+pub fn key_repeat() -> KeyRepeat {
+    *KEY_REPEAT.lock().unwrap()
+}
+
+/// Reads the key repeat settings again, returning whether they changed.
+pub fn refresh_key_repeat() -> bool {
+    let Some(class) = Class::get("NSUserDefaults") else {
+        return false;
+    };
+
+    let (initial, interval) = unsafe {
+        let defaults: *mut Object = msg_send![class, standardUserDefaults];
+        let read = |key: &str| {
+            let value: *mut Object = msg_send![defaults, objectForKey: ns_string(key)];
+            if value.is_null() {
+                return None;
+            }
+
+            let value: f64 = msg_send![value, doubleValue];
+            Some(value)
+        };
+        (read("InitialKeyRepeat"), read("KeyRepeat"))
+    };
+
+    let key_repeat = key_repeat_from_ticks(initial, interval);
+    let mut cached = KEY_REPEAT.lock().unwrap();
+    if *cached == key_repeat {
+        return false;
+    }
+
+    debug!("macOS key repeat settings: {key_repeat:?}");
+    *cached = key_repeat;
+    true
+}
+
+fn key_repeat_from_ticks(initial: Option<f64>, interval: Option<f64>) -> KeyRepeat {
+    let delay = initial.map(|ticks| ticks * KEY_REPEAT_TICK_MS);
+
+    // Turning key repeat off in System Settings sets a delay of more than an hour.
+    if delay.is_some_and(|delay| delay > f64::from(u16::MAX)) {
+        return KeyRepeat {
+            delay: None,
+            rate: Some(0),
+        };
+    }
+
+    let rate = interval
+        .filter(|ticks| *ticks > 0.)
+        .map(|ticks| (1000. / (ticks * KEY_REPEAT_TICK_MS)).round().clamp(1., 255.) as u8);
+
+    KeyRepeat {
+        delay: delay.map(|delay| delay.round().max(0.) as u16),
+        rate,
+    }
+}
+
+/// Returns whether the keyboard that was typed on last has the ISO layout, with the extra key
+/// right of the left Shift.
+pub fn keyboard_is_iso() -> bool {
+    // kKeyboardISO from HIToolbox.
+    const KEYBOARD_ISO: u32 = u32::from_be_bytes(*b"ISO ");
+
+    #[link(name = "Carbon", kind = "framework")]
+    extern "C" {
+        fn LMGetKbdType() -> u8;
+        fn KBGetLayoutType(keyboard_type: i16) -> u32;
+    }
+
+    unsafe { KBGetLayoutType(i16::from(LMGetKbdType())) == KEYBOARD_ISO }
+}
+
+/// Returns the refresh rate in mHz of the screen currently showing the window.
+pub fn screen_refresh_rate(window: &Window) -> Option<i32> {
+    let ns_window = ns_window(window)?;
+
+    unsafe {
+        let screen: *mut Object = msg_send![ns_window, screen];
+        if screen.is_null() {
+            return None;
+        }
+
+        // Available since macOS 12.
+        let responds: BOOL = msg_send![screen, respondsToSelector: sel!(maximumFramesPerSecond)];
+        if responds == NO {
+            return None;
+        }
+
+        let fps: isize = msg_send![screen, maximumFramesPerSecond];
+        if fps <= 0 {
+            return None;
+        }
+
+        // The shortest frame duration is exact for rates like 59.94 Hz, while the frame count is
+        // rounded to whole Hz.
+        let responds: BOOL = msg_send![screen, respondsToSelector: sel!(minimumRefreshInterval)];
+        if responds != NO {
+            let interval: f64 = msg_send![screen, minimumRefreshInterval];
+            if interval > 0. {
+                return Some((1000. / interval).round() as i32);
+            }
+        }
+
+        Some(fps as i32 * 1000)
+    }
+}
+
+/// Returns the refresh rates in mHz that the display showing the window offers at its current
+/// resolution, fastest first.
+///
+/// Built-in panels often report no rates here, so the list can be empty.
+pub fn display_refresh_rates(window: &Window) -> Vec<i32> {
+    type CGDisplayModeRef = *mut c_void;
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGDisplayCopyDisplayMode(display: u32) -> CGDisplayModeRef;
+        fn CGDisplayCopyAllDisplayModes(display: u32, options: CFDictionaryRef) -> CFArrayRef;
+        fn CGDisplayModeGetPixelWidth(mode: CGDisplayModeRef) -> usize;
+        fn CGDisplayModeGetPixelHeight(mode: CGDisplayModeRef) -> usize;
+        fn CGDisplayModeGetRefreshRate(mode: CGDisplayModeRef) -> f64;
+        fn CGDisplayModeRelease(mode: CGDisplayModeRef);
+    }
+
+    let Some(display) = window.current_monitor().map(|monitor| monitor.native_id()) else {
+        return Vec::new();
+    };
+
+    let mut rates = Vec::new();
+    unsafe {
+        let current = CGDisplayCopyDisplayMode(display);
+        if current.is_null() {
+            return rates;
+        }
+        let width = CGDisplayModeGetPixelWidth(current);
+        let height = CGDisplayModeGetPixelHeight(current);
+        CGDisplayModeRelease(current);
+
+        let modes = CGDisplayCopyAllDisplayModes(display, std::ptr::null());
+        if modes.is_null() {
+            return rates;
+        }
+
+        for i in 0..CFArrayGetCount(modes) {
+            let mode = CFArrayGetValueAtIndex(modes, i) as CGDisplayModeRef;
+            if CGDisplayModeGetPixelWidth(mode) != width
+                || CGDisplayModeGetPixelHeight(mode) != height
+            {
+                continue;
+            }
+
+            let rate = (CGDisplayModeGetRefreshRate(mode) * 1000.).round() as i32;
+            if rate > 0 {
+                rates.push(rate);
+            }
+        }
+
+        CFRelease(modes.cast());
+    }
+
+    rates.sort_unstable_by(|a, b| b.cmp(a));
+    rates.dedup();
+    rates
+}
+
+/// Returns whether the screen showing the window can vary its refresh rate, like ProMotion
+/// displays do.
+pub fn screen_supports_vrr(window: &Window) -> bool {
+    let Some(ns_window) = ns_window(window) else {
+        return false;
+    };
+
+    unsafe {
+        let screen: *mut Object = msg_send![ns_window, screen];
+        if screen.is_null() {
+            return false;
+        }
+
+        // Available since macOS 12.
+        let responds: BOOL = msg_send![screen, respondsToSelector: sel!(minimumRefreshInterval)];
+        if responds == NO {
+            return false;
+        }
+
+        let fps: isize = msg_send![screen, maximumFramesPerSecond];
+        // Fixed rate screens refresh at the same interval at both ends.
+        let min_interval: f64 = msg_send![screen, minimumRefreshInterval];
+        let max_interval: f64 = msg_send![screen, maximumRefreshInterval];
+        fps > 60 && min_interval < max_interval
+    }
+}
+
+/// Sets the app name that the menu bar, the Dock and Activity Monitor show.
+///
+/// Without a bundle, macOS falls back to the process name, so set both.
+pub fn set_app_name(name: &str) {
+    unsafe {
+        if let Some(class) = Class::get("NSProcessInfo") {
+            let info: *mut Object = msg_send![class, processInfo];
+            let _: () = msg_send![info, setProcessName: ns_string(name)];
+        }
+
+        let Some(class) = Class::get("NSBundle") else {
+            return;
+        };
+        let bundle: *mut Object = msg_send![class, mainBundle];
+        let dict: *mut Object = msg_send![bundle, infoDictionary];
+        // The dictionary is mutable in practice, but check rather than crash if it isn't.
+        let responds: BOOL = msg_send![dict, respondsToSelector: sel!(setObject:forKey:)];
+        if dict.is_null() || responds == NO {
+            return;
+        }
+        let _: () = msg_send![dict, setObject: ns_string(name) forKey: ns_string("CFBundleName")];
+    }
+}
+
+/// Sets up the menu bar: the app menu with About, Hide and Quit, and an Edit menu.
+///
+/// The items send the standard selectors. Quit sends `terminate:`, which
+/// [`intercept_terminate()`] turns into the same clean quit as closing the window. The Edit items
+/// go to the first responder, and since winit's view doesn't implement them, AppKit disables them
+/// and their shortcuts reach the Wayland clients like before.
+pub fn install_menu_bar(app_name: &str) {
+    let Some(app) = shared_application() else {
+        return;
+    };
+    let (Some(menu_class), Some(item_class)) = (Class::get("NSMenu"), Class::get("NSMenuItem"))
+    else {
+        return;
+    };
+
+    unsafe {
+        let new_menu = |title: &str| -> *mut Object {
+            let menu: *mut Object = msg_send![menu_class, alloc];
+            let menu: *mut Object = msg_send![menu, initWithTitle: ns_string(title)];
+            msg_send![menu, autorelease]
+        };
+        // Key equivalents come with Cmd, and uppercase letters add Shift.
+        let add_item = |menu: *mut Object, title: &str, action: Sel, key: &str| {
+            let item: *mut Object = msg_send![item_class, alloc];
+            let item: *mut Object = msg_send![item,
+                initWithTitle: ns_string(title)
+                action: action
+                keyEquivalent: ns_string(key)];
+            let _: () = msg_send![menu, addItem: item];
+            let _: () = msg_send![item, release];
+        };
+        let add_separator = |menu: *mut Object| {
+            let item: *mut Object = msg_send![item_class, separatorItem];
+            let _: () = msg_send![menu, addItem: item];
+        };
+        let add_submenu = |menu_bar: *mut Object, submenu: *mut Object| {
+            let item: *mut Object = msg_send![item_class, new];
+            let _: () = msg_send![item, setSubmenu: submenu];
+            let _: () = msg_send![menu_bar, addItem: item];
+            let _: () = msg_send![item, release];
+        };
+
+        let app_menu = new_menu(app_name);
+        add_item(
+            app_menu,
+            &format!("About {app_name}"),
+            sel!(orderFrontStandardAboutPanel:),
+            "",
+        );
+        add_separator(app_menu);
+        add_item(app_menu, &format!("Hide {app_name}"), sel!(hide:), "h");
+        add_separator(app_menu);
+        add_item(app_menu, &format!("Quit {app_name}"), sel!(terminate:), "q");
+
+        let edit_menu = new_menu("Edit");
+        add_item(edit_menu, "Undo", sel!(undo:), "z");
+        add_item(edit_menu, "Redo", sel!(redo:), "Z");
+        add_separator(edit_menu);
+        add_item(edit_menu, "Cut", sel!(cut:), "x");
+        add_item(edit_menu, "Copy", sel!(copy:), "c");
+        add_item(edit_menu, "Paste", sel!(paste:), "v");
+        add_item(edit_menu, "Select All", sel!(selectAll:), "a");
+
+        let menu_bar = new_menu("");
+        add_submenu(menu_bar, app_menu);
+        add_submenu(menu_bar, edit_menu);
+        let _: () = msg_send![app, setMainMenu: menu_bar];
+    }
+}
+
+/// Hides the app, giving the focus to the next one like Cmd+H does.
+pub fn hide_app() {
+    let Some(app) = shared_application() else {
+        return;
+    };
+
+    unsafe {
+        let sender: *mut Object = std::ptr::null_mut();
+        let _: () = msg_send![app, hide: sender];
+    }
+}
+
+/// Shows the app again after `hide_app()` and brings it to the front.
+pub fn unhide_app() {
+    let Some(app) = shared_application() else {
+        return;
+    };
+
+    unsafe {
+        let sender: *mut Object = std::ptr::null_mut();
+        let _: () = msg_send![app, unhide: sender];
+        let _: () = msg_send![app, activateIgnoringOtherApps: true];
+    }
+}
+
+static SYSTEM_ASLEEP: AtomicBool = AtomicBool::new(false);
+static SCREENS_ASLEEP: AtomicBool = AtomicBool::new(false);
+
+/// Starts tracking system and display sleep for [`is_asleep()`].
+pub fn observe_sleep() {
+    static REGISTER: Once = Once::new();
+    REGISTER.call_once(|| unsafe { register_sleep_observer() });
+}
+
+/// Returns whether the system or the displays are asleep.
+pub fn is_asleep() -> bool {
+    SYSTEM_ASLEEP.load(Ordering::Relaxed) || SCREENS_ASLEEP.load(Ordering::Relaxed)
+}
+
+unsafe fn register_sleep_observer() {
+    extern "C" fn system_will_sleep(_this: &Object, _cmd: Sel, _notification: *mut Object) {
+        SYSTEM_ASLEEP.store(true, Ordering::Relaxed);
+    }
+    extern "C" fn system_did_wake(_this: &Object, _cmd: Sel, _notification: *mut Object) {
+        SYSTEM_ASLEEP.store(false, Ordering::Relaxed);
+    }
+    extern "C" fn screens_did_sleep(_this: &Object, _cmd: Sel, _notification: *mut Object) {
+        SCREENS_ASLEEP.store(true, Ordering::Relaxed);
+    }
+    extern "C" fn screens_did_wake(_this: &Object, _cmd: Sel, _notification: *mut Object) {
+        SCREENS_ASLEEP.store(false, Ordering::Relaxed);
+    }
+
+    type Callback = extern "C" fn(&Object, Sel, *mut Object);
+
+    let Some(superclass) = Class::get("NSObject") else {
+        return;
+    };
+    let Some(mut decl) = ClassDecl::new("NiriSleepObserver", superclass) else {
+        return;
+    };
+    decl.add_method(sel!(systemWillSleep:), system_will_sleep as Callback);
+    decl.add_method(sel!(systemDidWake:), system_did_wake as Callback);
+    decl.add_method(sel!(screensDidSleep:), screens_did_sleep as Callback);
+    decl.add_method(sel!(screensDidWake:), screens_did_wake as Callback);
+    let class = decl.register();
+
+    let Some(workspace) = Class::get("NSWorkspace") else {
+        return;
+    };
+    let workspace: *mut Object = msg_send![workspace, sharedWorkspace];
+    let center: *mut Object = msg_send![workspace, notificationCenter];
+
+    // The observer lives for as long as the process.
+    let observer: *mut Object = msg_send![class, new];
+    let object: *mut Object = std::ptr::null_mut();
+    for (name, selector) in [
+        ("NSWorkspaceWillSleepNotification", sel!(systemWillSleep:)),
+        ("NSWorkspaceDidWakeNotification", sel!(systemDidWake:)),
+        ("NSWorkspaceScreensDidSleepNotification", sel!(screensDidSleep:)),
+        ("NSWorkspaceScreensDidWakeNotification", sel!(screensDidWake:)),
+    ] {
+        let name = ns_string(name);
+        let _: () = msg_send![center, addObserver: observer selector: selector name: name object: object];
+    }
+}
+
+static DISPLAYS_CHANGED: AtomicBool = AtomicBool::new(false);
+static SCREEN_PARAMETERS_CHANGED: AtomicBool = AtomicBool::new(false);
+
+/// Starts tracking displays getting connected and disconnected for [`take_displays_changed()`],
+/// and any change to the screens for [`take_screen_parameters_changed()`].
+pub fn observe_displays() {
+    type ReconfigurationCallback = extern "C" fn(display: u32, flags: u32, user_info: *mut c_void);
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGDisplayRegisterReconfigurationCallback(
+            callback: ReconfigurationCallback,
+            user_info: *mut c_void,
+        ) -> i32;
+    }
+
+    // kCGDisplayAddFlag, kCGDisplayRemoveFlag, kCGDisplayEnabledFlag, kCGDisplayDisabledFlag.
+    const CONNECTION_FLAGS: u32 = (1 << 4) | (1 << 5) | (1 << 8) | (1 << 9);
+
+    extern "C" fn reconfigured(_display: u32, flags: u32, _user_info: *mut c_void) {
+        if flags & CONNECTION_FLAGS != 0 {
+            DISPLAYS_CHANGED.store(true, Ordering::Relaxed);
+        }
+    }
+
+    static REGISTER: Once = Once::new();
+    REGISTER.call_once(|| unsafe {
+        let err = CGDisplayRegisterReconfigurationCallback(reconfigured, std::ptr::null_mut());
+        if err != 0 {
+            warn!("error registering the display reconfiguration callback: {err}");
+        }
+        register_screen_parameters_observer();
+    });
+}
+
+/// Returns whether a display was connected or disconnected since the last call.
+pub fn take_displays_changed() -> bool {
+    DISPLAYS_CHANGED.swap(false, Ordering::Relaxed)
+}
+
+/// Returns whether AppKit reported changed screens since the last call.
+///
+/// Besides displays coming and going, this covers resolution and arrangement changes, and the
+/// GPU switches that can come with them.
+pub fn take_screen_parameters_changed() -> bool {
+    SCREEN_PARAMETERS_CHANGED.swap(false, Ordering::Relaxed)
+}
+
+unsafe fn register_screen_parameters_observer() {
+    extern "C" fn screen_parameters_changed(_this: &Object, _cmd: Sel, _notification: *mut Object) {
+        SCREEN_PARAMETERS_CHANGED.store(true, Ordering::Relaxed);
+    }
+
+    type Callback = extern "C" fn(&Object, Sel, *mut Object);
+
+    let Some(superclass) = Class::get("NSObject") else {
+        return;
+    };
+    let Some(mut decl) = ClassDecl::new("NiriScreenParametersObserver", superclass) else {
+        return;
+    };
+    decl.add_method(sel!(screenParametersChanged:), screen_parameters_changed as Callback);
+    let class = decl.register();
+
+    let Some(center) = Class::get("NSNotificationCenter") else {
+        return;
+    };
+    let center: *mut Object = msg_send![center, defaultCenter];
+
+    // The observer lives for as long as the process.
+    let observer: *mut Object = msg_send![class, new];
+    let object: *mut Object = std::ptr::null_mut();
+    let name = ns_string("NSApplicationDidChangeScreenParametersNotification");
+    let _: () = msg_send![center, addObserver: observer selector: sel!(screenParametersChanged:) name: name object: object];
+}
+
+static QUIT_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Suspended quit Apple Event, answered by [`reply_to_quit()`], 0 when there is none.
+static SUSPENDED_QUIT_EVENT: AtomicUsize = AtomicUsize::new(0);
+
+/// Routes Cmd-Q, the Quit menu item, quitting from the Dock and logging out through
+/// [`take_quit_requested()`].
+///
+/// AppKit's `terminate:` otherwise exits the process on the spot, so Wayland clients never see
+/// the compositor go away. This adds `applicationShouldTerminate:` and a quit Apple Event handler
+/// to winit's application delegate, so call it after creating the winit event loop, which sets
+/// the delegate.
+///
+/// macOS quits apps from the Dock, and on logout, restart and shutdown, with a quit Apple Event.
+/// The handler suspends it, so macOS waits while the compositor quits on its own, and
+/// [`reply_to_quit()`] lets macOS go on afterwards. Cmd-Q and the Quit menu item call
+/// `terminate:` directly, and nothing waits for those, so the delegate cancels the termination.
+/// `terminate:` would wait for an `NSTerminateLater` reply in a modal run loop, inside winit's
+/// event pump, which the compositor only gets to reply from after that pump returns.
+pub fn intercept_terminate() {
+    // NSTerminateCancel.
+    const TERMINATE_CANCEL: usize = 0;
+    // kCoreEventClass and kAEQuitApplication.
+    const CORE_EVENT_CLASS: u32 = u32::from_be_bytes(*b"aevt");
+    const QUIT_APPLICATION: u32 = u32::from_be_bytes(*b"quit");
+
+    extern "C" fn should_terminate(_this: &Object, _cmd: Sel, _sender: *mut Object) -> usize {
+        QUIT_REQUESTED.store(true, Ordering::Relaxed);
+        TERMINATE_CANCEL
+    }
+
+    extern "C" fn handle_quit_event(
+        _this: &Object,
+        _cmd: Sel,
+        _event: *mut Object,
+        _reply: *mut Object,
+    ) {
+        unsafe {
+            let Some(manager) = apple_event_manager() else {
+                return;
+            };
+            let id: *mut c_void = msg_send![manager, suspendCurrentAppleEvent];
+            // A second quit event while the first waits has nothing more to ask for.
+            let previous = SUSPENDED_QUIT_EVENT.swap(id as usize, Ordering::Relaxed);
+            if previous != 0 {
+                let previous = previous as *mut c_void;
+                let _: () = msg_send![manager, resumeWithSuspensionID: previous];
+            }
+        }
+        QUIT_REQUESTED.store(true, Ordering::Relaxed);
+    }
+
+    static REGISTER: Once = Once::new();
+    REGISTER.call_once(|| unsafe {
+        let Some(app) = shared_application() else {
+            return;
+        };
+        let delegate: *mut Object = msg_send![app, delegate];
+        if delegate.is_null() {
+            warn!("no application delegate, Cmd-Q quits without disconnecting clients");
+            return;
+        }
+
+        type Callback = extern "C" fn(&Object, Sel, *mut Object) -> usize;
+        let imp: Imp = std::mem::transmute(should_terminate as Callback);
+        let class = object_getClass(delegate) as *mut Class;
+        // NSUInteger return, then self, _cmd and the sender.
+        let types = c"Q@:@";
+        if class_addMethod(class, sel!(applicationShouldTerminate:), imp, types.as_ptr()) == NO {
+            warn!("the application delegate already handles termination, not intercepting it");
+        }
+
+        type QuitCallback = extern "C" fn(&Object, Sel, *mut Object, *mut Object);
+        let imp: Imp = std::mem::transmute(handle_quit_event as QuitCallback);
+        let sel = sel!(handleQuitEvent:withReplyEvent:);
+        // No return, then self, _cmd, the event and the reply.
+        let types = c"v@:@@";
+        if class_addMethod(class, sel, imp, types.as_ptr()) == NO {
+            warn!("error adding the quit event handler, logout skips disconnecting clients");
+            return;
+        }
+        let Some(manager) = apple_event_manager() else {
+            return;
+        };
+        let _: () = msg_send![manager,
+            setEventHandler: delegate
+            andSelector: sel
+            forEventClass: CORE_EVENT_CLASS
+            andEventID: QUIT_APPLICATION];
+    });
+}
+
+/// Returns whether macOS asked the app to quit since the last call.
+pub fn take_quit_requested() -> bool {
+    QUIT_REQUESTED.swap(false, Ordering::Relaxed)
+}
+
+/// Answers the quit Apple Event that macOS waits on, if any, once the compositor quit.
+///
+/// This lets a logout, restart or shutdown go on.
+pub fn reply_to_quit() {
+    let id = SUSPENDED_QUIT_EVENT.swap(0, Ordering::Relaxed);
+    if id == 0 {
+        return;
+    }
+
+    unsafe {
+        let Some(manager) = apple_event_manager() else {
+            return;
+        };
+        let _: () = msg_send![manager, resumeWithSuspensionID: id as *mut c_void];
+    }
+}
+
+// NSEventModifierFlags.
+const MODIFIER_SHIFT: usize = 1 << 17;
+const MODIFIER_CONTROL: usize = 1 << 18;
+const MODIFIER_OPTION: usize = 1 << 19;
+const MODIFIER_COMMAND: usize = 1 << 20;
+const MODIFIER_MASK: usize = MODIFIER_SHIFT | MODIFIER_CONTROL | MODIFIER_OPTION | MODIFIER_COMMAND;
+
+/// A key combination that macOS keeps handling, see [`intercept_key_equivalents()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SystemShortcut {
+    /// `NSEventModifierFlags` bits of the modifiers.
+    modifiers: usize,
+    /// Character of the key without modifiers, lowercase.
+    key: String,
+}
+
+impl SystemShortcut {
+    /// Parses a shortcut like `Cmd+Q` or `Ctrl+Shift+Tab`.
+    ///
+    /// Keys are single characters, or `Tab`, `Escape`, `Space`, `Return` and `Grave`.
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut parts = s.split('+').map(str::trim);
+        let key = parts.next_back()?;
+        let mut modifiers = 0;
+        for part in parts {
+            modifiers |= match &*part.to_ascii_lowercase() {
+                "cmd" | "command" | "super" => MODIFIER_COMMAND,
+                "ctrl" | "control" => MODIFIER_CONTROL,
+                "alt" | "option" => MODIFIER_OPTION,
+                "shift" => MODIFIER_SHIFT,
+                _ => return None,
+            };
+        }
+
+        let key = match &*key.to_ascii_lowercase() {
+            "tab" => "\t".to_owned(),
+            "escape" => "\u{1b}".to_owned(),
+            "space" => " ".to_owned(),
+            "return" => "\r".to_owned(),
+            "grave" => "`".to_owned(),
+            key if key.chars().count() == 1 => key.to_owned(),
+            _ => return None,
+        };
+
+        Some(Self { modifiers, key })
+    }
+
+    fn matches(&self, modifiers: usize, key: &str) -> bool {
+        self.modifiers == modifiers & MODIFIER_MASK && self.key == key.to_lowercase()
+    }
+}
+
+static SYSTEM_SHORTCUTS: Mutex<Vec<SystemShortcut>> = Mutex::new(Vec::new());
+
+/// Sets the shortcuts that [`intercept_key_equivalents()`] leaves to macOS.
+pub fn set_system_shortcuts(shortcuts: Vec<SystemShortcut>) {
+    *SYSTEM_SHORTCUTS.lock().unwrap() = shortcuts;
+}
+
+/// Returns whether a key down goes to the view rather than through the key equivalents.
+///
+/// AppKit offers keys with Cmd or Ctrl to the menu bar and the window first, and those claim
+/// some of them, like the menu items and Ctrl+Tab, which moves between the controls of a window.
+/// Escape can close or leave full screen. Other keys go to the view anyway.
+fn intercepts_key(modifiers: usize, key: &str, system_shortcuts: &[SystemShortcut]) -> bool {
+    let is_equivalent = modifiers & (MODIFIER_COMMAND | MODIFIER_CONTROL) != 0 || key == "\u{1b}";
+    is_equivalent && !system_shortcuts.iter().any(|s| s.matches(modifiers, key))
+}
+
+/// Sends key downs with Cmd or Ctrl, and Escape, straight to winit's view.
+///
+/// That way they reach the Wayland clients and niri bindings instead of the menu bar, except for
+/// the shortcuts from [`set_system_shortcuts()`]. Shortcuts that the system handles before the
+/// app sees them, like Cmd+Tab and Cmd+Space, are out of reach. Call it after creating the winit
+/// event loop.
+pub fn intercept_key_equivalents() {
+    // NSEventMaskKeyDown.
+    const KEY_DOWN_MASK: u64 = 1 << 10;
+
+    extern "C" fn handle_key_down(_block: *mut GlobalBlock, event: *mut Object) -> *mut Object {
+        unsafe {
+            let modifiers: usize = msg_send![event, modifierFlags];
+            let chars: *mut Object = msg_send![event, charactersIgnoringModifiers];
+            let Some(key) = rust_string(chars) else {
+                return event;
+            };
+            if !intercepts_key(modifiers, &key, &SYSTEM_SHORTCUTS.lock().unwrap()) {
+                return event;
+            }
+
+            let window: *mut Object = msg_send![event, window];
+            if window.is_null() {
+                return event;
+            }
+            let responder: *mut Object = msg_send![window, firstResponder];
+            if responder.is_null() {
+                return event;
+            }
+            let _: () = msg_send![responder, keyDown: event];
+            std::ptr::null_mut()
+        }
+    }
+
+    static REGISTER: Once = Once::new();
+    REGISTER.call_once(|| unsafe {
+        let Some(class) = Class::get("NSEvent") else {
+            return;
+        };
+
+        // AppKit keeps the monitor, and the block, for as long as the app runs.
+        let block = Box::leak(Box::new(GlobalBlock::new(handle_key_down)));
+        let block = block as *mut GlobalBlock as *mut c_void;
+        let monitor: *mut Object =
+            msg_send![class, addLocalMonitorForEventsMatchingMask: KEY_DOWN_MASK handler: block];
+        if monitor.is_null() {
+            warn!("error adding the key event monitor, the menu bar keeps its shortcuts");
+        }
+    });
+}
+
+extern "C" {
+    static _NSConcreteGlobalBlock: [*const c_void; 32];
+}
+
+#[repr(C)]
+struct BlockDescriptor {
+    reserved: usize,
+    size: usize,
+}
+
+static GLOBAL_BLOCK_DESCRIPTOR: BlockDescriptor = BlockDescriptor {
+    reserved: 0,
+    size: std::mem::size_of::<GlobalBlock>(),
+};
+
+/// Objective-C block without captured variables, taking and returning an object.
+///
+/// AppKit handlers that only touch statics need nothing more, which spares a dependency on a
+/// block crate.
+#[repr(C)]
+struct GlobalBlock {
+    isa: *const c_void,
+    flags: i32,
+    reserved: i32,
+    invoke: extern "C" fn(*mut GlobalBlock, *mut Object) -> *mut Object,
+    descriptor: *const BlockDescriptor,
+}
+
+impl GlobalBlock {
+    fn new(invoke: extern "C" fn(*mut GlobalBlock, *mut Object) -> *mut Object) -> Self {
+        // BLOCK_IS_GLOBAL.
+        const IS_GLOBAL: i32 = 1 << 28;
+
+        Self {
+            isa: unsafe { _NSConcreteGlobalBlock.as_ptr().cast() },
+            flags: IS_GLOBAL,
+            reserved: 0,
+            invoke,
+            descriptor: &GLOBAL_BLOCK_DESCRIPTOR,
+        }
+    }
+}
+
+/// Copies an `NSString` into a Rust string.
+pub(super) unsafe fn rust_string(string: *mut Object) -> Option<String> {
+    if string.is_null() {
+        return None;
+    }
+    let chars: *const c_char = msg_send![string, UTF8String];
+    if chars.is_null() {
+        return None;
+    }
+    Some(CStr::from_ptr(chars).to_string_lossy().into_owned())
+}
+
+/// Puts the displays to sleep right away, like the display sleep hot corner does.
+pub fn sleep_displays() -> Result<(), String> {
+    #[link(name = "IOKit", kind = "framework")]
+    extern "C" {
+        fn IORegistryEntryFromPath(main_port: u32, path: *const c_char) -> u32;
+        fn IORegistryEntrySetCFProperty(entry: u32, name: CFStringRef, value: CFTypeRef) -> i32;
+        fn IOObjectRelease(object: u32) -> i32;
+    }
+
+    unsafe {
+        // kIOMainPortDefault is MACH_PORT_NULL.
+        let path = c"IOService:/IOResources/IODisplayWrangler";
+        let wrangler = IORegistryEntryFromPath(0, path.as_ptr());
+        if wrangler == 0 {
+            return Err("IODisplayWrangler not found".to_owned());
+        }
+
+        let name = CFString::from_static_string("IORequestIdle");
+        let res = IORegistryEntrySetCFProperty(
+            wrangler,
+            name.as_concrete_TypeRef(),
+            CFBoolean::true_value().as_CFTypeRef(),
+        );
+        IOObjectRelease(wrangler);
+
+        if res != 0 {
+            return Err(format!("error requesting display sleep: 0x{res:x}"));
+        }
+    }
+
+    Ok(())
+}
+
+#[link(name = "IOKit", kind = "framework")]
+extern "C" {
+    fn IOPMAssertionCreateWithName(
+        assertion_type: CFStringRef,
+        level: u32,
+        name: CFStringRef,
+        id: *mut u32,
+    ) -> i32;
+    fn IOPMAssertionRelease(id: u32) -> i32;
+}
+
+/// Power assertion keeping the displays from going to sleep on their own.
+///
+/// Dropping it releases the assertion and lets the OS blank the displays again.
+pub struct DisplaySleepAssertion {
+    id: u32,
+}
+
+impl DisplaySleepAssertion {
+    pub fn new(reason: &str) -> Result<Self, String> {
+        // kIOPMAssertionLevelOn.
+        const LEVEL_ON: u32 = 255;
+
+        let assertion_type = CFString::from_static_string("PreventUserIdleDisplaySleep");
+        let name = CFString::new(reason);
+        let mut id = 0;
+        let res = unsafe {
+            IOPMAssertionCreateWithName(
+                assertion_type.as_concrete_TypeRef(),
+                LEVEL_ON,
+                name.as_concrete_TypeRef(),
+                &mut id,
+            )
+        };
+
+        if res != 0 {
+            return Err(format!("error creating power assertion: 0x{res:x}"));
+        }
+
+        Ok(Self { id })
+    }
+}
+
+impl Drop for DisplaySleepAssertion {
+    fn drop(&mut self) {
+        unsafe {
+            IOPMAssertionRelease(self.id);
+        }
+    }
+}
+
+type CGLPixelFormatObj = *mut c_void;
+
+#[link(name = "OpenGL", kind = "framework")]
+extern "C" {
+    fn CGLChoosePixelFormat(
+        attribs: *const u32,
+        pix: *mut CGLPixelFormatObj,
+        npix: *mut i32,
+    ) -> i32;
+    fn CGLReleasePixelFormat(pix: CGLPixelFormatObj);
+}
+
+/// Keeps the discrete GPU of dual-GPU Macs on while alive.
+///
+/// A pixel format that doesn't allow offline renderers makes macOS switch to the discrete GPU,
+/// and stay there until the pixel format is gone.
+pub struct DiscreteGpuPin {
+    pixel_format: CGLPixelFormatObj,
+}
+
+impl DiscreteGpuPin {
+    pub fn new() -> Result<Self, String> {
+        // kCGLPFAAccelerated, without kCGLPFAAllowOfflineRenderers.
+        let attribs = [73, 0];
+        let mut pixel_format = std::ptr::null_mut();
+        let mut count = 0;
+        let err = unsafe { CGLChoosePixelFormat(attribs.as_ptr(), &mut pixel_format, &mut count) };
+        if err != 0 || pixel_format.is_null() {
+            return Err(format!("error choosing a pixel format: {err}"));
+        }
+
+        Ok(Self { pixel_format })
+    }
+}
+
+impl Drop for DiscreteGpuPin {
+    fn drop(&mut self) {
+        unsafe {
+            CGLReleasePixelFormat(self.pixel_format);
+        }
+    }
+}
+
+/// Returns the physical size in millimeters of the display showing the window.
+///
+/// Returns `None` when the display doesn't report it, like AirPlay and some projectors do.
+pub fn display_size_mm(window: &Window) -> Option<(i32, i32)> {
+    #[repr(C)]
+    struct CGSize {
+        width: f64,
+        height: f64,
+    }
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGDisplayScreenSize(display: u32) -> CGSize;
+    }
+
+    let display = window.current_monitor()?.native_id();
+    let size = unsafe { CGDisplayScreenSize(display) };
+
+    let (w, h) = (size.width.round() as i32, size.height.round() as i32);
+    (w > 0 && h > 0).then_some((w, h))
+}
+
+/// Sets whether the cursor follows the mouse.
+///
+/// The mouse keeps reporting its motion either way, which is how games lock the pointer.
+pub fn set_cursor_follows_mouse(follows: bool) {
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGAssociateMouseAndMouseCursorPosition(connected: u32) -> i32;
+    }
+
+    let err = unsafe { CGAssociateMouseAndMouseCursorPosition(u32::from(follows)) };
+    if err != 0 {
+        warn!("error setting whether the cursor follows the mouse: {err}");
+    }
+}
+
+/// Moves the cursor to a point in the global display space, then sets whether it follows the
+/// mouse like [`set_cursor_follows_mouse`].
+///
+/// The point is in points from the top left of the main display. Moving the cursor this way sends
+/// no mouse events, and setting the association right after also ends the quarter second that
+/// macOS holds mouse events back after a warp.
+pub fn warp_cursor(x: f64, y: f64, follows_mouse: bool) {
+    #[repr(C)]
+    struct CGPoint {
+        x: f64,
+        y: f64,
+    }
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGWarpMouseCursorPosition(point: CGPoint) -> i32;
+    }
+
+    let err = unsafe { CGWarpMouseCursorPosition(CGPoint { x, y }) };
+    if err != 0 {
+        warn!("error moving the cursor: {err}");
+    }
+
+    set_cursor_follows_mouse(follows_mouse);
+}
+
+/// Returns where the cursor is in the global display space, in points from the top left of the
+/// main display like for [`warp_cursor`].
+///
+/// Unlike mouse events, this keeps up while another app drags something over the windows.
+pub fn cursor_location() -> Option<(f64, f64)> {
+    #[repr(C)]
+    struct CGPoint {
+        x: f64,
+        y: f64,
+    }
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGEventCreate(source: *mut c_void) -> *mut c_void;
+        fn CGEventGetLocation(event: *mut c_void) -> CGPoint;
+    }
+
+    unsafe {
+        let event = CGEventCreate(std::ptr::null_mut());
+        if event.is_null() {
+            return None;
+        }
+        let location = CGEventGetLocation(event);
+        CFRelease(event as CFTypeRef);
+        Some((location.x, location.y))
+    }
+}
+
+/// Returns whether we run in a window server session, where windows can open.
+///
+/// Processes started over SSH or by launch daemons have no session, and winit panics or fails to
+/// create windows there.
+pub fn has_gui_session() -> bool {
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGSessionCopyCurrentDictionary() -> CFDictionaryRef;
+    }
+
+    let session = unsafe { CGSessionCopyCurrentDictionary() };
+    if session.is_null() {
+        return false;
+    }
+
+    unsafe { CFRelease(session as CFTypeRef) };
+    true
+}
+
+/// Creates an autoreleased `NSString`.
+pub(super) unsafe fn ns_string(s: &str) -> *mut Object {
+    let class = Class::get("NSString").unwrap();
+    let bytes = s.as_ptr();
+    let len = s.len();
+    // NSUTF8StringEncoding.
+    let encoding: usize = 4;
+    let string: *mut Object = msg_send![class, alloc];
+    let string: *mut Object =
+        msg_send![string, initWithBytes: bytes length: len encoding: encoding];
+    msg_send![string, autorelease]
+}
+
+/// Returns the event that AppKit is currently dispatching.
+fn current_event() -> Option<*mut Object> {
+    let app = shared_application()?;
+
+    unsafe {
+        let event: *mut Object = msg_send![app, currentEvent];
+        (!event.is_null()).then_some(event)
+    }
+}
+
+fn shared_application() -> Option<*mut Object> {
+    let class = Class::get("NSApplication")?;
+    let app: *mut Object = unsafe { msg_send![class, sharedApplication] };
+    (!app.is_null()).then_some(app)
+}
+
+fn apple_event_manager() -> Option<*mut Object> {
+    let class = Class::get("NSAppleEventManager")?;
+    let manager: *mut Object = unsafe { msg_send![class, sharedAppleEventManager] };
+    (!manager.is_null()).then_some(manager)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_equivalents_skip_system_shortcuts() {
+        let cmd_q = SystemShortcut::parse("Cmd+Q").unwrap();
+        assert_eq!(
+            SystemShortcut::parse("command + shift + tab"),
+            Some(SystemShortcut {
+                modifiers: MODIFIER_COMMAND | MODIFIER_SHIFT,
+                key: "\t".to_owned(),
+            })
+        );
+        assert_eq!(SystemShortcut::parse("Hyper+Q"), None);
+        assert_eq!(SystemShortcut::parse("Cmd+PageUp"), None);
+
+        let system = [cmd_q];
+        // Caps Lock and the device-dependent bits don't matter.
+        assert!(!intercepts_key(MODIFIER_COMMAND | (1 << 16) | 0b1000, "q", &system));
+        assert!(intercepts_key(MODIFIER_COMMAND | MODIFIER_SHIFT, "Q", &system));
+        assert!(intercepts_key(MODIFIER_COMMAND, "w", &system));
+        assert!(intercepts_key(MODIFIER_CONTROL | MODIFIER_SHIFT, "\t", &system));
+        assert!(intercepts_key(0, "\u{1b}", &system));
+        assert!(!intercepts_key(0, "\t", &system));
+        assert!(!intercepts_key(MODIFIER_SHIFT, "A", &system));
+    }
+
+    #[test]
+    fn taps_are_quick_and_still() {
+        let ms = Duration::from_millis;
+        let mut detector = TapDetector::new();
+
+        // One finger down and up.
+        assert_eq!(detector.update(1, (0.5, 0.5), ms(1000)), None);
+        assert_eq!(detector.update(0, (0., 0.), ms(1080)), Some(1));
+
+        // Two fingers landing and lifting one after the other.
+        assert_eq!(detector.update(1, (0.4, 0.5), ms(2000)), None);
+        assert_eq!(detector.update(2, (0.45, 0.5), ms(2010)), None);
+        assert_eq!(detector.update(1, (0.5, 0.5), ms(2090)), None);
+        assert_eq!(detector.update(0, (0., 0.), ms(2100)), Some(2));
+
+        // Resting too long.
+        detector.update(3, (0.5, 0.5), ms(3000));
+        assert_eq!(detector.update(0, (0., 0.), ms(3500)), None);
+
+        // Moving.
+        detector.update(1, (0.5, 0.5), ms(4000));
+        detector.update(1, (0.6, 0.5), ms(4050));
+        assert_eq!(detector.update(0, (0., 0.), ms(4100)), None);
+
+        // Clicking.
+        detector.update(1, (0.5, 0.5), ms(5000));
+        detector.cancel();
+        assert_eq!(detector.update(0, (0., 0.), ms(5100)), None);
+
+        // The next touch starts over.
+        detector.update(1, (0.5, 0.5), ms(6000));
+        assert_eq!(detector.update(0, (0., 0.), ms(6050)), Some(1));
+    }
+
+    #[test]
+    fn tablet_tool_kinds_and_tilt() {
+        assert_eq!(tablet_tool_kind(0), TabletToolKind::Pen);
+        assert_eq!(tablet_tool_kind(1), TabletToolKind::Pen);
+        assert_eq!(tablet_tool_kind(2), TabletToolKind::Cursor);
+        assert_eq!(tablet_tool_kind(3), TabletToolKind::Eraser);
+
+        assert_eq!(tilt_degrees(0., 0.), (0., 0.));
+        assert_eq!(tilt_degrees(0.5, 1.), (45., -90.));
+        assert_eq!(tilt_degrees(-2., -0.5), (-90., 45.));
+    }
+
+    #[test]
+    fn event_time_from_uptime() {
+        let now = Duration::from_secs(1000);
+        assert_eq!(
+            monotonic_event_time(50., 50.25, now),
+            Some(Duration::from_millis(999_750))
+        );
+        // Rounding can put the event a bit after the uptime.
+        assert_eq!(monotonic_event_time(50.001, 50., now), Some(now));
+        assert_eq!(monotonic_event_time(0., 2000., now), Some(Duration::ZERO));
+        assert_eq!(monotonic_event_time(f64::NEG_INFINITY, 50., now), None);
+    }
+
+    #[test]
+    fn key_repeat_from_system_ticks() {
+        assert_eq!(key_repeat_from_ticks(None, None), KeyRepeat::default());
+        assert_eq!(
+            key_repeat_from_ticks(Some(15.), Some(2.)),
+            KeyRepeat {
+                delay: Some(225),
+                rate: Some(33),
+            }
+        );
+        assert_eq!(
+            key_repeat_from_ticks(None, Some(1.)),
+            KeyRepeat {
+                delay: None,
+                rate: Some(67),
+            }
+        );
+        assert_eq!(
+            key_repeat_from_ticks(Some(300_000.), Some(2.)),
+            KeyRepeat {
+                delay: None,
+                rate: Some(0),
+            }
+        );
+    }
+}
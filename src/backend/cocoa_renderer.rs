@@ -1,100 +1,147 @@
 use std::num::NonZeroU32;
+
 use glutin::config::{ConfigTemplateBuilder, GetGlConfig};
 use glutin::context::{ContextAttributesBuilder, PossiblyCurrentContext};
-use glutin::display::GetGlDisplay;
+use glutin::display::{Display, DisplayApiPreference, GetGlDisplay};
+use glutin::error::ErrorKind;
 use glutin::prelude::*;
 use glutin::surface::{Surface, SwapInterval, WindowSurface};
 use glutin_winit::{DisplayBuilder, GlWindow};
-use raw_window_handle::HasWindowHandle;
-use winit::window::Window;
+use raw_window_handle::{AppKitDisplayHandle, HasWindowHandle, RawDisplayHandle};
+use smithay::backend::allocator::Fourcc;
+use smithay::backend::renderer::gles::{ffi, GlesRenderer, GlesTexture};
+use smithay::backend::renderer::ImportMem;
+use winit::monitor::MonitorHandle;
+use winit::window::{Window, WindowAttributes};
 
 pub struct GlRenderer {
-    pub window: Window,
     pub gl_context: PossiblyCurrentContext,
     pub gl_surface: Surface<WindowSurface>,
+    /// Dropped after the GL surface, which draws into its view.
+    pub window: Window,
     pub width: u32,
     pub height: u32,
+    gl: ffi::Gles2,
+    vsync: bool,
 }
 
 impl GlRenderer {
-    pub fn new(event_loop: &winit::event_loop::EventLoop<()>, title: &str, width: u32, height: u32) -> Result<Self, String> {
-        let template = ConfigTemplateBuilder::new()
-            .with_alpha_size(8)
-            .with_transparency(false);
-            
+    /// Creates the main window.
+    ///
+    /// Without a `size` in logical pixels, the window starts out maximized. The GL config has no
+    /// multisampling unless `msaa_samples` asks for it.
+    ///
+    /// A `transparent` window shows the desktop wherever its alpha is below one. winit makes the
+    /// `NSWindow` non-opaque with a clear background, and glutin sets the surface opacity of
+    /// contexts with a transparent config to zero.
+    ///
+    /// A `borderless` window has no title bar and no traffic light buttons. It can still be
+    /// resized from its edges and go fullscreen.
+    pub fn new(
+        event_loop: &winit::event_loop::EventLoop<()>,
+        title: &str,
+        size: Option<(u32, u32)>,
+        msaa_samples: u8,
+        transparent: bool,
+        borderless: bool,
+    ) -> Result<Self, String> {
+        let (width, height) = size.unwrap_or((1600, 1000));
         let window_attributes = Window::default_attributes()
             .with_title(title)
-            .with_transparent(false)
+            .with_transparent(transparent)
+            .with_decorations(!borderless)
             .with_visible(true) // Explicitly force visibility
             .with_inner_size(winit::dpi::LogicalSize::new(width as f64, height as f64));
-            
+
+        Self::with_attributes(
+            event_loop,
+            window_attributes,
+            None,
+            size.is_none(),
+            msaa_samples,
+            transparent,
+        )
+    }
+
+    /// Creates a window on `monitor` whose context shares textures and buffers with `share`.
+    ///
+    /// Sharing lets a single `GlesRenderer` draw into every window. The window gets the same
+    /// multisampling as `share`, and should get the same `transparent` as the window of `share`.
+    pub fn new_on_monitor(
+        event_loop: &winit::event_loop::EventLoop<()>,
+        title: &str,
+        monitor: &MonitorHandle,
+        share: &PossiblyCurrentContext,
+        transparent: bool,
+        borderless: bool,
+    ) -> Result<Self, String> {
+        let window_attributes = Window::default_attributes()
+            .with_title(title)
+            .with_transparent(transparent)
+            .with_decorations(!borderless)
+            .with_visible(true)
+            .with_position(monitor.position())
+            .with_inner_size(monitor.size());
+
+        let msaa_samples = share.config().num_samples();
+        Self::with_attributes(
+            event_loop,
+            window_attributes,
+            Some(share),
+            true,
+            msaa_samples,
+            transparent,
+        )
+    }
+
+    fn with_attributes(
+        event_loop: &winit::event_loop::EventLoop<()>,
+        window_attributes: WindowAttributes,
+        share: Option<&PossiblyCurrentContext>,
+        maximize: bool,
+        msaa_samples: u8,
+        transparent: bool,
+    ) -> Result<Self, String> {
+        let mut template = ConfigTemplateBuilder::new()
+            .with_alpha_size(8)
+            .with_transparency(transparent);
+        if msaa_samples > 0 {
+            template = template.with_multisampling(msaa_samples);
+        }
+
         let display_builder = DisplayBuilder::new().with_window_attributes(Some(window_attributes));
         
         let (window, gl_config) = display_builder
             .build(event_loop, template, |configs| {
                 configs
-                    .reduce(|accum, config| {
-                        if config.num_samples() > accum.num_samples() {
-                            config
-                        } else {
-                            accum
-                        }
+                    .min_by_key(|config| {
+                        config_rank(
+                            config.num_samples(),
+                            config.alpha_size(),
+                            config.depth_size(),
+                            config.stencil_size(),
+                            msaa_samples,
+                        )
                     })
                     .unwrap()
             })
             .map_err(|e| format!("Failed to build display: {:?}", e))?;
             
         let window = window.ok_or("No window created")?;
-        let raw_window_handle = window.window_handle().map_err(|e| format!("Window handle error: {}", e))?.as_raw();
-        let gl_display = gl_config.display();
-        
-        let context_attributes = ContextAttributesBuilder::new().build(Some(raw_window_handle));
-        let not_current_context = unsafe {
-            gl_display
-                .create_context(&gl_config, &context_attributes)
-                .map_err(|e| format!("Failed to create context: {:?}", e))?
-        };
-        
-        let attrs = window.build_surface_attributes(Default::default()).expect("Failed to build surface attributes");
-        let gl_surface = unsafe {
-            gl_display
-                .create_window_surface(&gl_config, &attrs)
-            .unwrap()
-        };
-        
-        let gl_context = not_current_context
-            .make_current(&gl_surface)
-            .map_err(|e| format!("Failed to make current: {:?}", e))?;
-
-        // CORE PROFILE HACK: Generate and Bind a Dummy VAO.
-        // Without this, glDrawArrays fails silently on macOS Core Profile (3.2+).
-        unsafe {
-             use smithay::backend::renderer::gles::ffi;
-             let gl = ffi::Gles2::load_with(|s| {
-                gl_display.get_proc_address(&std::ffi::CString::new(s).unwrap()) as *const _
-             });
-             let mut vao = 0;
-             gl.GenVertexArrays(1, &mut vao);
-             gl.BindVertexArray(vao);
-             println!("DEBUG: Core Profile VAO Hack active. VAO: {}", vao);
-        }
-
-        if let Err(e) = gl_surface.set_swap_interval(&gl_context, SwapInterval::DontWait) {
-            log::warn!("Error setting vsync: {:?}", e);
-        }
+        let (gl_context, gl_surface, gl) = create_context(&window, &gl_config, share)?;
 
         window.set_visible(true);
         // window.request_redraw(); // Optional, but usually Niri handles this.
 
         window.set_visible(true);
-        window.set_cursor_visible(true); // User wants OS cursor visible
-        println!("DEBUG: Forced cursor visibility to TRUE");
         window.focus_window();
-        window.set_maximized(true);
+        if maximize {
+            window.set_maximized(true);
+        }
         
         let size = window.inner_size();
         let pos = window.outer_position().unwrap_or(winit::dpi::PhysicalPosition::new(0, 0));
-        println!("DEBUG: Window created at {:?} with size {:?}", pos, size);
+        log::debug!("window created at {pos:?} with size {size:?}");
 
         Ok(Self {
             window,
@@ -102,9 +149,103 @@ impl GlRenderer {
             gl_surface,
             width: size.width,
             height: size.height,
+            gl,
+            vsync: false,
+        })
+    }
+
+    /// Creates a replacement for the GL context and surface of the window, after the old context
+    /// was lost.
+    ///
+    /// The new context has the same config and shares with `share` like in
+    /// [`GlRenderer::new_on_monitor`]. The window keeps drawing with the old one until
+    /// [`GlRenderer::replace_context`], so that a failure partway through a rebuild of several
+    /// windows leaves all of them as they were.
+    pub fn new_context(
+        &self,
+        share: Option<&PossiblyCurrentContext>,
+    ) -> Result<WindowContext, String> {
+        let gl_config = self.gl_context.config();
+        let (gl_context, gl_surface, gl) = create_context(&self.window, &gl_config, share)?;
+        Ok(WindowContext {
+            gl_context,
+            gl_surface,
+            gl,
         })
     }
 
+    /// Switches the window over to a context from [`GlRenderer::new_context`].
+    ///
+    /// Everything created in the old context is gone.
+    pub fn replace_context(&mut self, context: WindowContext) {
+        self.gl_context = context.gl_context;
+        self.gl_surface = context.gl_surface;
+        self.gl = context.gl;
+
+        let size = self.window.inner_size();
+        self.resize(size.width, size.height);
+        self.set_vsync(self.vsync);
+    }
+
+    /// Replaces the surface of the window, keeping the context and everything created in it.
+    ///
+    /// After macOS moves the window to another display, the old surface can keep drawing for the
+    /// display that it was made for, which shows up as black frames.
+    pub fn recreate_surface(&mut self) -> Result<(), String> {
+        let attrs = self
+            .window
+            .build_surface_attributes(Default::default())
+            .map_err(|e| format!("Failed to build surface attributes: {:?}", e))?;
+        let gl_config = self.gl_context.config();
+        let gl_surface = unsafe {
+            gl_config
+                .display()
+                .create_window_surface(&gl_config, &attrs)
+                .map_err(|e| format!("Failed to create surface: {:?}", e))?
+        };
+        self.gl_context
+            .make_current(&gl_surface)
+            .map_err(|e| format!("Failed to make current: {:?}", e))?;
+        self.gl_surface = gl_surface;
+
+        let size = self.window.inner_size();
+        self.resize(size.width, size.height);
+        self.set_vsync(self.vsync);
+        Ok(())
+    }
+
+    /// Returns the name of the GPU that the context renders on, for diagnostics.
+    pub fn gpu_name(&self) -> String {
+        unsafe {
+            let name = self.gl.GetString(ffi::RENDERER);
+            if name.is_null() {
+                return String::from("unknown GPU");
+            }
+            std::ffi::CStr::from_ptr(name.cast()).to_string_lossy().into_owned()
+        }
+    }
+
+    /// Makes buffer swaps wait for the display refresh, or go out right away when disabled.
+    ///
+    /// Some GL configs refuse to change the swap interval, then the setting stays as it was.
+    pub fn set_vsync(&mut self, enabled: bool) {
+        let interval = if enabled {
+            SwapInterval::Wait(NonZeroU32::MIN)
+        } else {
+            SwapInterval::DontWait
+        };
+
+        match self.gl_surface.set_swap_interval(&self.gl_context, interval) {
+            Ok(()) => self.vsync = enabled,
+            Err(e) => log::warn!("Error setting the swap interval: {:?}", e),
+        }
+    }
+
+    /// Returns whether buffer swaps wait for the display refresh.
+    pub fn is_vsync(&self) -> bool {
+        self.vsync
+    }
+
     pub fn resize(&mut self, width: u32, height: u32) {
         if width > 0 && height > 0 {
             self.width = width;
@@ -118,15 +259,16 @@ impl GlRenderer {
         }
     }
 
-    pub fn make_current(&self) -> Result<(), String> {
+    pub fn make_current(&self) -> Result<(), ContextError> {
         if !self.gl_context.is_current() {
-            self.gl_context.make_current(&self.gl_surface)
-                .map_err(|e| format!("Failed to make context current: {:?}", e))?;
+            self.gl_context
+                .make_current(&self.gl_surface)
+                .map_err(|e| ContextError::new("Failed to make context current", e))?;
         }
         Ok(())
     }
 
-    pub fn swap_buffers(&self) -> Result<(), String> {
+    pub fn swap_buffers(&self) -> Result<(), ContextError> {
         // self.make_current()?; // Ensure current before swap?
         
         // Flush before swap to ensure commands aren't buffered
@@ -135,6 +277,473 @@ impl GlRenderer {
         
         self.gl_surface
             .swap_buffers(&self.gl_context)
-            .map_err(|e| format!("Failed to swap buffers: {:?}", e))
+            .map_err(|e| ContextError::new("Failed to swap buffers", e))
+    }
+
+    /// Reads back the window contents as top-to-bottom RGBA rows.
+    ///
+    /// Call this after rendering and before swapping, while the frame is in the back buffer.
+    /// Returns the size in physical pixels, which is twice the logical window size on Retina.
+    pub fn read_pixels(&self) -> Result<(u32, u32, Vec<u8>), String> {
+        self.make_current()?;
+
+        let width = self.gl_surface.width().ok_or("Surface has no width")?;
+        let height = self.gl_surface.height().ok_or("Surface has no height")?;
+        let stride = width as usize * 4;
+        let mut pixels = vec![0u8; stride * height as usize];
+
+        let gl = &self.gl;
+        unsafe {
+            gl.BindFramebuffer(ffi::FRAMEBUFFER, 0);
+            gl.PixelStorei(ffi::PACK_ALIGNMENT, 1);
+            gl.ReadPixels(
+                0,
+                0,
+                width as i32,
+                height as i32,
+                ffi::RGBA,
+                ffi::UNSIGNED_BYTE,
+                pixels.as_mut_ptr().cast(),
+            );
+
+            let error = gl.GetError();
+            if error != ffi::NO_ERROR {
+                return Err(format!("glReadPixels failed: 0x{error:x}"));
+            }
+        }
+
+        // GL rows start at the bottom.
+        let mut flipped = Vec::with_capacity(pixels.len());
+        for row in pixels.chunks_exact(stride).rev() {
+            flipped.extend_from_slice(row);
+        }
+
+        Ok((width, height, flipped))
+    }
+}
+
+/// Orders GL configs from the best fit to the worst, lowest first.
+///
+/// The compositor only draws textured quads of buffers that clients already rendered, so
+/// multisampling and depth or stencil buffers are wasted memory and fill rate, unless MSAA was
+/// asked for to smooth the debug shapes.
+fn config_rank(
+    samples: u8,
+    alpha: u8,
+    depth: u8,
+    stencil: u8,
+    msaa_samples: u8,
+) -> (u8, bool, u8, u8) {
+    (samples.abs_diff(msaa_samples), alpha != 8, depth, stencil)
+}
+
+/// A GL context with a surface for a window, which the window doesn't use yet.
+pub struct WindowContext {
+    gl_context: PossiblyCurrentContext,
+    gl_surface: Surface<WindowSurface>,
+    gl: ffi::Gles2,
+}
+
+impl WindowContext {
+    pub fn gl_context(&self) -> &PossiblyCurrentContext {
+        &self.gl_context
+    }
+
+    pub fn make_current(&self) -> Result<(), ContextError> {
+        self.gl_context
+            .make_current(&self.gl_surface)
+            .map_err(|e| ContextError::new("Failed to make context current", e))
+    }
+}
+
+/// An error from making a window context current or swapping its buffers.
+#[derive(Debug)]
+pub struct ContextError {
+    message: String,
+    lost: bool,
+}
+
+impl ContextError {
+    fn new(message: &str, err: glutin::error::Error) -> Self {
+        Self {
+            lost: is_context_lost(&err),
+            message: format!("{message}: {err:?}"),
+        }
+    }
+
+    /// Returns whether the context is gone for good, rather than failing once.
+    ///
+    /// Only then does it need a rebuild. After other errors the frame is dropped, and the next
+    /// one tries the same context again.
+    pub fn is_context_lost(&self) -> bool {
+        self.lost
+    }
+}
+
+impl std::fmt::Display for ContextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ContextError {}
+
+impl From<ContextError> for String {
+    fn from(err: ContextError) -> Self {
+        err.message
+    }
+}
+
+/// `kCGLBadContext`, for a context that CGL no longer knows.
+const CGL_BAD_CONTEXT: i64 = 10004;
+
+fn is_context_lost(err: &glutin::error::Error) -> bool {
+    let kind = err.error_kind();
+    matches!(kind, ErrorKind::ContextLost | ErrorKind::BadContext)
+        || err.raw_code() == Some(CGL_BAD_CONTEXT)
+}
+
+/// Creates a GL context with a surface for `window` and makes it current.
+fn create_context(
+    window: &Window,
+    gl_config: &glutin::config::Config,
+    share: Option<&PossiblyCurrentContext>,
+) -> Result<(PossiblyCurrentContext, Surface<WindowSurface>, ffi::Gles2), String> {
+    let raw_window_handle = window
+        .window_handle()
+        .map_err(|e| format!("Window handle error: {}", e))?
+        .as_raw();
+    let gl_display = gl_config.display();
+
+    let mut context_attributes = ContextAttributesBuilder::new();
+    if let Some(share) = share {
+        context_attributes = context_attributes.with_sharing(share);
+    }
+    let context_attributes = context_attributes.build(Some(raw_window_handle));
+    let not_current_context = unsafe {
+        gl_display
+            .create_context(gl_config, &context_attributes)
+            .map_err(|e| format!("Failed to create context: {:?}", e))?
+    };
+
+    let attrs = window
+        .build_surface_attributes(Default::default())
+        .map_err(|e| format!("Failed to build surface attributes: {:?}", e))?;
+    let gl_surface = unsafe {
+        gl_display
+            .create_window_surface(gl_config, &attrs)
+            .map_err(|e| format!("Failed to create surface: {:?}", e))?
+    };
+
+    let gl_context = not_current_context
+        .make_current(&gl_surface)
+        .map_err(|e| format!("Failed to make current: {:?}", e))?;
+
+    // CORE PROFILE HACK: Generate and Bind a Dummy VAO.
+    // Without this, glDrawArrays fails silently on macOS Core Profile (3.2+).
+    let gl = ffi::Gles2::load_with(|s| {
+        gl_display.get_proc_address(&std::ffi::CString::new(s).unwrap()) as *const _
+    });
+    unsafe {
+        let mut vao = 0;
+        gl.GenVertexArrays(1, &mut vao);
+        gl.BindVertexArray(vao);
+        log::debug!("bound the Core Profile dummy VAO {vao}");
+    }
+
+    Ok((gl_context, gl_surface, gl))
+}
+
+/// A GL context without a window, for rendering into offscreen textures.
+pub struct OffscreenContext {
+    display: Display,
+    context: PossiblyCurrentContext,
+}
+
+impl OffscreenContext {
+    /// Creates a CGL context and makes it current without a surface.
+    pub fn new() -> Result<Self, String> {
+        Self::with_acceleration(None)
+    }
+
+    /// Creates a context on the Apple software renderer, which needs no working GPU.
+    ///
+    /// It renders on the CPU, so frames take many times longer than on a GPU.
+    pub fn new_software() -> Result<Self, String> {
+        Self::with_acceleration(Some(false))
+    }
+
+    fn with_acceleration(hardware_accelerated: Option<bool>) -> Result<Self, String> {
+        let handle = RawDisplayHandle::AppKit(AppKitDisplayHandle::new());
+        let display = unsafe { Display::new(handle, DisplayApiPreference::Cgl) }
+            .map_err(|e| format!("Failed to create display: {:?}", e))?;
+
+        let template = ConfigTemplateBuilder::new()
+            .prefer_hardware_accelerated(hardware_accelerated)
+            .build();
+        let config = unsafe { display.find_configs(template) }
+            .map_err(|e| format!("Failed to find configs: {:?}", e))?
+            .next()
+            .ok_or("No GL config available")?;
+
+        let context_attributes = ContextAttributesBuilder::new().build(None);
+        let context = unsafe { display.create_context(&config, &context_attributes) }
+            .map_err(|e| format!("Failed to create context: {:?}", e))?
+            .make_current_surfaceless()
+            .map_err(|e| format!("Failed to make current: {:?}", e))?;
+
+        // Same Core Profile VAO hack as for the windows.
+        let gl = ffi::Gles2::load_with(|s| {
+            display.get_proc_address(&std::ffi::CString::new(s).unwrap()) as *const _
+        });
+        unsafe {
+            let mut vao = 0;
+            gl.GenVertexArrays(1, &mut vao);
+            gl.BindVertexArray(vao);
+        }
+
+        Ok(Self { display, context })
+    }
+
+    pub fn make_current(&self) -> Result<(), String> {
+        if !self.context.is_current() {
+            self.context
+                .make_current_surfaceless()
+                .map_err(|e| format!("Failed to make context current: {:?}", e))?;
+        }
+        Ok(())
+    }
+
+    pub fn display(&self) -> &Display {
+        &self.display
     }
-}
\ No newline at end of file
+}
+
+/// Uploads a `CGImage` into a texture, e.g. for macOS cursor images or icons.
+///
+/// Images with 8-bit RGB components in any order, with or without (premultiplied) alpha, are
+/// converted into the premultiplied RGBA that render elements expect.
+pub fn import_cgimage(
+    renderer: &mut GlesRenderer,
+    image: cgimage::CGImageRef,
+) -> Result<GlesTexture, String> {
+    let (width, height, pixels) = unsafe { cgimage::premultiplied_rgba(image)? };
+    renderer
+        .import_memory(
+            &pixels,
+            Fourcc::Abgr8888,
+            (width as i32, height as i32).into(),
+            false,
+        )
+        .map_err(|e| format!("Failed to import image: {:?}", e))
+}
+
+/// Memory layout of 8-bit pixels with four components.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PixelLayout {
+    /// Byte offsets of red, green and blue.
+    rgb: [usize; 3],
+    /// Byte offset of alpha, if the fourth byte isn't padding.
+    alpha: Option<usize>,
+    premultiplied: bool,
+}
+
+impl PixelLayout {
+    /// Returns the layout for a `CGBitmapInfo` of a 32-bit RGB image.
+    fn from_bitmap_info(bitmap_info: u32) -> Option<Self> {
+        const ALPHA_INFO_MASK: u32 = 0x1f;
+        const FLOAT_COMPONENTS: u32 = 1 << 8;
+        const BYTE_ORDER_MASK: u32 = 0x7000;
+        const BYTE_ORDER_32_LITTLE: u32 = 2 << 12;
+
+        if bitmap_info & FLOAT_COMPONENTS != 0 {
+            return None;
+        }
+
+        // (alpha first, has alpha, premultiplied) for the kCGImageAlpha values.
+        let (alpha_first, has_alpha, premultiplied) = match bitmap_info & ALPHA_INFO_MASK {
+            1 => (false, true, true),
+            2 => (true, true, true),
+            3 => (false, true, false),
+            4 => (true, true, false),
+            5 => (false, false, false),
+            6 => (true, false, false),
+            _ => return None,
+        };
+
+        // Offsets in component order, A or padding then RGB, or RGB then A or padding.
+        let (mut rgb, mut alpha) = if alpha_first {
+            ([1, 2, 3], 0)
+        } else {
+            ([0, 1, 2], 3)
+        };
+        // Little-endian 32-bit pixels have their components in reverse order in memory.
+        if bitmap_info & BYTE_ORDER_MASK == BYTE_ORDER_32_LITTLE {
+            rgb = rgb.map(|offset| 3 - offset);
+            alpha = 3 - alpha;
+        }
+
+        Some(Self {
+            rgb,
+            alpha: has_alpha.then_some(alpha),
+            premultiplied,
+        })
+    }
+
+    /// Converts rows of pixels in this layout into tightly packed, premultiplied RGBA.
+    fn to_premultiplied_rgba(self, data: &[u8], width: usize, stride: usize) -> Vec<u8> {
+        let mut rgba = Vec::with_capacity(width * 4 * (data.len() / stride.max(1)));
+        for row in data.chunks(stride) {
+            for pixel in row[..width * 4].chunks_exact(4) {
+                let a = self.alpha.map_or(255, |offset| pixel[offset]);
+                for offset in self.rgb {
+                    let c = pixel[offset];
+                    let c = if self.premultiplied {
+                        c
+                    } else {
+                        ((u16::from(c) * u16::from(a) + 127) / 255) as u8
+                    };
+                    rgba.push(c);
+                }
+                rgba.push(a);
+            }
+        }
+        rgba
+    }
+}
+
+/// Minimal CGImage bindings.
+pub mod cgimage {
+    use std::ffi::c_void;
+
+    use core_foundation_sys::base::CFRelease;
+    use core_foundation_sys::data::{CFDataGetBytePtr, CFDataGetLength, CFDataRef};
+
+    use super::PixelLayout;
+
+    pub type CGImageRef = *mut c_void;
+    type CGDataProviderRef = *mut c_void;
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGImageGetWidth(image: CGImageRef) -> usize;
+        fn CGImageGetHeight(image: CGImageRef) -> usize;
+        fn CGImageGetBitsPerComponent(image: CGImageRef) -> usize;
+        fn CGImageGetBitsPerPixel(image: CGImageRef) -> usize;
+        fn CGImageGetBytesPerRow(image: CGImageRef) -> usize;
+        fn CGImageGetBitmapInfo(image: CGImageRef) -> u32;
+        fn CGImageGetDataProvider(image: CGImageRef) -> CGDataProviderRef;
+        fn CGDataProviderCopyData(provider: CGDataProviderRef) -> CFDataRef;
+    }
+
+    /// Reads the image as tightly packed, premultiplied RGBA rows.
+    pub(super) unsafe fn premultiplied_rgba(
+        image: CGImageRef,
+    ) -> Result<(usize, usize, Vec<u8>), String> {
+        if image.is_null() {
+            return Err(String::from("No image"));
+        }
+
+        let width = CGImageGetWidth(image);
+        let height = CGImageGetHeight(image);
+        let stride = CGImageGetBytesPerRow(image);
+        if width == 0 || height == 0 || stride < width * 4 {
+            return Err(format!("Invalid image layout: {width}x{height}, stride {stride}"));
+        }
+
+        let bits = (CGImageGetBitsPerComponent(image), CGImageGetBitsPerPixel(image));
+        let bitmap_info = CGImageGetBitmapInfo(image);
+        let layout = PixelLayout::from_bitmap_info(bitmap_info)
+            .filter(|_| bits == (8, 32))
+            .ok_or_else(|| format!("Unsupported image format: {bits:?}, info 0x{bitmap_info:x}"))?;
+
+        // The image owns its data provider, only the data copy is ours to release.
+        let provider = CGImageGetDataProvider(image);
+        if provider.is_null() {
+            return Err(String::from("Image has no data provider"));
+        }
+        let data = CGDataProviderCopyData(provider);
+        if data.is_null() {
+            return Err(String::from("Failed to copy image data"));
+        }
+
+        let len = CFDataGetLength(data) as usize;
+        let res = if len < stride * (height - 1) + width * 4 {
+            Err(format!("Image data is too short: {len} bytes"))
+        } else {
+            let bytes = std::slice::from_raw_parts(CFDataGetBytePtr(data), len);
+            let bytes = &bytes[..(stride * height).min(len)];
+            Ok((width, height, layout.to_premultiplied_rgba(bytes, width, stride)))
+        };
+        CFRelease(data.cast());
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bgra_premultiplied_first_little_endian() {
+        // kCGImageAlphaPremultipliedFirst | kCGBitmapByteOrder32Little, the common macOS format.
+        let layout = PixelLayout::from_bitmap_info(2 | (2 << 12)).unwrap();
+        let bgra = [10, 20, 30, 128];
+        assert_eq!(layout.to_premultiplied_rgba(&bgra, 1, 4), [30, 20, 10, 128]);
+    }
+
+    #[test]
+    fn rgba_straight_alpha_gets_premultiplied() {
+        // kCGImageAlphaLast with the default byte order.
+        let layout = PixelLayout::from_bitmap_info(3).unwrap();
+        let rgba = [255, 128, 0, 128];
+        assert_eq!(layout.to_premultiplied_rgba(&rgba, 1, 4), [128, 64, 0, 128]);
+    }
+
+    #[test]
+    fn padding_becomes_opaque_and_rows_lose_their_padding() {
+        // kCGImageAlphaNoneSkipFirst: X, R, G, B, with 4 bytes of row padding.
+        let layout = PixelLayout::from_bitmap_info(6).unwrap();
+        let xrgb = [0, 1, 2, 3, 9, 9, 9, 9, 0, 4, 5, 6, 9, 9, 9, 9];
+        assert_eq!(
+            layout.to_premultiplied_rgba(&xrgb, 1, 8),
+            [1, 2, 3, 255, 4, 5, 6, 255]
+        );
+    }
+
+    #[test]
+    fn unsupported_bitmap_info() {
+        // kCGImageAlphaNone, kCGImageAlphaOnly, and float components.
+        assert_eq!(PixelLayout::from_bitmap_info(0), None);
+        assert_eq!(PixelLayout::from_bitmap_info(7), None);
+        assert_eq!(PixelLayout::from_bitmap_info(1 | (1 << 8)), None);
+    }
+
+    #[test]
+    fn config_rank_prefers_single_sample_without_depth() {
+        let best = |configs: &[(u8, u8, u8, u8)], msaa_samples| {
+            *configs
+                .iter()
+                .min_by_key(|(samples, alpha, depth, stencil)| {
+                    config_rank(*samples, *alpha, *depth, *stencil, msaa_samples)
+                })
+                .unwrap()
+        };
+
+        let configs = [(4, 8, 24, 8), (0, 8, 24, 8), (0, 8, 0, 0), (8, 8, 0, 0)];
+        assert_eq!(best(&configs, 0), (0, 8, 0, 0));
+        assert_eq!(best(&configs, 4), (4, 8, 24, 8));
+        assert_eq!(best(&configs, 8), (8, 8, 0, 0));
+
+        // 8-bit alpha wins over a smaller depth buffer.
+        assert_eq!(best(&[(0, 0, 0, 0), (0, 8, 24, 0)], 0), (0, 8, 24, 0));
+    }
+
+    #[test]
+    fn only_lost_contexts_count_as_lost() {
+        assert!(is_context_lost(&ErrorKind::ContextLost.into()));
+        assert!(is_context_lost(&ErrorKind::BadContext.into()));
+        assert!(!is_context_lost(&ErrorKind::BadSurface.into()));
+        assert!(!is_context_lost(&ErrorKind::BadCurrentSurface.into()));
+    }
+}
@@ -0,0 +1,183 @@
+//! Drag and drop of files from macOS apps, like Finder, into Wayland clients.
+//!
+//! winit reports the files dragged over a window and dropped on it. While they hover, a DnD grab
+//! with a [`FileDropSource`] offers them to clients as `text/uri-list`, and the winit backend
+//! moves the pointer along with the drag, so the offer follows it from client to client like a
+//! drag that started in a Wayland client.
+
+use std::fs::File;
+use std::io::Write;
+use std::os::fd::OwnedFd;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use smithay::backend::input::ButtonState;
+use smithay::input::dnd::{DnDGrab, Source};
+use smithay::input::pointer::{ButtonEvent, Focus, GrabStartData, MotionEvent};
+use smithay::reexports::rustix::fs::{fcntl_setfl, OFlags};
+use smithay::reexports::wayland_server::protocol::wl_data_device_manager::DndAction;
+use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
+use smithay::utils::{IsAlive, SERIAL_COUNTER};
+use smithay::wayland::selection::data_device::SourceMetadata;
+
+use crate::niri::State;
+use crate::utils::get_monotonic_time;
+
+const URI_LIST: &str = "text/uri-list";
+
+/// Button that the drag holds, as far as the Wayland clients can tell.
+const BTN_LEFT: u32 = 0x110;
+
+/// DnD source offering files from a macOS app.
+#[derive(Debug, Clone)]
+pub struct FileDropSource {
+    uri_list: Arc<[u8]>,
+    /// Cleared when the drag is cancelled or finished.
+    alive: Arc<AtomicBool>,
+}
+
+impl FileDropSource {
+    pub fn new(paths: &[PathBuf]) -> Self {
+        Self {
+            uri_list: uri_list(paths).into_bytes().into(),
+            alive: Arc::new(AtomicBool::new(true)),
+        }
+    }
+}
+
+impl IsAlive for FileDropSource {
+    fn alive(&self) -> bool {
+        self.alive.load(Ordering::Relaxed)
+    }
+}
+
+impl Source for FileDropSource {
+    fn metadata(&self) -> Option<SourceMetadata> {
+        Some(SourceMetadata {
+            mime_types: vec![URI_LIST.to_owned()],
+            dnd_action: DndAction::Copy,
+        })
+    }
+
+    fn choose_action(&self, _action: DndAction) {}
+
+    fn send(&self, mime_type: &str, fd: OwnedFd) {
+        if mime_type != URI_LIST {
+            warn!("client asked for dropped files as {mime_type:?}");
+            return;
+        }
+
+        let buf = self.uri_list.clone();
+        thread::spawn(move || {
+            // Clear O_NONBLOCK, otherwise File::write_all() will stop halfway.
+            if let Err(err) = fcntl_setfl(&fd, OFlags::empty()) {
+                warn!("error clearing flags on dropped files target fd: {err:?}");
+            }
+            if let Err(err) = File::from(fd).write_all(&buf) {
+                warn!("error writing dropped files: {err:?}");
+            }
+        });
+    }
+
+    fn drop_performed(&self) {}
+
+    fn cancel(&self) {
+        self.alive.store(false, Ordering::Relaxed);
+    }
+
+    fn finished(&self) {
+        self.alive.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Starts offering the files to the client under the pointer.
+pub fn start(state: &mut State, paths: &[PathBuf]) {
+    let pointer = state.niri.seat.get_pointer().unwrap();
+    let start_data = GrabStartData {
+        focus: None,
+        button: BTN_LEFT,
+        location: pointer.current_location(),
+    };
+    let grab = DnDGrab::new_pointer(
+        &state.niri.display_handle,
+        start_data,
+        FileDropSource::new(paths),
+        state.niri.seat.clone(),
+    );
+    pointer.set_grab(state, grab, SERIAL_COUNTER.next_serial(), Focus::Clear);
+}
+
+/// Ends the offer from [`start`], dropping the files on the client under the pointer, or
+/// cancelling the offer when the drag left the windows without a drop.
+pub fn finish(state: &mut State, dropped: bool) {
+    let pointer = state.niri.seat.get_pointer().unwrap();
+    let is_file_drop = pointer
+        .with_grab(|_, grab| grab.as_any().is::<DnDGrab<State, FileDropSource, WlSurface>>());
+    if is_file_drop != Some(true) {
+        return;
+    }
+
+    let time = get_monotonic_time().as_millis() as u32;
+    let location = pointer.current_location();
+
+    // Without a target, the release below cancels the offer.
+    if !dropped {
+        let event = MotionEvent {
+            location,
+            serial: SERIAL_COUNTER.next_serial(),
+            time,
+        };
+        pointer.motion(state, None, &event);
+    }
+
+    // The mouse button went up in the app that the drag started in, so the release that ends the
+    // grab has to come from here.
+    let event = ButtonEvent {
+        serial: SERIAL_COUNTER.next_serial(),
+        time,
+        button: BTN_LEFT,
+        state: ButtonState::Released,
+    };
+    pointer.button(state, &event);
+    pointer.frame(state);
+}
+
+/// Formats the paths as a `text/uri-list`.
+fn uri_list(paths: &[PathBuf]) -> String {
+    paths.iter().map(|path| file_uri(path) + "\r\n").collect()
+}
+
+/// Returns the `file://` URI of an absolute path, percent-encoding everything but unreserved
+/// characters and slashes.
+fn file_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for &byte in path.as_os_str().as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-._~".contains(&byte) {
+            uri.push(char::from(byte));
+        } else {
+            uri.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    uri
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_to_uri_list() {
+        let paths = [
+            PathBuf::from("/Users/me/Desktop/notes.txt"),
+            PathBuf::from("/tmp/a b/ünï#1.png"),
+        ];
+        assert_eq!(
+            uri_list(&paths),
+            "file:///Users/me/Desktop/notes.txt\r\n\
+             file:///tmp/a%20b/%C3%BCn%C3%AF%231.png\r\n"
+        );
+    }
+}
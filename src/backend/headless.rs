@@ -2,63 +2,169 @@
 //!
 //! This can eventually grow into a more complete backend if needed, but for now it's missing some
 //! crucial parts like dmabufs.
+//!
+//! When a renderer is added, outputs render into offscreen textures, which tests can read back
+//! with [`Headless::copy_framebuffer()`]. [`Headless::benchmark()`] uses them to time rendering.
 
+use std::collections::HashMap;
+use std::ffi::CString;
 use std::mem;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use anyhow::Context as _;
+use anyhow::{anyhow, Context as _};
+use glutin::display::GlDisplay as _;
 use niri_config::OutputName;
+use serde::Serialize;
 use smithay::backend::allocator::dmabuf::Dmabuf;
-use smithay::backend::egl::native::EGLSurfacelessDisplay;
-use smithay::backend::egl::{EGLContext, EGLDisplay};
+use smithay::backend::allocator::Fourcc;
+use smithay::backend::renderer::damage::OutputDamageTracker;
 use smithay::backend::renderer::element::RenderElementStates;
-use smithay::backend::renderer::gles::GlesRenderer;
+use smithay::backend::renderer::gles::{GlesRenderer, GlesTexture};
+use smithay::backend::renderer::{Bind, ExportMem, Offscreen, Texture};
 use smithay::output::{Mode, Output, PhysicalProperties, Subpixel};
 use smithay::reexports::wayland_protocols::wp::presentation_time::server::wp_presentation_feedback;
-use smithay::utils::Size;
+use smithay::utils::{Physical, Rectangle, Size, Transform};
 use smithay::wayland::presentation::Refresh;
 
-use super::{IpcOutputMap, OutputId, RenderResult};
+use super::cocoa_renderer::OffscreenContext;
+use super::{
+    configured_transparency, IpcOutputMap, OutputId, RenderResult, DEFAULT_CLEAR_COLOR,
+    TRANSPARENT_CLEAR_COLOR,
+};
 use crate::niri::{Niri, RedrawState};
-use crate::render_helpers::{resources, shaders};
+use crate::render_helpers::{resources, shaders, RenderTarget};
 use crate::utils::{get_monotonic_time, logical_output};
 
+/// Format of the offscreen framebuffers: RGBA bytes in memory.
+const FRAMEBUFFER_FORMAT: Fourcc = Fourcc::Abgr8888;
+
+/// Size of the output that [`Headless::benchmark`] adds when there are none.
+const BENCHMARK_OUTPUT_SIZE: (u16, u16) = (1920, 1080);
+
 pub struct Headless {
+    /// Context that `renderer` draws with, there's no window to get one from.
+    context: Option<OffscreenContext>,
     renderer: Option<GlesRenderer>,
+    framebuffers: HashMap<Output, Framebuffer>,
     ipc_outputs: Arc<Mutex<IpcOutputMap>>,
+    /// Color that the framebuffers are cleared to.
+    clear_color: [f32; 4],
+    /// Mode of the output to add in [`Headless::init`], see [`Headless::with_output`].
+    initial_output: Option<Mode>,
+}
+
+/// Frame times measured by [`Headless::benchmark`], in milliseconds.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BenchmarkReport {
+    pub frames: usize,
+    pub width: i32,
+    pub height: i32,
+    pub total_ms: f64,
+    pub mean_ms: f64,
+    pub median_ms: f64,
+    pub p99_ms: f64,
+    /// Time spent building the frames and submitting them to the GPU.
+    pub cpu_ms: f64,
+    /// Time spent waiting for the GPU to finish the submitted frames.
+    pub gpu_wait_ms: f64,
+}
+
+/// Offscreen render target of a headless output.
+struct Framebuffer {
+    texture: GlesTexture,
+    damage_tracker: OutputDamageTracker,
+    /// Age of the texture contents, 0 until the first frame is rendered.
+    age: usize,
 }
 
 impl Headless {
     pub fn new() -> Self {
         Self {
+            context: None,
             renderer: None,
+            framebuffers: HashMap::new(),
             ipc_outputs: Default::default(),
+            clear_color: DEFAULT_CLEAR_COLOR,
+            initial_output: None,
         }
     }
 
-    pub fn init(&mut self, _niri: &mut Niri) {}
+    /// Creates a backend with a renderer and one output of this size and refresh rate in mHz.
+    ///
+    /// The output is added when the backend is initialized. This needs no display, so it also
+    /// works on CI runners without a GUI session.
+    pub fn with_output(size: Size<i32, Physical>, refresh: u32) -> anyhow::Result<Self> {
+        let mut headless = Self::without_renderer(size, refresh);
+        headless.add_renderer()?;
+        Ok(headless)
+    }
+
+    /// Creates a backend with one output like [`Headless::with_output()`], but no renderer.
+    ///
+    /// Clients work the same, their frames just don't get drawn anywhere.
+    pub fn without_renderer(size: Size<i32, Physical>, refresh: u32) -> Self {
+        let mut headless = Self::new();
+        headless.initial_output = Some(Mode {
+            size,
+            refresh: refresh as i32,
+        });
+        headless
+    }
+
+    pub fn init(&mut self, niri: &mut Niri) {
+        // Like the winit windows, so that tests see what a transparent window would show.
+        if configured_transparency(&niri.config.borrow()) {
+            self.clear_color = TRANSPARENT_CLEAR_COLOR;
+        }
+
+        if self.renderer.is_some() {
+            niri.update_shaders();
+        }
 
+        if let Some(mode) = self.initial_output.take() {
+            self.add_output_with_mode(niri, 1, mode);
+        }
+    }
+
+    /// Adds a renderer on the GPU, or on the CPU through the Apple software renderer when the GPU
+    /// doesn't work, like on some CI runners and in VMs.
     pub fn add_renderer(&mut self) -> anyhow::Result<()> {
         if self.renderer.is_some() {
             error!("add_renderer: renderer must not already exist");
             return Ok(());
         }
 
-        let mut renderer = unsafe {
-            let display =
-                EGLDisplay::new(EGLSurfacelessDisplay).context("error creating EGL display")?;
-            let context = EGLContext::new(&display).context("error creating EGL context")?;
-            GlesRenderer::new(context).context("error creating renderer")?
+        let (context, mut renderer) = match create_renderer(OffscreenContext::new) {
+            Ok(res) => res,
+            Err(hardware_err) => {
+                warn!(
+                    "error creating a GPU renderer, falling back to software rendering, \
+                     which is much slower: {hardware_err:#}"
+                );
+                create_renderer(OffscreenContext::new_software).map_err(|err| {
+                    anyhow!("{hardware_err:#}; with software rendering: {err:#}")
+                })?
+            }
         };
 
         resources::init(&mut renderer);
         shaders::init(&mut renderer);
 
+        self.context = Some(context);
         self.renderer = Some(renderer);
         Ok(())
     }
 
     pub fn add_output(&mut self, niri: &mut Niri, n: u8, size: (u16, u16)) {
+        let mode = Mode {
+            size: Size::from((i32::from(size.0), i32::from(size.1))),
+            refresh: 60_000,
+        };
+        self.add_output_with_mode(niri, n, mode);
+    }
+
+    fn add_output_with_mode(&mut self, niri: &mut Niri, n: u8, mode: Mode) {
         let connector = format!("headless-{n}");
         let make = "niri".to_string();
         let model = "headless".to_string();
@@ -75,10 +181,6 @@ impl Headless {
             },
         );
 
-        let mode = Mode {
-            size: Size::from((i32::from(size.0), i32::from(size.1))),
-            refresh: 60_000,
-        };
         output.change_current_state(Some(mode), None, None, None);
         output.set_preferred(mode);
 
@@ -98,12 +200,7 @@ impl Headless {
                 model: physical_properties.model,
                 serial: None,
                 physical_size: None,
-                modes: vec![niri_ipc::Mode {
-                    width: size.0,
-                    height: size.1,
-                    refresh_rate: 60_000,
-                    is_preferred: true,
-                }],
+                modes: vec![ipc_mode(mode)],
                 current_mode: Some(0),
                 is_custom_mode: true,
                 vrr_supported: false,
@@ -115,6 +212,26 @@ impl Headless {
         niri.add_output(output, None, false);
     }
 
+    /// Changes the size of an output, like resizing a winit window does.
+    pub fn resize_output(&mut self, niri: &mut Niri, output: &Output, size: Size<i32, Physical>) {
+        let mode = Mode {
+            size,
+            refresh: output.current_mode().unwrap().refresh,
+        };
+        output.change_current_state(Some(mode), None, None, None);
+        output.set_preferred(mode);
+
+        let name = output.name();
+        let mut ipc_outputs = self.ipc_outputs.lock().unwrap();
+        if let Some(ipc_output) = ipc_outputs.values_mut().find(|o| o.name == name) {
+            ipc_output.modes = vec![ipc_mode(mode)];
+        }
+        drop(ipc_outputs);
+        niri.ipc_outputs_changed = true;
+
+        niri.output_resized(output);
+    }
+
     pub fn seat_name(&self) -> String {
         "headless".to_owned()
     }
@@ -123,11 +240,46 @@ impl Headless {
         &mut self,
         f: impl FnOnce(&mut GlesRenderer) -> T,
     ) -> Option<T> {
+        self.renderer.as_ref()?;
+        if let Err(err) = self.make_current() {
+            warn!("error making the offscreen context current: {err:?}");
+            return None;
+        }
+
         self.renderer.as_mut().map(f)
     }
 
+    /// Makes the offscreen context current, GL calls go nowhere otherwise.
+    fn make_current(&self) -> anyhow::Result<()> {
+        if let Some(context) = &self.context {
+            context.make_current().map_err(anyhow::Error::msg)?;
+        }
+        Ok(())
+    }
+
     pub fn render(&mut self, niri: &mut Niri, output: &Output) -> RenderResult {
-        let states = RenderElementStates::default();
+        let current = self.make_current();
+        let (res, states) = match &mut self.renderer {
+            Some(_) if current.is_err() => {
+                warn!("error making the offscreen context current: {current:?}");
+                (RenderResult::Skipped, RenderElementStates::default())
+            }
+            Some(renderer) => {
+                let framebuffers = &mut self.framebuffers;
+                match render_to_framebuffer(renderer, framebuffers, niri, output, self.clear_color) {
+                    Ok((true, states)) => (RenderResult::Submitted, states),
+                    Ok((false, states)) => (RenderResult::NoDamage, states),
+                    Err(err) => {
+                        warn!("error rendering headless output: {err:?}");
+                        (RenderResult::Skipped, RenderElementStates::default())
+                    }
+                }
+            }
+            // Without a renderer there's nothing to draw into; pretend that the frame went out.
+            None => (RenderResult::Submitted, RenderElementStates::default()),
+        };
+
+        niri.update_primary_scanout_output(output, &states);
         let mut presentation_feedbacks = niri.take_presentation_feedbacks(output, &states);
         presentation_feedbacks.presented::<_, smithay::utils::Monotonic>(
             get_monotonic_time(),
@@ -149,7 +301,92 @@ impl Headless {
 
         // FIXME: request redraw on unfinished animations remain
 
-        RenderResult::Submitted
+        res
+    }
+
+    /// Downloads the last frame rendered for the output as tightly packed RGBA bytes.
+    pub fn copy_framebuffer(&mut self, output: &Output) -> anyhow::Result<Vec<u8>> {
+        self.make_current()?;
+        let renderer = self.renderer.as_mut().context("no renderer")?;
+        let framebuffer = self
+            .framebuffers
+            .get_mut(output)
+            .context("output was never rendered")?;
+
+        let size = framebuffer.texture.size();
+        let target = renderer
+            .bind(&mut framebuffer.texture)
+            .context("error binding framebuffer")?;
+        let mapping = renderer
+            .copy_framebuffer(&target, Rectangle::from_size(size), FRAMEBUFFER_FORMAT)
+            .context("error copying framebuffer")?;
+        let bytes = renderer
+            .map_texture(&mapping)
+            .context("error mapping framebuffer")?;
+        Ok(bytes.to_vec())
+    }
+
+    /// Renders the first output `frames` times in a row, as fast as possible, and times it.
+    ///
+    /// The scene is the output with the hotkey overlay open, redrawn in full every frame. Each
+    /// frame waits for the GPU, which separates the CPU time from the GPU time. Adds a renderer
+    /// and an output of [`BENCHMARK_OUTPUT_SIZE`] if missing.
+    pub fn benchmark(
+        &mut self,
+        niri: &mut Niri,
+        frames: usize,
+    ) -> anyhow::Result<BenchmarkReport> {
+        let _span = tracy_client::span!("Headless::benchmark");
+
+        if self.renderer.is_none() {
+            self.add_renderer()?;
+            niri.update_shaders();
+        }
+        if niri.global_space.outputs().next().is_none() {
+            let (w, h) = BENCHMARK_OUTPUT_SIZE;
+            self.add_output(niri, 1, (w, h));
+        }
+        let output = niri.global_space.outputs().next().unwrap().clone();
+        niri.hotkey_overlay.show();
+
+        self.make_current()?;
+        let renderer = self.renderer.as_mut().unwrap();
+
+        let mut frame_times = Vec::with_capacity(frames);
+        let mut cpu = Duration::ZERO;
+        let mut gpu_wait = Duration::ZERO;
+        let start = Instant::now();
+        for _ in 0..frames {
+            let _span = tracy_client::span!("Headless::benchmark frame");
+
+            // Forget the previous frame, otherwise the damage tracker has nothing to draw.
+            if let Some(framebuffer) = self.framebuffers.get_mut(&output) {
+                framebuffer.age = 0;
+            }
+
+            let frame_start = Instant::now();
+            let framebuffers = &mut self.framebuffers;
+            render_to_framebuffer(renderer, framebuffers, niri, &output, self.clear_color)?;
+            let submitted = Instant::now();
+            renderer
+                .with_context(|gl| unsafe { gl.Finish() })
+                .context("error waiting for the GPU")?;
+            let finished = Instant::now();
+
+            cpu += submitted - frame_start;
+            gpu_wait += finished - submitted;
+            frame_times.push(finished - frame_start);
+        }
+        let total = start.elapsed();
+
+        let size = output.current_mode().unwrap().size;
+        Ok(BenchmarkReport::new(
+            &mut frame_times,
+            total,
+            cpu,
+            gpu_wait,
+            (size.w, size.h),
+        ))
     }
 
     pub fn import_dmabuf(&mut self, _dmabuf: &Dmabuf) -> bool {
@@ -161,8 +398,127 @@ impl Headless {
     }
 }
 
+/// Renders the output into its offscreen framebuffer, (re)creating it to match the output mode.
+///
+/// Returns whether anything was damaged, along with the element states for presentation feedback.
+fn render_to_framebuffer(
+    renderer: &mut GlesRenderer,
+    framebuffers: &mut HashMap<Output, Framebuffer>,
+    niri: &mut Niri,
+    output: &Output,
+    clear_color: [f32; 4],
+) -> anyhow::Result<(bool, RenderElementStates)> {
+    let _span = tracy_client::span!("Headless::render_to_framebuffer");
+
+    let mode = output.current_mode().context("output has no mode")?;
+    let buffer_size = mode.size.to_logical(1).to_buffer(1, Transform::Normal);
+
+    let needs_new = framebuffers
+        .get(output)
+        .map_or(true, |fb| fb.texture.size() != buffer_size);
+    if needs_new {
+        let texture = renderer
+            .create_buffer(FRAMEBUFFER_FORMAT, buffer_size)
+            .context("error creating framebuffer")?;
+        let framebuffer = Framebuffer {
+            texture,
+            damage_tracker: OutputDamageTracker::from_output(output),
+            age: 0,
+        };
+        framebuffers.insert(output.clone(), framebuffer);
+    }
+    let framebuffer = framebuffers.get_mut(output).unwrap();
+
+    let elements = niri.render::<GlesRenderer>(renderer, output, true, RenderTarget::Output);
+
+    let mut target = renderer
+        .bind(&mut framebuffer.texture)
+        .context("error binding framebuffer")?;
+    let res = framebuffer
+        .damage_tracker
+        .render_output(
+            renderer,
+            &mut target,
+            framebuffer.age,
+            &elements,
+            clear_color,
+        )
+        .context("error rendering")?;
+
+    // The same texture is reused every frame, so its contents are always one frame old.
+    framebuffer.age = 1;
+
+    Ok((res.damage.is_some(), res.states))
+}
+
+impl BenchmarkReport {
+    fn new(
+        frame_times: &mut [Duration],
+        total: Duration,
+        cpu: Duration,
+        gpu_wait: Duration,
+        (width, height): (i32, i32),
+    ) -> Self {
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.;
+
+        frame_times.sort_unstable();
+        // Nearest-rank percentile.
+        let percentile = |p: f64| {
+            let rank = (p * frame_times.len() as f64).ceil() as usize;
+            frame_times
+                .get(rank.saturating_sub(1))
+                .copied()
+                .map_or(0., ms)
+        };
+        let sum: Duration = frame_times.iter().sum();
+        let mean_ms = if frame_times.is_empty() {
+            0.
+        } else {
+            ms(sum) / frame_times.len() as f64
+        };
+
+        Self {
+            frames: frame_times.len(),
+            width,
+            height,
+            total_ms: ms(total),
+            mean_ms,
+            median_ms: percentile(0.5),
+            p99_ms: percentile(0.99),
+            cpu_ms: ms(cpu),
+            gpu_wait_ms: ms(gpu_wait),
+        }
+    }
+}
+
 impl Default for Headless {
     fn default() -> Self {
         Self::new()
     }
 }
+
+fn create_renderer(
+    new_context: fn() -> Result<OffscreenContext, String>,
+) -> anyhow::Result<(OffscreenContext, GlesRenderer)> {
+    let context = new_context()
+        .map_err(anyhow::Error::msg)
+        .context("error creating offscreen GL context")?;
+    let display = context.display();
+    let renderer = unsafe {
+        GlesRenderer::new_with_loader(|s| {
+            let symbol = CString::new(s).unwrap();
+            display.get_proc_address(symbol.as_c_str()).cast()
+        })
+        .context("error creating renderer")?
+    };
+    Ok((context, renderer))
+}
+
+fn ipc_mode(mode: Mode) -> niri_ipc::Mode {
+    niri_ipc::Mode {
+        width: mode.size.w.clamp(0, i32::from(u16::MAX)) as u16,
+        height: mode.size.h.clamp(0, i32::from(u16::MAX)) as u16,
+        refresh_rate: mode.refresh as u32,
+        is_preferred: true,
+    }
+}
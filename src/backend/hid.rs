@@ -0,0 +1,362 @@
+//! Keyboard LED control, Caps Lock and mouse monitoring through the IOKit HID manager.
+//!
+//! AppKit has no API for keyboard LEDs, it reports Caps Lock as a toggle rather than as key
+//! presses, and it doesn't tell which pointing devices are attached, so this talks to the HID
+//! devices directly. Opening them may need the Input Monitoring permission; without it nothing
+//! changes.
+
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use calloop::channel::Sender;
+use core_foundation::base::{CFType, TCFType};
+use core_foundation::boolean::CFBoolean;
+use core_foundation::dictionary::CFDictionary;
+use core_foundation::number::CFNumber;
+use core_foundation::string::CFString;
+use core_foundation_sys::array::{CFArrayGetCount, CFArrayGetValueAtIndex, CFArrayRef};
+use core_foundation_sys::base::{kCFAllocatorDefault, CFAllocatorRef, CFIndex, CFRelease};
+use core_foundation_sys::dictionary::CFDictionaryRef;
+use core_foundation_sys::runloop::{kCFRunLoopDefaultMode, CFRunLoopGetMain, CFRunLoopRef};
+use core_foundation_sys::set::{CFSetGetCount, CFSetGetValues, CFSetRef};
+use core_foundation_sys::string::CFStringRef;
+
+type IOHIDManagerRef = *mut c_void;
+type IOHIDDeviceRef = *mut c_void;
+type IOHIDElementRef = *mut c_void;
+type IOHIDValueRef = *mut c_void;
+type IOHIDValueCallback =
+    extern "C" fn(context: *mut c_void, result: i32, sender: *mut c_void, value: IOHIDValueRef);
+type IOHIDDeviceCallback =
+    extern "C" fn(context: *mut c_void, result: i32, sender: *mut c_void, device: IOHIDDeviceRef);
+
+#[link(name = "IOKit", kind = "framework")]
+extern "C" {
+    fn IOHIDManagerCreate(allocator: CFAllocatorRef, options: u32) -> IOHIDManagerRef;
+    fn IOHIDManagerSetDeviceMatching(manager: IOHIDManagerRef, matching: CFDictionaryRef);
+    fn IOHIDManagerOpen(manager: IOHIDManagerRef, options: u32) -> i32;
+    fn IOHIDManagerClose(manager: IOHIDManagerRef, options: u32) -> i32;
+    fn IOHIDManagerCopyDevices(manager: IOHIDManagerRef) -> CFSetRef;
+    fn IOHIDManagerSetInputValueMatching(manager: IOHIDManagerRef, matching: CFDictionaryRef);
+    fn IOHIDManagerRegisterInputValueCallback(
+        manager: IOHIDManagerRef,
+        callback: Option<IOHIDValueCallback>,
+        context: *mut c_void,
+    );
+    fn IOHIDManagerRegisterDeviceMatchingCallback(
+        manager: IOHIDManagerRef,
+        callback: Option<IOHIDDeviceCallback>,
+        context: *mut c_void,
+    );
+    fn IOHIDManagerRegisterDeviceRemovalCallback(
+        manager: IOHIDManagerRef,
+        callback: Option<IOHIDDeviceCallback>,
+        context: *mut c_void,
+    );
+    fn IOHIDManagerScheduleWithRunLoop(
+        manager: IOHIDManagerRef,
+        run_loop: CFRunLoopRef,
+        mode: CFStringRef,
+    );
+    fn IOHIDManagerUnscheduleFromRunLoop(
+        manager: IOHIDManagerRef,
+        run_loop: CFRunLoopRef,
+        mode: CFStringRef,
+    );
+    fn IOHIDDeviceCopyMatchingElements(
+        device: IOHIDDeviceRef,
+        matching: CFDictionaryRef,
+        options: u32,
+    ) -> CFArrayRef;
+    fn IOHIDDeviceGetProperty(device: IOHIDDeviceRef, key: CFStringRef) -> *const c_void;
+    fn IOHIDElementGetUsage(element: IOHIDElementRef) -> u32;
+    fn IOHIDValueCreateWithIntegerValue(
+        allocator: CFAllocatorRef,
+        element: IOHIDElementRef,
+        timestamp: u64,
+        value: CFIndex,
+    ) -> IOHIDValueRef;
+    fn IOHIDDeviceSetValue(
+        device: IOHIDDeviceRef,
+        element: IOHIDElementRef,
+        value: IOHIDValueRef,
+    ) -> i32;
+    fn IOHIDValueGetIntegerValue(value: IOHIDValueRef) -> CFIndex;
+}
+
+const USAGE_PAGE_GENERIC_DESKTOP: i32 = 0x01;
+const USAGE_MOUSE: i32 = 0x02;
+const USAGE_KEYBOARD: i32 = 0x06;
+const USAGE_PAGE_KEYBOARD: i32 = 0x07;
+const USAGE_KEYBOARD_CAPS_LOCK: i32 = 0x39;
+const USAGE_PAGE_LED: i32 = 0x08;
+const USAGE_LED_CAPS_LOCK: u32 = 0x02;
+
+/// Reports presses and releases of the Caps Lock key on every attached keyboard.
+///
+/// AppKit only tells when Caps Lock turns on or off, which takes two presses. The keyboards
+/// report the key itself, so it can work as a momentary key, e.g. when remapped to Control.
+pub struct CapsLockMonitor {
+    manager: IOHIDManagerRef,
+    sender: *mut Sender<bool>,
+}
+
+impl CapsLockMonitor {
+    /// Starts sending `true` for presses and `false` for releases to `sender`.
+    ///
+    /// The reports arrive on the main run loop, also while another app is in front.
+    pub fn new(sender: Sender<bool>) -> Option<Self> {
+        extern "C" fn on_value(
+            context: *mut c_void,
+            _result: i32,
+            _sender: *mut c_void,
+            value: IOHIDValueRef,
+        ) {
+            let sender = unsafe { &*(context as *const Sender<bool>) };
+            let pressed = unsafe { IOHIDValueGetIntegerValue(value) } != 0;
+            let _ = sender.send(pressed);
+        }
+
+        let device_matching = matching_dictionary(&[
+            ("DeviceUsagePage", USAGE_PAGE_GENERIC_DESKTOP),
+            ("DeviceUsage", USAGE_KEYBOARD),
+        ]);
+        let value_matching = matching_dictionary(&[
+            ("UsagePage", USAGE_PAGE_KEYBOARD),
+            ("Usage", USAGE_KEYBOARD_CAPS_LOCK),
+        ]);
+
+        unsafe {
+            let manager = IOHIDManagerCreate(kCFAllocatorDefault, 0);
+            if manager.is_null() {
+                return None;
+            }
+
+            IOHIDManagerSetDeviceMatching(manager, device_matching.as_concrete_TypeRef());
+            IOHIDManagerSetInputValueMatching(manager, value_matching.as_concrete_TypeRef());
+
+            let sender = Box::into_raw(Box::new(sender));
+            IOHIDManagerRegisterInputValueCallback(manager, Some(on_value), sender.cast());
+            IOHIDManagerScheduleWithRunLoop(manager, CFRunLoopGetMain(), kCFRunLoopDefaultMode);
+
+            let monitor = Self { manager, sender };
+            if IOHIDManagerOpen(manager, 0) != 0 {
+                debug!("error opening the HID keyboards, not monitoring Caps Lock");
+                return None;
+            }
+
+            Some(monitor)
+        }
+    }
+}
+
+impl Drop for CapsLockMonitor {
+    fn drop(&mut self) {
+        unsafe {
+            IOHIDManagerUnscheduleFromRunLoop(
+                self.manager,
+                CFRunLoopGetMain(),
+                kCFRunLoopDefaultMode,
+            );
+            IOHIDManagerRegisterInputValueCallback(self.manager, None, std::ptr::null_mut());
+            // Closing a manager that failed to open is harmless.
+            IOHIDManagerClose(self.manager, 0);
+            CFRelease(self.manager);
+            drop(Box::from_raw(self.sender));
+        }
+    }
+}
+
+static EXTERNAL_MICE: AtomicUsize = AtomicUsize::new(0);
+
+/// Keeps count of the attached mice for [`external_mouse_connected()`].
+///
+/// Trackpads report themselves as mice too, built-in ones and Magic Trackpads alike, so they
+/// don't count.
+pub struct MouseMonitor {
+    manager: IOHIDManagerRef,
+}
+
+impl MouseMonitor {
+    /// Starts counting the mice, which arrive on the main run loop.
+    pub fn new() -> Option<Self> {
+        extern "C" fn on_matched(
+            _context: *mut c_void,
+            _result: i32,
+            _sender: *mut c_void,
+            device: IOHIDDeviceRef,
+        ) {
+            if unsafe { is_external_mouse(device) } {
+                EXTERNAL_MICE.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        extern "C" fn on_removed(
+            _context: *mut c_void,
+            _result: i32,
+            _sender: *mut c_void,
+            device: IOHIDDeviceRef,
+        ) {
+            if unsafe { is_external_mouse(device) } {
+                let _ = EXTERNAL_MICE.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+                    count.checked_sub(1)
+                });
+            }
+        }
+
+        let device_matching = matching_dictionary(&[
+            ("DeviceUsagePage", USAGE_PAGE_GENERIC_DESKTOP),
+            ("DeviceUsage", USAGE_MOUSE),
+        ]);
+
+        unsafe {
+            let manager = IOHIDManagerCreate(kCFAllocatorDefault, 0);
+            if manager.is_null() {
+                return None;
+            }
+
+            IOHIDManagerSetDeviceMatching(manager, device_matching.as_concrete_TypeRef());
+            let context = std::ptr::null_mut();
+            IOHIDManagerRegisterDeviceMatchingCallback(manager, Some(on_matched), context);
+            IOHIDManagerRegisterDeviceRemovalCallback(manager, Some(on_removed), context);
+            IOHIDManagerScheduleWithRunLoop(manager, CFRunLoopGetMain(), kCFRunLoopDefaultMode);
+
+            let monitor = Self { manager };
+            if IOHIDManagerOpen(manager, 0) != 0 {
+                debug!("error opening the HID mice, not telling when a mouse is plugged in");
+                return None;
+            }
+
+            Some(monitor)
+        }
+    }
+}
+
+impl Drop for MouseMonitor {
+    fn drop(&mut self) {
+        unsafe {
+            IOHIDManagerUnscheduleFromRunLoop(
+                self.manager,
+                CFRunLoopGetMain(),
+                kCFRunLoopDefaultMode,
+            );
+            IOHIDManagerRegisterDeviceMatchingCallback(self.manager, None, std::ptr::null_mut());
+            IOHIDManagerRegisterDeviceRemovalCallback(self.manager, None, std::ptr::null_mut());
+            // Closing a manager that failed to open is harmless.
+            IOHIDManagerClose(self.manager, 0);
+            CFRelease(self.manager);
+        }
+        EXTERNAL_MICE.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Returns whether a mouse other than a trackpad is plugged in, as far as a [`MouseMonitor`] can
+/// tell.
+pub fn external_mouse_connected() -> bool {
+    EXTERNAL_MICE.load(Ordering::Relaxed) > 0
+}
+
+unsafe fn is_external_mouse(device: IOHIDDeviceRef) -> bool {
+    let property = |key: &'static str| {
+        let key = CFString::from_static_string(key);
+        let value = IOHIDDeviceGetProperty(device, key.as_concrete_TypeRef());
+        (!value.is_null()).then(|| CFType::wrap_under_get_rule(value))
+    };
+
+    let built_in = property("Built-In")
+        .and_then(|value| value.downcast::<CFBoolean>())
+        .is_some_and(bool::from);
+    let product = property("Product")
+        .and_then(|value| value.downcast::<CFString>())
+        .map(|product| product.to_string())
+        .unwrap_or_default();
+    counts_as_external_mouse(built_in, &product)
+}
+
+fn counts_as_external_mouse(built_in: bool, product: &str) -> bool {
+    !built_in && !product.contains("Trackpad")
+}
+
+/// Turns the Caps Lock LED of every attached keyboard on or off.
+///
+/// Keyboards without a settable LED are skipped.
+pub fn set_caps_lock_led(on: bool) {
+    let device_matching = matching_dictionary(&[
+        ("DeviceUsagePage", USAGE_PAGE_GENERIC_DESKTOP),
+        ("DeviceUsage", USAGE_KEYBOARD),
+    ]);
+    let element_matching = matching_dictionary(&[("UsagePage", USAGE_PAGE_LED)]);
+
+    unsafe {
+        let manager = IOHIDManagerCreate(kCFAllocatorDefault, 0);
+        if manager.is_null() {
+            return;
+        }
+
+        IOHIDManagerSetDeviceMatching(manager, device_matching.as_concrete_TypeRef());
+        if IOHIDManagerOpen(manager, 0) != 0 {
+            debug!("error opening the HID keyboards, not updating the Caps Lock LED");
+            CFRelease(manager);
+            return;
+        }
+
+        let devices = IOHIDManagerCopyDevices(manager);
+        if !devices.is_null() {
+            let count = CFSetGetCount(devices);
+            let mut values = vec![std::ptr::null(); count as usize];
+            CFSetGetValues(devices, values.as_mut_ptr());
+
+            for device in values {
+                set_device_led(
+                    device as IOHIDDeviceRef,
+                    element_matching.as_concrete_TypeRef(),
+                    USAGE_LED_CAPS_LOCK,
+                    on,
+                );
+            }
+
+            CFRelease(devices.cast());
+        }
+
+        IOHIDManagerClose(manager, 0);
+        CFRelease(manager);
+    }
+}
+
+unsafe fn set_device_led(
+    device: IOHIDDeviceRef,
+    element_matching: CFDictionaryRef,
+    usage: u32,
+    on: bool,
+) {
+    let elements = IOHIDDeviceCopyMatchingElements(device, element_matching, 0);
+    if elements.is_null() {
+        return;
+    }
+
+    for i in 0..CFArrayGetCount(elements) {
+        let element = CFArrayGetValueAtIndex(elements, i) as IOHIDElementRef;
+        if IOHIDElementGetUsage(element) != usage {
+            continue;
+        }
+
+        let value = IOHIDValueCreateWithIntegerValue(kCFAllocatorDefault, element, 0, on as CFIndex);
+        if value.is_null() {
+            continue;
+        }
+
+        // Fails for keyboards that don't let the host drive the LED.
+        let _ = IOHIDDeviceSetValue(device, element, value);
+        CFRelease(value);
+    }
+
+    CFRelease(elements.cast());
+}
+
+fn matching_dictionary(pairs: &[(&'static str, i32)]) -> CFDictionary<CFString, CFNumber> {
+    let pairs: Vec<_> = pairs
+        .iter()
+        .map(|&(key, value)| (CFString::from_static_string(key), CFNumber::from(value)))
+        .collect();
+    CFDictionary::from_CFType_pairs(&pairs)
+}
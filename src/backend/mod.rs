@@ -1,15 +1,28 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use niri_config::{Config, ModKey};
+use anyhow::{anyhow, Context};
+use niri_config::{Color, Config, ModKey};
 use smithay::backend::renderer::gles::GlesRenderer;
 use smithay::output::Output;
+use smithay::reexports::calloop::LoopHandle;
 use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
+use smithay::utils::{Logical, Physical, Point, Size};
 
-use crate::niri::Niri;
+use crate::niri::{Niri, State};
 use crate::utils::id::IdCounter;
 
+#[cfg(target_os = "macos")]
+pub mod appkit;
+#[cfg(target_os = "macos")]
+pub mod hid;
+#[cfg(target_os = "macos")]
+pub mod pasteboard;
+
+pub mod file_drop;
 pub mod winit;
 pub use winit::Winit;
 pub mod winit_input;
@@ -37,6 +50,37 @@ pub enum RenderResult {
 
 pub type IpcOutputMap = HashMap<OutputId, niri_ipc::Output>;
 
+/// Allocator for the buffers that screencasts render into and share with their consumers.
+#[cfg(all(feature = "xdp-gnome-screencast", target_os = "linux"))]
+pub type CastAllocator =
+    smithay::backend::allocator::gbm::GbmDevice<smithay::backend::drm::DrmDeviceFd>;
+/// Allocator for the buffers that screencasts render into and share with their consumers.
+///
+/// There is none on macOS: without PipeWire, nothing could consume the buffers, so screencasts
+/// stop right at the start instead of rendering frames that go nowhere.
+#[cfg(all(feature = "xdp-gnome-screencast", target_os = "macos"))]
+pub type CastAllocator = std::convert::Infallible;
+
+/// Color that the outputs are cleared to before the backdrop covers them.
+///
+/// It only shows where the backdrop doesn't reach, the configurable color is the backdrop's, see
+/// [`configured_clear_color`].
+pub const DEFAULT_CLEAR_COLOR: [f32; 4] = [0.1, 0.1, 0.1, 1.0];
+
+/// Clear color in transparent mode, premultiplied like everything the renderer draws.
+pub const TRANSPARENT_CLEAR_COLOR: [f32; 4] = [0.; 4];
+
+/// Output that [`Backend::new`] gives the headless backend when it picks it on its own.
+const HEADLESS_OUTPUT_SIZE: (i32, i32) = (1920, 1080);
+
+/// Backend to run on, from `MACNIRI_BACKEND`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BackendKind {
+    Auto,
+    Winit,
+    Headless,
+}
+
 static OUTPUT_ID_COUNTER: IdCounter = IdCounter::new();
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -53,6 +97,116 @@ impl OutputId {
 }
 
 impl Backend {
+    /// Creates the backend to run the compositor on.
+    ///
+    /// This picks headless when `prefer_headless` is set, when `MACNIRI_BACKEND=headless`, or when
+    /// there's no GUI session to open windows in, like over SSH and on CI runners. Otherwise it
+    /// picks winit, and falls back to headless if the windows fail to open. `MACNIRI_BACKEND=winit`
+    /// turns off both the detection and the fallback.
+    ///
+    /// With `prefer_headless` the backend has no renderer and no outputs, so that tests can set
+    /// them up. Otherwise headless comes with a 1920×1080 output at 60 Hz.
+    pub fn new(
+        config: Rc<RefCell<Config>>,
+        event_loop: LoopHandle<'static, State>,
+        prefer_headless: bool,
+    ) -> anyhow::Result<Self> {
+        if prefer_headless {
+            return Ok(Backend::Headless(Headless::new()));
+        }
+
+        let kind = configured_backend();
+        match kind {
+            BackendKind::Headless => {
+                info!("using the headless backend as set by MACNIRI_BACKEND");
+                return Self::new_default_headless();
+            }
+            BackendKind::Auto if !has_gui_session() => {
+                info!("no GUI session, using the headless backend");
+                return Self::new_default_headless()
+                    .context("no GUI session for the winit backend, and headless failed");
+            }
+            _ => (),
+        }
+
+        let winit_err = match Winit::new(config, event_loop) {
+            Ok(winit) => return Ok(Backend::Winit(winit)),
+            Err(err) => anyhow!("{err}"),
+        };
+        if kind == BackendKind::Winit {
+            return Err(winit_err.context("error creating the winit backend"));
+        }
+
+        warn!("error creating the winit backend, falling back to headless: {winit_err}");
+        Self::new_default_headless().map_err(|err| {
+            anyhow!(
+                "error creating the winit backend: {winit_err}; \
+                 error creating the headless backend: {err:#}"
+            )
+        })
+    }
+
+    /// Creates the headless backend that stands in for the winit one.
+    ///
+    /// Without any working GL, not even the software renderer, clients still run, but nothing
+    /// gets drawn.
+    fn new_default_headless() -> anyhow::Result<Self> {
+        let (w, h) = HEADLESS_OUTPUT_SIZE;
+        let size = Size::from((w, h));
+        match Headless::with_output(size, 60_000) {
+            Ok(headless) => Ok(Backend::Headless(headless)),
+            Err(err) => {
+                warn!("error creating a renderer, running without one: {err:#}");
+                Ok(Backend::Headless(Headless::without_renderer(size, 60_000)))
+            }
+        }
+    }
+
+    /// Creates a headless backend with a renderer and one output, for tests and benchmarks.
+    ///
+    /// `refresh` is in mHz. Pass the backend to [`State::with_backend`] to get a compositor
+    /// without any windows:
+    ///
+    /// ```no_run
+    /// use std::cell::RefCell;
+    /// use std::rc::Rc;
+    ///
+    /// use niri::backend::Backend;
+    /// use niri::niri::State;
+    /// use niri_config::Config;
+    /// use smithay::reexports::calloop::EventLoop;
+    /// use smithay::reexports::wayland_server::Display;
+    /// use smithay::utils::Size;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut event_loop = EventLoop::try_new()?;
+    /// let backend = Backend::new_headless(Size::from((1920, 1080)), 60_000)?;
+    /// let mut state = State::with_backend(
+    ///     Rc::new(RefCell::new(Config::default())),
+    ///     event_loop.handle(),
+    ///     event_loop.get_signal(),
+    ///     Display::new()?,
+    ///     backend,
+    ///     false,
+    ///     false,
+    /// )?;
+    ///
+    /// let output = state.niri.global_space.outputs().next().unwrap().clone();
+    /// state.niri.queue_redraw(&output);
+    /// event_loop.dispatch(None, &mut state)?;
+    /// state.refresh_and_flush_clients();
+    ///
+    /// let frame = state.backend.headless().copy_framebuffer(&output)?;
+    /// assert_eq!(frame.len(), 1920 * 1080 * 4);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`State::with_backend`]: crate::niri::State::with_backend
+    pub fn new_headless(size: Size<i32, Physical>, refresh: u32) -> anyhow::Result<Self> {
+        Ok(Backend::Headless(Headless::with_output(size, refresh)?))
+    }
+
     pub fn init(&mut self, niri: &mut Niri) {
         let _span = tracy_client::span!("Backend::init");
         match self {
@@ -92,21 +246,16 @@ impl Backend {
 
     pub fn mod_key(&self, config: &Config) -> ModKey {
         match self {
-            Backend::Winit(_) => config.input.mod_key_nested.unwrap_or({
-                if let Some(ModKey::Alt) = config.input.mod_key {
-                    ModKey::Super
-                } else {
-                    ModKey::Alt
-                }
-            }),
+            Backend::Winit(_) => winit::mod_key(&config.input),
             Backend::Headless(_) => config.input.mod_key.unwrap_or(ModKey::Super),
         }
     }
 
-    pub fn change_vt(&mut self, _vt: i32) {
-    }
-
-    pub fn suspend(&mut self) {
+    pub fn change_vt(&mut self, vt: i32) {
+        match self {
+            Backend::Winit(winit) => winit.change_vt(vt),
+            Backend::Headless(_) => (),
+        }
     }
 
     pub fn toggle_debug_tint(&mut self) {
@@ -116,14 +265,46 @@ impl Backend {
         }
     }
 
-    pub fn import_dmabuf(&mut self, _dmabuf: &smithay::backend::allocator::dmabuf::Dmabuf) -> bool {
+    pub fn toggle_fps_overlay(&mut self) {
         match self {
-            Backend::Winit(winit) => false, // winit.import_dmabuf(dmabuf),
-            Backend::Headless(headless) => false, // headless.import_dmabuf(dmabuf),
+            Backend::Winit(winit) => winit.toggle_fps_overlay(),
+            Backend::Headless(_) => (),
         }
     }
 
-    pub fn early_import(&mut self, _surface: &WlSurface) {
+    pub fn reload_shaders(&mut self, niri: &mut Niri) {
+        match self {
+            Backend::Winit(winit) => winit.reload_shaders(niri),
+            Backend::Headless(_) => (),
+        }
+    }
+
+    pub fn toggle_fullscreen(&mut self) {
+        match self {
+            Backend::Winit(winit) => winit.toggle_fullscreen(),
+            Backend::Headless(_) => (),
+        }
+    }
+
+    pub fn set_vsync(&mut self, enabled: bool) {
+        match self {
+            Backend::Winit(winit) => winit.set_vsync(enabled),
+            Backend::Headless(_) => (),
+        }
+    }
+
+    pub fn import_dmabuf(&mut self, dmabuf: &smithay::backend::allocator::dmabuf::Dmabuf) -> bool {
+        match self {
+            Backend::Winit(winit) => winit.import_dmabuf(dmabuf),
+            Backend::Headless(_) => false,
+        }
+    }
+
+    pub fn early_import(&mut self, surface: &WlSurface) {
+        match self {
+            Backend::Winit(winit) => winit.early_import(surface),
+            Backend::Headless(_) => (),
+        }
     }
 
     pub fn ipc_outputs(&self) -> Arc<Mutex<IpcOutputMap>> {
@@ -134,23 +315,69 @@ impl Backend {
     }
 
     #[cfg(feature = "xdp-gnome-screencast")]
-    pub fn gbm_device(
-        &self,
-    ) -> Option<smithay::backend::allocator::gbm::GbmDevice<smithay::backend::drm::DrmDeviceFd>>
-    {
+    pub fn cast_allocator(&self) -> Option<CastAllocator> {
         None
     }
 
-    pub fn set_monitors_active(&mut self, _active: bool) {
+    pub fn set_monitors_active(&mut self, active: bool) {
+        match self {
+            Backend::Winit(winit) => winit.set_monitors_active(active),
+            Backend::Headless(_) => (),
+        }
+    }
+
+    pub fn set_idle_inhibited(&mut self, inhibited: bool) {
+        match self {
+            Backend::Winit(winit) => winit.set_idle_inhibited(inhibited),
+            Backend::Headless(_) => (),
+        }
+    }
+
+    pub fn set_system_shortcuts(&mut self, shortcuts: Option<&[String]>) {
+        match self {
+            Backend::Winit(winit) => winit.set_system_shortcuts(shortcuts),
+            Backend::Headless(_) => (),
+        }
+    }
+
+    pub fn set_borderless(&mut self, borderless: bool) {
+        match self {
+            Backend::Winit(winit) => winit.set_borderless(borderless),
+            Backend::Headless(_) => (),
+        }
+    }
+
+    pub fn set_movable_by_background(&mut self, movable: bool) {
+        match self {
+            Backend::Winit(winit) => winit.set_movable_by_background(movable),
+            Backend::Headless(_) => (),
+        }
+    }
+
+    pub fn warp_pointer(&mut self, niri: &Niri, pos: Point<f64, Logical>) {
+        match self {
+            Backend::Winit(winit) => {
+                winit.warp_pointer(niri, pos);
+            }
+            Backend::Headless(_) => (),
+        }
     }
 
     pub fn set_output_on_demand_vrr(&mut self, niri: &mut Niri, output: &Output, enable_vrr: bool) {
+        match self {
+            Backend::Winit(winit) => winit.set_output_on_demand_vrr(niri, output, enable_vrr),
+            Backend::Headless(_) => (),
+        }
     }
 
     pub fn update_ignored_nodes_config(&mut self, niri: &mut Niri) {
     }
 
     pub fn on_output_config_changed(&mut self, niri: &mut Niri) {
+        match self {
+            Backend::Winit(winit) => winit.on_output_config_changed(niri),
+            Backend::Headless(_) => (),
+        }
     }
 
     // pub fn tty_checked(&mut self) -> Option<&mut Tty> { None }
@@ -172,3 +399,119 @@ impl Backend {
         }
     }
 }
+
+/// Returns the color behind everything set through the environment or the config, if any.
+///
+/// It replaces the overview backdrop color of outputs that don't set their own.
+pub fn configured_clear_color(config: &Config) -> Option<Color> {
+    if let Ok(color) = std::env::var("MACNIRI_CLEAR_COLOR") {
+        match parse_clear_color(&color) {
+            Some(color) => return Some(color),
+            None => warn!("invalid MACNIRI_CLEAR_COLOR {color:?}, expected a color or R, G, B"),
+        }
+    }
+
+    config.debug.clear_color
+}
+
+/// Returns whether the outputs should be transparent, from `MACNIRI_TRANSPARENT` or the config.
+///
+/// Transparent winit windows show the desktop wherever nothing opaque is drawn. Only the startup
+/// value applies to them.
+pub fn configured_transparency(config: &Config) -> bool {
+    let env = std::env::var_os("MACNIRI_TRANSPARENT").is_some_and(|v| v != "0");
+    env || config.debug.winit_transparent
+}
+
+/// Returns the backend set through `MACNIRI_BACKEND`.
+fn configured_backend() -> BackendKind {
+    let Ok(backend) = std::env::var("MACNIRI_BACKEND") else {
+        return BackendKind::Auto;
+    };
+    parse_backend(&backend).unwrap_or_else(|| {
+        warn!("invalid MACNIRI_BACKEND {backend:?}, expected winit, headless or auto");
+        BackendKind::Auto
+    })
+}
+
+fn parse_backend(backend: &str) -> Option<BackendKind> {
+    match backend.trim().to_ascii_lowercase().as_str() {
+        "" | "auto" => Some(BackendKind::Auto),
+        "winit" => Some(BackendKind::Winit),
+        "headless" => Some(BackendKind::Headless),
+        _ => None,
+    }
+}
+
+/// Returns whether windows can open, that is whether we run in a window server session.
+#[cfg(target_os = "macos")]
+fn has_gui_session() -> bool {
+    appkit::has_gui_session()
+}
+
+/// Returns whether windows can open, that is whether there's a display to connect to.
+#[cfg(not(target_os = "macos"))]
+fn has_gui_session() -> bool {
+    std::env::var_os("WAYLAND_DISPLAY").is_some()
+        || std::env::var_os("WAYLAND_SOCKET").is_some()
+        || std::env::var_os("DISPLAY").is_some()
+}
+
+/// Parses a color like `#1a1a1a`, `#1a1a1a80` or `0.1, 0.1, 0.1`, with optional alpha.
+///
+/// Anything but the normalized channels goes to the same parser as the colors in the config.
+fn parse_clear_color(color: &str) -> Option<Color> {
+    let color = color.trim();
+
+    let channels = color
+        .strip_prefix('(')
+        .and_then(|color| color.strip_suffix(')'))
+        .unwrap_or(color)
+        .split(',')
+        .map(|channel| channel.trim().parse::<f32>().ok())
+        .collect::<Option<Vec<_>>>();
+    let Some(channels) = channels else {
+        return color.parse().ok();
+    };
+    if !matches!(channels.len(), 3 | 4) || channels.iter().any(|c| !(0. ..=1.).contains(c)) {
+        return None;
+    }
+
+    let mut rv = [1.; 4];
+    rv[..channels.len()].copy_from_slice(&channels);
+    Some(Color::from_array_unpremul(rv))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clear_color_parsing() {
+        let color = |rgba| Some(Color::from_array_unpremul(rgba));
+        assert_eq!(parse_clear_color("#ff0000"), color([1., 0., 0., 1.]));
+        assert_eq!(parse_clear_color(" #00FF0000 "), color([0., 1., 0., 0.]));
+        assert_eq!(
+            parse_clear_color("0.5, 0.25, 0"),
+            color([0.5, 0.25, 0., 1.])
+        );
+        assert_eq!(
+            parse_clear_color("(1, 1, 1, 0.5)"),
+            color([1., 1., 1., 0.5])
+        );
+        assert_eq!(parse_clear_color("red"), color([1., 0., 0., 1.]));
+
+        assert_eq!(parse_clear_color("#gg0000"), None);
+        assert_eq!(parse_clear_color("0.5, 0.5"), None);
+        assert_eq!(parse_clear_color("1.5, 0, 0"), None);
+    }
+
+    #[test]
+    fn backend_parsing() {
+        assert_eq!(parse_backend("headless"), Some(BackendKind::Headless));
+        assert_eq!(parse_backend(" Winit "), Some(BackendKind::Winit));
+        assert_eq!(parse_backend("auto"), Some(BackendKind::Auto));
+        assert_eq!(parse_backend(""), Some(BackendKind::Auto));
+        assert_eq!(parse_backend("tty"), None);
+    }
+}
@@ -0,0 +1,221 @@
+//! Clipboard sharing between Wayland clients and macOS apps through `NSPasteboard`.
+//!
+//! The general pasteboard has no change notifications, so the winit backend polls its change
+//! count and offers new contents as the Wayland selection. Selections that Wayland clients set
+//! are offered on the pasteboard. Either way, the contents are only read once someone pastes.
+
+use std::cell::RefCell;
+use std::sync::OnceLock;
+
+use objc::declare::ClassDecl;
+use objc::runtime::{Class, Object, Sel, BOOL, NO};
+use objc::{msg_send, sel, sel_impl};
+
+use super::appkit::{ns_string, rust_string};
+
+/// Pasteboard types that we share, with the MIME types that Wayland clients see them as.
+///
+/// The first MIME type is the one to request from Wayland clients. Earlier entries win when the
+/// pasteboard has several of these types.
+const TYPES: &[(&str, &[&str])] = &[
+    (
+        "public.utf8-plain-text",
+        &[
+            "text/plain;charset=utf-8",
+            "text/plain",
+            "UTF8_STRING",
+            "STRING",
+            "TEXT",
+        ],
+    ),
+    ("public.png", &["image/png"]),
+    ("public.tiff", &["image/tiff"]),
+    ("public.jpeg", &["image/jpeg"]),
+    ("com.compuserve.gif", &["image/gif"]),
+    ("public.html", &["text/html"]),
+    ("public.url", &["text/uri-list"]),
+];
+
+/// Returns the change count of the general pasteboard, which goes up with every change.
+pub fn change_count() -> Option<isize> {
+    let pasteboard = general_pasteboard()?;
+    let count: isize = unsafe { msg_send![pasteboard, changeCount] };
+    Some(count)
+}
+
+/// Returns the MIME types to offer for the pasteboard type.
+pub fn mime_types(pasteboard_type: &str) -> &'static [&'static str] {
+    TYPES
+        .iter()
+        .find(|(ty, _)| *ty == pasteboard_type)
+        .map_or(&[], |(_, mime_types)| mime_types)
+}
+
+/// Picks the pasteboard types to offer a selection with these MIME types as.
+///
+/// Returns each pasteboard type with the MIME type to request from the client.
+pub fn pick_types(offered: &[String]) -> Vec<(&'static str, String)> {
+    TYPES
+        .iter()
+        .filter_map(|(ty, mime_types)| {
+            mime_types
+                .iter()
+                .find(|mime_type| offered.iter().any(|offered| offered == *mime_type))
+                .map(|mime_type| (*ty, (*mime_type).to_owned()))
+        })
+        .collect()
+}
+
+/// Returns the shared types on the general pasteboard.
+pub fn available_types() -> Vec<&'static str> {
+    let Some(pasteboard) = general_pasteboard() else {
+        return Vec::new();
+    };
+
+    unsafe {
+        let types: *mut Object = msg_send![pasteboard, types];
+        if types.is_null() {
+            return Vec::new();
+        }
+
+        TYPES
+            .iter()
+            .map(|(ty, _)| *ty)
+            .filter(|ty| {
+                let contains: BOOL = msg_send![types, containsObject: ns_string(ty)];
+                contains != NO
+            })
+            .collect()
+    }
+}
+
+/// Reads the general pasteboard as the MIME type.
+///
+/// Apps can provide their data only once it's asked for, then this waits for them.
+pub fn read(mime_type: &str) -> Option<Vec<u8>> {
+    let (ty, _) = TYPES
+        .iter()
+        .find(|(_, mime_types)| mime_types.contains(&mime_type))?;
+    let pasteboard = general_pasteboard()?;
+
+    unsafe {
+        let data: *mut Object = msg_send![pasteboard, dataForType: ns_string(ty)];
+        if data.is_null() {
+            return None;
+        }
+
+        let bytes: *const u8 = msg_send![data, bytes];
+        let len: usize = msg_send![data, length];
+        let contents = if len == 0 {
+            Vec::new()
+        } else {
+            std::slice::from_raw_parts(bytes, len).to_vec()
+        };
+        Some(contents)
+    }
+}
+
+type Provider = Box<dyn Fn(&'static str) -> Option<Vec<u8>>>;
+
+thread_local! {
+    /// Provides the data of the types that we declared last, see [`offer()`].
+    static PROVIDER: RefCell<Option<Provider>> = const { RefCell::new(None) };
+}
+
+/// Replaces the general pasteboard contents with the pasteboard types, returning the change count
+/// after the change.
+///
+/// Nothing gets read up front: `provide` runs on the main thread once an app pastes one of the
+/// types, and returns its data.
+pub fn offer(
+    types: &[&'static str],
+    provide: impl Fn(&'static str) -> Option<Vec<u8>> + 'static,
+) -> Option<isize> {
+    let pasteboard = general_pasteboard()?;
+    let owner = provider_owner()?;
+
+    unsafe {
+        let class = Class::get("NSMutableArray")?;
+        let array: *mut Object = msg_send![class, array];
+        for ty in types {
+            let _: () = msg_send![array, addObject: ns_string(ty)];
+        }
+        let _: isize = msg_send![pasteboard, declareTypes: array owner: owner];
+    }
+    PROVIDER.with_borrow_mut(|provider| *provider = Some(Box::new(provide)));
+
+    change_count()
+}
+
+/// Returns the pasteboard owner that hands out the data of [`PROVIDER`].
+fn provider_owner() -> Option<*mut Object> {
+    extern "C" fn provide_data(
+        _this: &Object,
+        _cmd: Sel,
+        pasteboard: *mut Object,
+        ty: *mut Object,
+    ) {
+        let Some(ty) = (unsafe { rust_string(ty) }) else {
+            return;
+        };
+        let Some((ty, _)) = TYPES.iter().find(|(shared, _)| *shared == ty) else {
+            return;
+        };
+        let Some(contents) =
+            PROVIDER.with_borrow(|provider| provider.as_ref().and_then(|provide| provide(ty)))
+        else {
+            return;
+        };
+
+        unsafe {
+            let Some(class) = Class::get("NSData") else {
+                return;
+            };
+            let data: *mut Object =
+                msg_send![class, dataWithBytes: contents.as_ptr() length: contents.len()];
+            let _: BOOL = msg_send![pasteboard, setData: data forType: ns_string(ty)];
+        }
+    }
+
+    type Callback = extern "C" fn(&Object, Sel, *mut Object, *mut Object);
+
+    // Objective-C objects aren't Send, so keep the address.
+    static OWNER: OnceLock<Option<usize>> = OnceLock::new();
+    let owner = OWNER.get_or_init(|| unsafe {
+        let superclass = Class::get("NSObject")?;
+        let mut decl = ClassDecl::new("NiriPasteboardProvider", superclass)?;
+        decl.add_method(
+            sel!(pasteboard:provideDataForType:),
+            provide_data as Callback,
+        );
+        let class = decl.register();
+
+        // The owner lives for as long as the process.
+        let owner: *mut Object = msg_send![class, new];
+        Some(owner as usize)
+    });
+    owner.map(|owner| owner as *mut Object)
+}
+
+fn general_pasteboard() -> Option<*mut Object> {
+    let class = Class::get("NSPasteboard")?;
+    let pasteboard: *mut Object = unsafe { msg_send![class, generalPasteboard] };
+    (!pasteboard.is_null()).then_some(pasteboard)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_every_shared_type() {
+        let offered = ["image/png", "text/plain", "application/x-custom"].map(String::from);
+        assert_eq!(
+            pick_types(&offered),
+            [
+                ("public.utf8-plain-text", String::from("text/plain")),
+                ("public.png", String::from("image/png")),
+            ]
+        );
+    }
+}
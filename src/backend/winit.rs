@@ -1,69 +1,331 @@
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 use std::ffi::CString;
+use std::fs::File;
+use std::io::{self, Read as _};
+use std::os::fd::AsRawFd as _;
+use std::path::PathBuf;
+use std::iter;
 
-use niri_config::{Config, OutputName};
+use anyhow::Context as _;
+
+use niri_config::input::{AccelProfile, ClickMethod, Input};
+use niri_config::debug::{WinitGpu, WinitThreeFingerDrag, WinitWindowSize};
+use niri_config::{Config, ModKey, OutputName};
 use smithay::backend::allocator::dmabuf::Dmabuf;
 use smithay::backend::renderer::damage::OutputDamageTracker;
+use smithay::backend::renderer::utils::{import_surface, RendererSurfaceStateUserData};
+use smithay::backend::renderer::{buffer_type, BufferType};
 use smithay::backend::renderer::{
     gles::GlesRenderer,
     Renderer,
     Bind,
+    DebugFlags,
 };
-use smithay::output::{Mode, Output, PhysicalProperties, Subpixel};
+use smithay::output::{Mode, Output, PhysicalProperties, Scale, Subpixel};
+use smithay::utils::{IsAlive, Logical, Physical, Point, Size, Transform};
 
 use calloop::{LoopHandle, RegistrationToken, EventSource, Interest, PostAction};
 use calloop::ping::{Ping, PingSource, make_ping};
-use calloop::channel::{Channel, Sender, channel};
+use calloop::channel::{Channel, channel};
+use calloop::timer::{TimeoutAction, Timer};
 
 use smithay::reexports::wayland_protocols::wp::presentation_time::server::wp_presentation_feedback;
+use smithay::input::pointer::{CursorIcon, CursorImageStatus};
+use smithay::input::Seat;
+use smithay::reexports::rustix::pipe::{pipe_with, PipeFlags};
+use smithay::reexports::wayland_server::protocol::wl_surface::WlSurface;
+use smithay::reexports::wayland_server::DisplayHandle;
+use smithay::wayland::compositor::with_states;
+use smithay::wayland::pointer_constraints::{with_pointer_constraint, PointerConstraint};
 use smithay::wayland::presentation::Refresh;
+use smithay::wayland::selection::data_device::{
+    request_data_device_client_selection, set_data_device_selection,
+};
+use smithay::wayland::shell::wlr_layer::Layer;
+use smithay::wayland::tablet_manager::{TabletDescriptor, TabletSeatTrait};
 
-use winit::event::{Event, WindowEvent};
+use winit::dpi::{LogicalPosition, PhysicalPosition, PhysicalSize};
+use winit::event::{
+    DeviceEvent, ElementState, Event, MouseButton as WinitMouseButton, MouseScrollDelta, Touch,
+    TouchPhase, WindowEvent,
+};
 use winit::event_loop::{EventLoop, ControlFlow};
+use winit::keyboard::{KeyCode, ModifiersState, PhysicalKey};
 use winit::platform::pump_events::EventLoopExtPumpEvents;
 use winit::platform::scancode::PhysicalKeyExtScancode; // Needed for scancode
+use winit::platform::macos::MonitorHandleExtMacOS;
 
 use calloop::{Readiness, Token, TokenFactory};
-use winit::window::Window;
+use winit::window::{Fullscreen, Window, WindowId};
 use glutin::prelude::GlDisplay; 
 use glutin::display::GetGlDisplay;
 use glutin::context::GlContext;
 
-use super::{IpcOutputMap, OutputId, RenderResult};
-use crate::backend::Backend;
-use crate::niri::{Niri, RedrawState, State};
+use smithay::backend::input::{InputEvent, ProximityState};
+
+use super::appkit::{self, ScrollPhase};
+use super::file_drop;
+use super::hid;
+use super::pasteboard;
+use super::{
+    configured_transparency, Backend, IpcOutputMap, OutputId, RenderResult, DEFAULT_CLEAR_COLOR,
+    TRANSPARENT_CLEAR_COLOR,
+};
+use crate::backend::winit_input::{
+    axis_amounts, clickfinger_button, RelativePosition, WinitGesturePinchBeginEvent,
+    WinitGesturePinchEndEvent, WinitGesturePinchUpdateEvent, WinitGestureSwipeBeginEvent,
+    WinitGestureSwipeEndEvent, WinitGestureSwipeUpdateEvent, WinitInput, WinitKeyboardInputEvent,
+    WinitMouseInputEvent, WinitMouseMovedEvent, WinitMouseRelativeEvent, WinitMouseWheelEvent,
+    WinitTabletToolAxisEvent, WinitTabletToolButtonEvent, WinitTabletToolProximityEvent,
+    WinitTabletToolTipEvent, WinitTouchCancelEvent, WinitTouchDownEvent, WinitTouchFrameEvent,
+    WinitTouchMotionEvent, WinitTouchUpEvent, WinitVirtualDevice,
+};
+use crate::handlers::SelectionData;
+use crate::input::{key_repeat_info, modifiers_from_state};
+use crate::input_shim::{Device as ShimDevice, TapButtonMap};
+use crate::niri::{Niri, OutputRenderElements, PointerVisibility, RedrawState, State};
 use crate::render_helpers::debug::draw_damage;
 use crate::render_helpers::{resources, shaders, RenderTarget};
-use crate::utils::{get_monotonic_time, logical_output};
-use crate::backend::cocoa_renderer::GlRenderer as CocoaWindowHandle;
+use crate::ui::fps_overlay::FpsOverlay;
+use crate::utils::scale::{closest_representable_scale, PreferredScale};
+use crate::utils::{get_monotonic_time, logical_output, LoopHandleExt as _};
+use crate::backend::cocoa_renderer::{ContextError, GlRenderer as CocoaWindowHandle};
 
-// Serializable version of winit events that can be sent across threads
-#[derive(Debug, Clone)]
+/// A winit event that niri handles, along with what AppKit reported about it.
+///
+/// AppKit only describes the event that it is dispatching, like the scroll phase or the fingers on
+/// the trackpad, so [`WinitEventMsg::from_winit`] reads all of that in the winit callback. The
+/// messages then go over a channel to [`handle_event`], which applies them to the state.
+#[derive(Debug, Clone, PartialEq)]
 pub enum WinitEventMsg {
-    Resized(u32, u32),
+    Resized {
+        window_id: WindowId,
+        size: PhysicalSize<u32>,
+    },
+    Moved {
+        window_id: WindowId,
+    },
+    ScaleFactorChanged {
+        window_id: WindowId,
+        scale_factor: f64,
+    },
     CloseRequested,
-    RedrawRequested,
-    Focused(bool),
-    KeyboardInput { scancode: u32, pressed: bool },
-    CursorMoved { x: f64, y: f64 },
-    MouseButton { button: u32, pressed: bool },
-    MouseWheel { delta_x: f64, delta_y: f64 },
-    ScaleFactorChanged(f64),
-    Occluded(bool),
-    // Add more as needed
+    RedrawRequested {
+        window_id: WindowId,
+    },
+    /// Something covered the window completely, or it got minimized or hidden.
+    Covered {
+        window_id: WindowId,
+    },
+    /// The window became visible again after something covered it completely.
+    Uncovered {
+        window_id: WindowId,
+    },
+    ModifiersChanged {
+        state: ModifiersState,
+        device_flags: Option<usize>,
+    },
+    KeyboardInput {
+        key: PhysicalKey,
+        state: ElementState,
+        /// When winit delivered the event, for telling duplicates apart.
+        time: Instant,
+        /// When AppKit generated the event, see [`appkit::current_event_time`].
+        event_time: Option<Duration>,
+    },
+    Focused {
+        focused: bool,
+        /// Modifier flags held on focus, see [`appkit::device_modifier_flags`].
+        device_flags: Option<usize>,
+    },
+    CursorMoved {
+        window_id: WindowId,
+        position: PhysicalPosition<f64>,
+        /// Fingers on the trackpad, see [`appkit::trackpad_touch_count`].
+        touch_count: u32,
+        /// When AppKit generated the event, see [`appkit::current_event_time`].
+        event_time: Option<Duration>,
+        /// Pen state when a graphics tablet moved the cursor, see
+        /// [`appkit::current_event_tablet_point`].
+        tablet: Option<appkit::TabletPoint>,
+    },
+    CursorEntered {
+        window_id: WindowId,
+    },
+    CursorLeft {
+        window_id: WindowId,
+    },
+    MouseButton {
+        window_id: WindowId,
+        button: WinitMouseButton,
+        state: ElementState,
+        /// Where the click happened, see [`appkit::current_event_location_in_window`].
+        location: Option<(f64, f64)>,
+        /// Fingers on the trackpad, see [`appkit::trackpad_touch_count`].
+        touch_count: u32,
+        /// When AppKit generated the event, see [`appkit::current_event_time`].
+        event_time: Option<Duration>,
+        /// Pen state when a graphics tablet clicked, see
+        /// [`appkit::current_event_tablet_point`].
+        tablet: Option<appkit::TabletPoint>,
+    },
+    MouseWheel {
+        delta: MouseScrollDelta,
+        phase: TouchPhase,
+        /// Fingers on the trackpad, see [`appkit::trackpad_touch_count`].
+        touch_count: u32,
+        scroll_phase: ScrollPhase,
+        inverted: bool,
+        /// When AppKit generated the event, see [`appkit::current_event_time`].
+        event_time: Option<Duration>,
+    },
+    PinchGesture {
+        delta: f64,
+        phase: TouchPhase,
+        /// When AppKit generated the event, see [`appkit::current_event_time`].
+        event_time: Option<Duration>,
+    },
+    Touch {
+        window_id: WindowId,
+        touch: Touch,
+        /// When AppKit generated the event, see [`appkit::current_event_time`].
+        event_time: Option<Duration>,
+    },
+    /// A file that a macOS app drags over the window, one message per file.
+    HoveredFile {
+        path: PathBuf,
+    },
+    /// A file dropped on the window, one message per file.
+    DroppedFile {
+        path: PathBuf,
+    },
+    /// The files dragged over the window left it without a drop.
+    HoveredFileCancelled,
+    MouseMotion {
+        delta: (f64, f64),
+        /// Fingers on the trackpad, zero when the motion comes from a mouse.
+        touch_count: u32,
+        /// When AppKit generated the event, see [`appkit::current_event_time`].
+        event_time: Option<Duration>,
+    },
+    AboutToWait,
+}
+
+impl WinitEventMsg {
+    /// Translates a winit event, or returns `None` for events that niri ignores.
+    ///
+    /// Has to be called from the winit callback, while AppKit still dispatches the event.
+    pub fn from_winit(event: Event<()>) -> Option<Self> {
+        let msg = match event {
+            Event::WindowEvent { window_id, event } => match event {
+                WindowEvent::Resized(size) => Self::Resized { window_id, size },
+                WindowEvent::Moved(_) => Self::Moved { window_id },
+                WindowEvent::ScaleFactorChanged { scale_factor, .. } => Self::ScaleFactorChanged {
+                    window_id,
+                    scale_factor,
+                },
+                WindowEvent::CloseRequested => Self::CloseRequested,
+                WindowEvent::RedrawRequested => Self::RedrawRequested { window_id },
+                WindowEvent::Occluded(true) => Self::Covered { window_id },
+                WindowEvent::Occluded(false) => Self::Uncovered { window_id },
+                WindowEvent::ModifiersChanged(modifiers) => Self::ModifiersChanged {
+                    state: modifiers.state(),
+                    device_flags: appkit::device_modifier_flags(),
+                },
+                WindowEvent::KeyboardInput { event, .. } => {
+                    // Filter out repeat events - Smithay handles repeats internally.
+                    // Not filtering these causes event queue flooding when keys are held.
+                    if event.repeat {
+                        return None;
+                    }
+
+                    Self::KeyboardInput {
+                        key: event.physical_key,
+                        state: event.state,
+                        time: Instant::now(),
+                        event_time: appkit::current_event_time(),
+                    }
+                }
+                WindowEvent::Focused(focused) => Self::Focused {
+                    focused,
+                    // Only what is held on the way back in matters.
+                    device_flags: if focused {
+                        appkit::device_modifier_flags()
+                    } else {
+                        None
+                    },
+                },
+                WindowEvent::CursorMoved { position, .. } => Self::CursorMoved {
+                    window_id,
+                    position,
+                    touch_count: appkit::trackpad_touch_count(),
+                    event_time: appkit::current_event_time(),
+                    tablet: appkit::current_event_tablet_point(),
+                },
+                WindowEvent::CursorEntered { .. } => Self::CursorEntered { window_id },
+                WindowEvent::CursorLeft { .. } => Self::CursorLeft { window_id },
+                WindowEvent::MouseInput { state, button, .. } => Self::MouseButton {
+                    window_id,
+                    button,
+                    state,
+                    location: appkit::current_event_location_in_window(),
+                    touch_count: appkit::trackpad_touch_count(),
+                    event_time: appkit::current_event_time(),
+                    tablet: appkit::current_event_tablet_point(),
+                },
+                WindowEvent::MouseWheel { delta, phase, .. } => Self::MouseWheel {
+                    delta,
+                    phase,
+                    touch_count: appkit::trackpad_touch_count(),
+                    scroll_phase: appkit::current_event_scroll_phase(),
+                    // AppKit already applies the natural scrolling setting (and tracking speed)
+                    // to the deltas, so they match other macOS apps as is. Only report the
+                    // direction so that clients can tell.
+                    inverted: appkit::current_event_direction_inverted(),
+                    event_time: appkit::current_event_time(),
+                },
+                WindowEvent::PinchGesture { delta, phase, .. } => Self::PinchGesture {
+                    delta,
+                    phase,
+                    event_time: appkit::current_event_time(),
+                },
+                WindowEvent::Touch(touch) => Self::Touch {
+                    window_id,
+                    touch,
+                    event_time: appkit::current_event_time(),
+                },
+                WindowEvent::HoveredFile(path) => Self::HoveredFile { path },
+                WindowEvent::DroppedFile(path) => Self::DroppedFile { path },
+                WindowEvent::HoveredFileCancelled => Self::HoveredFileCancelled,
+                _ => return None,
+            },
+            Event::DeviceEvent {
+                event: DeviceEvent::MouseMotion { delta },
+                ..
+            } if appkit::current_event_tablet_point().is_none() => Self::MouseMotion {
+                delta,
+                touch_count: appkit::trackpad_touch_count(),
+                event_time: appkit::current_event_time(),
+            },
+            Event::AboutToWait => Self::AboutToWait,
+            _ => return None,
+        };
+        Some(msg)
+    }
 }
 
 pub struct WinitEventSource {
-    event_loop: EventLoop<()>,
+    /// Shared with [`Winit`], which opens windows on displays that get connected.
+    event_loop: Rc<RefCell<EventLoop<()>>>,
     ping: PingSource,
 }
 
 impl WinitEventSource {
-    pub fn new(event_loop: EventLoop<()>) -> (Self, Ping) {
+    pub fn new(event_loop: Rc<RefCell<EventLoop<()>>>) -> (Self, Ping) {
         let (ping_sender, ping) = make_ping().unwrap();
         (Self { 
             event_loop,
@@ -92,7 +354,7 @@ impl EventSource for WinitEventSource {
 
         let timeout = Some(Duration::ZERO);
         #[allow(deprecated)]
-        self.event_loop.pump_events(timeout, |event, target| {
+        self.event_loop.borrow_mut().pump_events(timeout, |event, target| {
             
             callback(event, &mut ());
             target.set_control_flow(ControlFlow::Wait);
@@ -129,15 +391,118 @@ impl EventSource for WinitEventSource {
 
 pub struct Winit {
     config: Rc<RefCell<Config>>,
-    output: Output,
-    cocoa_window: CocoaWindowHandle,
+    /// Event loop to open windows with, it's pumped by [`WinitEventSource`].
+    winit_loop: Rc<RefCell<EventLoop<()>>>,
+    /// Title of the output windows.
+    title: String,
+    /// Dropped before the outputs, it frees its GL objects through their contexts.
     gles_renderer: GlesRenderer,
-    damage_tracker: OutputDamageTracker,
+    /// One output per display, the first one in the window we started with.
+    outputs: Vec<WinitOutput>,
+    /// Number in the connector name of the next output, see [`Winit::sync_outputs`].
+    next_output_number: usize,
+    /// Whether buffer swaps should wait for the display refresh, see [`Winit::set_vsync`].
+    vsync: bool,
+    /// Last display refresh that the display link saw, see [`Winit::set_display_timing`].
+    display_timing: Option<DisplayTiming>,
+    /// Color that the windows are cleared to, transparent for transparent windows.
+    clear_color: [f32; 4],
+    /// Whether the windows show the desktop behind them, see [`configured_transparency`].
+    transparent: bool,
+    /// Whether the windows go without a title bar, see [`Winit::set_borderless`].
+    borderless: bool,
     ipc_outputs: Arc<Mutex<IpcOutputMap>>,
     ping_sender: calloop::ping::Ping,
-    last_modifiers: winit::keyboard::ModifiersState,
-    // Debounce: Track last event time per scancode to filter buffered event bursts
-    last_key_time: std::cell::RefCell<HashMap<u32, std::time::Instant>>,
+    last_modifiers: SidedModifiers,
+    is_focused: bool,
+    /// Finger count of the trackpad swipe in progress, if any.
+    swipe_fingers: Option<u32>,
+    /// Three-finger drag moving a window, see [`handle_window_drag`].
+    window_drag: Option<WindowDrag>,
+    /// Whether Mod and a drag over empty space move the output window, see
+    /// [`Winit::set_movable_by_background`].
+    movable_background: bool,
+    /// Drag moving an output window, see [`handle_background_drag`].
+    background_drag: Option<BackgroundDrag>,
+    /// Accumulated scale of the trackpad pinch in progress, if any.
+    pinch_scale: Option<f64>,
+    /// Button that the held left click was sent as, see [`Winit::trackpad_click_button`].
+    left_click_as: Option<WinitMouseButton>,
+    /// Pixel scrolling left over from the last discrete steps.
+    scroll_v120: V120Accumulator,
+    /// Where the current trackpad scroll is, to send its axis stop once.
+    scroll_gesture: ScrollGesture,
+    /// Timer sending the axis stop of a trackpad scroll unless momentum follows.
+    scroll_stop_timer: Option<RegistrationToken>,
+    /// Window that the cursor is over, if any.
+    hovered_window: Option<WindowId>,
+    /// Whether the cursor left a window since the last batch of events.
+    cursor_left: bool,
+    /// Whether touch events were sent since the last touch frame.
+    touch_frame_pending: bool,
+    debug_tint: bool,
+    /// Whether the windows show the frame time overlay, see [`Winit::toggle_fps_overlay`].
+    fps_overlay: bool,
+    /// Whether rendering is paused because the system or the displays are asleep.
+    suspended: bool,
+    /// Whether the monitors are on, see [`Winit::set_monitors_active`].
+    monitors_active: bool,
+    /// Whether clients inhibit idle, see [`Winit::set_idle_inhibited`].
+    idle_inhibited: bool,
+    /// Keeps the displays on while the monitors are active and clients inhibit idle.
+    display_sleep_assertion: Option<appkit::DisplaySleepAssertion>,
+    /// VT that the user last switched to, see [`Winit::change_vt`].
+    active_vt: i32,
+    /// Filters key events that AppKit duplicated in a burst.
+    key_bursts: KeyBurstFilter,
+    /// When the last key went down, for disable-while-typing.
+    last_key_press: Option<Instant>,
+    /// Trackpad buttons whose press disable-while-typing held back, so that their releases go
+    /// too.
+    typing_held_buttons: HashSet<WinitMouseButton>,
+    /// Pasteboard change count that the Wayland selection is up to date with.
+    pasteboard_change_count: Option<isize>,
+    /// Reports the Caps Lock key itself, as opposed to AppKit's Caps Lock toggle.
+    caps_lock_monitor: Option<hid::CapsLockMonitor>,
+    /// Tells when a mouse is plugged in, for `disabled-on-external-mouse`.
+    _mouse_monitor: Option<hid::MouseMonitor>,
+    /// macOS cursor last set on the windows.
+    os_cursor: Option<OsCursor>,
+    /// How the cursor is held for a pointer constraint, see [`sync_pointer_constraint`].
+    cursor_hold: Option<CursorHold>,
+    /// Output of the window that the touch or the tablet event being processed happened in.
+    event_output: Option<Output>,
+    /// Tablet tool in proximity, see [`handle_tablet_motion`].
+    tablet_tool: Option<TabletToolProximity>,
+    /// Files that a macOS app drags over the windows, see [`sync_file_drag`].
+    dragged_files: Vec<PathBuf>,
+    /// Whether the dragged files are offered to the clients.
+    file_drag_active: bool,
+    /// How the file drag ended in the last batch of events: `true` for a drop.
+    file_drag_end: Option<bool>,
+    /// GL context rebuilds since the last frame, see [`Winit::recover_context`].
+    context_rebuilds: u32,
+    /// GPU that the context renders on, see [`Winit::refresh_surfaces`].
+    gpu: String,
+    /// Keeps the discrete GPU on when configured, see [`configured_gpu`].
+    _gpu_pin: Option<appkit::DiscreteGpuPin>,
+}
+
+impl Drop for Winit {
+    fn drop(&mut self) {
+        // The renderer goes first and frees its textures and shaders, which needs a current
+        // context. After a lost context there's nothing left to free.
+        if let Some(output) = self.outputs.first() {
+            if let Err(err) = output.cocoa_window.make_current() {
+                debug!("error making the context current for teardown: {err}");
+            }
+        }
+
+        // Give the cursor back in case a pointer constraint still holds it.
+        if self.cursor_hold.is_some() {
+            appkit::set_cursor_follows_mouse(true);
+        }
+    }
 }
 
 impl Winit {
@@ -149,8 +514,10 @@ impl Winit {
 
         use winit::platform::macos::{EventLoopBuilderExtMacOS, ActivationPolicy};
 
+        // We install our own menu bar below.
         let winit_loop = winit::event_loop::EventLoopBuilder::new()
             .with_activation_policy(ActivationPolicy::Regular)
+            .with_default_menu(false)
             .build()?;
 
         // Force activation to ensure we get focus
@@ -164,382 +531,236 @@ impl Winit {
 
 
 
-        let cocoa_window = CocoaWindowHandle::new(&winit_loop, "niri (macOS)", 1600, 1000)
-            .map_err(|e| format!("Failed to initialize Cocoa backend: {}", e))?;
+        // The Info.plist that build.rs embeds lets the contexts stay on the integrated GPU.
+        let gpu_pin = match configured_gpu(&config.borrow()) {
+            WinitGpu::Integrated => None,
+            WinitGpu::Discrete => appkit::DiscreteGpuPin::new()
+                .map_err(|err| warn!("error keeping the discrete GPU on: {err}"))
+                .ok(),
+        };
+
+        let title = configured_window_title(&config.borrow());
+        if let Some(title) = &title {
+            appkit::set_app_name(title);
+        }
+        let title = title.as_deref().unwrap_or(DEFAULT_WINDOW_TITLE);
+        appkit::install_menu_bar(title);
+
+        let size = configured_window_size(&config.borrow());
+        let msaa_samples = config.borrow().debug.winit_msaa_samples.unwrap_or(0);
+        let transparent = configured_transparency(&config.borrow());
+        let borderless = config.borrow().debug.winit_borderless;
+        let cocoa_window = CocoaWindowHandle::new(
+            &winit_loop,
+            title,
+            size,
+            msaa_samples,
+            transparent,
+            borderless,
+        )
+        .map_err(|e| format!("Failed to initialize Cocoa backend: {}", e))?;
+
+        // Open a window on every other display too, left to right to match the default output
+        // placement.
+        let primary_monitor = cocoa_window.window.current_monitor();
+        #[allow(deprecated)]
+        let mut monitors: Vec<_> = winit_loop
+            .available_monitors()
+            .filter(|monitor| Some(monitor) != primary_monitor.as_ref())
+            .collect();
+        monitors.sort_by_key(|monitor| {
+            let pos = monitor.position();
+            (pos.x, pos.y)
+        });
+
+        let mut windows = vec![cocoa_window];
+        for monitor in monitors {
+            match CocoaWindowHandle::new_on_monitor(
+                &winit_loop,
+                title,
+                &monitor,
+                &windows[0].gl_context,
+                transparent,
+                borderless,
+            ) {
+                Ok(window) => windows.push(window),
+                Err(err) => warn!("error opening a window on {:?}: {err}", monitor.name()),
+            }
+        }
+
+        appkit::observe_sleep();
+        appkit::observe_displays();
+        appkit::intercept_terminate();
+        let shortcuts = config.borrow().debug.winit_system_shortcuts.clone();
+        appkit::set_system_shortcuts(system_shortcuts(shortcuts.as_deref()));
+        appkit::intercept_key_equivalents();
+        appkit::track_tablet_proximity();
+        appkit::track_trackpad_taps();
+
+        // AppKit only reports motion with the macOS acceleration applied.
+        {
+            let config = config.borrow();
+            let flat = Some(AccelProfile::Flat);
+            if config.input.mouse.accel_profile == flat
+                || config.input.touchpad.accel_profile == flat
+            {
+                warn!("the flat accel-profile is unsupported, macOS accelerates the pointer");
+            }
+        }
+        appkit::refresh_key_repeat();
+
+        // Every new window takes the focus and makes its context current, so give both back to
+        // the first one.
+        windows[0].window.focus_window();
+        windows[0].make_current()?;
 
         // Initialize GlesRenderer using the custom macOS constructor
-        let display = cocoa_window.gl_context.display();
+        let display = windows[0].gl_context.display();
         let renderer = unsafe {
              GlesRenderer::new_with_loader(|s| {
                  let symbol = CString::new(s).unwrap();
                  display.get_proc_address(symbol.as_c_str()).cast()
-             })?
-        };
-
-        println!("DEBUG: Initialized GlesRenderer on macOS!");
-
-        let output = Output::new(
-            "winit".to_string(),
-            PhysicalProperties {
-                size: (0, 0).into(),
-                subpixel: Subpixel::Unknown,
-                make: "Smithay".into(),
-                model: "Winit".into(),
-                serial_number: "Unknown".into(),
-            },
-        );
-
-        // Access inner window size from cocoa_window
-        let window_size = cocoa_window.width; // u32
-        let height = cocoa_window.height;     // u32
-
-        let mode = Mode {
-            size: (window_size as i32, height as i32).into(),
-            refresh: 60_000,
+             })
+             .map_err(|err| format!("error creating the renderer: {err}"))?
         };
-        output.change_current_state(Some(mode), None, None, None);
-        output.set_preferred(mode);
 
-        output.user_data().insert_if_missing(|| OutputName {
-            connector: "winit".to_string(),
-            make: Some("Smithay".to_string()),
-            model: Some("Winit".to_string()),
-            serial: None,
-        });
+        debug!("initialized the GlesRenderer");
 
-        let physical_properties = output.physical_properties();
-        let ipc_outputs = Arc::new(Mutex::new(HashMap::from([(
-            OutputId::next(),
-            niri_ipc::Output {
-                name: output.name(),
-                make: physical_properties.make,
-                model: physical_properties.model,
-                serial: None,
-                physical_size: None,
-                modes: vec![niri_ipc::Mode {
-                    width: window_size as u16,
-                    height: height as u16,
-                    refresh_rate: 60_000,
-                    is_preferred: true,
-                }],
-                current_mode: Some(0),
-                is_custom_mode: true,
-                vrr_supported: false,
-                vrr_enabled: false,
-                logical: Some(logical_output(&output)),
-            },
-        )])));
+        let vsync = !config.borrow().debug.winit_disable_vsync;
+        for window in &mut windows {
+            window.set_vsync(vsync);
+        }
 
-        let damage_tracker = OutputDamageTracker::from_output(&output);
+        let gpu = windows[0].gpu_name();
+        let next_output_number = windows.len() + 1;
+        let mut outputs = Vec::new();
+        let mut ipc_outputs = HashMap::new();
+        for (idx, cocoa_window) in windows.into_iter().enumerate() {
+            // The first output keeps the old name so that existing output configs still apply.
+            let connector = if idx == 0 {
+                "winit".to_owned()
+            } else {
+                format!("winit-{}", idx + 1)
+            };
+            let (output, ipc_output) = WinitOutput::new(connector, cocoa_window);
+            ipc_outputs.insert(output.ipc_id, ipc_output);
+            outputs.push(output);
+        }
+        let ipc_outputs = Arc::new(Mutex::new(ipc_outputs));
 
-        use calloop::timer::{Timer, TimeoutAction};
+        let title = title.to_owned();
+        let winit_loop = Rc::new(RefCell::new(winit_loop));
+        let (winit_source, ping_sender) = WinitEventSource::new(winit_loop.clone());
 
-        let (winit_source, ping_sender) = WinitEventSource::new(winit_loop);
+        // AppKit describes the event being dispatched only during the winit callback, so events
+        // are translated right there, and applied to the state when they come out of the channel.
+        let (event_sender, event_channel) = channel::<WinitEventMsg>();
+        event_loop
+            .insert_source(winit_source, move |event, _, _| {
+                if let Some(msg) = WinitEventMsg::from_winit(event) {
+                    let _ = event_sender.send(msg);
+                }
+            })
+            .unwrap();
+        event_loop
+            .insert_source(event_channel, |event, _, state| {
+                if let calloop::channel::Event::Msg(msg) = event {
+                    handle_event(state, msg);
+                }
+            })
+            .unwrap();
 
+        let (caps_lock_sender, caps_lock_channel) = channel::<bool>();
         event_loop
-            .insert_source(winit_source, move |event, _, state| {
-                match &event {
-                    Event::WindowEvent { event: w_event, .. } => {
-                       match w_event {
-                            WindowEvent::Resized(size) => {
-                                if let Backend::Winit(winit) = &mut state.backend {
-                                    winit.cocoa_window.resize(size.width, size.height);
-                                }
-                            }
-                            WindowEvent::ScaleFactorChanged { .. } => {
-                                // No-op or trigger resize
-                            }
-                            _ => {}
-                       }
-                    }
-                    _ => {}
+            .insert_source(caps_lock_channel, |event, _, state| {
+                let calloop::channel::Event::Msg(pressed) = event else {
+                    return;
                 };
 
-               match event {
-                   Event::WindowEvent { event, .. } => match event {
-                       WindowEvent::Resized(size) => {
-                           tracing::info!("Niri received WinitEvent::Resized: {:?}", size);
-                           let winit = state.backend.winit();
-                           winit.output.change_current_state(
-                               Some(Mode {
-                                   size: (size.width as i32, size.height as i32).into(),
-                                   refresh: 60_000,
-                               }),
-                               None,
-                               None,
-                               None,
-                            );
-                            
-                           winit.CocoaResize(size.width, size.height);
-
-                           {
-                               let mut ipc_outputs = winit.ipc_outputs.lock().unwrap();
-                               let output = ipc_outputs.values_mut().next().unwrap();
-                               let mode = &mut output.modes[0];
-                               mode.width = size.width as u16;
-                               mode.height = size.height as u16;
-                                if let Some(logical) = output.logical.as_mut() {
-                                   logical.width = size.width;
-                                   logical.height = size.height;
-                               }
-                               state.niri.ipc_outputs_changed = true;
-                           }
-
-                           state.niri.output_resized(&winit.output);
-                       }
-                       WindowEvent::CloseRequested => state.niri.stop_signal.stop(),
-                       WindowEvent::RedrawRequested => {
-                           state.niri.queue_redraw(&state.backend.winit().output);
-                       }
-                        WindowEvent::ModifiersChanged(modifiers_event) => {
-                            tracing::info!("Modifiers Changed: {:?}", modifiers_event);
-                            
-                            // Synthesize key events for modifiers (Winit 0.30/macOS swallows them)
-                            let new_state = modifiers_event.state();
-                            let old_state = state.backend.winit().last_modifiers;
-                            
-                            // If states differ, check each flag
-                            if new_state != old_state {
-                                use winit::keyboard::{ModifiersState, KeyCode, PhysicalKey};
-                                use winit::event::ElementState;
-                                
-                                let mut check_mod = |mask: ModifiersState, code: KeyCode| {
-                                    let was_on = old_state.contains(mask);
-                                    let is_on = new_state.contains(mask);
-                                    
-                                    if was_on != is_on {
-                                        let state_enum = if is_on { ElementState::Pressed } else { ElementState::Released };
-                                        
-                                        // Manual Scancode Map (Evdev + 8)
-                                        // ShiftLeft(42)  -> 50
-                                        // CtrlLeft(29)   -> 37
-                                        // AltLeft(56)    -> 64
-                                        // SuperLeft(125) -> 133
-                                        
-                                        let evdev = match code {
-                                             KeyCode::ShiftLeft => 42,
-                                             KeyCode::ControlLeft => 29, 
-                                             KeyCode::AltLeft => 56, 
-                                             KeyCode::SuperLeft => 125, 
-                                             _ => 0
-                                        };
-                                        // WinitKeyboardInputEvent adds 8 automatically in implementation of KeyboardKeyEvent.
-                                        // So we pass raw Evdev code here.
-                                        let scancode = evdev;
-                                        
-                                        println!("Synthesizing Modifier Event: {:?} -> {} ({:?})", code, scancode, state_enum);
-                                        
-                                        use smithay::backend::input::InputEvent;
-                                        use crate::backend::winit_input::{WinitInput, WinitKeyboardInputEvent};
-                                        let time = get_monotonic_time().as_micros() as u64;
-                                        
-                                        let event = InputEvent::<WinitInput>::Keyboard {
-                                             event: WinitKeyboardInputEvent {
-                                                 time,
-                                                 key: scancode,
-                                                 count: 1, 
-                                                 state: state_enum,
-                                             },
-                                         };
-                                         state.process_input_event(event);
-                                    }
-                                };
-                                
-                                // Map Generic Flags to Left Keys (Good enough for most bindings)
-                                check_mod(ModifiersState::SHIFT, KeyCode::ShiftLeft);
-                                check_mod(ModifiersState::CONTROL, KeyCode::ControlLeft);
-                                check_mod(ModifiersState::ALT, KeyCode::AltLeft);
-                                check_mod(ModifiersState::SUPER, KeyCode::SuperLeft);
-                                
-                                state.backend.winit().last_modifiers = new_state;
-                            }
-                        }
-                        // INPUT HANDLING MAPPING
-                        WindowEvent::KeyboardInput { event, is_synthetic, .. } => {
-
-                             // Filter out synthetic and repeat events - Smithay handles repeats internally.
-                             // Not filtering these causes event queue flooding when keys are held.
-                             if event.repeat {
-                                 // Only skip repeats, not synthetic (synthetic are modifier state updates)
-                                 return;
-                             }
-
-                             // DEBOUNCE: Skip buffered event bursts caused by CFRunLoop sleep
-                             // When the loop sleeps for 16ms, events accumulate and arrive as bursts.
-                             // This causes Press-Release-Press-Release sequences within 1ms.
-                             // Debounce: Skip Press if same key was pressed < 50ms ago.
-                             let scancode_for_debounce = match event.physical_key {
-                                 PhysicalKey::Code(code) => code as u32,
-                                 PhysicalKey::Unidentified(_) => 0,
-                             };
-                             if event.state == winit::event::ElementState::Pressed {
-                                 let now = std::time::Instant::now();
-                                 let mut last_times = state.backend.winit().last_key_time.borrow_mut();
-                                 if let Some(last) = last_times.get(&scancode_for_debounce) {
-                                     if now.duration_since(*last) < std::time::Duration::from_millis(50) {
-                                         // tracing::info!("Debounce: Skipping rapid Press for scancode {}", scancode_for_debounce);
-                                         return;
-                                     }
-                                 }
-                                 last_times.insert(scancode_for_debounce, now);
-                             }
-
-                             use smithay::backend::input::InputEvent;
-                             use crate::backend::winit_input::{WinitInput, WinitKeyboardInputEvent};
-                             use winit::keyboard::{KeyCode, PhysicalKey};
-
-                             let time = get_monotonic_time().as_micros() as u64;
-                             
-                            // Filter out real modifier key events to avoid stuck keys.
-                            // Winit/macOS often swallows the Release events for modifiers, so we rely 
-                            // entirely on the Synthetic events from `ModifiersChanged` to ensure valid Press/Release pairs.
-                            match event.physical_key {
-                                PhysicalKey::Code(KeyCode::ShiftLeft) |
-                                PhysicalKey::Code(KeyCode::ShiftRight) |
-                                PhysicalKey::Code(KeyCode::ControlLeft) |
-                                PhysicalKey::Code(KeyCode::ControlRight) |
-                                PhysicalKey::Code(KeyCode::AltLeft) |
-                                PhysicalKey::Code(KeyCode::AltRight) |
-                                PhysicalKey::Code(KeyCode::SuperLeft) |
-                                PhysicalKey::Code(KeyCode::SuperRight) => {
-                                    tracing::info!("Suppressing Real Modifier Event (using synthetic instead): {:?}", event.physical_key);
-                                    return;
-                                }
-                                _ => {}
-                            }
-
-                             // Robust Mapping based on Cocoa-Way (KeyCode -> Evdev + 8)
-                             // This bypasses macOS specific scancodes and uses Winit's unified PhysicalKey
-                             let evdev_code = match event.physical_key {
-                                 PhysicalKey::Code(code) => match code {
-                                     KeyCode::Escape => Some(1),
-                                     KeyCode::Digit1 => Some(2), KeyCode::Digit2 => Some(3), KeyCode::Digit3 => Some(4),
-                                     KeyCode::Digit4 => Some(5), KeyCode::Digit5 => Some(6), KeyCode::Digit6 => Some(7),
-                                     KeyCode::Digit7 => Some(8), KeyCode::Digit8 => Some(9), KeyCode::Digit9 => Some(10),
-                                     KeyCode::Digit0 => Some(11), KeyCode::Minus => Some(12), KeyCode::Equal => Some(13),
-                                     KeyCode::Backspace => Some(14), KeyCode::Tab => Some(15),
-                                     KeyCode::KeyQ => Some(16), KeyCode::KeyW => Some(17), KeyCode::KeyE => Some(18),
-                                     KeyCode::KeyR => Some(19), KeyCode::KeyT => Some(20), KeyCode::KeyY => Some(21),
-                                     KeyCode::KeyU => Some(22), KeyCode::KeyI => Some(23), KeyCode::KeyO => Some(24),
-                                     KeyCode::KeyP => Some(25), KeyCode::BracketLeft => Some(26), KeyCode::BracketRight => Some(27),
-                                     KeyCode::Enter => Some(28), KeyCode::ControlLeft => Some(29),
-                                     KeyCode::KeyA => Some(30), KeyCode::KeyS => Some(31), KeyCode::KeyD => Some(32),
-                                     KeyCode::KeyF => Some(33), KeyCode::KeyG => Some(34), KeyCode::KeyH => Some(35),
-                                     KeyCode::KeyJ => Some(36), KeyCode::KeyK => Some(37), KeyCode::KeyL => Some(38),
-                                     KeyCode::Semicolon => Some(39), KeyCode::Quote => Some(40), KeyCode::Backquote => Some(41),
-                                     KeyCode::ShiftLeft => Some(42), KeyCode::Backslash => Some(43),
-                                     KeyCode::KeyZ => Some(44), KeyCode::KeyX => Some(45), KeyCode::KeyC => Some(46),
-                                     KeyCode::KeyV => Some(47), KeyCode::KeyB => Some(48), KeyCode::KeyN => Some(49),
-                                     KeyCode::KeyM => Some(50), KeyCode::Comma => Some(51), KeyCode::Period => Some(52),
-                                     KeyCode::Slash => Some(53), KeyCode::ShiftRight => Some(54),
-                                     KeyCode::AltLeft => Some(56), KeyCode::Space => Some(57), KeyCode::CapsLock => Some(58),
-                                     KeyCode::F1 => Some(59), KeyCode::F2 => Some(60), KeyCode::F3 => Some(61), KeyCode::F4 => Some(62),
-                                     KeyCode::F5 => Some(63), KeyCode::F6 => Some(64), KeyCode::F7 => Some(65), KeyCode::F8 => Some(66),
-                                     KeyCode::F9 => Some(67), KeyCode::F10 => Some(68),
-                                     KeyCode::AltRight => Some(100),
-                                     KeyCode::ArrowUp => Some(103), KeyCode::ArrowLeft => Some(105),
-                                     KeyCode::ArrowRight => Some(106), KeyCode::ArrowDown => Some(108),
-                                     KeyCode::SuperLeft => Some(125), KeyCode::SuperRight => Some(126),
-                                     _ => None,
-                                 },
-                                 _ => None,
-                             };
-
-                             let scancode = if let Some(code) = evdev_code {
-                                 code // Raw Evdev (winit_input adds +8)
-                             } else {
-                                 // Fallback for unmapped keys (try raw + 8 heuristic? No, winit_input adds 8)
-                                 // So we send raw macOS scancode?
-                                 // If winit_input adds 8, and we send raw mac scancode, result is mac+8.
-                                 // This is consistent with what we tried before, but maybe incorrect for unmapped keys.
-                                 // However, for mapped keys, we MUST NOT add 8.
-                                 event.physical_key.to_scancode().unwrap_or(0)
-                             };
-                             
-                             println!("Key Input Debug [PRINTLN]: key={:?}, evdev={:?}, final_scancode={} (raw), state={:?}", 
-                               event.physical_key, evdev_code, scancode, event.state);
-
-                             tracing::info!("Key Input Debug: key={:?}, evdev={:?}, final_scancode={}, state={:?}", 
-                                event.physical_key, evdev_code, scancode, event.state);
-                             
-                             let event = InputEvent::<WinitInput>::Keyboard {
-                                 event: WinitKeyboardInputEvent {
-                                     time,
-                                     key: scancode,
-                                     count: 1, 
-                                     state: event.state,
-                                 },
-                             };
-                             state.process_input_event(event);
-                        }
-                        WindowEvent::Focused(focused) => {
-                            tracing::info!("Window Focus Changed: {}", focused);
-                            // If we gain focus, ensure we are active
-                            if focused {
-                                // optional: force activation again?
-                            }
-                        }
-                       WindowEvent::CursorMoved { position, .. } => {
-                            use smithay::backend::input::InputEvent;
-                            use crate::backend::winit_input::{WinitInput, WinitMouseMovedEvent, RelativePosition};
-                            
-                            let winit = state.backend.winit();
-                            let size = winit.window().inner_size();
-                            let x = position.x / size.width as f64;
-                            let y = position.y / size.height as f64;
-                            
-                            let event = InputEvent::<WinitInput>::PointerMotionAbsolute {
-                                event: WinitMouseMovedEvent {
-                                    time: get_monotonic_time().as_micros() as u64,
-                                    position: RelativePosition::new(x, y),
-                                    global_position: position,
-                                }
-                            };
-                            state.process_input_event(event);
-                       }
-                       WindowEvent::MouseInput { state: element_state, button, .. } => {
-                            use smithay::backend::input::InputEvent;
-                            use crate::backend::winit_input::{WinitInput, WinitMouseInputEvent};
-                            
-                            let event = InputEvent::<WinitInput>::PointerButton {
-                                event: WinitMouseInputEvent {
-                                    time: get_monotonic_time().as_micros() as u64,
-                                    button,
-                                    state: element_state,
-                                    is_x11: false,
-                                }
-                            };
-                            state.process_input_event(event);
-                       }
-                       WindowEvent::MouseWheel { delta, .. } => {
-                            use smithay::backend::input::InputEvent;
-                            use crate::backend::winit_input::{WinitInput, WinitMouseWheelEvent};
-                            
-                            let event = InputEvent::<WinitInput>::PointerAxis {
-                                event: WinitMouseWheelEvent {
-                                    time: get_monotonic_time().as_micros() as u64,
-                                    delta,
-                                }
-                            };
-                            state.process_input_event(event);
-                       }
-                       _ => (),
-                   },
-                   _ => (),
-               }
+                // The keyboards report to us even while another app is in front.
+                if !state.backend.winit().is_focused {
+                    return;
+                }
+
+                let key_state = if pressed {
+                    winit::event::ElementState::Pressed
+                } else {
+                    winit::event::ElementState::Released
+                };
+                let event = InputEvent::<WinitInput>::Keyboard {
+                    event: WinitKeyboardInputEvent {
+                        time: get_monotonic_time().as_micros() as u64,
+                        key: CAPS_LOCK_EVDEV,
+                        count: 1,
+                        state: key_state,
+                    },
+                };
+                state.process_input_event(event);
             })
             .unwrap();
+        let caps_lock_monitor = hid::CapsLockMonitor::new(caps_lock_sender);
+        if caps_lock_monitor.is_none() {
+            debug!("Caps Lock works as a toggle without access to the HID keyboards");
+        }
+
+        let key_debounce = configured_key_debounce(&config.borrow());
+        let clear_color = if transparent {
+            TRANSPARENT_CLEAR_COLOR
+        } else {
+            DEFAULT_CLEAR_COLOR
+        };
 
         Ok(Self {
             config,
-            output,
-            cocoa_window,
+            winit_loop,
+            title,
+            outputs,
+            next_output_number,
+            vsync,
+            display_timing: None,
             gles_renderer: renderer,
-            damage_tracker,
+            clear_color,
+            transparent,
+            borderless,
             ipc_outputs,
             ping_sender,
-            last_modifiers: winit::keyboard::ModifiersState::empty(),
-            last_key_time: std::cell::RefCell::new(HashMap::new()),
+            last_modifiers: SidedModifiers::empty(),
+            is_focused: true,
+            swipe_fingers: None,
+            window_drag: None,
+            movable_background: config.borrow().debug.winit_movable_background,
+            background_drag: None,
+            pinch_scale: None,
+            left_click_as: None,
+            scroll_v120: V120Accumulator::default(),
+            scroll_gesture: ScrollGesture::default(),
+            scroll_stop_timer: None,
+            hovered_window: None,
+            cursor_left: false,
+            touch_frame_pending: false,
+            debug_tint: false,
+            fps_overlay: false,
+            suspended: false,
+            monitors_active: true,
+            idle_inhibited: false,
+            display_sleep_assertion: None,
+            active_vt: COMPOSITOR_VT,
+            key_bursts: KeyBurstFilter::new(key_debounce),
+            last_key_press: None,
+            typing_held_buttons: HashSet::new(),
+            pasteboard_change_count: None,
+            caps_lock_monitor,
+            _mouse_monitor: hid::MouseMonitor::new(),
+            os_cursor: None,
+            cursor_hold: None,
+            event_output: None,
+            tablet_tool: None,
+            dragged_files: Vec::new(),
+            file_drag_active: false,
+            file_drag_end: None,
+            context_rebuilds: 0,
+            gpu,
+            _gpu_pin: gpu_pin,
         })
     }
 
@@ -547,14 +768,528 @@ impl Winit {
         self.ping_sender.ping();
     }
 
+    /// Offers new macOS pasteboard contents to Wayland clients as the clipboard selection.
+    ///
+    /// The pasteboard is only read once a client asks for the selection.
+    pub fn sync_pasteboard(&mut self, niri: &mut Niri) {
+        let Some(count) = pasteboard::change_count() else {
+            return;
+        };
+        if self.pasteboard_change_count == Some(count) {
+            return;
+        }
+        self.pasteboard_change_count = Some(count);
+
+        let mime_types: Vec<_> = pasteboard::available_types()
+            .into_iter()
+            .flat_map(pasteboard::mime_types)
+            .map(|&mime_type| mime_type.to_owned())
+            .collect();
+        if mime_types.is_empty() {
+            return;
+        }
+        set_data_device_selection(
+            &niri.display_handle,
+            &niri.seat,
+            mime_types,
+            SelectionData::Pasteboard,
+        );
+    }
+
+    /// Offers a clipboard selection that a Wayland client set to macOS apps.
+    ///
+    /// The selection is only read from the client once an app pastes it.
+    pub fn export_selection(
+        &mut self,
+        seat: &Seat<State>,
+        display_handle: &DisplayHandle,
+        mime_types: Vec<String>,
+    ) {
+        let types = pasteboard::pick_types(&mime_types);
+        if types.is_empty() {
+            return;
+        }
+        let pasteboard_types: Vec<_> = types.iter().map(|(ty, _)| *ty).collect();
+
+        let seat = seat.clone();
+        let display_handle = display_handle.clone();
+        let provide = move |ty| {
+            let (_, mime_type) = types.iter().find(|(offered, _)| *offered == ty)?;
+            read_client_selection(&seat, &display_handle, mime_type.clone())
+        };
+
+        // Remember the change count so that our own offer isn't offered back to clients.
+        if let Some(count) = pasteboard::offer(&pasteboard_types, provide) {
+            self.pasteboard_change_count = Some(count);
+        }
+    }
+
+    /// Changes the title of the compositor windows.
+    pub fn set_title(&mut self, title: &str) {
+        for winit_output in &self.outputs {
+            winit_output.window().set_title(title);
+        }
+        self.title = title.to_owned();
+    }
+
+    /// Follows changes to the displays, opening and closing output windows as they get connected
+    /// and disconnected, and bringing the GL surfaces up to date.
+    ///
+    /// This runs before the next frame renders, so the frame sees the outputs and the surfaces
+    /// after the whole change.
+    pub fn sync_outputs(&mut self, niri: &mut Niri) {
+        let displays_changed = appkit::take_displays_changed();
+        let screens_changed = appkit::take_screen_parameters_changed();
+
+        if displays_changed {
+            self.sync_output_windows(niri);
+        }
+        if displays_changed || screens_changed {
+            self.refresh_surfaces(niri);
+        }
+    }
+
+    fn sync_output_windows(&mut self, niri: &mut Niri) {
+        let _span = tracy_client::span!("Winit::sync_output_windows");
+
+        let winit_loop = self.winit_loop.clone();
+        let winit_loop = winit_loop.borrow();
+        #[allow(deprecated)]
+        let monitors: Vec<_> = winit_loop.available_monitors().collect();
+
+        // The first output has the context that the others share, so it stays, and macOS moves
+        // its window over to a remaining display. Windows of disconnected displays may land on a
+        // display that already has an output, so keep one output per display.
+        let mut shown = HashSet::from([self.outputs[0].display()]);
+        let mut idx = 1;
+        while idx < self.outputs.len() {
+            let display = self.outputs[idx].display();
+            let connected = monitors
+                .iter()
+                .any(|monitor| Some(monitor.native_id()) == display);
+            if connected && shown.insert(display) {
+                idx += 1;
+                continue;
+            }
+
+            let winit_output = self.outputs.remove(idx);
+            debug!(
+                "{}: display disconnected or shown by another output, removing output",
+                winit_output.output.name()
+            );
+            niri.remove_output(&winit_output.output);
+            self.ipc_outputs
+                .lock()
+                .unwrap()
+                .remove(&winit_output.ipc_id);
+            niri.ipc_outputs_changed = true;
+        }
+
+        let mut opened = false;
+        for monitor in monitors {
+            if shown.contains(&Some(monitor.native_id())) {
+                continue;
+            }
+
+            let mut cocoa_window = match CocoaWindowHandle::new_on_monitor(
+                &winit_loop,
+                &self.title,
+                &monitor,
+                &self.outputs[0].cocoa_window.gl_context,
+                self.transparent,
+                self.borderless,
+            ) {
+                Ok(window) => window,
+                Err(err) => {
+                    warn!("error opening a window on {:?}: {err}", monitor.name());
+                    continue;
+                }
+            };
+            opened = true;
+            cocoa_window.set_vsync(self.vsync);
+
+            let connector = format!("winit-{}", self.next_output_number);
+            self.next_output_number += 1;
+            debug!("{connector}: display connected, adding output");
+
+            let (mut winit_output, ipc_output) = WinitOutput::new(connector, cocoa_window);
+            if self.fps_overlay {
+                winit_output.fps_overlay = Some(FpsOverlay::new());
+            }
+            self.ipc_outputs
+                .lock()
+                .unwrap()
+                .insert(winit_output.ipc_id, ipc_output);
+            niri.add_output(winit_output.output.clone(), None, false);
+            niri.ipc_outputs_changed = true;
+            self.outputs.push(winit_output);
+        }
+
+        // Like at startup, give the focus back to the first window.
+        if opened {
+            self.outputs[0].cocoa_window.window.focus_window();
+        }
+    }
+
+    /// Recreates the GL surfaces of the windows after the screens changed.
+    ///
+    /// The windows may have moved to displays driven by another GPU, then the context ends up
+    /// there too, and gets rebuilt like after losing it. Either way, everything redraws.
+    fn refresh_surfaces(&mut self, niri: &mut Niri) {
+        let _span = tracy_client::span!("Winit::refresh_surfaces");
+
+        for winit_output in &mut self.outputs {
+            if let Err(err) = winit_output.cocoa_window.recreate_surface() {
+                warn!("{}: error recreating the GL surface: {err}", winit_output.output.name());
+            }
+        }
+
+        let primary = &self.outputs[0].cocoa_window;
+        if let Err(err) = primary.make_current() {
+            warn!("error making the primary context current: {err}");
+        }
+
+        let gpu = primary.gpu_name();
+        if gpu != self.gpu {
+            let reason = format!("the screens moved from {} to {gpu}", self.gpu);
+            self.recover_context(niri, &reason);
+            return;
+        }
+
+        self.force_full_damage();
+        niri.queue_redraw_all();
+    }
+
+    /// Returns the time between two refreshes of the fastest display.
+    pub fn refresh_interval(&self) -> Duration {
+        let refresh_rate = self
+            .outputs
+            .iter()
+            .map(|o| o.refresh_rate)
+            .max()
+            .unwrap_or(60_000);
+        Duration::from_secs_f64(1000. / f64::from(refresh_rate))
+    }
+
+    /// Switches between frames paced by the display refresh and uncapped frames.
+    ///
+    /// Uncapped frames are meant for benchmarking, they get presented as fast as they render.
+    pub fn set_vsync(&mut self, enabled: bool) {
+        self.vsync = enabled;
+        for winit_output in &mut self.outputs {
+            winit_output.cocoa_window.set_vsync(enabled);
+        }
+    }
+
+    /// Sets the shortcuts that macOS keeps handling, like the menu bar items.
+    ///
+    /// Other keys with Cmd or Ctrl go to the Wayland clients and niri bindings.
+    pub fn set_system_shortcuts(&mut self, shortcuts: Option<&[String]>) {
+        appkit::set_system_shortcuts(system_shortcuts(shortcuts));
+    }
+
+    /// Sets whether holding Mod and dragging over empty space moves the output window.
+    ///
+    /// Empty space is where the pointer is over neither a window nor a layer surface other than a
+    /// background one, so drags meant for the clients still go to them.
+    pub fn set_movable_by_background(&mut self, movable: bool) {
+        self.movable_background = movable;
+        if !movable {
+            self.background_drag = None;
+        }
+    }
+
+    /// Sets the display refresh timing that presentation feedback reports to clients.
+    ///
+    /// Without it, or with VRR on, the feedback has no refresh interval or retrace counter, and
+    /// the time is when the buffers were swapped.
+    pub fn set_display_timing(&mut self, timing: Option<DisplayTiming>) {
+        self.display_timing = timing;
+    }
+
+    /// Returns whether buffer swaps wait for the display refresh on every output.
+    ///
+    /// Then the swaps pace rendering on their own. Otherwise, or when the swap interval couldn't
+    /// be set, rendering has to be throttled to the refresh rate. macOS doesn't wait on the swaps
+    /// of windows that are completely covered, minimized or hidden, so those need the throttling
+    /// too.
+    pub fn is_vsync_active(&self) -> bool {
+        self.outputs
+            .iter()
+            .all(|o| o.cocoa_window.is_vsync() && !o.covered)
+    }
+
+    /// Returns whether any output has on-demand VRR on.
+    ///
+    /// Frames then go out as soon as they're ready instead of waiting for the next display
+    /// refresh, and the display ramps its refresh rate to match.
+    pub fn is_vrr_enabled(&self) -> bool {
+        self.outputs.iter().any(|o| o.vrr_enabled)
+    }
+
+    /// Applies the output sections of the config to the windows after a config change.
+    ///
+    /// niri itself updates the scale, transform and position of every output. On top of that, a
+    /// scale left unset follows the window's backing scale factor, and a mode resizes the window
+    /// to it. The `Resized` event that follows updates the output and IPC modes; the refresh
+    /// rate always follows the display.
+    pub fn on_output_config_changed(&mut self, niri: &mut Niri) {
+        let _span = tracy_client::span!("Winit::on_output_config_changed");
+
+        let config = self.config.borrow();
+        let mut resized = Vec::new();
+        for winit_output in &mut self.outputs {
+            let output = winit_output.output.clone();
+            let name = output.user_data().get::<OutputName>().unwrap();
+            let output_config = config.outputs.find(name);
+
+            if output_config.and_then(|c| c.scale).is_none() {
+                let scale_factor = winit_output.window().scale_factor();
+                let preferred = output.user_data().get::<PreferredScale>().unwrap();
+                preferred.0.set(scale_factor);
+
+                let scale = closest_representable_scale(scale_factor.clamp(0.1, 10.));
+                if output.current_scale().fractional_scale() != scale {
+                    output.change_current_state(None, None, Some(Scale::Fractional(scale)), None);
+                    winit_output.reset_damage();
+                    resized.push(output.clone());
+                    niri.ipc_outputs_changed = true;
+                }
+            }
+
+            let Some(mode) = output_config.and_then(|c| c.mode) else {
+                continue;
+            };
+
+            let window = winit_output.window();
+            if window.fullscreen().is_some() || window.is_maximized() {
+                debug!(
+                    "{}: not resizing a fullscreen or maximized window to the configured mode",
+                    output.name()
+                );
+                continue;
+            }
+
+            let size = PhysicalSize::new(u32::from(mode.mode.width), u32::from(mode.mode.height));
+            if window.inner_size() != size {
+                debug!("{}: resizing the window to {}x{}", output.name(), size.width, size.height);
+                let _ = window.request_inner_size(size);
+            }
+        }
+        drop(config);
+
+        for output in resized {
+            niri.output_resized(&output);
+        }
+    }
+
+    pub fn set_output_on_demand_vrr(&mut self, niri: &mut Niri, output: &Output, enable_vrr: bool) {
+        let _span = tracy_client::span!("Winit::set_output_on_demand_vrr");
+
+        let output_state = niri.output_state.get_mut(output).unwrap();
+        output_state.on_demand_vrr_enabled = enable_vrr;
+
+        let Some(winit_output) = self.outputs.iter_mut().find(|o| &o.output == output) else {
+            return;
+        };
+        let enable_vrr = enable_vrr && winit_output.vrr_supported;
+        if winit_output.vrr_enabled == enable_vrr {
+            return;
+        }
+
+        winit_output.vrr_enabled = enable_vrr;
+        output_state.frame_clock.set_vrr(enable_vrr);
+
+        let mut ipc_outputs = self.ipc_outputs.lock().unwrap();
+        if let Some(ipc_output) = ipc_outputs.get_mut(&winit_output.ipc_id) {
+            ipc_output.vrr_enabled = enable_vrr;
+        }
+        niri.ipc_outputs_changed = true;
+    }
+
+    /// Returns the window that shows `output`.
+    pub fn output_window_id(&self, output: &Output) -> Option<WindowId> {
+        let winit_output = self.outputs.iter().find(|o| o.output == *output)?;
+        Some(winit_output.window().id())
+    }
+
+    fn output_for_window(&mut self, id: WindowId) -> Option<&mut WinitOutput> {
+        self.outputs.iter_mut().find(|o| o.window().id() == id)
+    }
+
+    /// Returns the window under a point in the global display space, in points like from
+    /// [`appkit::cursor_location`], along with the point within the window in physical pixels.
+    fn window_at(&self, x: f64, y: f64) -> Option<(WindowId, PhysicalPosition<f64>)> {
+        self.outputs.iter().find_map(|winit_output| {
+            let window = winit_output.window();
+            let origin = window.inner_position().ok()?;
+            let size = window.inner_size();
+            let scale = window.scale_factor();
+
+            let pos_x = x * scale - f64::from(origin.x);
+            let pos_y = y * scale - f64::from(origin.y);
+            let inside = (0. ..f64::from(size.width)).contains(&pos_x)
+                && (0. ..f64::from(size.height)).contains(&pos_y);
+            inside.then(|| (window.id(), PhysicalPosition::new(pos_x, pos_y)))
+        })
+    }
+
+    /// Returns the button to send a click as, applying `click-method "clickfinger"`.
+    ///
+    /// macOS sends two-finger clicks and taps as right clicks by itself when secondary click is
+    /// on in the trackpad settings. Otherwise they arrive as left clicks, and with clickfinger
+    /// configured the finger count picks the button instead. The release goes out as the same
+    /// button as the press, even if the fingers changed in between.
+    fn trackpad_click_button(
+        &mut self,
+        button: WinitMouseButton,
+        element_state: ElementState,
+        touch_count: u32,
+    ) -> WinitMouseButton {
+        if button != WinitMouseButton::Left {
+            return button;
+        }
+
+        match element_state {
+            ElementState::Pressed => {
+                let clickfinger = self.config.borrow().input.touchpad.click_method
+                    == Some(ClickMethod::Clickfinger);
+                // Clicks of a mouse come without touches and stay as they are.
+                let button = if clickfinger && touch_count > 0 {
+                    clickfinger_button(touch_count)
+                } else {
+                    button
+                };
+                self.left_click_as = Some(button);
+                button
+            }
+            ElementState::Released => self.left_click_as.take().unwrap_or(button),
+        }
+    }
 
+    /// Returns whether disable-while-typing holds back the events of `device` right now.
+    ///
+    /// Only the trackpad has it, so an external mouse keeps working while typing.
+    fn held_back_while_typing(&self, device: &ShimDevice) -> bool {
+        if !device.config_dwt_enabled() {
+            return false;
+        }
+
+        let timeout = configured_dwt_timeout(&self.config.borrow());
+        typed_within(self.last_key_press, Instant::now(), timeout)
+    }
+
+    /// Moves the cursor back onto the pointer after dropping motion that macOS already applied to
+    /// the cursor.
+    ///
+    /// Otherwise, the dropped motion would show up all at once with the next motion that goes
+    /// through, since that comes with the cursor position. A held cursor doesn't move anyway.
+    fn restore_cursor(&mut self, niri: &Niri) {
+        if self.cursor_hold.is_none() {
+            let pointer = niri.seat.get_pointer().unwrap();
+            self.warp_pointer(niri, pointer.current_location());
+        }
+    }
+
+    /// Turns phased trackpad scrolling with three or more fingers into swipe gesture events.
+    ///
+    /// Returns `None` for ordinary scrolling, which should go through the wheel path instead.
+    fn swipe_gesture_event(
+        &mut self,
+        delta: MouseScrollDelta,
+        phase: TouchPhase,
+        touch_count: u32,
+        inverted: bool,
+        event_time: Option<Duration>,
+    ) -> Option<InputEvent<WinitInput>> {
+        let MouseScrollDelta::PixelDelta(delta) = delta else {
+            return None;
+        };
+
+        let time = input_event_time(event_time);
+        match phase {
+            TouchPhase::Started => {
+                let fingers = touch_count;
+                if fingers < 3 {
+                    return None;
+                }
+
+                // niri only has bindings for three- and four-finger swipes.
+                let fingers = fingers.min(4);
+                self.swipe_fingers = Some(fingers);
+                Some(InputEvent::GestureSwipeBegin {
+                    event: WinitGestureSwipeBeginEvent { time, fingers },
+                })
+            }
+            TouchPhase::Moved => {
+                self.swipe_fingers?;
+                let delta = finger_motion(delta, inverted);
+                Some(InputEvent::GestureSwipeUpdate {
+                    event: WinitGestureSwipeUpdateEvent {
+                        time,
+                        delta: (delta.x, delta.y),
+                    },
+                })
+            }
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                self.swipe_fingers.take()?;
+                Some(InputEvent::GestureSwipeEnd {
+                    event: WinitGestureSwipeEndEvent {
+                        time,
+                        cancelled: phase == TouchPhase::Cancelled,
+                    },
+                })
+            }
+        }
+    }
+
+
+    /// Turns a macOS magnify gesture into pinch gesture events.
+    fn pinch_gesture_event(
+        &mut self,
+        delta: f64,
+        phase: TouchPhase,
+        event_time: Option<Duration>,
+    ) -> Option<InputEvent<WinitInput>> {
+        let time = input_event_time(event_time);
+        match phase {
+            TouchPhase::Started => {
+                self.pinch_scale = Some(1.);
+                Some(InputEvent::GesturePinchBegin {
+                    event: WinitGesturePinchBeginEvent { time },
+                })
+            }
+            TouchPhase::Moved => {
+                let scale = self.pinch_scale.as_mut()?;
+                // Magnification deltas are additive on top of the current scale.
+                *scale += delta;
+                Some(InputEvent::GesturePinchUpdate {
+                    event: WinitGesturePinchUpdateEvent {
+                        time,
+                        scale: *scale,
+                    },
+                })
+            }
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                self.pinch_scale.take()?;
+                Some(InputEvent::GesturePinchEnd {
+                    event: WinitGesturePinchEndEvent {
+                        time,
+                        cancelled: phase == TouchPhase::Cancelled,
+                    },
+                })
+            }
+        }
+    }
 
     pub fn init(&mut self, niri: &mut Niri) {
         let renderer = &mut self.gles_renderer;
         resources::init(renderer);
         shaders::init(renderer);
         niri.update_shaders();
-        niri.add_output(self.output.clone(), None, false);
+        for winit_output in &self.outputs {
+            niri.add_output(winit_output.output.clone(), None, false);
+        }
     }
 
     pub fn seat_name(&self) -> String {
@@ -570,10 +1305,44 @@ impl Winit {
 
     pub fn render(&mut self, niri: &mut Niri, output: &Output) -> RenderResult {
         let _span = tracy_client::span!("Winit::render");
-        
-        // Bind renderer to the window size (framebuffer 0)
-        let mut bind_size = (self.cocoa_window.width as i32, self.cocoa_window.height as i32);
-        let mut target = self.gles_renderer.bind(&mut bind_size).expect("Failed to bind renderer");
+
+        // The windows aren't visible, and the GL surfaces may be gone until wake.
+        if self.suspended || !self.monitors_active {
+            return RenderResult::Skipped;
+        }
+
+        // niri draws the cursor itself, with the cursor theme and client cursor surfaces, so
+        // the macOS one stays hidden.
+        let os_cursor = OsCursor::for_niri(niri);
+        if self.os_cursor != Some(os_cursor) {
+            for winit_output in &self.outputs {
+                os_cursor.apply(winit_output.window());
+            }
+            self.os_cursor = Some(os_cursor);
+        }
+
+        let Some(winit_output) = self.outputs.iter_mut().find(|o| &o.output == output) else {
+            return RenderResult::Skipped;
+        };
+
+        // All windows share one renderer, so switch it over to this output's window.
+        if let Err(err) = winit_output.cocoa_window.make_current() {
+            self.context_error(niri, err);
+            return RenderResult::Skipped;
+        }
+
+        // Bind renderer to the window size (framebuffer 0). This is the output mode size, the
+        // damage tracker rotates the output into it, so for 90 and 270 degrees the logical output
+        // has the width and height swapped against it.
+        let cocoa_window = &winit_output.cocoa_window;
+        let mut bind_size = (cocoa_window.width as i32, cocoa_window.height as i32);
+        let mut target = match self.gles_renderer.bind(&mut bind_size) {
+            Ok(target) => target,
+            Err(err) => {
+                warn!("error binding the renderer: {err:?}");
+                return RenderResult::Skipped;
+            }
+        };
 
         let mut elements = niri.render::<GlesRenderer>(
             &mut self.gles_renderer,
@@ -587,12 +1356,22 @@ impl Winit {
             draw_damage(&mut output_state.debug_damage_tracker, &mut elements);
         }
 
-        let res = self.damage_tracker.render_output(
+        if let Some(fps_overlay) = &mut winit_output.fps_overlay {
+            fps_overlay.record_frame(Instant::now());
+
+            let scale = output.current_scale().fractional_scale();
+            let vsync = cocoa_window.is_vsync();
+            if let Some(elem) = fps_overlay.render(&mut self.gles_renderer, scale, vsync) {
+                elements.insert(0, OutputRenderElements::Texture(elem));
+            }
+        }
+
+        let res = winit_output.damage_tracker.render_output(
             &mut self.gles_renderer,
             &mut target,
             0,
             &elements,
-            [0.1, 0.1, 0.1, 1.0], 
+            self.clear_color,
         );
 
         let render_result = match res {
@@ -603,60 +1382,3559 @@ impl Winit {
              }
         };
 
-        if let Err(e) = self.cocoa_window.make_current() {
-             tracing::error!("Make current failed: {}", e);
+        let cocoa_window = &winit_output.cocoa_window;
+        if let Err(err) = cocoa_window.swap_buffers() {
+            self.context_error(niri, err);
+            return RenderResult::Skipped;
         }
+        self.context_rebuilds = 0;
+
+        // The frame shows up with the first display refresh after the swap.
+        let swap_time = get_monotonic_time();
+        let timing = self.display_timing.filter(|_| !winit_output.vrr_enabled);
+        let (time, refresh, seq, flags) = match timing {
+            Some(timing) => {
+                let (vblank, seq) = timing.next_vblank(swap_time);
+                let mut flags = wp_presentation_feedback::Kind::HwClock;
+                if cocoa_window.is_vsync() {
+                    flags.insert(wp_presentation_feedback::Kind::Vsync);
+                }
+                (vblank, Refresh::Fixed(timing.refresh), seq, flags)
+            }
+            None => (
+                swap_time,
+                Refresh::Unknown,
+                0,
+                wp_presentation_feedback::Kind::empty(),
+            ),
+        };
+
+        // Among other things, idle inhibitors only count for surfaces that are on an output.
+        niri.update_primary_scanout_output(output, &render_result.states);
 
-        if let Err(e) = self.cocoa_window.swap_buffers() {
-             tracing::error!("Swap buffers failed: {}", e);
-        }
-        
          let mut presentation_feedbacks = niri.take_presentation_feedbacks(output, &render_result.states);
          presentation_feedbacks.presented::<_, smithay::utils::Monotonic>(
-             get_monotonic_time(),
-             Refresh::Unknown,
-             0,
-             wp_presentation_feedback::Kind::empty(),
+             time,
+             refresh,
+             seq,
+             flags,
          );
 
-         // Crucial: Request the next frame to keep the event loop spinning at VSync.
-         // Without this, the loop sleeps until external input, causing lag.
-         self.cocoa_window.window.request_redraw();
-         
-         // FORCE CURSOR VISIBILITY (User Request)
-         // Overrides any Smithay/Niri logic that hides it.
-         self.cocoa_window.window.set_cursor_visible(true);
-         self.cocoa_window.window.set_cursor_icon(winit::window::CursorIcon::Default);
+         // Keep rendering while something animates; otherwise the next redraw gets queued when
+         // something changes, and the event loop can idle.
+         if niri.output_state[output].unfinished_animations_remain {
+             cocoa_window.window.request_redraw();
+         }
 
         RenderResult::Submitted
     }
-    
-    pub fn toggle_debug_tint(&mut self) {}
 
-    #[cfg(target_os = "linux")]
-    pub fn import_dmabuf(&mut self, _dmabuf: &Dmabuf) -> bool { false }
+    /// Drops the frame after a GL error, and rebuilds the contexts if it lost them.
+    fn context_error(&mut self, niri: &mut Niri, err: ContextError) {
+        if err.is_context_lost() {
+            self.recover_context(niri, &err.to_string());
+        } else {
+            warn!("dropping frame: {err}");
+        }
+    }
+
+    /// Replaces the lost GL contexts of the windows and the renderer on top of them.
+    ///
+    /// macOS can take the context away when a dual-GPU MacBook switches GPUs, or when the displays
+    /// are reconfigured. The frame that noticed is dropped, and everything redraws with the new
+    /// context. Rebuilding stops after [`MAX_CONTEXT_REBUILDS`] attempts without a frame in
+    /// between, then frames keep getting dropped.
+    fn recover_context(&mut self, niri: &mut Niri, reason: &str) {
+        if self.context_rebuilds >= MAX_CONTEXT_REBUILDS {
+            trace!("dropping frame with a lost GL context: {reason}");
+            return;
+        }
+        self.context_rebuilds += 1;
+        warn!(
+            "rebuilding the GL context, attempt {} of {MAX_CONTEXT_REBUILDS}: {reason}",
+            self.context_rebuilds
+        );
 
-    #[cfg(not(target_os = "linux"))]
-    pub fn import_dmabuf(&mut self, _dmabuf: &Dmabuf) -> bool { false }
+        if let Err(err) = self.rebuild_context() {
+            warn!("error rebuilding the GL context: {err}");
+            if self.context_rebuilds == MAX_CONTEXT_REBUILDS {
+                error!("giving up on the GL context, the windows will stop updating");
+            }
+            return;
+        }
 
-    pub fn ipc_outputs(&self) -> Arc<Mutex<IpcOutputMap>> {
-        self.ipc_outputs.clone()
+        let renderer = &mut self.gles_renderer;
+        resources::init(renderer);
+        shaders::init(renderer);
+        niri.update_shaders();
+        niri.cursor_texture_cache.clear();
+
+        for winit_output in &mut self.outputs {
+            winit_output.reset_damage();
+            if winit_output.fps_overlay.is_some() {
+                winit_output.fps_overlay = Some(FpsOverlay::new());
+            }
+        }
+        niri.queue_redraw_all();
+
+        let gpu = self.outputs[0].cocoa_window.gpu_name();
+        info!("rebuilt the GL context on {gpu}");
+        self.gpu = gpu;
     }
-    
-    pub fn CocoaResize(&mut self, w: u32, h: u32) {
-         self.cocoa_window.resize(w, h);
+
+    /// Rebuilds the contexts of all windows, or of none if anything fails along the way.
+    ///
+    /// Windows with a new context can't draw with the old renderer, nor share textures with
+    /// windows on an old context, so the new ones only replace the old ones once they all exist.
+    fn rebuild_context(&mut self) -> Result<(), String> {
+        let (first, rest) = self.outputs.split_first().unwrap();
+        let first_context = first.cocoa_window.new_context(None)?;
+        let share = first_context.gl_context();
+        let rest_contexts = rest
+            .iter()
+            .map(|winit_output| winit_output.cocoa_window.new_context(Some(share)))
+            .collect::<Result<Vec<_>, _>>()?;
+        first_context.make_current()?;
+
+        let display = share.display();
+        let renderer = unsafe {
+            GlesRenderer::new_with_loader(|s| {
+                let symbol = CString::new(s).unwrap();
+                display.get_proc_address(symbol.as_c_str()).cast()
+            })
+        }
+        .map_err(|err| format!("error creating the renderer: {err:?}"))?;
+
+        let contexts = iter::once(first_context).chain(rest_contexts);
+        for (winit_output, context) in self.outputs.iter_mut().zip(contexts) {
+            winit_output.cocoa_window.replace_context(context);
+        }
+        // The old renderer goes with its context, which is already gone.
+        self.gles_renderer = renderer;
+        self.outputs[0].cocoa_window.make_current()?;
+        Ok(())
     }
-    
-    pub fn window(&self) -> &Window {
-         &self.cocoa_window.window
+
+    /// Renders `output` into its window once more and returns the size and RGBA pixels.
+    ///
+    /// This reads back the window framebuffer instead of rendering offscreen, so it captures
+    /// exactly what the window shows, at its full Retina resolution.
+    pub fn capture(
+        &mut self,
+        niri: &mut Niri,
+        output: &Output,
+        include_pointer: bool,
+    ) -> anyhow::Result<(Size<i32, Physical>, Vec<u8>)> {
+        let _span = tracy_client::span!("Winit::capture");
+
+        let winit_output = self
+            .outputs
+            .iter_mut()
+            .find(|o| &o.output == output)
+            .context("output has no window")?;
+        winit_output
+            .cocoa_window
+            .make_current()
+            .map_err(anyhow::Error::msg)?;
+
+        let cocoa_window = &winit_output.cocoa_window;
+        let mut bind_size = (cocoa_window.width as i32, cocoa_window.height as i32);
+        let mut target = self
+            .gles_renderer
+            .bind(&mut bind_size)
+            .context("error binding the window framebuffer")?;
+
+        let elements = niri.render::<GlesRenderer>(
+            &mut self.gles_renderer,
+            output,
+            include_pointer,
+            RenderTarget::ScreenCapture,
+        );
+
+        // Age 0 redraws everything, so the back buffer holds a complete frame. It's not swapped;
+        // the next regular frame overwrites it.
+        winit_output
+            .damage_tracker
+            .render_output(&mut self.gles_renderer, &mut target, 0, &elements, self.clear_color)
+            .context("error rendering")?;
+        drop(target);
+
+        let (width, height, pixels) = winit_output
+            .cocoa_window
+            .read_pixels()
+            .map_err(anyhow::Error::msg)?;
+
+        Ok((Size::from((width as i32, height as i32)), pixels))
     }
-}
 
-use crate::input::backend_ext::NiriInputDevice;
-use crate::backend::winit_input::WinitVirtualDevice;
+    /// Makes the next frame of every output redraw the whole window, after which the damage
+    /// tracking picks up again.
+    pub fn force_full_damage(&mut self) {
+        for winit_output in &mut self.outputs {
+            winit_output.reset_damage();
+        }
+    }
 
-impl NiriInputDevice for WinitVirtualDevice {
-    fn output(&self, _state: &State) -> Option<Output> {
-        None
+    /// Pauses or resumes rendering as the system or the displays go to sleep and wake up.
+    fn update_suspended(&mut self, niri: &mut Niri) {
+        let asleep = appkit::is_asleep();
+        if self.suspended == asleep {
+            return;
+        }
+        self.suspended = asleep;
+
+        if asleep {
+            debug!("displays went to sleep, pausing rendering");
+            return;
+        }
+
+        debug!("displays woke up, resuming rendering");
+        if let Some(winit_output) = self.outputs.first() {
+            if let Err(err) = winit_output.cocoa_window.make_current() {
+                warn!("error making the context current after wake: {err}");
+            }
+        }
+        // The frames from before the sleep can be gone, along with anything that changed since.
+        self.force_full_damage();
+        niri.queue_redraw_all();
+    }
+
+    /// Powers the displays down or back up.
+    ///
+    /// Turning them off releases the assertion keeping them awake and puts them to sleep right
+    /// away. Any input wakes them up again, and niri then activates the monitors.
+    pub fn set_monitors_active(&mut self, active: bool) {
+        if self.monitors_active == active {
+            return;
+        }
+        self.monitors_active = active;
+        self.update_display_sleep_assertion();
+
+        if !active {
+            if let Err(err) = appkit::sleep_displays() {
+                warn!("error putting the displays to sleep: {err}");
+            }
+        }
+    }
+
+    /// Sets whether clients inhibit idle, like a video player through idle-inhibit.
+    ///
+    /// Otherwise the displays go to sleep after the time set in the macOS settings, so while
+    /// clients inhibit idle, a power assertion keeps them awake.
+    pub fn set_idle_inhibited(&mut self, inhibited: bool) {
+        if self.idle_inhibited == inhibited {
+            return;
+        }
+        self.idle_inhibited = inhibited;
+        self.update_display_sleep_assertion();
+    }
+
+    /// Moves the cursor to `pos` in the global space, onto the window of the output there.
+    ///
+    /// Moving the cursor sends no mouse events, so clients see no relative motion from it. Only
+    /// while we have focus, otherwise this would yank the cursor from the app in front. Returns
+    /// whether the cursor moved.
+    pub fn warp_pointer(&mut self, niri: &Niri, pos: Point<f64, Logical>) -> bool {
+        if !self.is_focused {
+            return false;
+        }
+        let Some((x, y)) = self.screen_position(niri, pos) else {
+            return false;
+        };
+        appkit::warp_cursor(x, y, self.cursor_hold.is_none());
+        true
+    }
+
+    /// Returns where the cursor is in the global space, or `None` while it is off the windows.
+    pub fn pointer_position(&self, niri: &Niri) -> Option<Point<f64, Logical>> {
+        let (x, y) = appkit::cursor_location()?;
+        let (window_id, position) = self.window_at(x, y)?;
+        let winit_output = self.outputs.iter().find(|o| o.window().id() == window_id)?;
+        let window_size = winit_output.window().inner_size();
+        window_to_global(niri, &winit_output.output, window_size, position)
+    }
+
+    /// Returns where `pos` in the global space shows on the screen, in points from the top left
+    /// of the main display.
+    fn screen_position(&self, niri: &Niri, pos: Point<f64, Logical>) -> Option<(f64, f64)> {
+        let (output, pos_within_output) = niri.output_under(pos)?;
+        let output_size = niri.global_space.output_geometry(output)?.size.to_f64();
+
+        let winit_output = self.outputs.iter().find(|o| o.output == *output)?;
+        let window = winit_output.window();
+        let window_size = window.inner_size();
+        let origin = window.inner_position().ok()?;
+        let scale = window.scale_factor();
+
+        // The window shows the output rotated, see window_to_global().
+        let pos = window_rotation(output).transform_point_in(
+            Point::<f64, Logical>::from((
+                pos_within_output.x / output_size.w,
+                pos_within_output.y / output_size.h,
+            )),
+            &Size::from((1., 1.)),
+        );
+        let x = f64::from(origin.x) + pos.x * f64::from(window_size.width);
+        let y = f64::from(origin.y) + pos.y * f64::from(window_size.height);
+        Some((x / scale, y / scale))
+    }
+
+    fn update_display_sleep_assertion(&mut self) {
+        let keep_awake = self.monitors_active && self.idle_inhibited;
+        if keep_awake == self.display_sleep_assertion.is_some() {
+            return;
+        }
+
+        self.display_sleep_assertion = if keep_awake {
+            debug!("clients inhibit idle, keeping the displays awake");
+            create_display_sleep_assertion()
+        } else {
+            None
+        };
+    }
+
+    /// Returns whether there's nothing to render until the displays come back.
+    pub fn is_rendering_paused(&self) -> bool {
+        self.suspended || !self.monitors_active
+    }
+
+    /// Switches away from or back to the compositor.
+    ///
+    /// macOS has no VTs. Instead, the compositor acts as if it ran on VT 1: switching to any
+    /// other VT hides it and gives the focus to the next app, and switching back to VT 1 shows it
+    /// again. Unhiding it from the Dock also counts as switching back.
+    pub fn change_vt(&mut self, vt: i32) {
+        if self.active_vt == vt {
+            return;
+        }
+        self.active_vt = vt;
+
+        if vt == COMPOSITOR_VT {
+            appkit::unhide_app();
+            if let Some(winit_output) = self.outputs.first() {
+                winit_output.window().focus_window();
+            }
+        } else {
+            appkit::hide_app();
+        }
+    }
+
+    /// Removes or brings back the title bar and the traffic light buttons of every output window.
+    ///
+    /// Cmd+Q and the menu bar keep working without them, and so does fullscreen.
+    pub fn set_borderless(&mut self, borderless: bool) {
+        self.borderless = borderless;
+        for winit_output in &self.outputs {
+            winit_output.window().set_decorations(!borderless);
+        }
+    }
+
+    /// Enters or leaves native fullscreen on every output window.
+    ///
+    /// AppKit animates the transition and sends a Resized at the end, which updates the output
+    /// modes like any other window resize.
+    pub fn set_fullscreen(&mut self, fullscreen: bool) {
+        for winit_output in &mut self.outputs {
+            let window = &winit_output.cocoa_window.window;
+            if window.fullscreen().is_some() == fullscreen {
+                continue;
+            }
+
+            if fullscreen {
+                // The windows start out maximized unless a size is configured, remember that to
+                // come back to it.
+                winit_output.maximized_before_fullscreen = window.is_maximized();
+                window.set_fullscreen(Some(Fullscreen::Borderless(None)));
+            } else {
+                window.set_fullscreen(None);
+                if winit_output.maximized_before_fullscreen {
+                    window.set_maximized(true);
+                }
+            }
+        }
+    }
+
+    pub fn toggle_fullscreen(&mut self) {
+        let fullscreen = self.window().fullscreen().is_some();
+        self.set_fullscreen(!fullscreen);
+    }
+
+    pub fn toggle_debug_tint(&mut self) {
+        self.debug_tint = !self.debug_tint;
+
+        // All outputs render through the one renderer, so this tints every window.
+        let renderer = &mut self.gles_renderer;
+        let mut flags = renderer.debug_flags();
+        flags.set(DebugFlags::TINT, self.debug_tint);
+        renderer.set_debug_flags(flags);
+    }
+
+    /// Shows or hides the frame rate, 1% low and vsync state in the corner of every window.
+    pub fn toggle_fps_overlay(&mut self) {
+        self.fps_overlay = !self.fps_overlay;
+
+        for winit_output in &mut self.outputs {
+            // Start from an empty history, frames from before the toggle are long gone.
+            winit_output.fps_overlay = self.fps_overlay.then(FpsOverlay::new);
+            winit_output.window().request_redraw();
+        }
+    }
+
+    /// Recompiles the built-in shaders from their source files, see [`shaders::reload`].
+    pub fn reload_shaders(&mut self, niri: &mut Niri) {
+        let dir = shaders::source_dir();
+        if let Err(err) = shaders::reload(&mut self.gles_renderer, &dir) {
+            warn!("error reloading shaders from {dir:?}, keeping the current ones: {err:?}");
+            return;
+        }
+
+        info!("reloaded shaders from {dir:?}");
+        niri.update_shaders();
+        niri.queue_redraw_all();
+    }
+
+    /// Rejects the dmabuf, so that the client falls back to SHM buffers.
+    ///
+    /// macOS has no dmabufs, and the renderer can only sample from textures that it imported
+    /// itself, not from IOSurfaces bound to rectangle textures.
+    pub fn import_dmabuf(&mut self, _dmabuf: &Dmabuf) -> bool {
+        false
+    }
+
+    /// Uploads the SHM buffer that `surface` just committed, so that rendering doesn't stall on it.
+    ///
+    /// The texture is cached in the surface state, where rendering finds it until the surface
+    /// commits another buffer. Dmabufs are left to the render path, which imports them.
+    pub fn early_import(&mut self, surface: &WlSurface) {
+        let _span = tracy_client::span!("Winit::early_import");
+
+        with_states(surface, |states| {
+            let is_shm = states
+                .data_map
+                .get::<RendererSurfaceStateUserData>()
+                .is_some_and(|data| {
+                    let data = data.lock().unwrap();
+                    let buffer_type = data.buffer().and_then(|buffer| buffer_type(buffer));
+                    matches!(buffer_type, Some(BufferType::Shm))
+                });
+            if !is_shm {
+                return;
+            }
+
+            // The contexts share objects, so the texture works in every window.
+            if let Err(err) = self.outputs[0].cocoa_window.make_current() {
+                warn!("error making the GL context current: {err}");
+                return;
+            }
+
+            if let Err(err) = import_surface(&mut self.gles_renderer, states) {
+                warn!("error doing early import: {err:?}");
+            }
+        });
+    }
+
+    pub fn ipc_outputs(&self) -> Arc<Mutex<IpcOutputMap>> {
+        self.ipc_outputs.clone()
+    }
+    
+    /// Returns the window that the backend started with.
+    pub fn window(&self) -> &Window {
+        self.outputs[0].window()
+    }
+}
+
+/// Applies a window system event to the compositor state.
+///
+/// The winit backend calls this for the messages that come out of its channel. Tests can feed it
+/// messages directly.
+pub fn handle_event(state: &mut State, msg: WinitEventMsg) {
+    match msg {
+        WinitEventMsg::Resized { window_id, size } => {
+            tracing::info!("Niri received WinitEvent::Resized: {:?}", size);
+            let winit = state.backend.winit();
+            let Some(winit_output) = winit.outputs.iter_mut().find(|o| o.window().id() == window_id)
+            else {
+                return;
+            };
+            winit_output.update_refresh_rate();
+            winit_output.cocoa_window.resize(size.width, size.height);
+
+            {
+                let mut ipc_outputs = winit.ipc_outputs.lock().unwrap();
+                let output = ipc_outputs.get_mut(&winit_output.ipc_id).unwrap();
+                let mode_size = (size.width as i32, size.height as i32).into();
+                winit_output.update_modes(mode_size, None, output);
+                winit_output.reset_damage();
+                if let Some(logical) = output.logical.as_mut() {
+                    logical.width = size.width;
+                    logical.height = size.height;
+                }
+                state.niri.ipc_outputs_changed = true;
+            }
+
+            state.niri.output_resized(&winit_output.output);
+        }
+        // The window may have moved to a different display.
+        WinitEventMsg::Moved { window_id } => {
+            let winit = state.backend.winit();
+            let Some(winit_output) = winit.outputs.iter_mut().find(|o| o.window().id() == window_id)
+            else {
+                return;
+            };
+            let modes_changed = winit_output.update_refresh_rate();
+
+            // The Output keeps the size it was created with, smithay has no way to change it, but
+            // IPC clients can see the new one.
+            let physical_size = appkit::display_size_mm(winit_output.window())
+                .map(|(w, h)| (w as u32, h as u32));
+            let mut ipc_outputs = winit.ipc_outputs.lock().unwrap();
+            let ipc_output = ipc_outputs.get_mut(&winit_output.ipc_id).unwrap();
+            if modes_changed {
+                let size = winit_output.output.current_mode().unwrap().size;
+                winit_output.update_modes(size, None, ipc_output);
+                state.niri.ipc_outputs_changed = true;
+            }
+            if ipc_output.physical_size != physical_size {
+                ipc_output.physical_size = physical_size;
+                state.niri.ipc_outputs_changed = true;
+            }
+
+            // The window may have moved to a screen with a different refresh.
+            if ipc_output.vrr_supported != winit_output.vrr_supported
+                || ipc_output.vrr_enabled != winit_output.vrr_enabled
+            {
+                ipc_output.vrr_supported = winit_output.vrr_supported;
+                ipc_output.vrr_enabled = winit_output.vrr_enabled;
+                state.niri.ipc_outputs_changed = true;
+
+                let output_state = state.niri.output_state.get_mut(&winit_output.output);
+                if let Some(output_state) = output_state {
+                    output_state.frame_clock.set_vrr(winit_output.vrr_enabled);
+                }
+            }
+        }
+        WinitEventMsg::ScaleFactorChanged {
+            window_id,
+            scale_factor,
+        } => {
+            tracing::info!("Niri received WinitEvent::ScaleFactorChanged: {scale_factor}");
+            let winit = state.backend.winit();
+            let Some(winit_output) = winit.outputs.iter_mut().find(|o| o.window().id() == window_id)
+            else {
+                return;
+            };
+            winit_output.update_refresh_rate();
+
+            let output = winit_output.output.clone();
+            output.user_data().get::<PreferredScale>().unwrap().0.set(scale_factor);
+
+            // A scale set in the config wins over the display one. The scale factor isn't rounded
+            // to an integer, so that clients with fractional scaling get the exact value.
+            let name = output.user_data().get::<OutputName>().unwrap();
+            let configured = winit.config.borrow().outputs.find(name).and_then(|c| c.scale);
+            let scale = if configured.is_some() {
+                output.current_scale().fractional_scale()
+            } else {
+                closest_representable_scale(scale_factor.clamp(0.1, 10.))
+            };
+
+            // The backing size changes together with the scale factor, also when the config sets
+            // the scale, e.g. when the window moves between a Retina and a regular display. The
+            // Resized event that follows may come late, so pick the size up right away.
+            let size = winit_output.window().inner_size();
+            winit_output.cocoa_window.resize(size.width, size.height);
+
+            {
+                let mut ipc_outputs = winit.ipc_outputs.lock().unwrap();
+                let ipc_output = ipc_outputs.get_mut(&winit_output.ipc_id).unwrap();
+                winit_output.update_modes(
+                    (size.width as i32, size.height as i32).into(),
+                    Some(Scale::Fractional(scale)),
+                    ipc_output,
+                );
+                winit_output.reset_damage();
+                if let Some(logical) = ipc_output.logical.as_mut() {
+                    logical.scale = scale;
+                }
+                state.niri.ipc_outputs_changed = true;
+            }
+
+            // This sends the new wl_output scale and preferred fractional scale to clients.
+            state.niri.output_resized(&output);
+        }
+        WinitEventMsg::CloseRequested => state.niri.stop(),
+        WinitEventMsg::RedrawRequested { window_id } => {
+            if let Some(winit_output) = state.backend.winit().output_for_window(window_id) {
+                let output = winit_output.output.clone();
+                state.niri.queue_redraw(&output);
+            }
+        }
+        WinitEventMsg::Covered { window_id } => {
+            if let Some(winit_output) = state.backend.winit().output_for_window(window_id) {
+                winit_output.covered = true;
+            }
+        }
+        WinitEventMsg::Uncovered { window_id } => {
+            // macOS may have dropped the window contents while nothing of it was visible.
+            if let Some(winit_output) = state.backend.winit().output_for_window(window_id) {
+                winit_output.covered = false;
+                winit_output.reset_damage();
+                let output = winit_output.output.clone();
+                state.niri.queue_redraw(&output);
+            }
+        }
+        WinitEventMsg::ModifiersChanged {
+            state: modifiers,
+            device_flags,
+        } => {
+            // Synthesize key events for modifiers (Winit 0.30/macOS swallows them)
+            let new_state = SidedModifiers::from_winit(modifiers, device_flags);
+            sync_modifiers(state, new_state);
+        }
+        WinitEventMsg::KeyboardInput {
+            key,
+            state: key_state,
+            time: now,
+            event_time,
+        } => {
+            // When the CFRunLoop sleeps, AppKit can hand over a key event twice, as a Release and
+            // a Press of the same key right after each other. Drop those, but keep fast presses
+            // that the user actually made.
+            let burst_key = match key {
+                PhysicalKey::Code(code) => code as u32,
+                PhysicalKey::Unidentified(_) => 0,
+            };
+            let pressed = key_state == ElementState::Pressed;
+            let winit = state.backend.winit();
+            if winit.key_bursts.is_duplicate(burst_key, pressed, now) {
+                return;
+            }
+            // Like in libinput, modifiers don't count as typing, so that Cmd+click works.
+            if pressed && !is_modifier_key(key) {
+                winit.last_key_press = Some(now);
+            }
+            let caps_lock_monitored = winit.caps_lock_monitor.is_some();
+            let iso_keyboard = appkit::keyboard_is_iso();
+
+            let time = input_event_time(event_time);
+            let events = keyboard_events(key, key_state, caps_lock_monitored, iso_keyboard);
+            for (key, key_state) in events {
+                let event = InputEvent::<WinitInput>::Keyboard {
+                    event: WinitKeyboardInputEvent {
+                        time,
+                        key,
+                        count: 1,
+                        state: key_state,
+                    },
+                };
+                state.process_input_event(event);
+            }
+        }
+        WinitEventMsg::Focused {
+            focused,
+            device_flags,
+        } => {
+            tracing::info!("Window Focus Changed: {}", focused);
+            let winit = state.backend.winit();
+            winit.is_focused = focused;
+            if !focused {
+                winit.background_drag = None;
+            }
+            // The user came back some other way than switching VTs, e.g. from the Dock.
+            if focused {
+                winit.active_vt = COMPOSITOR_VT;
+            }
+
+            // Key releases that came in while another app was in front went there, so earlier
+            // presses say nothing about what comes next.
+            winit.key_bursts = KeyBurstFilter::new(winit.key_bursts.window);
+
+            // Other apps get the modifier changes while we're in the background, e.g. the Cmd
+            // release after Cmd-Tab, so release everything on the way out and pick up what is
+            // held on the way back in.
+            let modifiers =
+                device_flags.map_or(SidedModifiers::empty(), SidedModifiers::from_device_flags);
+            sync_modifiers(state, modifiers);
+
+            // Give the cursor back to other apps.
+            sync_pointer_constraint(state);
+
+            // Coming back is also how the user returns from changing the key repeat in System
+            // Settings.
+            if focused && appkit::refresh_key_repeat() {
+                let config = state.niri.config.borrow();
+                let (repeat_delay, repeat_rate) = key_repeat_info(&config.input.keyboard);
+                let keyboard = state.niri.seat.get_keyboard().unwrap();
+                keyboard.change_repeat_info(repeat_rate.into(), repeat_delay.into());
+            }
+        }
+        WinitEventMsg::CursorMoved {
+            window_id,
+            position,
+            touch_count,
+            event_time,
+            tablet,
+        } => {
+            if let Some(point) = tablet {
+                handle_tablet_motion(state, window_id, position, point, event_time);
+                return;
+            }
+
+            let device = pointer_device(touch_count);
+            let winit = state.backend.winit();
+            if !device.sends_events() || winit.held_back_while_typing(&device) {
+                winit.restore_cursor(&state.niri);
+                return;
+            }
+
+            if move_background_drag(state) {
+                return;
+            }
+
+            // The cursor stays put while held, the pointer moves with the relative motion.
+            if state.backend.winit().cursor_hold.is_some() {
+                return;
+            }
+
+            let Some(winit_output) = state.backend.winit().output_for_window(window_id) else {
+                return;
+            };
+            let output = winit_output.output.clone();
+            let window_size = winit_output.window().inner_size();
+            let Some(relative_position) =
+                global_relative_position(state, &output, window_size, position)
+            else {
+                return;
+            };
+
+            let event = InputEvent::<WinitInput>::PointerMotionAbsolute {
+                event: WinitMouseMovedEvent {
+                    time: input_event_time(event_time),
+                    position: relative_position,
+                    global_position: position,
+                },
+            };
+            state.process_input_event(event);
+        }
+        WinitEventMsg::CursorEntered { window_id } => {
+            let winit = state.backend.winit();
+            winit.hovered_window = Some(window_id);
+            winit.cursor_left = false;
+        }
+        WinitEventMsg::CursorLeft { window_id } => {
+            // When crossing between two outputs, the Entered for the next window follows in the
+            // same batch, so wait for AboutToWait to tell.
+            let winit = state.backend.winit();
+            if winit.hovered_window == Some(window_id) {
+                winit.hovered_window = None;
+            }
+            winit.cursor_left = true;
+        }
+        WinitEventMsg::MouseButton {
+            window_id,
+            button,
+            state: element_state,
+            location,
+            touch_count,
+            event_time,
+            tablet,
+        } => {
+            // The pointer motion up to the click can come later or not at all, e.g. when the
+            // click activates the window. Move the pointer to the click first, so that it goes
+            // to the surface under it.
+            if let Some(location) = location {
+                if let Some(winit_output) = state.backend.winit().output_for_window(window_id) {
+                    let window = winit_output.window();
+                    let (size, scale) = (window.inner_size(), window.scale_factor());
+                    let position = window_location_to_physical(location, size, scale);
+                    handle_event(
+                        state,
+                        WinitEventMsg::CursorMoved {
+                            window_id,
+                            position,
+                            touch_count,
+                            event_time,
+                            tablet,
+                        },
+                    );
+                }
+            }
+
+            if let Some(point) = tablet {
+                handle_tablet_button(state, button, element_state, point, event_time);
+                return;
+            }
+
+            // Releases go the same way, so that clicks on a disabled trackpad leave no stray ones.
+            let device = pointer_device(touch_count);
+            if !device.sends_events() {
+                return;
+            }
+
+            let winit = state.backend.winit();
+            let held_back = match element_state {
+                ElementState::Pressed => {
+                    let held_back = winit.held_back_while_typing(&device);
+                    if held_back {
+                        winit.typing_held_buttons.insert(button);
+                    }
+                    held_back
+                }
+                ElementState::Released => winit.typing_held_buttons.remove(&button),
+            };
+            if held_back {
+                return;
+            }
+
+            let button =
+                state.backend.winit().trackpad_click_button(button, element_state, touch_count);
+            if button == WinitMouseButton::Left
+                && handle_background_drag(state, window_id, element_state)
+            {
+                return;
+            }
+
+            let event = InputEvent::<WinitInput>::PointerButton {
+                event: WinitMouseInputEvent {
+                    time: input_event_time(event_time),
+                    button,
+                    state: element_state,
+                    is_x11: false,
+                },
+            };
+            state.process_input_event(event);
+        }
+        WinitEventMsg::MouseWheel {
+            delta,
+            phase,
+            touch_count,
+            scroll_phase,
+            inverted,
+            event_time,
+        } => {
+            // Momentum scrolling goes on after the fingers lift.
+            let device = if scroll_phase == ScrollPhase::None {
+                pointer_device(touch_count)
+            } else {
+                ShimDevice::TRACKPAD
+            };
+            if !device.sends_events() {
+                return;
+            }
+
+            // Momentum from a scroll that began before the typing carries on.
+            let fingers = pointer_device(touch_count);
+            if state.backend.winit().held_back_while_typing(&fingers) {
+                return;
+            }
+
+            if handle_window_drag(state, delta, phase, touch_count, inverted) {
+                return;
+            }
+
+            let winit = state.backend.winit();
+            if let Some(event) =
+                winit.swipe_gesture_event(delta, phase, touch_count, inverted, event_time)
+            {
+                state.process_input_event(event);
+                return;
+            }
+
+            // Natural scrolling from the niri mouse settings reaches the shim devices, the touchpad
+            // one doesn't, so trackpads keep following macOS.
+            let (delta, inverted) = if scroll_phase == ScrollPhase::None {
+                let natural_scroll = ShimDevice::POINTER.configured_natural_scroll();
+                natural_scroll_delta(delta, inverted, natural_scroll)
+            } else {
+                (delta, inverted)
+            };
+
+            // Only with Shift alone, so that bindings like Mod+Shift+WheelScrollDown still work.
+            let shift = SidedModifiers::SHIFT_LEFT | SidedModifiers::SHIFT_RIGHT;
+            let modifiers = winit.last_modifiers;
+            let shift_only = !modifiers.is_empty() && shift.contains(modifiers);
+            let delta = if shift_only
+                && !winit.config.borrow().input.mouse.no_shift_horizontal_scroll
+            {
+                shift_scroll_delta(delta)
+            } else {
+                delta
+            };
+
+            // Scale before adding up the steps, otherwise a small factor could round every step
+            // down to nothing.
+            let factors = scroll_factors(&winit.config.borrow(), delta, scroll_phase);
+            let delta = scale_scroll_delta(delta, factors);
+
+            // Trackpads only send pixel deltas, add them up into discrete steps for clients that
+            // only scroll by those.
+            let pixel_v120 = match delta {
+                MouseScrollDelta::PixelDelta(_) => {
+                    if phase == TouchPhase::Started {
+                        winit.scroll_v120 = V120Accumulator::default();
+                    }
+                    let (dx, dy) = axis_amounts(delta);
+                    winit.scroll_v120.add(dx, dy)
+                }
+                MouseScrollDelta::LineDelta(_, _) => {
+                    winit.scroll_v120 = V120Accumulator::default();
+                    (0., 0.)
+                }
+            };
+
+            // Without the momentum, the axis stop goes out right after the fingers lift, when the
+            // timer fires.
+            if scroll_phase == ScrollPhase::Momentum
+                && winit.config.borrow().input.touchpad.no_scroll_momentum
+            {
+                return;
+            }
+
+            // A new scroll ends the previous one, which may still wait for momentum.
+            let stop = winit.scroll_gesture.start(scroll_phase, phase);
+            apply_scroll_stop(state, stop);
+
+            let event = InputEvent::<WinitInput>::PointerAxis {
+                event: WinitMouseWheelEvent {
+                    time: input_event_time(event_time),
+                    delta,
+                    phase,
+                    scroll_phase,
+                    pixel_v120,
+                    inverted,
+                    axis_stop: false,
+                },
+            };
+            state.process_input_event(event);
+
+            let stop = state.backend.winit().scroll_gesture.advance(scroll_phase, phase);
+            apply_scroll_stop(state, stop);
+        }
+        WinitEventMsg::PinchGesture {
+            delta,
+            phase,
+            event_time,
+        } => {
+            if !ShimDevice::TRACKPAD.sends_events() {
+                return;
+            }
+
+            let winit = state.backend.winit();
+            if let Some(event) = winit.pinch_gesture_event(delta, phase, event_time) {
+                state.process_input_event(event);
+            }
+        }
+        WinitEventMsg::Touch {
+            window_id,
+            touch,
+            event_time,
+        } => {
+            if !ShimDevice::TOUCHSCREEN.sends_events() {
+                return;
+            }
+
+            let Some(winit_output) = state.backend.winit().output_for_window(window_id) else {
+                return;
+            };
+            let size = winit_output.window().inner_size();
+            let output = winit_output.output.clone();
+
+            // Calibration applies to the position on the panel, before any rotation.
+            let position = ShimDevice::TOUCHSCREEN.calibrate_touch(
+                touch.location.x / f64::from(size.width),
+                touch.location.y / f64::from(size.height),
+            );
+            let position = window_position_in_output(&output, position);
+
+            let time = input_event_time(event_time);
+            let id = touch.id;
+            let event = match touch.phase {
+                TouchPhase::Started => InputEvent::<WinitInput>::TouchDown {
+                    event: WinitTouchDownEvent {
+                        time,
+                        position,
+                        global_position: touch.location,
+                        id,
+                    },
+                },
+                TouchPhase::Moved => InputEvent::TouchMotion {
+                    event: WinitTouchMotionEvent {
+                        time,
+                        position,
+                        global_position: touch.location,
+                        id,
+                    },
+                },
+                TouchPhase::Ended => InputEvent::TouchUp {
+                    event: WinitTouchUpEvent { time, id },
+                },
+                TouchPhase::Cancelled => InputEvent::TouchCancel {
+                    event: WinitTouchCancelEvent { time, id },
+                },
+            };
+            state.backend.winit().event_output = Some(output);
+            state.process_input_event(event);
+            let winit = state.backend.winit();
+            winit.event_output = None;
+            winit.touch_frame_pending = true;
+        }
+        WinitEventMsg::HoveredFile { path } => {
+            state.backend.winit().dragged_files.push(path);
+        }
+        WinitEventMsg::DroppedFile { path } => {
+            // winit already reported the files while they hovered, unless the drop came first.
+            let winit = state.backend.winit();
+            if !winit.file_drag_active && !winit.dragged_files.contains(&path) {
+                winit.dragged_files.push(path);
+            }
+            winit.file_drag_end = Some(true);
+        }
+        WinitEventMsg::HoveredFileCancelled => {
+            state.backend.winit().file_drag_end = Some(false);
+        }
+        WinitEventMsg::MouseMotion {
+            delta,
+            touch_count,
+            event_time,
+        } => {
+            // Raw deltas arrive even when another app is in front; only forward them while we
+            // have focus.
+            let device = pointer_device(touch_count);
+            let winit = state.backend.winit();
+            if !winit.is_focused || !device.sends_events() || winit.held_back_while_typing(&device) {
+                return;
+            }
+
+            if move_background_drag(state) {
+                return;
+            }
+
+            // Only a held cursor moves with the relative motion, otherwise the CursorMoved that
+            // follows puts the pointer where the macOS cursor is, at the macOS speed.
+            let factor = if state.backend.winit().cursor_hold.is_some() {
+                pointer_speed_factor(&state.niri.config.borrow(), touch_count > 0)
+            } else {
+                1.
+            };
+            let delta = (delta.0 * factor, delta.1 * factor);
+
+            // This moves the pointer by the delta and sends relative motion to clients. The
+            // CursorMoved that macOS emits for the same NSEvent then snaps the pointer to the
+            // exact window position rather than adding the delta again.
+            let event = InputEvent::<WinitInput>::PointerMotion {
+                event: WinitMouseRelativeEvent {
+                    time: input_event_time(event_time),
+                    delta,
+                },
+            };
+            state.process_input_event(event);
+
+            // Keep the cursor with the pointer, which niri keeps within the confinement region.
+            if state.backend.winit().cursor_hold == Some(CursorHold::Confined) {
+                let pos = state.niri.seat.get_pointer().unwrap().current_location();
+                if let Some((x, y)) = state.backend.winit().screen_position(&state.niri, pos) {
+                    appkit::warp_cursor(x, y, false);
+                }
+            }
+        }
+        WinitEventMsg::AboutToWait => {
+            let winit = state.backend.winit();
+            winit.update_suspended(&mut state.niri);
+            let touch_frame_pending = std::mem::take(&mut winit.touch_frame_pending);
+            let cursor_gone =
+                std::mem::take(&mut winit.cursor_left) && winit.hovered_window.is_none();
+
+            // Group the touch events of this batch into one frame.
+            if touch_frame_pending {
+                let event = InputEvent::<WinitInput>::TouchFrame {
+                    event: WinitTouchFrameEvent {
+                        time: get_monotonic_time().as_micros() as u64,
+                    },
+                };
+                state.process_input_event(event);
+            }
+
+            if appkit::take_tablet_left_proximity() {
+                tablet_proximity_out(state);
+            }
+
+            for fingers in appkit::take_trackpad_taps() {
+                handle_trackpad_tap(state, fingers);
+            }
+
+            // The events of this batch may have activated or ended a pointer constraint.
+            sync_pointer_constraint(state);
+            sync_file_drag(state);
+
+            // Frames render between event batches, so this never cuts one short: the run loop
+            // sees the stop right after this dispatch and returns before rendering again.
+            if appkit::take_quit_requested() {
+                info!("quitting as requested by macOS");
+                state.niri.stop();
+            }
+
+            if !cursor_gone {
+                return;
+            }
+
+            // The cursor went off all our windows, e.g. onto the menu bar or another app. Take
+            // the pointer focus away from clients until it comes back; the next absolute motion
+            // makes the pointer visible again.
+            state.niri.pointer_visibility = PointerVisibility::Disabled;
+            state.refresh_pointer_contents();
+            // Erase the drawn cursor from the window that it left.
+            state.niri.queue_redraw_all();
+        }
+    }
+}
+
+/// How the macOS cursor is held for an active pointer constraint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CursorHold {
+    Locked,
+    Confined,
+}
+
+/// Returns how the active pointer constraint under the pointer holds the cursor, if there is one.
+fn active_pointer_constraint(niri: &Niri) -> Option<CursorHold> {
+    let (surface, origin) = niri.pointer_contents.surface.as_ref()?;
+    let pointer = niri.seat.get_pointer().unwrap();
+    let pos_within_surface = pointer.current_location() - *origin;
+
+    with_pointer_constraint(surface, &pointer, |constraint| {
+        let constraint = constraint?;
+        if !constraint.is_active() {
+            return None;
+        }
+
+        // Constraint does not apply if not within region.
+        if let Some(region) = constraint.region() {
+            if !region.contains(pos_within_surface.to_i32_round()) {
+                return None;
+            }
+        }
+
+        match &*constraint {
+            PointerConstraint::Locked(_) => Some(CursorHold::Locked),
+            PointerConstraint::Confined(_) => Some(CursorHold::Confined),
+        }
+    })
+}
+
+/// Holds or releases the macOS cursor to match the active pointer constraint.
+///
+/// A held cursor stops following the mouse, so the pointer only moves with the relative motion,
+/// where niri applies the constraint, and absolute motion is dropped. The cursor is put where the
+/// pointer is on every switch. Warping sends no events, so this doesn't jump the pointer, neither
+/// on lock nor when the next absolute motion comes after a release.
+fn sync_pointer_constraint(state: &mut State) {
+    let hold = if state.backend.winit().is_focused {
+        active_pointer_constraint(&state.niri)
+    } else {
+        None
+    };
+
+    let winit = state.backend.winit();
+    if winit.cursor_hold == hold {
+        return;
+    }
+    debug!("pointer constraint changed: {:?} -> {hold:?}", winit.cursor_hold);
+    winit.cursor_hold = hold;
+
+    let pos = state.niri.seat.get_pointer().unwrap().current_location();
+    match state.backend.winit().screen_position(&state.niri, pos) {
+        Some((x, y)) => appkit::warp_cursor(x, y, hold.is_none()),
+        None => appkit::set_cursor_follows_mouse(hold.is_none()),
+    }
+}
+
+/// Converts a point in a window from AppKit, in points from the bottom left, to physical pixels
+/// from the top left like in winit events.
+fn window_location_to_physical(
+    (x, y): (f64, f64),
+    window_size: PhysicalSize<u32>,
+    scale: f64,
+) -> PhysicalPosition<f64> {
+    PhysicalPosition::new(x * scale, f64::from(window_size.height) - y * scale)
+}
+
+/// Offers files that a macOS app drags over the windows to the clients, see [`file_drop`].
+///
+/// AppKit sends no mouse events during the drag, so this moves the pointer to the cursor.
+fn sync_file_drag(state: &mut State) {
+    let winit = state.backend.winit();
+    if !winit.file_drag_active && !winit.dragged_files.is_empty() {
+        winit.file_drag_active = true;
+        let paths = winit.dragged_files.clone();
+        debug!("offering {} dragged files", paths.len());
+        file_drop::start(state, &paths);
+    }
+
+    let winit = state.backend.winit();
+    if !winit.file_drag_active {
+        return;
+    }
+
+    let under_cursor = appkit::cursor_location().and_then(|(x, y)| winit.window_at(x, y));
+    if let Some((window_id, position)) = under_cursor {
+        handle_event(
+            state,
+            WinitEventMsg::CursorMoved {
+                window_id,
+                position,
+                touch_count: 0,
+                event_time: None,
+                tablet: None,
+            },
+        );
+    }
+
+    let winit = state.backend.winit();
+    if let Some(dropped) = winit.file_drag_end.take() {
+        winit.file_drag_active = false;
+        winit.dragged_files.clear();
+        file_drop::finish(state, dropped);
+    }
+}
+
+/// Returns whether `key` is one of the modifiers that `ModifiersChanged` reports.
+fn is_modifier_key(key: PhysicalKey) -> bool {
+    matches!(
+        key,
+        PhysicalKey::Code(
+            KeyCode::ShiftLeft
+                | KeyCode::ShiftRight
+                | KeyCode::ControlLeft
+                | KeyCode::ControlRight
+                | KeyCode::AltLeft
+                | KeyCode::AltRight
+                | KeyCode::SuperLeft
+                | KeyCode::SuperRight
+        )
+    )
+}
+
+/// Returns the raw evdev keys to send for a key event, along with their states.
+///
+/// Modifier keys send nothing, [`sync_modifiers`] takes care of them.
+fn keyboard_events(
+    key: PhysicalKey,
+    key_state: ElementState,
+    caps_lock_monitored: bool,
+    iso_keyboard: bool,
+) -> Vec<(u32, ElementState)> {
+    // Filter out real modifier key events to avoid stuck keys.
+    // Winit/macOS often swallows the Release events for modifiers, so we rely
+    // entirely on the Synthetic events from `ModifiersChanged` to ensure valid Press/Release pairs.
+    if is_modifier_key(key) {
+        trace!("dropping modifier key event in favor of the synthetic one: {key:?}");
+        return Vec::new();
+    }
+
+    // AppKit reports Caps Lock turning on as a press and turning off as a release. The HID
+    // monitor sends the actual presses and releases, and without it every toggle becomes a tap,
+    // so that the key still works for xkb remaps like caps:escape.
+    if key == PhysicalKey::Code(KeyCode::CapsLock) {
+        if caps_lock_monitored {
+            return Vec::new();
+        }
+
+        return vec![
+            (CAPS_LOCK_EVDEV, ElementState::Pressed),
+            (CAPS_LOCK_EVDEV, ElementState::Released),
+        ];
+    }
+
+    // Robust Mapping based on Cocoa-Way (KeyCode -> Evdev + 8)
+    // This bypasses macOS specific scancodes and uses Winit's unified PhysicalKey
+    let evdev_code = match key {
+        PhysicalKey::Code(code) => keycode_to_evdev(iso_key_position(code, iso_keyboard)),
+        _ => None,
+    };
+
+    let scancode = if let Some(code) = evdev_code {
+        code // Raw Evdev (winit_input adds +8)
+    } else {
+        // Fallback for unmapped keys (try raw + 8 heuristic? No, winit_input adds 8)
+        // So we send raw macOS scancode?
+        // If winit_input adds 8, and we send raw mac scancode, result is mac+8.
+        // This is consistent with what we tried before, but maybe incorrect for unmapped keys.
+        // However, for mapped keys, we MUST NOT add 8.
+        key.to_scancode().unwrap_or(0)
+    };
+
+    tracing::trace!(
+        "Key Input Debug: key={:?}, evdev={:?}, final_scancode={}, state={:?}",
+        key,
+        evdev_code,
+        scancode,
+        key_state
+    );
+
+    vec![(scancode, key_state)]
+}
+
+/// How long pasting into a macOS app waits for the Wayland client to send the selection.
+const SELECTION_READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Reads the clipboard selection of a Wayland client as the MIME type.
+///
+/// This runs while a macOS app waits for the paste, so it gives up after
+/// [`SELECTION_READ_TIMEOUT`] instead of hanging on a client that never answers.
+fn read_client_selection(
+    seat: &Seat<State>,
+    display_handle: &DisplayHandle,
+    mime_type: String,
+) -> Option<Vec<u8>> {
+    let (read_fd, write_fd) = match pipe_with(PipeFlags::CLOEXEC | PipeFlags::NONBLOCK) {
+        Ok(fds) => fds,
+        Err(err) => {
+            warn!("error creating a pipe for the selection: {err:?}");
+            return None;
+        }
+    };
+    if let Err(err) = request_data_device_client_selection(seat, mime_type, write_fd) {
+        warn!("error requesting the selection: {err:?}");
+        return None;
+    }
+    // The run loop is busy with the paste, so send the request out right away.
+    if let Err(err) = display_handle.clone().flush_clients() {
+        warn!("error flushing the selection request: {err:?}");
+    }
+
+    let deadline = Instant::now() + SELECTION_READ_TIMEOUT;
+    let mut file = File::from(read_fd);
+    let mut contents = Vec::new();
+    let mut buf = [0; 4096];
+    loop {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        if timeout.is_zero() {
+            warn!("timed out reading the selection");
+            return None;
+        }
+
+        let mut fds = libc::pollfd {
+            fd: file.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout_ms = timeout.as_millis().max(1) as libc::c_int;
+        if unsafe { libc::poll(&mut fds, 1, timeout_ms) } < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                warn!("error waiting for the selection: {err:?}");
+                return None;
+            }
+            continue;
+        }
+
+        match file.read(&mut buf) {
+            Ok(0) => return Some(contents),
+            Ok(len) => contents.extend_from_slice(&buf[..len]),
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted
+                ) => {}
+            Err(err) => {
+                warn!("error reading the selection: {err:?}");
+                return None;
+            }
+        }
+    }
+}
+
+/// An output shown in its own window, one for each display.
+struct WinitOutput {
+    output: Output,
+    cocoa_window: CocoaWindowHandle,
+    damage_tracker: OutputDamageTracker,
+    ipc_id: OutputId,
+    /// Refresh rate of the display showing the window, in mHz.
+    refresh_rate: i32,
+    /// Refresh rates that the display offers, fastest first, including `refresh_rate`.
+    refresh_rates: Vec<i32>,
+    /// Whether to maximize the window again when leaving fullscreen.
+    maximized_before_fullscreen: bool,
+    /// Whether the screen showing the window can vary its refresh rate.
+    vrr_supported: bool,
+    /// Whether on-demand VRR is on, see [`Winit::set_output_on_demand_vrr`].
+    vrr_enabled: bool,
+    /// Recent frame times and their overlay, while it is shown.
+    fps_overlay: Option<FpsOverlay>,
+    /// Whether nothing of the window is visible, see [`Winit::is_vsync_active`].
+    covered: bool,
+}
+
+impl WinitOutput {
+    fn new(connector: String, cocoa_window: CocoaWindowHandle) -> (Self, niri_ipc::Output) {
+        appkit::enable_trackpad_touches(&cocoa_window.window);
+        let refresh_rate = appkit::screen_refresh_rate(&cocoa_window.window).unwrap_or(60_000);
+        let refresh_rates =
+            offered_refresh_rates(appkit::display_refresh_rates(&cocoa_window.window), refresh_rate);
+        debug!("{connector}: display refresh rate: {refresh_rate} mHz, offered: {refresh_rates:?}");
+        let physical_size = appkit::display_size_mm(&cocoa_window.window);
+        debug!("{connector}: display physical size: {physical_size:?} mm");
+        let vrr_supported = appkit::screen_supports_vrr(&cocoa_window.window);
+        debug!("{connector}: display supports VRR: {vrr_supported}");
+
+        let output = Output::new(
+            connector.clone(),
+            PhysicalProperties {
+                size: physical_size.unwrap_or((0, 0)).into(),
+                subpixel: Subpixel::Unknown,
+                make: "Smithay".into(),
+                model: "Winit".into(),
+                serial_number: "Unknown".into(),
+            },
+        );
+
+        // Access inner window size from cocoa_window
+        let window_size = cocoa_window.width; // u32
+        let height = cocoa_window.height;     // u32
+
+        output.user_data().insert_if_missing(|| OutputName {
+            connector,
+            make: Some("Smithay".to_string()),
+            model: Some("Winit".to_string()),
+            serial: None,
+        });
+        // Match the scale that other apps use on this display, e.g. 2 on Retina screens.
+        let scale_factor = cocoa_window.window.scale_factor();
+        output
+            .user_data()
+            .insert_if_missing(|| PreferredScale(Cell::new(scale_factor)));
+
+        let physical_properties = output.physical_properties();
+        let mut ipc_output = niri_ipc::Output {
+            name: output.name(),
+            make: physical_properties.make,
+            model: physical_properties.model,
+            serial: None,
+            physical_size: physical_size.map(|(w, h)| (w as u32, h as u32)),
+            // Filled in by update_modes() below, logical too since it needs the mode.
+            modes: Vec::new(),
+            current_mode: None,
+            is_custom_mode: true,
+            vrr_supported,
+            vrr_enabled: false,
+            logical: None,
+        };
+
+        let damage_tracker = OutputDamageTracker::from_output(&output);
+
+        let winit_output = Self {
+            output,
+            cocoa_window,
+            damage_tracker,
+            ipc_id: OutputId::next(),
+            refresh_rate,
+            refresh_rates,
+            maximized_before_fullscreen: false,
+            vrr_supported,
+            vrr_enabled: false,
+            fps_overlay: None,
+            covered: false,
+        };
+
+        let size = (window_size as i32, height as i32).into();
+        winit_output.update_modes(size, None, &mut ipc_output);
+        ipc_output.logical = Some(logical_output(&winit_output.output));
+
+        (winit_output, ipc_output)
+    }
+
+    fn window(&self) -> &Window {
+        &self.cocoa_window.window
+    }
+
+    /// Returns the display showing the window.
+    ///
+    /// macOS moves windows to other displays and renumbers displays as the screens change, so
+    /// this asks every time instead of keeping the display the window was opened on.
+    fn display(&self) -> Option<u32> {
+        self.window().current_monitor().map(|monitor| monitor.native_id())
+    }
+
+    /// Re-reads the refresh rate, e.g. after the window moved or the displays were reconfigured.
+    ///
+    /// Returns whether the modes need an update with [`WinitOutput::update_modes`].
+    fn update_refresh_rate(&mut self) -> bool {
+        let Some(refresh_rate) = appkit::screen_refresh_rate(self.window()) else {
+            return false;
+        };
+
+        let mut modes_changed = false;
+        if self.refresh_rate != refresh_rate {
+            debug!("{}: display refresh rate changed to {refresh_rate} mHz", self.output.name());
+            self.refresh_rate = refresh_rate;
+            modes_changed = true;
+        }
+
+        let refresh_rates =
+            offered_refresh_rates(appkit::display_refresh_rates(self.window()), refresh_rate);
+        if self.refresh_rates != refresh_rates {
+            debug!("{}: display refresh rates changed to {refresh_rates:?}", self.output.name());
+            self.refresh_rates = refresh_rates;
+            modes_changed = true;
+        }
+
+        let vrr_supported = appkit::screen_supports_vrr(self.window());
+        if self.vrr_supported != vrr_supported {
+            debug!("{}: display VRR support changed to {vrr_supported}", self.output.name());
+            self.vrr_supported = vrr_supported;
+            self.vrr_enabled &= vrr_supported;
+        }
+
+        modes_changed
+    }
+
+    /// Forgets the damage of earlier frames, so that the next frame redraws the whole window.
+    ///
+    /// Needed after a resize: the back buffers hold frames of the old size, and anything outside
+    /// of them would stay stale.
+    fn reset_damage(&mut self) {
+        self.damage_tracker = OutputDamageTracker::from_output(&self.output);
+    }
+
+    /// Sets the Output and IPC modes to the window size at every refresh rate of the display.
+    ///
+    /// The current mode runs at the display refresh rate, so that clients pace their frames to it.
+    fn update_modes(
+        &self,
+        size: Size<i32, Physical>,
+        scale: Option<Scale>,
+        ipc_output: &mut niri_ipc::Output,
+    ) {
+        let current = Mode {
+            size,
+            refresh: self.refresh_rate,
+        };
+
+        for mode in self.output.modes() {
+            if mode != current {
+                self.output.delete_mode(mode);
+            }
+        }
+        for &refresh in &self.refresh_rates {
+            self.output.add_mode(Mode { size, refresh });
+        }
+        self.output.change_current_state(Some(current), None, scale, None);
+        self.output.set_preferred(current);
+
+        ipc_output.modes = self
+            .refresh_rates
+            .iter()
+            .map(|&refresh| niri_ipc::Mode {
+                width: size.w as u16,
+                height: size.h as u16,
+                refresh_rate: refresh as u32,
+                is_preferred: refresh == self.refresh_rate,
+            })
+            .collect();
+        ipc_output.current_mode = self
+            .refresh_rates
+            .iter()
+            .position(|&refresh| refresh == self.refresh_rate);
+    }
+}
+
+/// Returns the refresh rates to offer out of the ones that the display has, fastest first.
+///
+/// `current` is always in the list. Rates within half a Hz of it are the same rate measured
+/// differently, so they are left out.
+fn offered_refresh_rates(mut rates: Vec<i32>, current: i32) -> Vec<i32> {
+    rates.retain(|rate| (rate - current).abs() >= 500);
+    rates.push(current);
+    rates.sort_unstable_by(|a, b| b.cmp(a));
+    rates
+}
+
+/// Default longest gap between a key Release and a Press of the same key that counts as a
+/// duplicate, see [`configured_key_debounce`].
+///
+/// Duplicates arrive back to back, while even the fastest double tap takes tens of milliseconds.
+const DUPLICATE_KEY_WINDOW: Duration = Duration::from_millis(5);
+
+/// Drops key events that AppKit delivers twice after the CFRunLoop slept.
+///
+/// A duplicate shows up as a Press right after a Release of the same key. Its Release is dropped
+/// too, so that clients see matching pairs.
+///
+/// A zero window turns the filter off.
+#[derive(Debug, Default)]
+struct KeyBurstFilter {
+    /// Longest gap between a Release and a Press that counts as a duplicate.
+    window: Duration,
+    last_release: HashMap<u32, Instant>,
+    suppressed: HashSet<u32>,
+}
+
+impl KeyBurstFilter {
+    fn new(window: Duration) -> Self {
+        Self {
+            window,
+            ..Self::default()
+        }
+    }
+
+    /// Returns whether the event duplicates an earlier one and should be dropped.
+    fn is_duplicate(&mut self, key: u32, pressed: bool, now: Instant) -> bool {
+        if self.window.is_zero() {
+            return false;
+        }
+
+        if pressed {
+            let duplicate = self
+                .last_release
+                .get(&key)
+                .is_some_and(|release| now.duration_since(*release) < self.window);
+            if duplicate {
+                self.suppressed.insert(key);
+            }
+            duplicate
+        } else {
+            if self.suppressed.remove(&key) {
+                return true;
+            }
+            self.last_release.insert(key, now);
+            false
+        }
+    }
+}
+
+/// Attempts at rebuilding a lost GL context before giving up, see [`Winit::recover_context`].
+const MAX_CONTEXT_REBUILDS: u32 = 3;
+
+/// How long to wait for momentum after the fingers lift before ending a trackpad scroll.
+///
+/// macOS starts the momentum right with the next event, so this only needs to cover a frame or
+/// two.
+const MOMENTUM_START_DELAY: Duration = Duration::from_millis(50);
+
+/// Trackpad scroll gesture, which ends with a single axis stop.
+///
+/// Clients start their kinetic scrolling on the axis stop. When macOS follows the fingers lifting
+/// with momentum, the scroll only ends with the momentum, or when new fingers cut it short.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum ScrollGesture {
+    /// No scroll that still needs its axis stop.
+    #[default]
+    Idle,
+    /// Fingers are on the trackpad.
+    Touching,
+    /// The fingers lifted and momentum may follow.
+    Lifted,
+    /// macOS is scrolling with momentum.
+    Momentum,
+}
+
+/// What to do about the axis stop after a scroll event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScrollStop {
+    /// Nothing changes.
+    Keep,
+    /// The scroll ended, send the axis stop right away.
+    Send,
+    /// Send the axis stop unless momentum starts within [`MOMENTUM_START_DELAY`].
+    SendUnlessMomentum,
+    /// Momentum started, so the axis stop waits for it to end.
+    WaitForMomentum,
+}
+
+impl ScrollGesture {
+    /// Updates the gesture before a scroll event goes out.
+    ///
+    /// Fingers touching down end the previous scroll, whose axis stop has to come first.
+    fn start(&mut self, scroll_phase: ScrollPhase, phase: TouchPhase) -> ScrollStop {
+        if (scroll_phase, phase) != (ScrollPhase::Touching, TouchPhase::Started) {
+            return ScrollStop::Keep;
+        }
+
+        let previous = std::mem::replace(self, Self::Touching);
+        if previous == Self::Idle {
+            ScrollStop::Keep
+        } else {
+            ScrollStop::Send
+        }
+    }
+
+    /// Updates the gesture after a scroll event went out.
+    fn advance(&mut self, scroll_phase: ScrollPhase, phase: TouchPhase) -> ScrollStop {
+        match (scroll_phase, phase) {
+            (ScrollPhase::Touching, TouchPhase::Ended | TouchPhase::Cancelled) => {
+                *self = Self::Lifted;
+                ScrollStop::SendUnlessMomentum
+            }
+            (ScrollPhase::Touching, _) => {
+                *self = Self::Touching;
+                ScrollStop::Keep
+            }
+            // Momentum that comes after the stop went out, or after new fingers cut it short,
+            // has nothing left to end.
+            (ScrollPhase::Momentum, _) if matches!(self, Self::Idle | Self::Touching) => {
+                ScrollStop::Keep
+            }
+            (ScrollPhase::Momentum, TouchPhase::Ended | TouchPhase::Cancelled) => {
+                *self = Self::Idle;
+                ScrollStop::Send
+            }
+            (ScrollPhase::Momentum, _) if *self == Self::Lifted => {
+                *self = Self::Momentum;
+                ScrollStop::WaitForMomentum
+            }
+            (ScrollPhase::Momentum, _) | (ScrollPhase::None, _) => ScrollStop::Keep,
+        }
+    }
+
+    /// Ends the gesture when no momentum followed the fingers lifting, returning whether its
+    /// axis stop should go out.
+    fn momentum_timed_out(&mut self) -> bool {
+        if *self == Self::Lifted {
+            *self = Self::Idle;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+fn apply_scroll_stop(state: &mut State, stop: ScrollStop) {
+    match stop {
+        ScrollStop::Keep => (),
+        ScrollStop::Send => {
+            cancel_scroll_stop_timer(state);
+            send_scroll_stop(state);
+        }
+        ScrollStop::SendUnlessMomentum => {
+            cancel_scroll_stop_timer(state);
+            let timer = Timer::from_duration(MOMENTUM_START_DELAY);
+            let token = state
+                .niri
+                .event_loop
+                .insert_timer(timer, |_, _, state| {
+                    let winit = state.backend.winit();
+                    winit.scroll_stop_timer = None;
+                    if winit.scroll_gesture.momentum_timed_out() {
+                        send_scroll_stop(state);
+                    }
+                    TimeoutAction::Drop
+                })
+                .unwrap();
+            state.backend.winit().scroll_stop_timer = Some(token);
+        }
+        ScrollStop::WaitForMomentum => {
+            cancel_scroll_stop_timer(state);
+        }
+    }
+}
+
+/// Sends a zero-amount trackpad axis event, which niri turns into an axis stop on both axes.
+fn send_scroll_stop(state: &mut State) {
+    state.backend.winit().scroll_v120 = V120Accumulator::default();
+
+    let event = InputEvent::<WinitInput>::PointerAxis {
+        event: WinitMouseWheelEvent {
+            time: get_monotonic_time().as_micros() as u64,
+            delta: MouseScrollDelta::PixelDelta(PhysicalPosition::new(0., 0.)),
+            phase: TouchPhase::Ended,
+            scroll_phase: ScrollPhase::Touching,
+            pixel_v120: (0., 0.),
+            inverted: false,
+            axis_stop: true,
+        },
+    };
+    state.process_input_event(event);
+}
+
+/// Tablet tool in proximity, along with where it last was.
+#[derive(Debug, Clone)]
+struct TabletToolProximity {
+    point: appkit::TabletPoint,
+    output: Output,
+    position: RelativePosition,
+    global_position: PhysicalPosition<f64>,
+}
+
+/// Converts a position within the window of `output`, from 0 to 1, into a position within the
+/// output.
+///
+/// niri maps touches and tablet tools onto the output of the window, see
+/// WinitVirtualDevice::output(), applying its transform on the way. Winit outputs are also flipped
+/// for rendering, so cancel the whole transform out and apply only the rotation that the window
+/// shows, to keep the position under the finger or the pen.
+fn window_position_in_output(output: &Output, position: (f64, f64)) -> RelativePosition {
+    let rotation = window_rotation(output);
+    let transform = output.current_transform();
+    let unit = Size::from((1., 1.));
+    let position =
+        rotation.invert().transform_point_in(Point::<f64, Logical>::from(position), &unit);
+    let position = transform.invert().transform_point_in(position, &unit);
+    RelativePosition::new(position.x, position.y)
+}
+
+/// Sends the motion of a tablet tool, bringing it into proximity first if it just came close.
+fn handle_tablet_motion(
+    state: &mut State,
+    window_id: WindowId,
+    global_position: PhysicalPosition<f64>,
+    point: appkit::TabletPoint,
+    event_time: Option<Duration>,
+) {
+    let Some(winit_output) = state.backend.winit().output_for_window(window_id) else {
+        return;
+    };
+    let size = winit_output.window().inner_size();
+    let output = winit_output.output.clone();
+    let position = window_position_in_output(
+        &output,
+        (
+            global_position.x / f64::from(size.width),
+            global_position.y / f64::from(size.height),
+        ),
+    );
+
+    // Turning the pen around to the eraser leaves and enters proximity, but the leaving only
+    // goes out at the end of the batch.
+    let tool_changed = state
+        .backend
+        .winit()
+        .tablet_tool
+        .as_ref()
+        .is_some_and(|tablet| tablet.point.tool != point.tool);
+    if tool_changed {
+        tablet_proximity_out(state);
+    }
+
+    let time = input_event_time(event_time);
+    let winit = state.backend.winit();
+    let entering = winit.tablet_tool.is_none();
+    winit.tablet_tool = Some(TabletToolProximity {
+        point,
+        output: output.clone(),
+        position,
+        global_position,
+    });
+    winit.event_output = Some(output);
+
+    if entering {
+        // AppKit doesn't announce tablets, so add the tablet when its tool first shows up.
+        let desc = TabletDescriptor::from(&WinitVirtualDevice);
+        let tablet_seat = state.niri.seat.tablet_seat();
+        tablet_seat.add_tablet::<State>(&state.niri.display_handle, &desc);
+
+        let event = InputEvent::<WinitInput>::TabletToolProximity {
+            event: WinitTabletToolProximityEvent {
+                time,
+                position,
+                global_position,
+                point,
+                state: ProximityState::In,
+            },
+        };
+        state.process_input_event(event);
+    }
+
+    let event = InputEvent::<WinitInput>::TabletToolAxis {
+        event: WinitTabletToolAxisEvent {
+            time,
+            position,
+            global_position,
+            point,
+        },
+    };
+    state.process_input_event(event);
+    state.backend.winit().event_output = None;
+}
+
+/// Sends a click of a tablet tool, as a tip event for the tip and a button event otherwise.
+fn handle_tablet_button(
+    state: &mut State,
+    button: WinitMouseButton,
+    element_state: ElementState,
+    point: appkit::TabletPoint,
+    event_time: Option<Duration>,
+) {
+    // The motion that brings the tool into proximity comes first.
+    let Some(tablet) = state.backend.winit().tablet_tool.clone() else {
+        return;
+    };
+
+    let time = input_event_time(event_time);
+    let event = if button == WinitMouseButton::Left {
+        InputEvent::<WinitInput>::TabletToolTip {
+            event: WinitTabletToolTipEvent {
+                time,
+                position: tablet.position,
+                global_position: tablet.global_position,
+                point,
+                state: element_state,
+            },
+        }
+    } else {
+        InputEvent::<WinitInput>::TabletToolButton {
+            event: WinitTabletToolButtonEvent {
+                time,
+                point,
+                button,
+                state: element_state,
+            },
+        }
+    };
+
+    state.backend.winit().event_output = Some(tablet.output);
+    state.process_input_event(event);
+    state.backend.winit().event_output = None;
+}
+
+/// Takes the tablet tool out of proximity, where it last was.
+fn tablet_proximity_out(state: &mut State) {
+    let winit = state.backend.winit();
+    let Some(tablet) = winit.tablet_tool.take() else {
+        return;
+    };
+    winit.event_output = Some(tablet.output);
+
+    let event = InputEvent::<WinitInput>::TabletToolProximity {
+        event: WinitTabletToolProximityEvent {
+            time: get_monotonic_time().as_micros() as u64,
+            position: tablet.position,
+            global_position: tablet.global_position,
+            point: tablet.point,
+            state: ProximityState::Out,
+        },
+    };
+    state.process_input_event(event);
+    state.backend.winit().event_output = None;
+}
+
+/// Returns the time of an input event in microseconds: when AppKit generated it, or now if that
+/// isn't known.
+///
+/// Events can wait in the channel before niri applies them, so the time of the dispatch would
+/// skew what clients go by for double clicks and gesture velocity.
+fn input_event_time(event_time: Option<Duration>) -> u64 {
+    event_time.unwrap_or_else(get_monotonic_time).as_micros() as u64
+}
+
+/// Distance in logical points that the fingers travel before a three-finger drag moves the window,
+/// same as for dragging with Mod and the left button.
+const WINDOW_DRAG_THRESHOLD: f64 = 8.;
+
+/// Three-finger drag on the trackpad moving the window that was under the cursor.
+#[derive(Debug)]
+struct WindowDrag {
+    window: smithay::desktop::Window,
+    start_location: Point<f64, Logical>,
+    start_output: Output,
+    start_pos_within_output: Point<f64, Logical>,
+    /// Finger motion since the fingers touched down.
+    offset: Point<f64, Logical>,
+    /// Whether the window moves yet, rather than waiting for the fingers to pass the threshold.
+    moving: bool,
+}
+
+/// Moves the window under the cursor with a three-finger drag, when
+/// `winit-three-finger-drag "move-window"` asks for it.
+///
+/// Returns `false` for scrolling that the drag doesn't take, which goes on to the swipe and the
+/// wheel paths.
+fn handle_window_drag(
+    state: &mut State,
+    delta: MouseScrollDelta,
+    phase: TouchPhase,
+    touch_count: u32,
+    inverted: bool,
+) -> bool {
+    let MouseScrollDelta::PixelDelta(delta) = delta else {
+        return false;
+    };
+
+    match phase {
+        TouchPhase::Started => {
+            let action = state.niri.config.borrow().debug.winit_three_finger_drag;
+            if action != Some(WinitThreeFingerDrag::MoveWindow) || touch_count != 3 {
+                return false;
+            }
+
+            // Over an empty spot, the fingers swipe instead.
+            let location = state.niri.seat.get_pointer().unwrap().current_location();
+            let Some(mapped) = state.niri.window_under(location) else {
+                return false;
+            };
+            let window = mapped.window.clone();
+            let Some((output, pos_within_output)) = state.niri.output_under(location) else {
+                return false;
+            };
+            let output = output.clone();
+
+            state.backend.winit().window_drag = Some(WindowDrag {
+                window,
+                start_location: location,
+                start_output: output,
+                start_pos_within_output: pos_within_output,
+                offset: Point::from((0., 0.)),
+                moving: false,
+            });
+            true
+        }
+        TouchPhase::Moved => {
+            let Some(drag) = &mut state.backend.winit().window_drag else {
+                return false;
+            };
+
+            let motion = finger_motion(delta, inverted);
+            drag.offset += motion;
+            let mut delta = motion;
+
+            if !drag.moving {
+                if !past_window_drag_threshold(drag.offset) {
+                    return true;
+                }
+
+                let drag = state.backend.winit().window_drag.take().unwrap();
+                let layout = &mut state.niri.layout;
+                if !drag.window.alive()
+                    || !layout.interactive_move_begin(
+                        drag.window.clone(),
+                        &drag.start_output,
+                        drag.start_pos_within_output,
+                    )
+                {
+                    return true;
+                }
+                layout.activate_window(&drag.window);
+                state
+                    .niri
+                    .cursor_manager
+                    .set_cursor_image(CursorImageStatus::Named(CursorIcon::Move));
+
+                // Apply the whole motion that accumulated during recognizing.
+                delta = drag.offset;
+                state.backend.winit().window_drag = Some(WindowDrag {
+                    moving: true,
+                    ..drag
+                });
+            }
+
+            let drag = state.backend.winit().window_drag.as_ref().unwrap();
+            let window = drag.window.clone();
+            // The cursor stays where it is, the window moves as if it followed the fingers.
+            let location = drag.start_location + drag.offset;
+            let Some((output, pos_within_output)) = state.niri.output_under(location) else {
+                return true;
+            };
+            let output = output.clone();
+
+            let layout = &mut state.niri.layout;
+            let ongoing = layout.interactive_move_update(&window, delta, output, pos_within_output);
+            if !ongoing {
+                state.backend.winit().window_drag = None;
+                state
+                    .niri
+                    .cursor_manager
+                    .set_cursor_image(CursorImageStatus::default_named());
+            }
+
+            // FIXME: only redraw the previous and the new output.
+            state.niri.queue_redraw_all();
+            true
+        }
+        TouchPhase::Ended | TouchPhase::Cancelled => {
+            let Some(drag) = state.backend.winit().window_drag.take() else {
+                return false;
+            };
+
+            if drag.moving {
+                state.niri.layout.interactive_move_end(&drag.window);
+                state
+                    .niri
+                    .cursor_manager
+                    .set_cursor_image(CursorImageStatus::default_named());
+                state.niri.queue_redraw_all();
+            }
+            true
+        }
+    }
+}
+
+/// Mod and left button drag over empty space moving an output window.
+#[derive(Debug)]
+struct BackgroundDrag {
+    window_id: WindowId,
+    /// Global cursor location when the button went down, see [`appkit::cursor_location`].
+    start_cursor: (f64, f64),
+    /// Top left corner of the window when the button went down, in points.
+    start_origin: LogicalPosition<f64>,
+}
+
+/// Starts or ends moving the output window with Mod and a left button drag over empty space,
+/// when `winit-movable-background` asks for it.
+///
+/// Returns `false` for clicks that the drag doesn't take, which go on to the clients.
+fn handle_background_drag(state: &mut State, window_id: WindowId, button: ElementState) -> bool {
+    if button == ElementState::Released {
+        return state.backend.winit().background_drag.take().is_some();
+    }
+
+    if !state.backend.winit().movable_background || state.niri.layout.is_overview_open() {
+        return false;
+    }
+
+    let pointer = state.niri.seat.get_pointer().unwrap();
+    if pointer.is_grabbed() {
+        return false;
+    }
+
+    let mod_key = state.backend.mod_key(&state.niri.config.borrow());
+    let mods = state.niri.seat.get_keyboard().unwrap().modifier_state();
+    if !modifiers_from_state(mods).contains(mod_key.to_modifiers()) {
+        return false;
+    }
+
+    // Clients get their drags, including resizes from their own edges and niri's borders, which
+    // count as the window. AppKit resizes the output window from its frame before we see a click.
+    let contents = state.niri.contents_under(pointer.current_location());
+    let over_layer = contents.layer.is_some_and(|layer| layer.layer() != Layer::Background);
+    if contents.output.is_none() || contents.window.is_some() || over_layer || contents.hot_corner
+    {
+        return false;
+    }
+
+    let winit = state.backend.winit();
+    let Some(winit_output) = winit.output_for_window(window_id) else {
+        return false;
+    };
+    let window = winit_output.window();
+    let Ok(origin) = window.outer_position() else {
+        return false;
+    };
+    let start_origin = origin.to_logical(window.scale_factor());
+    let Some(start_cursor) = appkit::cursor_location() else {
+        return false;
+    };
+
+    winit.background_drag = Some(BackgroundDrag {
+        window_id,
+        start_cursor,
+        start_origin,
+    });
+    true
+}
+
+/// Moves the output window along with the cursor during a [`handle_background_drag`].
+///
+/// Returns `true` while the drag takes the pointer motion.
+fn move_background_drag(state: &mut State) -> bool {
+    let winit = state.backend.winit();
+    let Some(drag) = &winit.background_drag else {
+        return false;
+    };
+    let Some(cursor) = appkit::cursor_location() else {
+        return true;
+    };
+
+    let (window_id, origin) = (drag.window_id, background_drag_origin(drag, cursor));
+    if let Some(winit_output) = winit.output_for_window(window_id) {
+        winit_output.window().set_outer_position(origin);
+    }
+    true
+}
+
+/// Returns where the window goes when the cursor is at `cursor` during a background drag.
+fn background_drag_origin(drag: &BackgroundDrag, cursor: (f64, f64)) -> LogicalPosition<f64> {
+    LogicalPosition::new(
+        drag.start_origin.x + cursor.0 - drag.start_cursor.0,
+        drag.start_origin.y + cursor.1 - drag.start_cursor.1,
+    )
+}
+
+/// Returns whether the fingers of a three-finger drag moved far enough to move the window.
+fn past_window_drag_threshold(offset: Point<f64, Logical>) -> bool {
+    offset.x * offset.x + offset.y * offset.y >= WINDOW_DRAG_THRESHOLD * WINDOW_DRAG_THRESHOLD
+}
+
+/// Returns the finger motion of a trackpad scroll delta.
+///
+/// Swipes and drags follow the fingers like libinput does, while scroll deltas follow the
+/// content, so this undoes the natural scrolling inversion if there is none.
+fn finger_motion(delta: PhysicalPosition<f64>, inverted: bool) -> Point<f64, Logical> {
+    let sign = if inverted { 1. } else { -1. };
+    Point::from((delta.x * sign, delta.y * sign))
+}
+
+/// Flips the scroll direction when the configured natural scrolling differs from the one that
+/// macOS already applied to the delta, going by `inverted`.
+///
+/// Returns the delta along with whether it ends up inverted.
+fn natural_scroll_delta(
+    delta: MouseScrollDelta,
+    inverted: bool,
+    natural_scroll: Option<bool>,
+) -> (MouseScrollDelta, bool) {
+    match natural_scroll {
+        Some(natural_scroll) if natural_scroll != inverted => {
+            let delta = match delta {
+                MouseScrollDelta::LineDelta(x, y) => MouseScrollDelta::LineDelta(-x, -y),
+                MouseScrollDelta::PixelDelta(pos) => {
+                    MouseScrollDelta::PixelDelta(PhysicalPosition::new(-pos.x, -pos.y))
+                }
+            };
+            (delta, natural_scroll)
+        }
+        _ => (delta, inverted),
+    }
+}
+
+/// Turns vertical mouse wheel scrolling sideways, like macOS does for Shift+wheel in its apps.
+///
+/// Wheels that scroll sideways by themselves, and trackpads, are left alone.
+fn shift_scroll_delta(delta: MouseScrollDelta) -> MouseScrollDelta {
+    match delta {
+        MouseScrollDelta::LineDelta(x, y) if x == 0. => MouseScrollDelta::LineDelta(y, 0.),
+        delta => delta,
+    }
+}
+
+/// Returns the horizontal and vertical `scroll-factor` for pixel scrolling.
+///
+/// Fingers on the trackpad and the momentum after they lift scroll with the touchpad factor,
+/// precise mouse wheels with the mouse one. Wheel notches are left to niri, which scales them
+/// after the wheel bindings had a look.
+fn scroll_factors(config: &Config, delta: MouseScrollDelta, phase: ScrollPhase) -> (f64, f64) {
+    let factor = match (delta, phase) {
+        (MouseScrollDelta::LineDelta(_, _), _) => None,
+        (MouseScrollDelta::PixelDelta(_), ScrollPhase::Touching | ScrollPhase::Momentum) => {
+            config.input.touchpad.scroll_factor
+        }
+        (MouseScrollDelta::PixelDelta(_), ScrollPhase::None) => config.input.mouse.scroll_factor,
+    };
+    factor.map_or((1., 1.), |factor| factor.h_v_factors())
+}
+
+fn scale_scroll_delta(delta: MouseScrollDelta, (h, v): (f64, f64)) -> MouseScrollDelta {
+    match delta {
+        MouseScrollDelta::PixelDelta(pos) => {
+            MouseScrollDelta::PixelDelta(PhysicalPosition::new(pos.x * h, pos.y * v))
+        }
+        delta => delta,
+    }
+}
+
+/// Removes the pending scroll stop timer, if any.
+fn cancel_scroll_stop_timer(state: &mut State) {
+    if let Some(token) = state.backend.winit().scroll_stop_timer.take() {
+        state.niri.event_loop.remove(token);
+    }
+}
+
+/// Pixels of scrolling that make up one wheel notch, about what macOS scrolls per notch.
+const PIXELS_PER_NOTCH: f64 = 53.;
+
+/// Turns pixel scroll deltas into discrete steps in v120 units.
+///
+/// Steps go out in whole v120 units, the rest carries over to the next delta.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct V120Accumulator {
+    remainder: (f64, f64),
+}
+
+impl V120Accumulator {
+    fn add(&mut self, dx: f64, dy: f64) -> (f64, f64) {
+        (
+            Self::add_axis(&mut self.remainder.0, dx),
+            Self::add_axis(&mut self.remainder.1, dy),
+        )
+    }
+
+    fn add_axis(remainder: &mut f64, pixels: f64) -> f64 {
+        if pixels == 0. {
+            return 0.;
+        }
+
+        // Start over when the direction flips, so that turning around takes effect right away.
+        if *remainder != 0. && remainder.signum() != pixels.signum() {
+            *remainder = 0.;
+        }
+
+        *remainder += pixels / PIXELS_PER_NOTCH * 120.;
+        let steps = remainder.trunc();
+        *remainder -= steps;
+        steps
+    }
+}
+
+/// Display refresh timing reported by the display link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayTiming {
+    /// Monotonic time of a display refresh.
+    pub vblank: Duration,
+    /// Vertical retrace counter of that refresh.
+    pub seq: u64,
+    /// Time between two refreshes.
+    pub refresh: Duration,
+}
+
+impl DisplayTiming {
+    /// Returns the time and counter of the first refresh at or after `time`.
+    ///
+    /// The display link only ticks while frames are coming, so the last refresh it saw can be a
+    /// while ago. The refresh interval stays the same in between, so the refreshes since then
+    /// follow from it.
+    fn next_vblank(&self, time: Duration) -> (Duration, u64) {
+        if time <= self.vblank || self.refresh.is_zero() {
+            return (self.vblank, self.seq);
+        }
+
+        let refresh = self.refresh.as_nanos();
+        let frames = (time - self.vblank).as_nanos().div_ceil(refresh);
+        let vblank = self.vblank + Duration::from_nanos((frames * refresh) as u64);
+        (vblank, self.seq + frames as u64)
+    }
+}
+
+const DEFAULT_WINDOW_TITLE: &str = "niri (macOS)";
+
+/// What the macOS cursor over the compositor windows looks like.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OsCursor {
+    /// niri draws the cursor into the windows.
+    Hidden,
+    /// The macOS cursor shows this icon on top of the drawn one, see [`force_show_os_cursor`].
+    Icon(CursorIcon),
+}
+
+impl OsCursor {
+    fn for_niri(niri: &Niri) -> Self {
+        if !force_show_os_cursor() {
+            return Self::Hidden;
+        }
+
+        match niri.cursor_manager.cursor_image() {
+            CursorImageStatus::Named(icon) => Self::Icon(*icon),
+            CursorImageStatus::Surface(_) | CursorImageStatus::Hidden => {
+                Self::Icon(CursorIcon::Default)
+            }
+        }
+    }
+
+    fn apply(self, window: &Window) {
+        match self {
+            Self::Hidden => window.set_cursor_visible(false),
+            Self::Icon(icon) => {
+                window.set_cursor(icon);
+                window.set_cursor_visible(true);
+            }
+        }
+    }
+}
+
+/// Returns whether `MACNIRI_SHOW_CURSOR` asks to show the macOS cursor too.
+///
+/// Useful for debugging, e.g. to check that the drawn cursor lines up with the real one.
+fn force_show_os_cursor() -> bool {
+    static FORCE: OnceLock<bool> = OnceLock::new();
+    *FORCE.get_or_init(|| std::env::var_os("MACNIRI_SHOW_CURSOR").is_some_and(|v| v != "0"))
+}
+
+/// Returns the window title set through the environment or the config, if any.
+fn configured_window_title(config: &Config) -> Option<String> {
+    std::env::var("MACNIRI_WINDOW_TITLE")
+        .ok()
+        .filter(|title| !title.is_empty())
+        .or_else(|| config.debug.winit_window_title.clone())
+}
+
+/// Returns the initial window size in logical pixels set through the environment or the config.
+///
+/// Without one, the window starts out maximized.
+fn configured_window_size(config: &Config) -> Option<(u32, u32)> {
+    if let Ok(geometry) = std::env::var("MACNIRI_GEOMETRY") {
+        match geometry.parse::<WinitWindowSize>() {
+            Ok(size) => return Some((size.width, size.height)),
+            Err(err) => warn!("invalid MACNIRI_GEOMETRY: {err}"),
+        }
+    }
+
+    let size = config.debug.winit_window_size?;
+    Some((size.width, size.height))
+}
+
+/// Returns the shim device that pointer events come from, going by the fingers on the trackpad.
+///
+/// Its send events mode, from the niri `off` and `disabled-on-external-mouse` settings, tells
+/// whether the events go on.
+fn pointer_device(touch_count: u32) -> ShimDevice {
+    if touch_count > 0 {
+        ShimDevice::TRACKPAD
+    } else {
+        ShimDevice::POINTER
+    }
+}
+
+/// Clicks for a tap on the trackpad when the niri `tap` setting is on.
+fn handle_trackpad_tap(state: &mut State, fingers: u32) {
+    let device = ShimDevice::TRACKPAD;
+    if !device.config_tap_enabled() || !device.sends_events() {
+        return;
+    }
+    if state.backend.winit().held_back_while_typing(&device) {
+        return;
+    }
+    let Some(button) = tap_button(fingers, device.config_tap_button_map()) else {
+        return;
+    };
+
+    let time = get_monotonic_time().as_micros() as u64;
+    for element_state in [ElementState::Pressed, ElementState::Released] {
+        let event = InputEvent::<WinitInput>::PointerButton {
+            event: WinitMouseInputEvent {
+                time,
+                button,
+                state: element_state,
+                is_x11: false,
+            },
+        };
+        state.process_input_event(event);
+    }
+}
+
+/// Returns the button that a tap with `fingers` clicks, or `None` for more than three.
+fn tap_button(fingers: u32, map: TapButtonMap) -> Option<WinitMouseButton> {
+    let button = match (fingers, map) {
+        (1, _) => WinitMouseButton::Left,
+        (2, TapButtonMap::LeftRightMiddle) | (3, TapButtonMap::LeftMiddleRight) => {
+            WinitMouseButton::Right
+        }
+        (2, TapButtonMap::LeftMiddleRight) | (3, TapButtonMap::LeftRightMiddle) => {
+            WinitMouseButton::Middle
+        }
+        _ => return None,
+    };
+    Some(button)
+}
+
+/// Returns the factor that relative pointer motion is scaled by, from the `accel-speed` of the
+/// touchpad or the mouse.
+///
+/// macOS accelerates the motion before we see it, so this scales on top of that, from half the
+/// speed at -1 to twice the speed at 1. It only applies while a pointer constraint holds the
+/// cursor: otherwise the pointer follows the macOS cursor, which moves at the macOS tracking
+/// speed, and scaled relative motion would drift away from it.
+fn pointer_speed_factor(config: &Config, touchpad: bool) -> f64 {
+    let speed = if touchpad {
+        config.input.touchpad.accel_speed.0
+    } else {
+        config.input.mouse.accel_speed.0
+    };
+    2f64.powf(speed.clamp(-1., 1.))
+}
+
+/// Returns the window of the key burst filter, from `MACNIRI_KEY_DEBOUNCE_MS` or the config.
+///
+/// Zero turns the filter off, for keyboards where AppKit never duplicates key events.
+fn configured_key_debounce(config: &Config) -> Duration {
+    if let Ok(ms) = std::env::var("MACNIRI_KEY_DEBOUNCE_MS") {
+        match ms.trim().parse() {
+            Ok(ms) => return Duration::from_millis(ms),
+            Err(_) => warn!("invalid MACNIRI_KEY_DEBOUNCE_MS {ms:?}, expected milliseconds"),
+        }
+    }
+
+    config
+        .debug
+        .winit_key_debounce_ms
+        .map_or(DUPLICATE_KEY_WINDOW, |ms| Duration::from_millis(ms.into()))
+}
+
+/// Default time after a key press that disable-while-typing holds back the trackpad, see
+/// [`configured_dwt_timeout`].
+const DWT_TIMEOUT: Duration = Duration::from_millis(300);
+
+/// Returns how long disable-while-typing holds back the trackpad after a key press.
+fn configured_dwt_timeout(config: &Config) -> Duration {
+    config
+        .debug
+        .winit_dwt_timeout_ms
+        .map_or(DWT_TIMEOUT, |ms| Duration::from_millis(ms.into()))
+}
+
+/// Returns whether the last key press came less than `timeout` before `now`.
+fn typed_within(last_key_press: Option<Instant>, now: Instant, timeout: Duration) -> bool {
+    last_key_press.is_some_and(|press| now.saturating_duration_since(press) < timeout)
+}
+
+/// Shortcuts that macOS handles unless configured otherwise: Quit and Hide from the menu bar.
+const DEFAULT_SYSTEM_SHORTCUTS: [&str; 2] = ["Cmd+Q", "Cmd+H"];
+
+/// Parses the `winit-system-shortcuts` from the config, skipping invalid ones.
+fn system_shortcuts(shortcuts: Option<&[String]>) -> Vec<appkit::SystemShortcut> {
+    let parse = |shortcut: &str| {
+        let parsed = appkit::SystemShortcut::parse(shortcut);
+        if parsed.is_none() {
+            warn!("invalid system shortcut {shortcut:?}, expected something like Cmd+Q");
+        }
+        parsed
+    };
+
+    match shortcuts {
+        Some(shortcuts) => shortcuts.iter().filter_map(|s| parse(s)).collect(),
+        None => DEFAULT_SYSTEM_SHORTCUTS.into_iter().filter_map(parse).collect(),
+    }
+}
+
+/// Returns the GPU to render on, from `MACNIRI_GPU` or the config.
+///
+/// Defaults to the integrated GPU, which lasts longer on battery.
+fn configured_gpu(config: &Config) -> WinitGpu {
+    if let Ok(gpu) = std::env::var("MACNIRI_GPU") {
+        match parse_gpu(&gpu) {
+            Some(gpu) => return gpu,
+            None => warn!("invalid MACNIRI_GPU {gpu:?}, expected integrated or discrete"),
+        }
+    }
+
+    config.debug.winit_gpu.unwrap_or(WinitGpu::Integrated)
+}
+
+fn parse_gpu(gpu: &str) -> Option<WinitGpu> {
+    match gpu.trim().to_ascii_lowercase().as_str() {
+        "integrated" => Some(WinitGpu::Integrated),
+        "discrete" => Some(WinitGpu::Discrete),
+        _ => None,
+    }
+}
+
+/// Returns the `Mod` key for the winit backend.
+///
+/// On macOS, the winit windows are the whole session rather than a window nested in another one,
+/// so `mod-key` applies as is, and `mod-key-nested` only overrides it. Without either, `Mod` is
+/// Command, which the synthetic modifier events send as Super.
+pub fn mod_key(input: &Input) -> ModKey {
+    input.mod_key_nested.or(input.mod_key).unwrap_or(ModKey::Super)
+}
+
+/// Returns the transform for a winit output that has `transform` configured.
+///
+/// GL framebuffers are bottom-up, so the window contents get flipped vertically on top of the
+/// configured rotation.
+pub fn winit_output_transform(transform: Transform) -> Transform {
+    transform + Transform::Flipped180
+}
+
+/// Returns the rotation that the window of `output` shows it with, without the flip from
+/// [`winit_output_transform`].
+fn window_rotation(output: &Output) -> Transform {
+    // Flipping twice cancels out.
+    output.current_transform() + Transform::Flipped180
+}
+
+/// VT that [`Winit::change_vt`] treats as the compositor's own.
+const COMPOSITOR_VT: i32 = 1;
+
+bitflags::bitflags! {
+    /// Modifier keys held down, tracked separately for the left and right side.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct SidedModifiers: u8 {
+        const SHIFT_LEFT    = 1 << 0;
+        const SHIFT_RIGHT   = 1 << 1;
+        const CONTROL_LEFT  = 1 << 2;
+        const CONTROL_RIGHT = 1 << 3;
+        const ALT_LEFT      = 1 << 4;
+        const ALT_RIGHT     = 1 << 5;
+        const SUPER_LEFT    = 1 << 6;
+        const SUPER_RIGHT   = 1 << 7;
+    }
+}
+
+// Device-dependent modifier masks from IOKit's IOLLEvent.h, reported in the low bits of
+// NSEvent.modifierFlags.
+const NX_DEVICELCTLKEYMASK: usize = 0x0000_0001;
+const NX_DEVICELSHIFTKEYMASK: usize = 0x0000_0002;
+const NX_DEVICERSHIFTKEYMASK: usize = 0x0000_0004;
+const NX_DEVICELCMDKEYMASK: usize = 0x0000_0008;
+const NX_DEVICERCMDKEYMASK: usize = 0x0000_0010;
+const NX_DEVICELALTKEYMASK: usize = 0x0000_0020;
+const NX_DEVICERALTKEYMASK: usize = 0x0000_0040;
+const NX_DEVICERCTLKEYMASK: usize = 0x0000_2000;
+
+// Device-independent modifier masks of NSEvent.modifierFlags.
+const NS_EVENT_MODIFIER_FLAG_SHIFT: usize = 1 << 17;
+const NS_EVENT_MODIFIER_FLAG_CONTROL: usize = 1 << 18;
+const NS_EVENT_MODIFIER_FLAG_OPTION: usize = 1 << 19;
+const NS_EVENT_MODIFIER_FLAG_COMMAND: usize = 1 << 20;
+
+impl SidedModifiers {
+    /// Raw evdev codes of every tracked modifier key.
+    const EVDEV_CODES: [(Self, u32); 8] = [
+        (Self::SHIFT_LEFT, 42),
+        (Self::SHIFT_RIGHT, 54),
+        (Self::CONTROL_LEFT, 29),
+        (Self::CONTROL_RIGHT, 97),
+        (Self::ALT_LEFT, 56),
+        (Self::ALT_RIGHT, 100),
+        (Self::SUPER_LEFT, 125),
+        (Self::SUPER_RIGHT, 126),
+    ];
+
+    /// Combines winit's generic modifier state with the macOS device-dependent flags.
+    ///
+    /// When the device-dependent flags are unavailable or don't indicate a side for an active
+    /// modifier, the left key is assumed.
+    fn from_winit(state: winit::keyboard::ModifiersState, device_flags: Option<usize>) -> Self {
+        use winit::keyboard::ModifiersState;
+
+        let sides = [
+            (
+                ModifiersState::SHIFT,
+                Self::SHIFT_LEFT,
+                NX_DEVICELSHIFTKEYMASK,
+                Self::SHIFT_RIGHT,
+                NX_DEVICERSHIFTKEYMASK,
+            ),
+            (
+                ModifiersState::CONTROL,
+                Self::CONTROL_LEFT,
+                NX_DEVICELCTLKEYMASK,
+                Self::CONTROL_RIGHT,
+                NX_DEVICERCTLKEYMASK,
+            ),
+            (
+                ModifiersState::ALT,
+                Self::ALT_LEFT,
+                NX_DEVICELALTKEYMASK,
+                Self::ALT_RIGHT,
+                NX_DEVICERALTKEYMASK,
+            ),
+            (
+                ModifiersState::SUPER,
+                Self::SUPER_LEFT,
+                NX_DEVICELCMDKEYMASK,
+                Self::SUPER_RIGHT,
+                NX_DEVICERCMDKEYMASK,
+            ),
+        ];
+
+        let flags = device_flags.unwrap_or(0);
+        let mut rv = Self::empty();
+        for (generic, left, left_mask, right, right_mask) in sides {
+            if !state.contains(generic) {
+                continue;
+            }
+
+            let is_left = flags & left_mask != 0;
+            let is_right = flags & right_mask != 0;
+            if is_left || !is_right {
+                rv |= left;
+            }
+            if is_right {
+                rv |= right;
+            }
+        }
+
+        rv
+    }
+
+    /// Reads the held modifiers from `NSEvent.modifierFlags` alone, when there is no winit event.
+    fn from_device_flags(flags: usize) -> Self {
+        use winit::keyboard::ModifiersState;
+
+        let generic = [
+            (NS_EVENT_MODIFIER_FLAG_SHIFT, ModifiersState::SHIFT),
+            (NS_EVENT_MODIFIER_FLAG_CONTROL, ModifiersState::CONTROL),
+            (NS_EVENT_MODIFIER_FLAG_OPTION, ModifiersState::ALT),
+            (NS_EVENT_MODIFIER_FLAG_COMMAND, ModifiersState::SUPER),
+        ];
+
+        let mut state = ModifiersState::empty();
+        for (mask, modifier) in generic {
+            if flags & mask != 0 {
+                state |= modifier;
+            }
+        }
+
+        Self::from_winit(state, Some(flags))
+    }
+}
+
+/// Sends presses and releases for the modifier keys that changed since the last sync.
+///
+/// winit on macOS swallows the key events of modifiers, so these are synthesized from the
+/// modifier state instead.
+fn sync_modifiers(state: &mut State, new_state: SidedModifiers) {
+    let winit = state.backend.winit();
+    let old_state = std::mem::replace(&mut winit.last_modifiers, new_state);
+
+    for (mask, evdev) in SidedModifiers::EVDEV_CODES {
+        let was_on = old_state.contains(mask);
+        let is_on = new_state.contains(mask);
+        if was_on == is_on {
+            continue;
+        }
+
+        let key_state = if is_on {
+            ElementState::Pressed
+        } else {
+            ElementState::Released
+        };
+        trace!("synthesizing modifier event: {evdev} {key_state:?}");
+
+        // WinitKeyboardInputEvent adds 8 itself, so pass the raw evdev code.
+        let event = InputEvent::<WinitInput>::Keyboard {
+            event: WinitKeyboardInputEvent {
+                time: get_monotonic_time().as_micros() as u64,
+                key: evdev,
+                count: 1,
+                state: key_state,
+            },
+        };
+        state.process_input_event(event);
+    }
+}
+
+/// Returns the key at the position that `code` stands for on a US keyboard.
+///
+/// On ISO keyboards, macOS swaps the codes of the key left of 1 and the extra `<>` key right of
+/// the left Shift, so that the former arrives as `IntlBackslash` and the latter as `Backquote`.
+/// xkb expects the physical positions, so swap them back.
+fn iso_key_position(code: KeyCode, iso_keyboard: bool) -> KeyCode {
+    match code {
+        KeyCode::Backquote if iso_keyboard => KeyCode::IntlBackslash,
+        KeyCode::IntlBackslash if iso_keyboard => KeyCode::Backquote,
+        code => code,
+    }
+}
+
+/// Raw evdev code of Caps Lock.
+const CAPS_LOCK_EVDEV: u32 = 58;
+
+/// Maps a winit [`KeyCode`](winit::keyboard::KeyCode) to its raw evdev code.
+///
+/// The returned code does not include the +8 xkb offset, which `WinitKeyboardInputEvent` adds.
+fn keycode_to_evdev(code: winit::keyboard::KeyCode) -> Option<u32> {
+    use winit::keyboard::KeyCode;
+
+    let evdev = match code {
+        KeyCode::Escape => 1,
+        KeyCode::Digit1 => 2,
+        KeyCode::Digit2 => 3,
+        KeyCode::Digit3 => 4,
+        KeyCode::Digit4 => 5,
+        KeyCode::Digit5 => 6,
+        KeyCode::Digit6 => 7,
+        KeyCode::Digit7 => 8,
+        KeyCode::Digit8 => 9,
+        KeyCode::Digit9 => 10,
+        KeyCode::Digit0 => 11,
+        KeyCode::Minus => 12,
+        KeyCode::Equal => 13,
+        KeyCode::Backspace => 14,
+        KeyCode::Tab => 15,
+        KeyCode::KeyQ => 16,
+        KeyCode::KeyW => 17,
+        KeyCode::KeyE => 18,
+        KeyCode::KeyR => 19,
+        KeyCode::KeyT => 20,
+        KeyCode::KeyY => 21,
+        KeyCode::KeyU => 22,
+        KeyCode::KeyI => 23,
+        KeyCode::KeyO => 24,
+        KeyCode::KeyP => 25,
+        KeyCode::BracketLeft => 26,
+        KeyCode::BracketRight => 27,
+        KeyCode::Enter => 28,
+        KeyCode::ControlLeft => 29,
+        KeyCode::KeyA => 30,
+        KeyCode::KeyS => 31,
+        KeyCode::KeyD => 32,
+        KeyCode::KeyF => 33,
+        KeyCode::KeyG => 34,
+        KeyCode::KeyH => 35,
+        KeyCode::KeyJ => 36,
+        KeyCode::KeyK => 37,
+        KeyCode::KeyL => 38,
+        KeyCode::Semicolon => 39,
+        KeyCode::Quote => 40,
+        KeyCode::Backquote => 41,
+        KeyCode::ShiftLeft => 42,
+        KeyCode::Backslash => 43,
+        KeyCode::KeyZ => 44,
+        KeyCode::KeyX => 45,
+        KeyCode::KeyC => 46,
+        KeyCode::KeyV => 47,
+        KeyCode::KeyB => 48,
+        KeyCode::KeyN => 49,
+        KeyCode::KeyM => 50,
+        KeyCode::Comma => 51,
+        KeyCode::Period => 52,
+        KeyCode::Slash => 53,
+        KeyCode::ShiftRight => 54,
+        KeyCode::NumpadMultiply => 55,
+        KeyCode::AltLeft => 56,
+        KeyCode::Space => 57,
+        KeyCode::CapsLock => CAPS_LOCK_EVDEV,
+        KeyCode::F1 => 59,
+        KeyCode::F2 => 60,
+        KeyCode::F3 => 61,
+        KeyCode::F4 => 62,
+        KeyCode::F5 => 63,
+        KeyCode::F6 => 64,
+        KeyCode::F7 => 65,
+        KeyCode::F8 => 66,
+        KeyCode::F9 => 67,
+        KeyCode::F10 => 68,
+        // On Apple keyboards this is the Clear key, which winit reports as NumLock.
+        KeyCode::NumLock => 69,
+        KeyCode::Numpad7 => 71,
+        KeyCode::Numpad8 => 72,
+        KeyCode::Numpad9 => 73,
+        KeyCode::NumpadSubtract => 74,
+        KeyCode::Numpad4 => 75,
+        KeyCode::Numpad5 => 76,
+        KeyCode::Numpad6 => 77,
+        KeyCode::NumpadAdd => 78,
+        KeyCode::Numpad1 => 79,
+        KeyCode::Numpad2 => 80,
+        KeyCode::Numpad3 => 81,
+        KeyCode::Numpad0 => 82,
+        KeyCode::NumpadDecimal => 83,
+        // Keys of ISO and JIS keyboards, see iso_key_position() for the ISO one.
+        KeyCode::Lang5 => 85,
+        KeyCode::IntlBackslash => 86,
+        KeyCode::F11 => 87,
+        KeyCode::F12 => 88,
+        KeyCode::IntlRo => 89,
+        KeyCode::Lang3 => 90,
+        KeyCode::Lang4 => 91,
+        KeyCode::Convert => 92,
+        KeyCode::KanaMode => 93,
+        KeyCode::NonConvert => 94,
+        KeyCode::NumpadEnter => 96,
+        KeyCode::NumpadDivide => 98,
+        KeyCode::AltRight => 100,
+        KeyCode::ArrowUp => 103,
+        KeyCode::ArrowLeft => 105,
+        KeyCode::ArrowRight => 106,
+        KeyCode::ArrowDown => 108,
+        KeyCode::AudioVolumeMute => 113,
+        KeyCode::AudioVolumeDown => 114,
+        KeyCode::AudioVolumeUp => 115,
+        KeyCode::Power => 116,
+        KeyCode::NumpadEqual => 117,
+        KeyCode::NumpadComma => 121,
+        // Kana and Eisu on Apple JIS keyboards, which Linux also reports as Hangeul and Hanja.
+        KeyCode::Lang1 => 122,
+        KeyCode::Lang2 => 123,
+        KeyCode::IntlYen => 124,
+        KeyCode::SuperLeft => 125,
+        KeyCode::SuperRight => 126,
+        KeyCode::Eject => 161,
+        KeyCode::MediaTrackNext => 163,
+        KeyCode::MediaPlayPause => 164,
+        KeyCode::MediaTrackPrevious => 165,
+        KeyCode::MediaStop => 166,
+        // Larger Apple keyboards go up to F19, the rest is there for other keyboards.
+        KeyCode::F13 => 183,
+        KeyCode::F14 => 184,
+        KeyCode::F15 => 185,
+        KeyCode::F16 => 186,
+        KeyCode::F17 => 187,
+        KeyCode::F18 => 188,
+        KeyCode::F19 => 189,
+        KeyCode::F20 => 190,
+        KeyCode::F21 => 191,
+        KeyCode::F22 => 192,
+        KeyCode::F23 => 193,
+        KeyCode::F24 => 194,
+        // Only when macOS lets it through, see the key bindings docs.
+        KeyCode::Fn => 464,
+        _ => return None,
+    };
+
+    Some(evdev)
+}
+
+use crate::input::backend_ext::NiriInputDevice;
+
+fn create_display_sleep_assertion() -> Option<appkit::DisplaySleepAssertion> {
+    match appkit::DisplaySleepAssertion::new("niri clients inhibit idle") {
+        Ok(assertion) => Some(assertion),
+        Err(err) => {
+            warn!("error keeping the displays awake: {err}");
+            None
+        }
+    }
+}
+
+/// Maps a position within the window of `output` into the global space.
+fn window_to_global(
+    niri: &Niri,
+    output: &Output,
+    window_size: PhysicalSize<u32>,
+    position: PhysicalPosition<f64>,
+) -> Option<Point<f64, Logical>> {
+    let output_geo = niri.global_space.output_geometry(output)?;
+
+    // The window shows the output rotated, so undo that to find the spot in the output. The
+    // output geometry already has the width and height swapped for 90 and 270 degrees.
+    let pos = window_rotation(output).invert().transform_point_in(
+        Point::<f64, Logical>::from((
+            position.x / f64::from(window_size.width.max(1)),
+            position.y / f64::from(window_size.height.max(1)),
+        )),
+        &Size::from((1., 1.)),
+    );
+    let size = output_geo.size.to_f64();
+    Some(output_geo.loc.to_f64() + Point::from((pos.x * size.w, pos.y * size.h)))
+}
+
+/// Maps a cursor position within the window of `output` into the bounding box of all outputs,
+/// which is what absolute motion events from winit are relative to.
+fn global_relative_position(
+    state: &State,
+    output: &Output,
+    window_size: PhysicalSize<u32>,
+    position: PhysicalPosition<f64>,
+) -> Option<RelativePosition> {
+    let output_geo = state.niri.global_space.output_geometry(output)?;
+    let bbox = state.global_bounding_rectangle()?;
+    let mut pos = window_to_global(&state.niri, output, window_size, position)?;
+
+    // While a button is held, AppKit keeps sending motion to the window where the drag started,
+    // even past its edges. Past the seam between monitors of different sizes this can point
+    // outside of every output, so keep the pointer on the window's own output then.
+    if state.niri.output_under(pos).is_none() {
+        let loc = output_geo.loc.to_f64();
+        let size = output_geo.size.to_f64();
+        pos.x = pos.x.clamp(loc.x, loc.x + size.w - 1.);
+        pos.y = pos.y.clamp(loc.y, loc.y + size.h - 1.);
+    }
+
+    let loc = bbox.loc.to_f64();
+    let size = bbox.size.to_f64();
+    Some(RelativePosition::new(
+        (pos.x - loc.x) / size.w,
+        (pos.y - loc.y) / size.h,
+    ))
+}
+
+impl NiriInputDevice for WinitVirtualDevice {
+    /// Returns the output of the window that the touch or the tablet event being processed
+    /// happened in.
+    ///
+    /// Pointer motion has none, winit positions it within the bounding box of all outputs, see
+    /// [`global_relative_position`].
+    fn output(&self, state: &State) -> Option<Output> {
+        match &state.backend {
+            Backend::Winit(winit) => winit.event_output.clone(),
+            Backend::Headless(_) => None,
+        }
+    }
+
+    /// Pixel scrolling is scaled before it's added up into discrete steps, see
+    /// [`scroll_factors`].
+    fn applies_touchpad_scroll_factor(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use niri_config::utils::FloatOrInt;
+    use winit::keyboard::KeyCode;
+
+    use super::*;
+
+    #[test]
+    fn sided_modifiers_from_device_flags() {
+        use winit::keyboard::ModifiersState;
+
+        let state = ModifiersState::SUPER | ModifiersState::ALT;
+        let flags = NX_DEVICERCMDKEYMASK | NX_DEVICELALTKEYMASK | NX_DEVICERALTKEYMASK;
+        assert_eq!(
+            SidedModifiers::from_winit(state, Some(flags)),
+            SidedModifiers::SUPER_RIGHT | SidedModifiers::ALT_LEFT | SidedModifiers::ALT_RIGHT
+        );
+
+        // Without device-dependent bits, fall back to the left keys.
+        assert_eq!(
+            SidedModifiers::from_winit(state, None),
+            SidedModifiers::SUPER_LEFT | SidedModifiers::ALT_LEFT
+        );
+
+        // Stale device bits don't produce events for released modifiers.
+        assert_eq!(
+            SidedModifiers::from_winit(ModifiersState::empty(), Some(flags)),
+            SidedModifiers::empty()
+        );
+    }
+
+    #[test]
+    fn sided_modifiers_from_flags_alone() {
+        let flags = NS_EVENT_MODIFIER_FLAG_COMMAND
+            | NX_DEVICERCMDKEYMASK
+            | NS_EVENT_MODIFIER_FLAG_SHIFT
+            | NX_DEVICELSHIFTKEYMASK;
+        assert_eq!(
+            SidedModifiers::from_device_flags(flags),
+            SidedModifiers::SUPER_RIGHT | SidedModifiers::SHIFT_LEFT
+        );
+
+        // Device bits without the generic ones mean nothing is held.
+        assert_eq!(
+            SidedModifiers::from_device_flags(NX_DEVICELALTKEYMASK),
+            SidedModifiers::empty()
+        );
+    }
+
+    #[test]
+    fn v120_accumulates_pixel_deltas() {
+        let mut acc = V120Accumulator::default();
+
+        // Half a notch worth of pixels makes half a step.
+        assert_eq!(acc.add(0., 26.5), (0., 60.));
+        assert_eq!(acc.add(26.5, 26.5), (60., 60.));
+
+        // Less than one unit carries over until enough comes in.
+        assert_eq!(acc.add(0., 0.265), (0., 0.));
+        assert_eq!(acc.add(0., 0.265), (0., 1.));
+
+        // Turning around drops what was left over from the other direction.
+        assert_eq!(acc.add(0., -53.), (0., -120.));
+    }
+
+    /// Feeds scroll events through a gesture, returning the axis stop decisions.
+    fn scroll_stops(
+        gesture: &mut ScrollGesture,
+        events: &[(ScrollPhase, TouchPhase)],
+    ) -> Vec<ScrollStop> {
+        let mut stops = Vec::new();
+        for &(scroll_phase, phase) in events {
+            stops.push(gesture.start(scroll_phase, phase));
+            stops.push(gesture.advance(scroll_phase, phase));
+        }
+        stops.retain(|stop| *stop != ScrollStop::Keep);
+        stops
+    }
+
+    #[test]
+    fn scroll_with_momentum_stops_once() {
+        use ScrollPhase::{Momentum, Touching};
+        use TouchPhase::{Ended, Moved, Started};
+
+        let mut gesture = ScrollGesture::default();
+        let stops = scroll_stops(
+            &mut gesture,
+            &[
+                (Touching, Started),
+                (Touching, Moved),
+                (Touching, Ended),
+                (Momentum, Started),
+                (Momentum, Moved),
+                (Momentum, Ended),
+            ],
+        );
+        assert_eq!(
+            stops,
+            [
+                ScrollStop::SendUnlessMomentum,
+                ScrollStop::WaitForMomentum,
+                ScrollStop::Send,
+            ]
+        );
+        assert_eq!(gesture, ScrollGesture::Idle);
+
+        // The momentum timer firing late doesn't send another stop.
+        assert!(!gesture.momentum_timed_out());
+    }
+
+    #[test]
+    fn scroll_without_momentum_stops_on_timeout() {
+        use ScrollPhase::Touching;
+        use TouchPhase::{Ended, Moved, Started};
+
+        let mut gesture = ScrollGesture::default();
+        let stops = scroll_stops(
+            &mut gesture,
+            &[(Touching, Started), (Touching, Moved), (Touching, Ended)],
+        );
+        assert_eq!(stops, [ScrollStop::SendUnlessMomentum]);
+        assert!(gesture.momentum_timed_out());
+        assert!(!gesture.momentum_timed_out());
+
+        // Momentum that starts after the stop went out doesn't send a second one.
+        let stops = scroll_stops(
+            &mut gesture,
+            &[(ScrollPhase::Momentum, Started), (ScrollPhase::Momentum, Ended)],
+        );
+        assert_eq!(stops, []);
+    }
+
+    #[test]
+    fn fingers_cancelling_momentum_stop_the_previous_scroll() {
+        use ScrollPhase::{Momentum, Touching};
+        use TouchPhase::{Cancelled, Ended, Moved, Started};
+
+        let mut gesture = ScrollGesture::default();
+        let stops = scroll_stops(
+            &mut gesture,
+            &[
+                (Touching, Started),
+                (Touching, Ended),
+                (Momentum, Started),
+                (Momentum, Moved),
+            ],
+        );
+        assert_eq!(
+            stops,
+            [ScrollStop::SendUnlessMomentum, ScrollStop::WaitForMomentum]
+        );
+
+        // Fingers touching down stop the momentum, its axis stop goes out before the new scroll.
+        assert_eq!(gesture.start(Touching, Started), ScrollStop::Send);
+        assert_eq!(gesture.advance(Touching, Started), ScrollStop::Keep);
+
+        // The momentum ending late belongs to the previous scroll.
+        let stops = scroll_stops(&mut gesture, &[(Momentum, Cancelled), (Touching, Moved)]);
+        assert_eq!(stops, []);
+        assert_eq!(gesture, ScrollGesture::Touching);
+
+        let stops = scroll_stops(&mut gesture, &[(Touching, Ended)]);
+        assert_eq!(stops, [ScrollStop::SendUnlessMomentum]);
+        assert!(gesture.momentum_timed_out());
+    }
+
+    #[test]
+    fn scroll_factor_scales_pixel_scrolling() {
+        let config = Config::parse_mem(
+            r#"
+input {
+    touchpad {
+        scroll-factor 2.0
+    }
+    mouse {
+        scroll-factor vertical=0.5 horizontal=1.0
+    }
+}
+"#,
+        )
+        .unwrap();
+
+        // A 2.0 factor doubles what the fingers scroll, and the steps that come out of it.
+        let delta = MouseScrollDelta::PixelDelta(PhysicalPosition::new(0., 26.5));
+        let factors = scroll_factors(&config, delta, ScrollPhase::Touching);
+        assert_eq!(factors, (2., 2.));
+        let scaled = scale_scroll_delta(delta, factors);
+        assert_eq!(axis_amounts(scaled), (-0., -53.));
+        assert_eq!(V120Accumulator::default().add(0., 53.), (0., 120.));
+
+        // The momentum keeps scrolling at the same speed.
+        let factors = scroll_factors(&config, delta, ScrollPhase::Momentum);
+        assert_eq!(factors, (2., 2.));
+
+        // Precise mouse wheels use the mouse factor, wheel notches are scaled by niri.
+        let factors = scroll_factors(&config, delta, ScrollPhase::None);
+        assert_eq!(factors, (1., 0.5));
+        let notch = MouseScrollDelta::LineDelta(0., 1.);
+        assert_eq!(scroll_factors(&config, notch, ScrollPhase::None), (1., 1.));
+
+        // Scaled down, small deltas still add up to steps.
+        let mut acc = V120Accumulator::default();
+        let small = MouseScrollDelta::PixelDelta(PhysicalPosition::new(0., 0.2));
+        let steps: f64 = (0..10)
+            .map(|_| {
+                let (dx, dy) = axis_amounts(scale_scroll_delta(small, (0.5, 0.5)));
+                acc.add(dx, dy).1
+            })
+            .sum();
+        assert_eq!(steps, -2.);
+    }
+
+    #[test]
+    fn key_burst_filter_drops_only_duplicates() {
+        let mut filter = KeyBurstFilter::new(DUPLICATE_KEY_WINDOW);
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+
+        assert!(!filter.is_duplicate(1, true, start));
+        assert!(!filter.is_duplicate(1, false, start + ms(30)));
+
+        // A fast double tap goes through.
+        assert!(!filter.is_duplicate(1, true, start + ms(60)));
+        assert!(!filter.is_duplicate(1, false, start + ms(90)));
+
+        // A Press right after the Release is a duplicate, and so is its Release.
+        assert!(filter.is_duplicate(1, true, start + ms(91)));
+        assert!(filter.is_duplicate(1, false, start + ms(91)));
+
+        // Other keys aren't affected.
+        assert!(!filter.is_duplicate(2, true, start + ms(91)));
+    }
+
+    #[test]
+    fn trackpad_held_back_while_typing() {
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+
+        assert!(!typed_within(None, start, DWT_TIMEOUT));
+
+        // Motion right after the key press is a palm, motion 500 ms later is the user.
+        let press = Some(start);
+        assert!(typed_within(press, start + ms(1), DWT_TIMEOUT));
+        assert!(!typed_within(press, start + ms(500), DWT_TIMEOUT));
+
+        // Only the trackpad has disable-while-typing.
+        let mut trackpad = ShimDevice::TRACKPAD;
+        let mut mouse = ShimDevice::POINTER;
+        trackpad.config_dwt_set_enabled(true).unwrap();
+        mouse.config_dwt_set_enabled(true).unwrap();
+        assert!(trackpad.config_dwt_enabled());
+        assert!(!mouse.config_dwt_enabled());
+        trackpad.config_dwt_set_enabled(false).unwrap();
+    }
+
+    #[test]
+    fn taps_click_the_mapped_buttons() {
+        let lrm = TapButtonMap::LeftRightMiddle;
+        assert_eq!(tap_button(1, lrm), Some(WinitMouseButton::Left));
+        assert_eq!(tap_button(2, lrm), Some(WinitMouseButton::Right));
+        assert_eq!(tap_button(3, lrm), Some(WinitMouseButton::Middle));
+        assert_eq!(tap_button(4, lrm), None);
+
+        let lmr = TapButtonMap::LeftMiddleRight;
+        assert_eq!(tap_button(1, lmr), Some(WinitMouseButton::Left));
+        assert_eq!(tap_button(2, lmr), Some(WinitMouseButton::Middle));
+        assert_eq!(tap_button(3, lmr), Some(WinitMouseButton::Right));
+    }
+
+    #[test]
+    fn click_location_to_physical() {
+        // 400×300 points at scale 2, clicked 50 points above the bottom left.
+        let size = PhysicalSize::new(800, 600);
+        let position = window_location_to_physical((100., 50.), size, 2.);
+        assert_eq!(position, PhysicalPosition::new(200., 500.));
+
+        let position = window_location_to_physical((0., 300.), size, 2.);
+        assert_eq!(position, PhysicalPosition::new(0., 0.));
+    }
+
+    #[test]
+    fn shift_turns_wheel_scrolling_sideways() {
+        let down = MouseScrollDelta::LineDelta(0., -1.);
+        assert_eq!(shift_scroll_delta(down), MouseScrollDelta::LineDelta(-1., 0.));
+
+        let sideways = MouseScrollDelta::LineDelta(2., 0.);
+        assert_eq!(shift_scroll_delta(sideways), sideways);
+
+        let trackpad = MouseScrollDelta::PixelDelta(PhysicalPosition::new(0., 10.));
+        assert_eq!(shift_scroll_delta(trackpad), trackpad);
+    }
+
+    #[test]
+    fn mod_key_follows_config() {
+        let mut input = Input::default();
+        assert_eq!(mod_key(&input), ModKey::Super);
+
+        input.mod_key = Some(ModKey::Alt);
+        assert_eq!(mod_key(&input), ModKey::Alt);
+
+        input.mod_key_nested = Some(ModKey::Ctrl);
+        assert_eq!(mod_key(&input), ModKey::Ctrl);
+    }
+
+    #[test]
+    fn background_drag_follows_cursor() {
+        let drag = BackgroundDrag {
+            window_id: unsafe { WindowId::dummy() },
+            start_cursor: (100., 200.),
+            start_origin: LogicalPosition::new(40., 30.),
+        };
+        assert_eq!(background_drag_origin(&drag, (100., 200.)), LogicalPosition::new(40., 30.));
+        assert_eq!(background_drag_origin(&drag, (90., 250.)), LogicalPosition::new(30., 80.));
+    }
+
+    #[test]
+    fn window_drag_needs_threshold() {
+        assert!(!past_window_drag_threshold(Point::from((0., 0.))));
+        assert!(!past_window_drag_threshold(Point::from((5., -5.))));
+        assert!(past_window_drag_threshold(Point::from((-8., 0.))));
+        assert!(past_window_drag_threshold(Point::from((6., 6.))));
+
+        // Without natural scrolling, the scroll delta goes against the fingers.
+        let delta = PhysicalPosition::new(3., -4.);
+        assert_eq!(finger_motion(delta, false), Point::from((-3., 4.)));
+        assert_eq!(finger_motion(delta, true), Point::from((3., -4.)));
+    }
+
+    #[test]
+    fn natural_scroll_setting_flips_axis_sign() {
+        // macOS scrolled naturally.
+        let delta = MouseScrollDelta::LineDelta(0., 1.);
+        assert_eq!(natural_scroll_delta(delta, true, None), (delta, true));
+        assert_eq!(natural_scroll_delta(delta, true, Some(true)), (delta, true));
+
+        let flipped = MouseScrollDelta::LineDelta(-0., -1.);
+        assert_eq!(natural_scroll_delta(delta, true, Some(false)), (flipped, false));
+
+        // And back when the setting is toggled again.
+        assert_eq!(natural_scroll_delta(flipped, false, Some(true)), (delta, true));
+
+        let pixels = MouseScrollDelta::PixelDelta(PhysicalPosition::new(3., -5.));
+        let flipped = MouseScrollDelta::PixelDelta(PhysicalPosition::new(-3., 5.));
+        assert_eq!(natural_scroll_delta(pixels, false, Some(true)), (flipped, true));
+    }
+
+    #[test]
+    fn key_burst_filter_can_be_turned_off() {
+        let mut filter = KeyBurstFilter::new(Duration::ZERO);
+        let start = Instant::now();
+
+        assert!(!filter.is_duplicate(1, false, start));
+        assert!(!filter.is_duplicate(1, true, start));
+        assert!(!filter.is_duplicate(1, false, start));
+    }
+
+    #[test]
+    fn winit_events_translate_to_messages() {
+        // SAFETY: the dummy IDs are only compared.
+        let window_id = unsafe { WindowId::dummy() };
+        let device_id = unsafe { winit::event::DeviceId::dummy() };
+        let window_event = |event| Event::<()>::WindowEvent { window_id, event };
+
+        let size = PhysicalSize::new(800, 600);
+        let cases = [
+            (
+                window_event(WindowEvent::Resized(size)),
+                Some(WinitEventMsg::Resized { window_id, size }),
+            ),
+            (
+                window_event(WindowEvent::CloseRequested),
+                Some(WinitEventMsg::CloseRequested),
+            ),
+            (
+                window_event(WindowEvent::Focused(false)),
+                Some(WinitEventMsg::Focused {
+                    focused: false,
+                    device_flags: None,
+                }),
+            ),
+            (
+                window_event(WindowEvent::CursorLeft { device_id }),
+                Some(WinitEventMsg::CursorLeft { window_id }),
+            ),
+            (
+                window_event(WindowEvent::HoveredFile(PathBuf::from("/tmp/a.txt"))),
+                Some(WinitEventMsg::HoveredFile {
+                    path: PathBuf::from("/tmp/a.txt"),
+                }),
+            ),
+            (
+                window_event(WindowEvent::HoveredFileCancelled),
+                Some(WinitEventMsg::HoveredFileCancelled),
+            ),
+            (
+                Event::DeviceEvent {
+                    device_id,
+                    event: DeviceEvent::MouseMotion { delta: (1., -2.) },
+                },
+                Some(WinitEventMsg::MouseMotion {
+                    delta: (1., -2.),
+                    touch_count: 0,
+                    event_time: None,
+                }),
+            ),
+            (Event::AboutToWait, Some(WinitEventMsg::AboutToWait)),
+            (
+                window_event(WindowEvent::Occluded(true)),
+                Some(WinitEventMsg::Covered { window_id }),
+            ),
+            (
+                window_event(WindowEvent::Occluded(false)),
+                Some(WinitEventMsg::Uncovered { window_id }),
+            ),
+        ];
+
+        for (event, expected) in cases {
+            assert_eq!(WinitEventMsg::from_winit(event), expected);
+        }
+    }
+
+    #[test]
+    fn keyboard_input_messages_to_evdev() {
+        let key = |code| PhysicalKey::Code(code);
+        let pressed = ElementState::Pressed;
+        let released = ElementState::Released;
+
+        assert_eq!(keyboard_events(key(KeyCode::KeyA), pressed, false, false), [(30, pressed)]);
+        assert_eq!(keyboard_events(key(KeyCode::KeyA), released, true, false), [(30, released)]);
+
+        // Modifiers come from ModifiersChanged instead.
+        assert!(keyboard_events(key(KeyCode::ShiftLeft), pressed, false, false).is_empty());
+        assert!(keyboard_events(key(KeyCode::SuperRight), released, false, false).is_empty());
+
+        // Without the HID monitor, every Caps Lock toggle is a tap.
+        let tap = [(CAPS_LOCK_EVDEV, pressed), (CAPS_LOCK_EVDEV, released)];
+        assert_eq!(keyboard_events(key(KeyCode::CapsLock), pressed, false, false), tap);
+        assert_eq!(keyboard_events(key(KeyCode::CapsLock), released, false, false), tap);
+        assert!(keyboard_events(key(KeyCode::CapsLock), pressed, true, false).is_empty());
+    }
+
+    #[test]
+    fn iso_keys_to_evdev() {
+        let key = |code| PhysicalKey::Code(code);
+        let pressed = ElementState::Pressed;
+
+        // On ISO keyboards, the `<>` key arrives as Backquote and goes out as KEY_102ND, and the
+        // key left of 1 the other way around.
+        assert_eq!(keyboard_events(key(KeyCode::Backquote), pressed, false, true), [(86, pressed)]);
+        assert_eq!(
+            keyboard_events(key(KeyCode::IntlBackslash), pressed, false, true),
+            [(41, pressed)]
+        );
+
+        // Other keyboards have nothing to swap.
+        assert_eq!(
+            keyboard_events(key(KeyCode::Backquote), pressed, false, false),
+            [(41, pressed)]
+        );
+        assert_eq!(keyboard_events(key(KeyCode::KeyA), pressed, false, true), [(30, pressed)]);
+    }
+
+    #[test]
+    fn keycode_to_evdev_iso_and_jis_keys() {
+        let expected = [
+            (KeyCode::IntlBackslash, 86),
+            (KeyCode::IntlRo, 89),
+            (KeyCode::IntlYen, 124),
+            (KeyCode::Lang1, 122),
+            (KeyCode::Lang2, 123),
+            (KeyCode::NumpadComma, 121),
+        ];
+
+        for (code, evdev) in expected {
+            assert_eq!(keycode_to_evdev(code), Some(evdev), "{code:?}");
+        }
+    }
+
+    #[test]
+    fn pointer_speed_from_accel_speed() {
+        let mut config = Config::default();
+        assert_eq!(pointer_speed_factor(&config, false), 1.);
+
+        config.input.mouse.accel_speed = FloatOrInt(1.);
+        config.input.touchpad.accel_speed = FloatOrInt(-1.);
+        assert_eq!(pointer_speed_factor(&config, false), 2.);
+        assert_eq!(pointer_speed_factor(&config, true), 0.5);
+    }
+
+    #[test]
+    fn gpu_parsing() {
+        assert_eq!(parse_gpu("integrated"), Some(WinitGpu::Integrated));
+        assert_eq!(parse_gpu(" Discrete\n"), Some(WinitGpu::Discrete));
+        assert_eq!(parse_gpu("auto"), None);
+        assert_eq!(parse_gpu("nvidia"), None);
+        assert_eq!(parse_gpu(""), None);
+    }
+
+    #[test]
+    fn winit_output_transform_keeps_rotation() {
+        for transform in [
+            Transform::Normal,
+            Transform::_90,
+            Transform::_180,
+            Transform::_270,
+            Transform::Flipped,
+            Transform::Flipped90,
+            Transform::Flipped180,
+            Transform::Flipped270,
+        ] {
+            let output_transform = winit_output_transform(transform);
+            assert_eq!(output_transform + Transform::Flipped180, transform);
+        }
+
+        assert_eq!(winit_output_transform(Transform::Normal), Transform::Flipped180);
+    }
+
+    #[test]
+    fn offered_refresh_rates_include_current() {
+        // Built-in panels list no rates.
+        assert_eq!(offered_refresh_rates(vec![], 120_000), [120_000]);
+
+        // The display mode list rounds 59.94 Hz differently than the screen does.
+        assert_eq!(
+            offered_refresh_rates(vec![144_000, 120_000, 60_000, 59_940], 59_940),
+            [144_000, 120_000, 59_940]
+        );
+    }
+
+    #[test]
+    fn keycode_to_evdev_function_and_numpad_keys() {
+        let expected = [
+            (KeyCode::F10, 68),
+            (KeyCode::F11, 87),
+            (KeyCode::F12, 88),
+            (KeyCode::NumLock, 69),
+            (KeyCode::Numpad0, 82),
+            (KeyCode::Numpad1, 79),
+            (KeyCode::Numpad2, 80),
+            (KeyCode::Numpad3, 81),
+            (KeyCode::Numpad4, 75),
+            (KeyCode::Numpad5, 76),
+            (KeyCode::Numpad6, 77),
+            (KeyCode::Numpad7, 71),
+            (KeyCode::Numpad8, 72),
+            (KeyCode::Numpad9, 73),
+            (KeyCode::NumpadAdd, 78),
+            (KeyCode::NumpadSubtract, 74),
+            (KeyCode::NumpadMultiply, 55),
+            (KeyCode::NumpadDivide, 98),
+            (KeyCode::NumpadEnter, 96),
+            (KeyCode::NumpadDecimal, 83),
+        ];
+
+        for (code, evdev) in expected {
+            assert_eq!(keycode_to_evdev(code), Some(evdev), "{code:?}");
+        }
+    }
+
+    #[test]
+    fn keycode_to_evdev_media_and_extra_function_keys() {
+        let expected = [
+            (KeyCode::AudioVolumeMute, 113),
+            (KeyCode::AudioVolumeDown, 114),
+            (KeyCode::AudioVolumeUp, 115),
+            (KeyCode::MediaTrackNext, 163),
+            (KeyCode::MediaPlayPause, 164),
+            (KeyCode::MediaTrackPrevious, 165),
+            (KeyCode::F13, 183),
+            (KeyCode::F19, 189),
+            (KeyCode::F24, 194),
+            (KeyCode::Fn, 464),
+        ];
+
+        for (code, evdev) in expected {
+            assert_eq!(keycode_to_evdev(code), Some(evdev), "{code:?}");
+        }
+    }
+
+    #[test]
+    fn display_timing_next_vblank() {
+        let timing = DisplayTiming {
+            vblank: Duration::from_millis(1000),
+            seq: 50,
+            refresh: Duration::from_micros(16_667),
+        };
+
+        // Frames rendered for the refresh the display link reported show up with it.
+        let before = Duration::from_millis(995);
+        assert_eq!(timing.next_vblank(before), (timing.vblank, 50));
+        assert_eq!(timing.next_vblank(timing.vblank), (timing.vblank, 50));
+
+        let after = Duration::from_millis(1001);
+        assert_eq!(timing.next_vblank(after), (Duration::from_micros(1_016_667), 51));
+
+        // A second after the last tick, the refreshes since then are counted.
+        let later = Duration::from_millis(2000);
+        assert_eq!(timing.next_vblank(later), (Duration::from_micros(2_000_020), 110));
     }
 }
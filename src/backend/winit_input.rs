@@ -2,16 +2,23 @@ use std::path::PathBuf;
 
 use winit::{
     dpi::PhysicalPosition,
-    event::{ElementState, MouseButton as WinitMouseButton, MouseScrollDelta},
+    event::{ElementState, MouseButton as WinitMouseButton, MouseScrollDelta, TouchPhase},
 };
 
 use smithay::backend::input::{
     self, AbsolutePositionEvent, Axis, AxisRelativeDirection, AxisSource, ButtonState, Device,
-    DeviceCapability, Event, InputBackend, KeyState, KeyboardKeyEvent, Keycode, PointerAxisEvent,
-    PointerButtonEvent, PointerMotionAbsoluteEvent, TouchCancelEvent, TouchDownEvent, TouchEvent,
-    TouchMotionEvent, TouchSlot, TouchUpEvent, UnusedEvent,
+    DeviceCapability, Event, GestureBeginEvent, GestureEndEvent, GesturePinchBeginEvent,
+    GesturePinchEndEvent, GesturePinchUpdateEvent, GestureSwipeBeginEvent, GestureSwipeEndEvent,
+    GestureSwipeUpdateEvent, InputBackend, KeyState, KeyboardKeyEvent,
+    Keycode, PointerAxisEvent, PointerButtonEvent, PointerMotionAbsoluteEvent, PointerMotionEvent,
+    ProximityState, TabletToolAxisEvent, TabletToolButtonEvent, TabletToolCapabilities,
+    TabletToolDescriptor, TabletToolEvent, TabletToolProximityEvent, TabletToolTipEvent,
+    TabletToolTipState, TabletToolType, TouchCancelEvent, TouchDownEvent, TouchEvent,
+    TouchFrameEvent, TouchMotionEvent, TouchSlot, TouchUpEvent, UnusedEvent,
 };
 
+use super::appkit::{ScrollPhase, TabletPoint, TabletToolKind};
+
 /// Marker used to define the `InputBackend` types for the winit backend.
 #[derive(Debug)]
 pub struct WinitInput;
@@ -32,7 +39,10 @@ impl Device for WinitVirtualDevice {
     fn has_capability(&self, capability: DeviceCapability) -> bool {
         matches!(
             capability,
-            DeviceCapability::Keyboard | DeviceCapability::Pointer | DeviceCapability::Touch
+            DeviceCapability::Keyboard
+                | DeviceCapability::Pointer
+                | DeviceCapability::Touch
+                | DeviceCapability::TabletTool
         )
     }
 
@@ -118,11 +128,70 @@ impl AbsolutePositionEvent<WinitInput> for WinitMouseMovedEvent {
     }
 }
 
+/// Winit-Backend internal event wrapping `winit`'s raw mouse deltas into a [`PointerMotionEvent`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WinitMouseRelativeEvent {
+    pub time: u64,
+    pub delta: (f64, f64),
+}
+
+impl Event<WinitInput> for WinitMouseRelativeEvent {
+    fn time(&self) -> u64 {
+        self.time
+    }
+
+    fn device(&self) -> WinitVirtualDevice {
+        WinitVirtualDevice
+    }
+}
+
+impl PointerMotionEvent<WinitInput> for WinitMouseRelativeEvent {
+    fn delta_x(&self) -> f64 {
+        self.delta.0
+    }
+
+    fn delta_y(&self) -> f64 {
+        self.delta.1
+    }
+
+    // macOS only reports deltas with the system acceleration already applied.
+    fn delta_x_unaccel(&self) -> f64 {
+        self.delta.0
+    }
+
+    fn delta_y_unaccel(&self) -> f64 {
+        self.delta.1
+    }
+}
+
 /// Winit-Backend internal event wrapping `winit`'s types into a [`PointerAxisEvent`]
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct WinitMouseWheelEvent {
     pub time: u64,
     pub delta: MouseScrollDelta,
+    /// Phase of the scroll gesture that winit reports.
+    pub phase: TouchPhase,
+    /// Whether the fingers are on the trackpad or macOS is scrolling with momentum.
+    pub scroll_phase: ScrollPhase,
+    /// Discrete steps accumulated from pixel deltas, in v120 units and the sign of `amount()`.
+    pub pixel_v120: (f64, f64),
+    /// Whether macOS natural scrolling already inverted the delta.
+    pub inverted: bool,
+    /// Whether this ends a trackpad scroll, which clients receive as an axis stop.
+    ///
+    /// Trackpad events often have zero amounts in the middle of a scroll, like on the axis that
+    /// doesn't move, so a zero amount alone doesn't mean a stop.
+    pub axis_stop: bool,
+}
+
+impl WinitMouseWheelEvent {
+    /// Returns whether macOS generated this event with momentum after the fingers lifted.
+    ///
+    /// Clients that scroll kinetically on their own start doing so at the axis stop, so these
+    /// events would move them a second time.
+    pub fn is_momentum(&self) -> bool {
+        self.scroll_phase == ScrollPhase::Momentum
+    }
 }
 
 impl Event<WinitInput> for WinitMouseWheelEvent {
@@ -137,30 +206,61 @@ impl Event<WinitInput> for WinitMouseWheelEvent {
 
 impl PointerAxisEvent<WinitInput> for WinitMouseWheelEvent {
     fn source(&self) -> AxisSource {
-        match self.delta {
-            MouseScrollDelta::LineDelta(_, _) => AxisSource::Wheel,
-            MouseScrollDelta::PixelDelta(_) => AxisSource::Continuous,
+        match (self.delta, self.scroll_phase) {
+            (MouseScrollDelta::LineDelta(_, _), _) => AxisSource::Wheel,
+            // Fingers on a trackpad or a Magic Mouse, this gives clients kinetic scrolling.
+            (MouseScrollDelta::PixelDelta(_), ScrollPhase::Touching) => AxisSource::Finger,
+            // Momentum that macOS adds after the fingers lift, and precise mouse wheels.
+            (MouseScrollDelta::PixelDelta(_), ScrollPhase::Momentum | ScrollPhase::None) => {
+                AxisSource::Continuous
+            }
         }
     }
 
     fn amount(&self, axis: Axis) -> Option<f64> {
-        match (axis, self.delta) {
-            (Axis::Horizontal, MouseScrollDelta::PixelDelta(delta)) => Some(-delta.x),
-            (Axis::Vertical, MouseScrollDelta::PixelDelta(delta)) => Some(-delta.y),
-            (_, MouseScrollDelta::LineDelta(_, _)) => None,
+        let (horizontal, vertical) = axis_amounts(self.delta);
+        let amount = match axis {
+            Axis::Horizontal => horizontal,
+            Axis::Vertical => vertical,
+        };
+
+        // niri sends an axis stop for a zero finger amount.
+        if amount == 0. && self.source() == AxisSource::Finger && !self.axis_stop {
+            return None;
         }
+        Some(amount)
     }
 
     fn amount_v120(&self, axis: Axis) -> Option<f64> {
-        match (axis, self.delta) {
-            (Axis::Horizontal, MouseScrollDelta::LineDelta(x, _)) => Some(-x as f64 * 120.),
-            (Axis::Vertical, MouseScrollDelta::LineDelta(_, y)) => Some(-y as f64 * 120.),
-            (_, MouseScrollDelta::PixelDelta(_)) => None,
-        }
+        let v120 = match (axis, self.delta) {
+            (Axis::Horizontal, MouseScrollDelta::LineDelta(x, _)) => -f64::from(x) * 120.,
+            (Axis::Vertical, MouseScrollDelta::LineDelta(_, y)) => -f64::from(y) * 120.,
+            (Axis::Horizontal, MouseScrollDelta::PixelDelta(_)) => self.pixel_v120.0,
+            (Axis::Vertical, MouseScrollDelta::PixelDelta(_)) => self.pixel_v120.1,
+        };
+        (v120 != 0.).then_some(v120)
     }
 
     fn relative_direction(&self, _axis: Axis) -> AxisRelativeDirection {
-        AxisRelativeDirection::Identical
+        if self.inverted {
+            AxisRelativeDirection::Inverted
+        } else {
+            AxisRelativeDirection::Identical
+        }
+    }
+}
+
+/// Converts a winit scroll delta into horizontal and vertical axis amounts.
+///
+/// winit deltas, like AppKit's, are positive when the content moves right or down. Axis amounts
+/// are positive when scrolling right or down, which moves the content left or up, so both axes
+/// flip. The deltas already follow the natural scrolling setting of macOS, which
+/// [`PointerAxisEvent::relative_direction`] reports.
+pub fn axis_amounts(delta: MouseScrollDelta) -> (f64, f64) {
+    match delta {
+        MouseScrollDelta::PixelDelta(delta) => (-delta.x, -delta.y),
+        // Like libinput, one notch scrolls by 15.
+        MouseScrollDelta::LineDelta(x, y) => (-f64::from(x) * 15., -f64::from(y) * 15.),
     }
 }
 
@@ -186,12 +286,12 @@ impl Event<WinitInput> for WinitMouseInputEvent {
 impl PointerButtonEvent<WinitInput> for WinitMouseInputEvent {
     fn button_code(&self) -> u32 {
         match self.button {
-            WinitMouseButton::Left => 0x110,
-            WinitMouseButton::Right => 0x111,
-            WinitMouseButton::Middle => 0x112,
-            WinitMouseButton::Forward => 0x115,
-            WinitMouseButton::Back => 0x116,
-            WinitMouseButton::Other(b) => b as u32,
+            WinitMouseButton::Left => BTN_LEFT,
+            WinitMouseButton::Right => BTN_RIGHT,
+            WinitMouseButton::Middle => BTN_MIDDLE,
+            WinitMouseButton::Forward => BTN_FORWARD,
+            WinitMouseButton::Back => BTN_BACK,
+            WinitMouseButton::Other(b) => evdev_button_code(b),
         }
     }
 
@@ -203,6 +303,644 @@ impl PointerButtonEvent<WinitInput> for WinitMouseInputEvent {
     }
 }
 
+const BTN_LEFT: u32 = 0x110;
+const BTN_RIGHT: u32 = 0x111;
+const BTN_MIDDLE: u32 = 0x112;
+const BTN_SIDE: u32 = 0x113;
+const BTN_EXTRA: u32 = 0x114;
+const BTN_FORWARD: u32 = 0x115;
+const BTN_BACK: u32 = 0x116;
+const BTN_TASK: u32 = 0x117;
+
+/// Converts a macOS `NSEvent.buttonNumber` into an evdev button code.
+///
+/// | buttonNumber | evdev                   |
+/// |--------------|-------------------------|
+/// | 0            | `BTN_LEFT`              |
+/// | 1            | `BTN_RIGHT`             |
+/// | 2            | `BTN_MIDDLE`            |
+/// | 3            | `BTN_BACK`              |
+/// | 4            | `BTN_FORWARD`           |
+/// | 5            | `BTN_SIDE`              |
+/// | 6            | `BTN_EXTRA`             |
+/// | 7            | `BTN_TASK`              |
+/// | 8 and up     | `0x118` and up in order |
+///
+/// winit reports 0 to 4 as named buttons, so only 5 and up normally get here.
+fn evdev_button_code(button_number: u16) -> u32 {
+    match button_number {
+        0 => BTN_LEFT,
+        1 => BTN_RIGHT,
+        2 => BTN_MIDDLE,
+        3 => BTN_BACK,
+        4 => BTN_FORWARD,
+        5 => BTN_SIDE,
+        6 => BTN_EXTRA,
+        7 => BTN_TASK,
+        n => BTN_TASK + 1 + u32::from(n - 8),
+    }
+}
+
+/// Converts an evdev button code back into a winit button, the inverse of
+/// [`PointerButtonEvent::button_code`].
+///
+/// Returns `None` for codes below `BTN_LEFT`, which aren't mouse buttons.
+pub fn winit_mouse_button(code: u32) -> Option<WinitMouseButton> {
+    let button = match code {
+        BTN_LEFT => WinitMouseButton::Left,
+        BTN_RIGHT => WinitMouseButton::Right,
+        BTN_MIDDLE => WinitMouseButton::Middle,
+        BTN_FORWARD => WinitMouseButton::Forward,
+        BTN_BACK => WinitMouseButton::Back,
+        BTN_SIDE => WinitMouseButton::Other(5),
+        BTN_EXTRA => WinitMouseButton::Other(6),
+        BTN_TASK => WinitMouseButton::Other(7),
+        code if code > BTN_TASK => {
+            let button_number = u16::try_from(code - BTN_TASK - 1 + 8).ok()?;
+            WinitMouseButton::Other(button_number)
+        }
+        _ => return None,
+    };
+    Some(button)
+}
+
+/// Returns the button of a trackpad click with `fingers` on the trackpad, like libinput's
+/// clickfinger method: two fingers give a right click and three a middle click.
+pub fn clickfinger_button(fingers: u32) -> WinitMouseButton {
+    match fingers {
+        2 => WinitMouseButton::Right,
+        3 => WinitMouseButton::Middle,
+        _ => WinitMouseButton::Left,
+    }
+}
+
+/// Winit-Backend internal event starting a multi-finger trackpad swipe
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WinitGestureSwipeBeginEvent {
+    pub time: u64,
+    pub fingers: u32,
+}
+
+impl Event<WinitInput> for WinitGestureSwipeBeginEvent {
+    fn time(&self) -> u64 {
+        self.time
+    }
+
+    fn device(&self) -> WinitVirtualDevice {
+        WinitVirtualDevice
+    }
+}
+
+impl GestureBeginEvent<WinitInput> for WinitGestureSwipeBeginEvent {
+    fn fingers(&self) -> u32 {
+        self.fingers
+    }
+}
+
+impl GestureSwipeBeginEvent<WinitInput> for WinitGestureSwipeBeginEvent {}
+
+/// Winit-Backend internal event carrying the motion of an ongoing trackpad swipe
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WinitGestureSwipeUpdateEvent {
+    pub time: u64,
+    pub delta: (f64, f64),
+}
+
+impl Event<WinitInput> for WinitGestureSwipeUpdateEvent {
+    fn time(&self) -> u64 {
+        self.time
+    }
+
+    fn device(&self) -> WinitVirtualDevice {
+        WinitVirtualDevice
+    }
+}
+
+impl GestureSwipeUpdateEvent<WinitInput> for WinitGestureSwipeUpdateEvent {
+    fn delta_x(&self) -> f64 {
+        self.delta.0
+    }
+
+    fn delta_y(&self) -> f64 {
+        self.delta.1
+    }
+}
+
+/// Winit-Backend internal event finishing a trackpad swipe
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WinitGestureSwipeEndEvent {
+    pub time: u64,
+    pub cancelled: bool,
+}
+
+impl Event<WinitInput> for WinitGestureSwipeEndEvent {
+    fn time(&self) -> u64 {
+        self.time
+    }
+
+    fn device(&self) -> WinitVirtualDevice {
+        WinitVirtualDevice
+    }
+}
+
+impl GestureEndEvent<WinitInput> for WinitGestureSwipeEndEvent {
+    fn cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+impl GestureSwipeEndEvent<WinitInput> for WinitGestureSwipeEndEvent {}
+
+/// Winit-Backend internal event starting a trackpad pinch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WinitGesturePinchBeginEvent {
+    pub time: u64,
+}
+
+impl Event<WinitInput> for WinitGesturePinchBeginEvent {
+    fn time(&self) -> u64 {
+        self.time
+    }
+
+    fn device(&self) -> WinitVirtualDevice {
+        WinitVirtualDevice
+    }
+}
+
+impl GestureBeginEvent<WinitInput> for WinitGesturePinchBeginEvent {
+    fn fingers(&self) -> u32 {
+        // macOS only recognizes two-finger magnification.
+        2
+    }
+}
+
+impl GesturePinchBeginEvent<WinitInput> for WinitGesturePinchBeginEvent {}
+
+/// Winit-Backend internal event carrying the scale of an ongoing trackpad pinch
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WinitGesturePinchUpdateEvent {
+    pub time: u64,
+    /// Scale relative to the start of the pinch.
+    pub scale: f64,
+}
+
+impl Event<WinitInput> for WinitGesturePinchUpdateEvent {
+    fn time(&self) -> u64 {
+        self.time
+    }
+
+    fn device(&self) -> WinitVirtualDevice {
+        WinitVirtualDevice
+    }
+}
+
+impl GesturePinchUpdateEvent<WinitInput> for WinitGesturePinchUpdateEvent {
+    // Magnify events don't report the motion of the pinch center.
+    fn delta_x(&self) -> f64 {
+        0.
+    }
+
+    fn delta_y(&self) -> f64 {
+        0.
+    }
+
+    fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    fn rotation(&self) -> f64 {
+        0.
+    }
+}
+
+/// Winit-Backend internal event finishing a trackpad pinch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WinitGesturePinchEndEvent {
+    pub time: u64,
+    pub cancelled: bool,
+}
+
+impl Event<WinitInput> for WinitGesturePinchEndEvent {
+    fn time(&self) -> u64 {
+        self.time
+    }
+
+    fn device(&self) -> WinitVirtualDevice {
+        WinitVirtualDevice
+    }
+}
+
+impl GestureEndEvent<WinitInput> for WinitGesturePinchEndEvent {
+    fn cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+impl GesturePinchEndEvent<WinitInput> for WinitGesturePinchEndEvent {}
+
+/// Winit-Backend internal event wrapping `winit`'s types into a [`TouchDownEvent`]
+#[derive(Debug, Clone)]
+pub struct WinitTouchDownEvent {
+    pub time: u64,
+    pub position: RelativePosition,
+    pub global_position: PhysicalPosition<f64>,
+    pub id: u64,
+}
+
+impl Event<WinitInput> for WinitTouchDownEvent {
+    fn time(&self) -> u64 {
+        self.time
+    }
+
+    fn device(&self) -> WinitVirtualDevice {
+        WinitVirtualDevice
+    }
+}
+
+impl TouchDownEvent<WinitInput> for WinitTouchDownEvent {}
+
+impl TouchEvent<WinitInput> for WinitTouchDownEvent {
+    fn slot(&self) -> TouchSlot {
+        touch_slot(self.id)
+    }
+}
+
+impl AbsolutePositionEvent<WinitInput> for WinitTouchDownEvent {
+    fn x(&self) -> f64 {
+        self.global_position.x
+    }
+
+    fn y(&self) -> f64 {
+        self.global_position.y
+    }
+
+    fn x_transformed(&self, width: i32) -> f64 {
+        f64::max(self.position.x * width as f64, 0.0)
+    }
+
+    fn y_transformed(&self, height: i32) -> f64 {
+        f64::max(self.position.y * height as f64, 0.0)
+    }
+}
+
+/// Winit-Backend internal event wrapping `winit`'s types into a [`TouchMotionEvent`]
+#[derive(Debug, Clone)]
+pub struct WinitTouchMotionEvent {
+    pub time: u64,
+    pub position: RelativePosition,
+    pub global_position: PhysicalPosition<f64>,
+    pub id: u64,
+}
+
+impl Event<WinitInput> for WinitTouchMotionEvent {
+    fn time(&self) -> u64 {
+        self.time
+    }
+
+    fn device(&self) -> WinitVirtualDevice {
+        WinitVirtualDevice
+    }
+}
+
+impl TouchMotionEvent<WinitInput> for WinitTouchMotionEvent {}
+
+impl TouchEvent<WinitInput> for WinitTouchMotionEvent {
+    fn slot(&self) -> TouchSlot {
+        touch_slot(self.id)
+    }
+}
+
+impl AbsolutePositionEvent<WinitInput> for WinitTouchMotionEvent {
+    fn x(&self) -> f64 {
+        self.global_position.x
+    }
+
+    fn y(&self) -> f64 {
+        self.global_position.y
+    }
+
+    fn x_transformed(&self, width: i32) -> f64 {
+        f64::max(self.position.x * width as f64, 0.0)
+    }
+
+    fn y_transformed(&self, height: i32) -> f64 {
+        f64::max(self.position.y * height as f64, 0.0)
+    }
+}
+
+/// Winit-Backend internal event wrapping `winit`'s types into a [`TouchUpEvent`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WinitTouchUpEvent {
+    pub time: u64,
+    pub id: u64,
+}
+
+impl Event<WinitInput> for WinitTouchUpEvent {
+    fn time(&self) -> u64 {
+        self.time
+    }
+
+    fn device(&self) -> WinitVirtualDevice {
+        WinitVirtualDevice
+    }
+}
+
+impl TouchUpEvent<WinitInput> for WinitTouchUpEvent {}
+
+impl TouchEvent<WinitInput> for WinitTouchUpEvent {
+    fn slot(&self) -> TouchSlot {
+        touch_slot(self.id)
+    }
+}
+
+/// Winit-Backend internal event wrapping `winit`'s types into a [`TouchCancelEvent`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WinitTouchCancelEvent {
+    pub time: u64,
+    pub id: u64,
+}
+
+impl Event<WinitInput> for WinitTouchCancelEvent {
+    fn time(&self) -> u64 {
+        self.time
+    }
+
+    fn device(&self) -> WinitVirtualDevice {
+        WinitVirtualDevice
+    }
+}
+
+impl TouchCancelEvent<WinitInput> for WinitTouchCancelEvent {}
+
+impl TouchEvent<WinitInput> for WinitTouchCancelEvent {
+    fn slot(&self) -> TouchSlot {
+        touch_slot(self.id)
+    }
+}
+
+/// Winit-Backend internal event grouping the touch events of one batch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WinitTouchFrameEvent {
+    pub time: u64,
+}
+
+impl Event<WinitInput> for WinitTouchFrameEvent {
+    fn time(&self) -> u64 {
+        self.time
+    }
+
+    fn device(&self) -> WinitVirtualDevice {
+        WinitVirtualDevice
+    }
+}
+
+impl TouchFrameEvent<WinitInput> for WinitTouchFrameEvent {}
+
+/// Converts a winit touch id into a slot.
+///
+/// The ids are unique among the ongoing touches, so they work as slots as is.
+fn touch_slot(id: u64) -> TouchSlot {
+    Some(id as u32).into()
+}
+
+/// Describes the tool of a tablet event.
+///
+/// AppKit reports pressure and tilt for every tool, and the rotation and the other axes only for
+/// a few special ones, so those stay out.
+fn tablet_tool_descriptor(point: &TabletPoint) -> TabletToolDescriptor {
+    let tool_type = match point.tool.kind {
+        TabletToolKind::Pen => TabletToolType::Pen,
+        TabletToolKind::Eraser => TabletToolType::Eraser,
+        TabletToolKind::Cursor => TabletToolType::Mouse,
+    };
+    TabletToolDescriptor {
+        tool_type,
+        hardware_serial: point.tool.serial,
+        hardware_id_wacom: point.tool.hardware_id,
+        capabilities: TabletToolCapabilities::PRESSURE | TabletToolCapabilities::TILT,
+    }
+}
+
+/// Implements [`TabletToolEvent`] for an event with a `point` field.
+macro_rules! impl_tablet_tool_event {
+    ($event:ty) => {
+        impl Event<WinitInput> for $event {
+            fn time(&self) -> u64 {
+                self.time
+            }
+
+            fn device(&self) -> WinitVirtualDevice {
+                WinitVirtualDevice
+            }
+        }
+
+        impl TabletToolEvent<WinitInput> for $event {
+            fn tool(&self) -> TabletToolDescriptor {
+                tablet_tool_descriptor(&self.point)
+            }
+
+            // niri only looks at the position.
+            fn delta_x(&self) -> f64 {
+                0.
+            }
+
+            fn delta_y(&self) -> f64 {
+                0.
+            }
+
+            fn tilt_x(&self) -> f64 {
+                self.point.tilt.0
+            }
+
+            fn tilt_y(&self) -> f64 {
+                self.point.tilt.1
+            }
+
+            fn tilt_x_has_changed(&self) -> bool {
+                true
+            }
+
+            fn tilt_y_has_changed(&self) -> bool {
+                true
+            }
+
+            fn pressure(&self) -> f64 {
+                self.point.pressure
+            }
+
+            fn pressure_has_changed(&self) -> bool {
+                true
+            }
+
+            fn distance(&self) -> f64 {
+                0.
+            }
+
+            fn distance_has_changed(&self) -> bool {
+                false
+            }
+
+            fn slider_position(&self) -> f64 {
+                0.
+            }
+
+            fn slider_has_changed(&self) -> bool {
+                false
+            }
+
+            fn rotation(&self) -> f64 {
+                0.
+            }
+
+            fn rotation_has_changed(&self) -> bool {
+                false
+            }
+
+            fn wheel_delta(&self) -> f64 {
+                0.
+            }
+
+            fn wheel_delta_discrete(&self) -> i32 {
+                0
+            }
+
+            fn wheel_has_changed(&self) -> bool {
+                false
+            }
+        }
+    };
+}
+
+/// Implements [`AbsolutePositionEvent`] for an event with `position` and `global_position`
+/// fields.
+macro_rules! impl_tablet_position {
+    ($event:ty) => {
+        impl AbsolutePositionEvent<WinitInput> for $event {
+            fn x(&self) -> f64 {
+                self.global_position.x
+            }
+
+            fn y(&self) -> f64 {
+                self.global_position.y
+            }
+
+            fn x_transformed(&self, width: i32) -> f64 {
+                f64::max(self.position.x * width as f64, 0.0)
+            }
+
+            fn y_transformed(&self, height: i32) -> f64 {
+                f64::max(self.position.y * height as f64, 0.0)
+            }
+        }
+    };
+}
+
+/// Winit-Backend internal event moving a tablet tool, built from a tablet point `NSEvent`
+#[derive(Debug, Clone)]
+pub struct WinitTabletToolAxisEvent {
+    pub time: u64,
+    pub position: RelativePosition,
+    pub global_position: PhysicalPosition<f64>,
+    pub point: TabletPoint,
+}
+
+impl_tablet_tool_event!(WinitTabletToolAxisEvent);
+impl_tablet_position!(WinitTabletToolAxisEvent);
+
+impl TabletToolAxisEvent<WinitInput> for WinitTabletToolAxisEvent {}
+
+/// Winit-Backend internal event bringing a tablet tool in or out of proximity
+#[derive(Debug, Clone)]
+pub struct WinitTabletToolProximityEvent {
+    pub time: u64,
+    pub position: RelativePosition,
+    pub global_position: PhysicalPosition<f64>,
+    pub point: TabletPoint,
+    pub state: ProximityState,
+}
+
+impl_tablet_tool_event!(WinitTabletToolProximityEvent);
+impl_tablet_position!(WinitTabletToolProximityEvent);
+
+impl TabletToolProximityEvent<WinitInput> for WinitTabletToolProximityEvent {
+    fn state(&self) -> ProximityState {
+        self.state
+    }
+}
+
+/// Winit-Backend internal event for the tip of a tablet tool touching or leaving the tablet
+#[derive(Debug, Clone)]
+pub struct WinitTabletToolTipEvent {
+    pub time: u64,
+    pub position: RelativePosition,
+    pub global_position: PhysicalPosition<f64>,
+    pub point: TabletPoint,
+    pub state: ElementState,
+}
+
+impl_tablet_tool_event!(WinitTabletToolTipEvent);
+impl_tablet_position!(WinitTabletToolTipEvent);
+
+impl TabletToolTipEvent<WinitInput> for WinitTabletToolTipEvent {
+    fn tip_state(&self) -> TabletToolTipState {
+        match self.state {
+            ElementState::Pressed => TabletToolTipState::Down,
+            ElementState::Released => TabletToolTipState::Up,
+        }
+    }
+}
+
+/// Winit-Backend internal event for a button on a tablet tool
+#[derive(Debug, Clone)]
+pub struct WinitTabletToolButtonEvent {
+    pub time: u64,
+    pub point: TabletPoint,
+    /// The mouse button that AppKit sent the tool button as.
+    pub button: WinitMouseButton,
+    pub state: ElementState,
+}
+
+impl_tablet_tool_event!(WinitTabletToolButtonEvent);
+
+impl TabletToolButtonEvent<WinitInput> for WinitTabletToolButtonEvent {
+    fn button(&self) -> u32 {
+        tablet_tool_button_code(self.button)
+    }
+
+    // There is only the one tool.
+    fn seat_button_count(&self) -> u32 {
+        match self.state {
+            ElementState::Pressed => 1,
+            ElementState::Released => 0,
+        }
+    }
+
+    fn button_state(&self) -> ButtonState {
+        match self.state {
+            ElementState::Pressed => ButtonState::Pressed,
+            ElementState::Released => ButtonState::Released,
+        }
+    }
+}
+
+const BTN_STYLUS: u32 = 0x14b;
+const BTN_STYLUS2: u32 = 0x14c;
+const BTN_STYLUS3: u32 = 0x149;
+
+/// Converts the mouse button that AppKit sends a tablet tool button as into an evdev code.
+///
+/// The lower button on the barrel of a pen clicks right, the upper one clicks middle.
+fn tablet_tool_button_code(button: WinitMouseButton) -> u32 {
+    match button {
+        WinitMouseButton::Right => BTN_STYLUS,
+        WinitMouseButton::Middle => BTN_STYLUS2,
+        WinitMouseButton::Other(_) | WinitMouseButton::Back | WinitMouseButton::Forward => {
+            BTN_STYLUS3
+        }
+        // The tip, which goes out as tip events instead.
+        WinitMouseButton::Left => BTN_LEFT,
+    }
+}
 
 /// Position relative to the source window
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -222,28 +960,190 @@ impl InputBackend for WinitInput {
     type KeyboardKeyEvent = WinitKeyboardInputEvent;
     type PointerAxisEvent = WinitMouseWheelEvent;
     type PointerButtonEvent = WinitMouseInputEvent;
-    type PointerMotionEvent = UnusedEvent;
+    type PointerMotionEvent = WinitMouseRelativeEvent;
     type PointerMotionAbsoluteEvent = WinitMouseMovedEvent;
 
-    type GestureSwipeBeginEvent = UnusedEvent;
-    type GestureSwipeUpdateEvent = UnusedEvent;
-    type GestureSwipeEndEvent = UnusedEvent;
-    type GesturePinchBeginEvent = UnusedEvent;
-    type GesturePinchUpdateEvent = UnusedEvent;
-    type GesturePinchEndEvent = UnusedEvent;
+    type GestureSwipeBeginEvent = WinitGestureSwipeBeginEvent;
+    type GestureSwipeUpdateEvent = WinitGestureSwipeUpdateEvent;
+    type GestureSwipeEndEvent = WinitGestureSwipeEndEvent;
+    type GesturePinchBeginEvent = WinitGesturePinchBeginEvent;
+    type GesturePinchUpdateEvent = WinitGesturePinchUpdateEvent;
+    type GesturePinchEndEvent = WinitGesturePinchEndEvent;
     type GestureHoldBeginEvent = UnusedEvent;
     type GestureHoldEndEvent = UnusedEvent;
 
-    type TouchDownEvent = UnusedEvent;
-    type TouchUpEvent = UnusedEvent;
-    type TouchMotionEvent = UnusedEvent;
-    type TouchCancelEvent = UnusedEvent;
-    type TouchFrameEvent = UnusedEvent;
-    type TabletToolAxisEvent = UnusedEvent;
-    type TabletToolProximityEvent = UnusedEvent;
-    type TabletToolTipEvent = UnusedEvent;
-    type TabletToolButtonEvent = UnusedEvent;
+    type TouchDownEvent = WinitTouchDownEvent;
+    type TouchUpEvent = WinitTouchUpEvent;
+    type TouchMotionEvent = WinitTouchMotionEvent;
+    type TouchCancelEvent = WinitTouchCancelEvent;
+    type TouchFrameEvent = WinitTouchFrameEvent;
+    type TabletToolAxisEvent = WinitTabletToolAxisEvent;
+    type TabletToolProximityEvent = WinitTabletToolProximityEvent;
+    type TabletToolTipEvent = WinitTabletToolTipEvent;
+    type TabletToolButtonEvent = WinitTabletToolButtonEvent;
 
     type SwitchToggleEvent = UnusedEvent;
     type SpecialEvent = UnusedEvent;
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::backend::appkit::TabletTool;
+
+    fn wheel_event(delta: MouseScrollDelta) -> WinitMouseWheelEvent {
+        WinitMouseWheelEvent {
+            time: 0,
+            delta,
+            phase: TouchPhase::Moved,
+            scroll_phase: ScrollPhase::None,
+            pixel_v120: (0., 0.),
+            inverted: false,
+            axis_stop: false,
+        }
+    }
+
+    #[test]
+    fn scroll_amount_signs() {
+        // The content moves left and up, revealing more on the right and below.
+        let event = wheel_event(MouseScrollDelta::PixelDelta(PhysicalPosition::new(-10., -20.)));
+        assert_eq!(event.amount(Axis::Horizontal), Some(10.));
+        assert_eq!(event.amount(Axis::Vertical), Some(20.));
+
+        // The content moves right and down.
+        let event = wheel_event(MouseScrollDelta::PixelDelta(PhysicalPosition::new(10., 20.)));
+        assert_eq!(event.amount(Axis::Horizontal), Some(-10.));
+        assert_eq!(event.amount(Axis::Vertical), Some(-20.));
+
+        // A wheel notch down and a tilt to the right.
+        let event = wheel_event(MouseScrollDelta::LineDelta(-1., -1.));
+        assert_eq!(event.amount(Axis::Horizontal), Some(15.));
+        assert_eq!(event.amount(Axis::Vertical), Some(15.));
+        assert_eq!(event.amount_v120(Axis::Horizontal), Some(120.));
+        assert_eq!(event.amount_v120(Axis::Vertical), Some(120.));
+
+        // A tilt to the left, like Shift with the wheel up.
+        let event = wheel_event(MouseScrollDelta::LineDelta(1., 0.));
+        assert_eq!(event.amount(Axis::Horizontal), Some(-15.));
+        assert_eq!(event.amount_v120(Axis::Horizontal), Some(-120.));
+        assert_eq!(event.amount_v120(Axis::Vertical), None);
+    }
+
+    #[test]
+    fn only_axis_stops_have_zero_finger_amounts() {
+        // A vertical trackpad scroll doesn't move horizontally.
+        let delta = MouseScrollDelta::PixelDelta(PhysicalPosition::new(0., -20.));
+        let mut event = wheel_event(delta);
+        event.scroll_phase = ScrollPhase::Touching;
+        assert_eq!(event.source(), AxisSource::Finger);
+        assert_eq!(event.amount(Axis::Horizontal), None);
+        assert_eq!(event.amount(Axis::Vertical), Some(20.));
+
+        // The fingers lifting doesn't stop the scroll yet, momentum may follow.
+        let mut event = wheel_event(MouseScrollDelta::PixelDelta(PhysicalPosition::new(0., 0.)));
+        event.scroll_phase = ScrollPhase::Touching;
+        event.phase = TouchPhase::Ended;
+        assert_eq!(event.amount(Axis::Vertical), None);
+
+        event.axis_stop = true;
+        assert_eq!(event.amount(Axis::Horizontal), Some(0.));
+        assert_eq!(event.amount(Axis::Vertical), Some(0.));
+
+        // Momentum isn't fingers, so it never stops the scroll on its own.
+        let mut event = wheel_event(MouseScrollDelta::PixelDelta(PhysicalPosition::new(0., 0.)));
+        event.scroll_phase = ScrollPhase::Momentum;
+        event.phase = TouchPhase::Ended;
+        assert_eq!(event.source(), AxisSource::Continuous);
+        assert_eq!(event.amount(Axis::Vertical), Some(0.));
+    }
+
+    #[test]
+    fn secondary_click_is_btn_right() {
+        // macOS sends two-finger taps and clicks as right clicks when secondary click is on.
+        let event = WinitMouseInputEvent {
+            time: 0,
+            button: WinitMouseButton::Right,
+            state: ElementState::Pressed,
+            is_x11: false,
+        };
+        assert_eq!(event.button_code(), BTN_RIGHT);
+        assert_eq!(event.state(), ButtonState::Pressed);
+    }
+
+    #[test]
+    fn clickfinger_buttons() {
+        assert_eq!(clickfinger_button(0), WinitMouseButton::Left);
+        assert_eq!(clickfinger_button(1), WinitMouseButton::Left);
+        assert_eq!(clickfinger_button(2), WinitMouseButton::Right);
+        assert_eq!(clickfinger_button(3), WinitMouseButton::Middle);
+        assert_eq!(clickfinger_button(4), WinitMouseButton::Left);
+    }
+
+    #[test]
+    fn winit_mouse_button_round_trips() {
+        for code in BTN_LEFT..BTN_LEFT + 32 {
+            let button = winit_mouse_button(code).unwrap();
+            let event = WinitMouseInputEvent {
+                time: 0,
+                button,
+                state: ElementState::Pressed,
+                is_x11: false,
+            };
+            assert_eq!(event.button_code(), code, "{button:?}");
+        }
+
+        // Keyboard keys aren't buttons.
+        assert_eq!(winit_mouse_button(30), None);
+    }
+
+    #[test]
+    fn tablet_tool_events() {
+        let point = TabletPoint {
+            tool: TabletTool {
+                kind: TabletToolKind::Eraser,
+                serial: 42,
+                hardware_id: 7,
+            },
+            pressure: 0.5,
+            tilt: (10., -20.),
+        };
+        let event = WinitTabletToolTipEvent {
+            time: 0,
+            position: RelativePosition::new(0.25, 0.5),
+            global_position: PhysicalPosition::new(100., 200.),
+            point,
+            state: ElementState::Pressed,
+        };
+        assert_eq!(event.tip_state(), TabletToolTipState::Down);
+        assert_eq!(event.tool().tool_type, TabletToolType::Eraser);
+        assert_eq!(event.tool().hardware_serial, 42);
+        assert_eq!(event.pressure(), 0.5);
+        assert_eq!(event.tilt(), (10., -20.));
+        assert_eq!(event.x_transformed(800), 200.);
+
+        let button = |button| WinitTabletToolButtonEvent {
+            time: 0,
+            point,
+            button,
+            state: ElementState::Released,
+        };
+        assert_eq!(button(WinitMouseButton::Right).button(), BTN_STYLUS);
+        assert_eq!(button(WinitMouseButton::Middle).button(), BTN_STYLUS2);
+        assert_eq!(button(WinitMouseButton::Other(5)).button(), BTN_STYLUS3);
+        assert_eq!(button(WinitMouseButton::Right).seat_button_count(), 0);
+    }
+
+    #[test]
+    fn other_buttons_dont_collide() {
+        let named = [BTN_LEFT, BTN_RIGHT, BTN_MIDDLE, BTN_FORWARD, BTN_BACK];
+
+        let mut seen = HashSet::new();
+        for button_number in 5..32 {
+            let code = evdev_button_code(button_number);
+            assert!(!named.contains(&code), "button {button_number} maps to a named button");
+            assert!(seen.insert(code), "button {button_number} maps to a taken code");
+        }
+    }
+}
@@ -3,6 +3,8 @@ use std::path::PathBuf;
 
 use clap::{Parser, Subcommand};
 use clap_complete::Shell;
+#[cfg(feature = "inject-input")]
+use niri_ipc::InjectedInput;
 use niri_ipc::{Action, OutputAction};
 
 use crate::utils::version;
@@ -26,6 +28,12 @@ pub struct Cli {
     /// on a TTY as your non-main compositor instance, to avoid messing up the global environment.
     #[arg(long)]
     pub session: bool,
+    /// Render frames headlessly as fast as possible, print their timings as JSON and exit.
+    ///
+    /// The frames show the first output with the hotkey overlay open. Useful for tracking
+    /// rendering performance.
+    #[arg(long, value_name = "FRAMES", num_args = 0..=1, default_missing_value = "1000")]
+    pub bench: Option<usize>,
     /// Command to run upon compositor startup.
     #[arg(last = true)]
     pub command: Vec<OsString>,
@@ -107,6 +115,13 @@ pub enum Msg {
     RequestError,
     /// Print the overview state.
     OverviewState,
+    /// Inject a synthetic input event, for scripted testing.
+    #[cfg(feature = "inject-input")]
+    InjectInput {
+        /// Input event to inject.
+        #[command(subcommand)]
+        input: InjectedInput,
+    },
 }
 
 #[derive(Clone, Debug, clap::ValueEnum)]
@@ -132,7 +132,10 @@ impl CursorManager {
                     cursor = Ok(Self::fallback_cursor());
                 }
 
-                cursor.ok().map(Rc::new)
+                cursor.ok().map(|mut cursor| {
+                    cursor.scale = image_scale(&cursor.images[0], self.size);
+                    Rc::new(cursor)
+                })
             })
             .clone()
     }
@@ -183,6 +186,7 @@ impl CursorManager {
         Ok(XCursor {
             images,
             animation_duration,
+            scale: 1,
         })
     }
 
@@ -207,6 +211,7 @@ impl CursorManager {
         XCursor {
             images,
             animation_duration: 0,
+            scale: 1,
         }
     }
 }
@@ -256,7 +261,7 @@ impl CursorTextureCache {
                             &frame.pixels_rgba,
                             Fourcc::Argb8888,
                             (frame.width as i32, frame.height as i32),
-                            scale,
+                            cursor.scale(),
                             Transform::Normal,
                             None,
                         )
@@ -275,6 +280,8 @@ pub struct XCursor {
     images: Vec<Image>,
     /// The total duration of the animation.
     animation_duration: u32,
+    /// Buffer scale of the images, see [`image_scale`].
+    scale: i32,
 }
 
 impl XCursor {
@@ -306,6 +313,11 @@ impl XCursor {
         &self.images
     }
 
+    /// Get the buffer scale that the images render at.
+    pub fn scale(&self) -> i32 {
+        self.scale
+    }
+
     /// Check whether the cursor is animated.
     pub fn is_animated_cursor(&self) -> bool {
         self.images.len() > 1
@@ -316,3 +328,14 @@ impl XCursor {
         (image.xhot as i32, image.yhot as i32).into()
     }
 }
+
+/// Returns the buffer scale that shows `image` at about `size` logical pixels.
+///
+/// Themes don't come in every size, and the fallback cursor comes in just one, so the closest
+/// image can be bigger or smaller than the output scale asks for. Going by the image keeps the
+/// cursor the same logical size on outputs with different scales, rather than tiny on one and
+/// huge on another.
+fn image_scale(image: &Image, size: u8) -> i32 {
+    let scale = f64::from(image.width) / f64::from(size.max(1));
+    (scale.round() as i32).max(1)
+}
@@ -19,6 +19,7 @@ use zbus::object_server::SignalEmitter;
 use zbus::zvariant::NoneValue;
 
 use super::Start;
+use crate::input::key_repeat_info;
 use crate::niri::State;
 
 #[derive(Debug, Default)]
@@ -472,7 +473,8 @@ impl State {
         });
 
         let config = self.niri.config.borrow();
-        let repeat_delay = Duration::from_millis(u64::from(config.input.keyboard.repeat_delay));
+        let (repeat_delay, _) = key_repeat_info(&config.input.keyboard);
+        let repeat_delay = Duration::from_millis(u64::from(repeat_delay));
         let released = state == KeyState::Released;
 
         let Some(monitor) = &self.niri.a11y_keyboard_monitor else {
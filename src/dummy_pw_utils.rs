@@ -1,13 +1,30 @@
-use anyhow::bail;
-use smithay::reexports::calloop::LoopHandle;
+//! Screencast stand-in for builds without PipeWire.
+//!
+//! There are no PipeWire streams here. Instead, a cast can capture its target into PNG files,
+//! which gives a way to record an output or a window without the xdg-desktop-portal stack.
 
-use crate::niri::State;
-
-use smithay::reexports::calloop::RegistrationToken;
-use smithay::utils::{Size, Physical};
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::thread;
 use std::time::Duration;
 
-use crate::niri::CastTarget;
+use anyhow::{bail, Context as _};
+use smithay::backend::allocator::Fourcc;
+use smithay::backend::renderer::damage::OutputDamageTracker;
+use smithay::backend::renderer::element::RenderElement;
+use smithay::backend::renderer::gles::GlesRenderer;
+use smithay::output::{Output, OutputModeSource};
+use smithay::reexports::calloop::{LoopHandle, RegistrationToken};
+use smithay::utils::{Physical, Scale, Size, Transform};
+
+use crate::niri::{CastTarget, State};
+use crate::render_helpers::render_to_vec;
+use crate::utils::write_png_rgba8;
+
+/// How many frames can wait for encoding before new frames are dropped.
+const ENCODE_QUEUE_LEN: usize = 2;
 
 #[derive(Debug)]
 pub struct DummyStream;
@@ -25,38 +42,219 @@ pub struct Cast {
     pub dynamic_target: bool,
     pub last_frame_time: Duration,
     pub stream: DummyStream,
+    capture: Option<FileCapture>,
+}
+
+/// Writes the frames of a cast into PNG files.
+struct FileCapture {
+    destination: CaptureDestination,
+    frame: u64,
+    min_time_between_frames: Duration,
+    size: Size<i32, Physical>,
+    damage_tracker: Option<OutputDamageTracker>,
+    to_encoder: SyncSender<EncodeJob>,
+}
+
+enum CaptureDestination {
+    /// Every frame replaces this file.
+    File(PathBuf),
+    /// Every frame goes into a new numbered file in this directory.
+    Directory(PathBuf),
+}
+
+struct EncodeJob {
+    path: PathBuf,
+    size: Size<i32, Physical>,
+    pixels: Vec<u8>,
 }
 
 impl Cast {
+    /// Creates a cast that captures its target into PNG files at `path`.
+    ///
+    /// A path ending in `.png` is a single file replaced with every frame, anything else is a
+    /// directory that receives numbered frames.
+    pub fn new_capture(
+        session_id: usize,
+        stream_id: usize,
+        target: CastTarget,
+        dynamic_target: bool,
+        path: &Path,
+        refresh: u32,
+    ) -> anyhow::Result<Self> {
+        let destination = if path.extension().is_some_and(|ext| ext == "png") {
+            if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+                fs::create_dir_all(parent).context("error creating the capture directory")?;
+            }
+            CaptureDestination::File(path.to_owned())
+        } else {
+            fs::create_dir_all(path).context("error creating the capture directory")?;
+            CaptureDestination::Directory(path.to_owned())
+        };
+
+        // Encoding is slow, so it happens on a thread.
+        let (to_encoder, from_main) = mpsc::sync_channel::<EncodeJob>(ENCODE_QUEUE_LEN);
+        thread::Builder::new()
+            .name("Cast Encoder".to_owned())
+            .spawn(move || {
+                for job in from_main {
+                    if let Err(err) = job.write() {
+                        warn!("error writing cast frame: {err:?}");
+                    }
+                }
+            })
+            .context("error spawning the encoder thread")?;
+
+        let mut capture = FileCapture {
+            destination,
+            frame: 0,
+            min_time_between_frames: Duration::ZERO,
+            size: Size::from((0, 0)),
+            damage_tracker: None,
+            to_encoder,
+        };
+        capture.set_refresh(refresh);
+
+        Ok(Self {
+            session_id,
+            stream_id,
+            target,
+            dynamic_target,
+            last_frame_time: Duration::ZERO,
+            stream: DummyStream,
+            capture: Some(capture),
+        })
+    }
+
     pub fn is_active(&self) -> bool {
-        false
+        self.capture.is_some()
     }
-    pub fn ensure_size(&self, _size: Size<i32, Physical>) -> anyhow::Result<CastSizeChange> {
+
+    pub fn ensure_size(&mut self, size: Size<i32, Physical>) -> anyhow::Result<CastSizeChange> {
+        if let Some(capture) = &mut self.capture {
+            if capture.size != size {
+                capture.size = size;
+                capture.damage_tracker = None;
+            }
+        }
         Ok(CastSizeChange::Ready)
     }
-    pub fn set_refresh(&mut self, _refresh: u32) -> anyhow::Result<()> {
+
+    pub fn set_refresh(&mut self, refresh: u32) -> anyhow::Result<()> {
+        if let Some(capture) = &mut self.capture {
+            capture.set_refresh(refresh);
+        }
         Ok(())
     }
-    pub fn check_time_and_schedule(&mut self, _output: &smithay::output::Output, _time: Duration) -> bool {
-        false
+
+    /// Returns `true` if the frame should be skipped to stay within the refresh rate.
+    pub fn check_time_and_schedule(&mut self, _output: &Output, time: Duration) -> bool {
+        let Some(capture) = &self.capture else {
+            return true;
+        };
+
+        // The first frame always goes through.
+        self.last_frame_time != Duration::ZERO
+            && time.saturating_sub(self.last_frame_time) < capture.min_time_between_frames
     }
-    pub fn dequeue_buffer_and_clear(&mut self, _renderer: &mut smithay::backend::renderer::gles::GlesRenderer) -> bool {
+
+    pub fn dequeue_buffer_and_clear(&mut self, _renderer: &mut GlesRenderer) -> bool {
+        // Without a target there's nothing worth writing out.
         false
     }
-    // Simplified signature to avoid strict type matching hell, assuming it's used in method calls
-    // But niri.rs calls it with arguments.
-    // If I use generic arguments where possible?
-    // GlesRenderer is specific. I need to import it.
-    pub fn dequeue_buffer_and_render<R>(
+
+    pub fn dequeue_buffer_and_render(
         &mut self,
-        _renderer: &mut R,
-        _elements: &[impl smithay::backend::renderer::element::RenderElement<R>],
-        _size: Size<i32, Physical>,
-        _scale: smithay::utils::Scale<f64>,
-    ) -> bool 
-    where R: smithay::backend::renderer::Renderer
-    {
-        false
+        renderer: &mut GlesRenderer,
+        elements: &[impl RenderElement<GlesRenderer>],
+        size: Size<i32, Physical>,
+        scale: Scale<f64>,
+    ) -> bool {
+        let Some(capture) = &mut self.capture else {
+            return false;
+        };
+
+        let damage_tracker = capture
+            .damage_tracker
+            .get_or_insert_with(|| OutputDamageTracker::new(size, scale, Transform::Normal));
+
+        // Size change will drop the damage tracker, but scale change won't, so check it here.
+        let OutputModeSource::Static { scale: t_scale, .. } = damage_tracker.mode() else {
+            unreachable!();
+        };
+        if *t_scale != scale {
+            *damage_tracker = OutputDamageTracker::new(size, scale, Transform::Normal);
+        }
+
+        let (damage, _states) = damage_tracker.damage_output(1, elements).unwrap();
+        if damage.is_none() {
+            trace!("no damage, skipping frame");
+            return false;
+        }
+
+        let pixels = match render_to_vec(
+            renderer,
+            size,
+            scale,
+            Transform::Normal,
+            Fourcc::Abgr8888,
+            elements.iter().rev(),
+        ) {
+            Ok(pixels) => pixels,
+            Err(err) => {
+                warn!("error rendering cast frame: {err:?}");
+                return false;
+            }
+        };
+
+        capture.send_frame(size, pixels)
+    }
+}
+
+impl FileCapture {
+    fn set_refresh(&mut self, refresh: u32) {
+        // Refresh is in mHz.
+        self.min_time_between_frames = if refresh == 0 {
+            Duration::ZERO
+        } else {
+            Duration::from_nanos(1_000_000_000_000 / u64::from(refresh))
+        };
+    }
+
+    fn send_frame(&mut self, size: Size<i32, Physical>, pixels: Vec<u8>) -> bool {
+        let path = match &self.destination {
+            CaptureDestination::File(path) => path.clone(),
+            CaptureDestination::Directory(dir) => dir.join(format!("{:06}.png", self.frame)),
+        };
+
+        match self.to_encoder.try_send(EncodeJob { path, size, pixels }) {
+            Ok(()) => {
+                self.frame += 1;
+                true
+            }
+            Err(TrySendError::Full(_)) => {
+                trace!("encoder is busy, dropping frame");
+                false
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                warn!("cast encoder thread is gone, dropping frame");
+                false
+            }
+        }
+    }
+}
+
+impl EncodeJob {
+    fn write(self) -> anyhow::Result<()> {
+        // Write next to the destination and rename, so that readers never see a partial frame.
+        let tmp = self.path.with_extension("png.tmp");
+
+        let file = File::create(&tmp).context("error creating file")?;
+        let w = BufWriter::new(file);
+        write_png_rgba8(w, self.size.w as u32, self.size.h as u32, &self.pixels)
+            .context("error encoding image")?;
+
+        fs::rename(&tmp, &self.path).context("error moving the frame into place")?;
+        Ok(())
     }
 }
 
@@ -65,9 +263,32 @@ pub struct PipeWire {
 }
 
 impl PipeWire {
-    pub fn new(_event_loop: &LoopHandle<'static, State>, _to_niri: calloop::channel::Sender<PwToNiri>) -> anyhow::Result<Self> {
+    pub fn new(
+        _event_loop: LoopHandle<'static, State>,
+        _to_niri: calloop::channel::Sender<PwToNiri>,
+    ) -> anyhow::Result<Self> {
         bail!("PipeWire support is disabled (see \"xdp-gnome-screencast\" feature)");
     }
+
+    /// Stands in for the PipeWire stream setup, which isn't available on this platform.
+    #[cfg(feature = "xdp-gnome-screencast")]
+    #[allow(clippy::too_many_arguments)]
+    pub fn start_cast(
+        &self,
+        _allocator: crate::backend::CastAllocator,
+        _formats: smithay::backend::allocator::format::FormatSet,
+        _session_id: usize,
+        _stream_id: usize,
+        _target: CastTarget,
+        _dynamic_target: bool,
+        _size: Size<i32, Physical>,
+        _refresh: u32,
+        _alpha: bool,
+        _cursor_mode: crate::dbus::mutter_screen_cast::CursorMode,
+        _signal_ctx: zbus::object_server::SignalEmitter<'static>,
+    ) -> anyhow::Result<Cast> {
+        bail!("PipeWire streams are not available on this platform");
+    }
 }
 
 #[derive(Debug)]
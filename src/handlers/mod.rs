@@ -57,7 +57,7 @@ use smithay::wayland::selection::primary_selection::{
 use smithay::wayland::selection::wlr_data_control::{
     DataControlHandler as WlrDataControlHandler, DataControlState as WlrDataControlState,
 };
-use smithay::wayland::selection::{SelectionHandler, SelectionTarget};
+use smithay::wayland::selection::{SelectionHandler, SelectionSource, SelectionTarget};
 use smithay::wayland::session_lock::{
     LockSurface, SessionLockHandler, SessionLockManagerState, SessionLocker,
 };
@@ -80,6 +80,9 @@ use smithay::{
 use smithay::delegate_drm_lease;
 
 pub use crate::handlers::xdg_shell::KdeDecorationsModeState;
+#[cfg(target_os = "macos")]
+use crate::backend::pasteboard;
+use crate::backend::Backend;
 use crate::layout::workspace::WorkspaceId;
 use crate::layout::ActivateWindow;
 use crate::niri::{DndIcon, NewClient, State};
@@ -143,6 +146,10 @@ impl SeatHandler for State {
         for mut keyboard in keyboards {
             keyboard.led_update(led_state.into());
         }
+
+        // macOS doesn't hand out keyboard devices, reach them through the HID manager instead.
+        #[cfg(target_os = "macos")]
+        input::Device::POINTER.led_update(led_state);
     }
 }
 delegate_seat!(State);
@@ -289,20 +296,55 @@ delegate_input_method_manager!(State);
 delegate_keyboard_shortcuts_inhibit!(State);
 delegate_virtual_keyboard_manager!(State);
 
+/// Contents of a selection that the compositor offers.
+#[derive(Debug, Clone)]
+pub enum SelectionData {
+    /// Contents at hand, like a screenshot.
+    Bytes(Arc<[u8]>),
+    /// The macOS pasteboard, read once a client asks for it.
+    #[cfg(target_os = "macos")]
+    Pasteboard,
+}
+
 impl SelectionHandler for State {
-    type SelectionUserData = Arc<[u8]>;
+    type SelectionUserData = SelectionData;
+
+    fn new_selection(
+        &mut self,
+        ty: SelectionTarget,
+        source: Option<SelectionSource>,
+        seat: Seat<Self>,
+    ) {
+        // Share the clipboard with macOS apps. They have no primary selection.
+        if let (SelectionTarget::Clipboard, Some(source), Backend::Winit(winit)) =
+            (ty, source, &mut self.backend)
+        {
+            winit.export_selection(&seat, &self.niri.display_handle, source.mime_types());
+        }
+    }
 
     fn send_selection(
         &mut self,
         _ty: SelectionTarget,
-        _mime_type: String,
+        mime_type: String,
         fd: OwnedFd,
         _seat: Seat<Self>,
         user_data: &Self::SelectionUserData,
     ) {
         let _span = tracy_client::span!("send_selection");
 
-        let buf = user_data.clone();
+        let buf = match user_data {
+            SelectionData::Bytes(buf) => buf.clone(),
+            // The pasteboard is read here on the main thread, only the writing goes on a thread.
+            #[cfg(target_os = "macos")]
+            SelectionData::Pasteboard => match pasteboard::read(&mime_type) {
+                Some(contents) => Arc::from(contents),
+                None => {
+                    warn!("error reading the pasteboard as {mime_type}");
+                    return;
+                }
+            },
+        };
         thread::spawn(move || {
             // Clear O_NONBLOCK, otherwise File::write_all() will stop halfway.
             if let Err(err) = fcntl_setfl(&fd, OFlags::empty()) {
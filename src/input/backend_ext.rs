@@ -24,6 +24,12 @@ pub trait NiriInputDevice: input::Device {
     // but it's not clear that this matters in practice?
     // it might be more obvious once we implement it for libinput
     fn output(&self, state: &State) -> Option<Output>;
+
+    /// Returns whether finger scrolling from the device already comes scaled by the touchpad
+    /// `scroll-factor`.
+    fn applies_touchpad_scroll_factor(&self) -> bool {
+        false
+    }
 }
 
 impl NiriInputDevice for libinput::Device {
@@ -0,0 +1,96 @@
+//! Synthetic input for scripted testing.
+//!
+//! The events go through [`State::process_input_event`] like the ones from the winit backend, so
+//! bindings, grabs and focus changes behave the same as with a real keyboard and mouse.
+
+use smithay::backend::input::InputEvent;
+use winit::dpi::PhysicalPosition;
+use winit::event::ElementState;
+
+use crate::backend::winit_input::{
+    winit_mouse_button, RelativePosition, WinitInput, WinitKeyboardInputEvent,
+    WinitMouseInputEvent, WinitMouseMovedEvent,
+};
+use crate::niri::State;
+use crate::utils::get_monotonic_time;
+
+impl State {
+    /// Presses or releases the key with this evdev code, without the +8 xkb offset.
+    pub fn inject_key(&mut self, evdev: u32, pressed: bool) {
+        let event = InputEvent::<WinitInput>::Keyboard {
+            event: WinitKeyboardInputEvent {
+                time: injected_time(),
+                key: evdev,
+                count: 1,
+                state: element_state(pressed),
+            },
+        };
+        self.process_input_event(event);
+    }
+
+    /// Moves the pointer to this position in the global logical coordinate space.
+    ///
+    /// Does nothing without outputs. Positions outside of them end up at the nearest edge.
+    pub fn inject_pointer_motion_abs(&mut self, x: f64, y: f64) {
+        let Some(bbox) = self.global_bounding_rectangle() else {
+            return;
+        };
+
+        let loc = bbox.loc.to_f64();
+        let size = bbox.size.to_f64();
+        let position = RelativePosition::new((x - loc.x) / size.w, (y - loc.y) / size.h);
+
+        let event = InputEvent::<WinitInput>::PointerMotionAbsolute {
+            event: WinitMouseMovedEvent {
+                time: injected_time(),
+                position,
+                global_position: PhysicalPosition::new(x, y),
+            },
+        };
+        self.process_input_event(event);
+    }
+
+    /// Presses or releases the mouse button with this evdev code, like `BTN_LEFT` (0x110).
+    ///
+    /// Codes below `BTN_LEFT` aren't mouse buttons and are ignored.
+    pub fn inject_button(&mut self, code: u32, pressed: bool) {
+        let Some(button) = winit_mouse_button(code) else {
+            warn!("ignoring injected button with non-button code {code:#x}");
+            return;
+        };
+
+        let event = InputEvent::<WinitInput>::PointerButton {
+            event: WinitMouseInputEvent {
+                time: injected_time(),
+                button,
+                state: element_state(pressed),
+                is_x11: false,
+            },
+        };
+        self.process_input_event(event);
+    }
+
+    /// Injects an input event received over IPC.
+    #[cfg(feature = "inject-input")]
+    pub fn inject_input(&mut self, input: niri_ipc::InjectedInput) {
+        use niri_ipc::InjectedInput;
+
+        match input {
+            InjectedInput::Key { code, pressed } => self.inject_key(code, pressed),
+            InjectedInput::PointerMotion { x, y } => self.inject_pointer_motion_abs(x, y),
+            InjectedInput::Button { code, pressed } => self.inject_button(code, pressed),
+        }
+    }
+}
+
+fn injected_time() -> u64 {
+    get_monotonic_time().as_micros() as u64
+}
+
+fn element_state(pressed: bool) -> ElementState {
+    if pressed {
+        ElementState::Pressed
+    } else {
+        ElementState::Released
+    }
+}
@@ -55,15 +55,17 @@ use self::resize_grab::ResizeGrab;
 use self::spatial_movement_grab::SpatialMovementGrab;
 #[cfg(feature = "dbus")]
 use crate::dbus::freedesktop_a11y::KbMonBlock;
+use crate::backend::Backend;
 use crate::layout::scrolling::ScrollDirection;
 use crate::layout::{ActivateWindow, LayoutElement as _};
 use crate::niri::{CastTarget, PointerVisibility, State};
 use crate::ui::mru::{WindowMru, WindowMruUi};
 use crate::ui::screenshot_ui::ScreenshotUi;
 use crate::utils::spawning::{spawn, spawn_sh};
-use crate::utils::{center, get_monotonic_time, ResizeEdge};
+use crate::utils::{center, get_monotonic_time, LoopHandleExt as _, ResizeEdge};
 
 pub mod backend_ext;
+pub mod inject;
 pub mod move_grab;
 pub mod pick_color_grab;
 pub mod pick_window_grab;
@@ -280,7 +282,7 @@ impl State {
     }
 
     /// Computes the rectangle that covers all outputs in global space.
-    fn global_bounding_rectangle(&self) -> Option<Rectangle<i32, Logical>> {
+    pub fn global_bounding_rectangle(&self) -> Option<Rectangle<i32, Logical>> {
         self.niri.global_space.outputs().fold(
             None,
             |acc: Option<Rectangle<i32, Logical>>, output| {
@@ -482,7 +484,7 @@ impl State {
                 if this.niri.exit_confirm_dialog.is_open() && pressed {
                     if raw == Some(Keysym::Return) {
                         info!("quitting after confirming exit dialog");
-                        this.niri.stop_signal.stop();
+                        this.niri.stop();
                     }
 
                     // Don't send this press to any clients.
@@ -582,21 +584,19 @@ impl State {
         }
 
         let config = self.niri.config.borrow();
-        let config = &config.input.keyboard;
+        let (repeat_delay, repeat_rate) = key_repeat_info(&config.input.keyboard);
 
-        let repeat_rate = config.repeat_rate;
         if repeat_rate == 0 {
             return;
         }
         let repeat_duration = Duration::from_secs_f64(1. / f64::from(repeat_rate));
 
-        let repeat_timer =
-            Timer::from_duration(Duration::from_millis(u64::from(config.repeat_delay)));
+        let repeat_timer = Timer::from_duration(Duration::from_millis(u64::from(repeat_delay)));
 
         let token = self
             .niri
             .event_loop
-            .insert_source(repeat_timer, move |_, _, state| {
+            .insert_timer(repeat_timer, move |_, _, state| {
                 state.handle_bind(bind.clone());
                 TimeoutAction::ToDuration(repeat_duration)
             })
@@ -646,7 +646,7 @@ impl State {
                 let token = self
                     .niri
                     .event_loop
-                    .insert_source(timer, move |_, _, state| {
+                    .insert_timer(timer, move |_, _, state| {
                         if state.niri.bind_cooldown_timers.remove(&bind.key).is_none() {
                             error!("bind cooldown timer entry disappeared");
                         }
@@ -677,7 +677,7 @@ impl State {
                 }
 
                 info!("quitting as requested");
-                self.niri.stop_signal.stop()
+                self.niri.stop()
             }
             Action::ChangeVt(vt) => {
                 self.backend.change_vt(vt);
@@ -685,7 +685,9 @@ impl State {
                 self.niri.suppressed_keys.clear();
             }
             Action::Suspend => {
-                self.backend.suspend();
+                // macOS keeps the system sleep to itself, so this puts the displays to sleep.
+                // Like with power-off-monitors, rendering stops until input wakes them up.
+                self.niri.deactivate_monitors(&mut self.backend);
                 // Suspend may not deliver the key releases, so clear the state.
                 self.niri.suppressed_keys.clear();
             }
@@ -706,6 +708,16 @@ impl State {
             Action::DebugToggleDamage => {
                 self.niri.debug_toggle_damage();
             }
+            Action::DebugToggleFpsOverlay => {
+                self.backend.toggle_fps_overlay();
+                self.niri.queue_redraw_all();
+            }
+            Action::DebugReloadShaders => {
+                self.backend.reload_shaders(&mut self.niri);
+            }
+            Action::ToggleCompositorFullscreen => {
+                self.backend.toggle_fullscreen();
+            }
             Action::Spawn(command) => {
                 let (token, _) = self.niri.activation_state.create_external_token(None);
                 spawn(command, Some(token.clone()));
@@ -722,17 +734,31 @@ impl State {
             Action::ScreenshotScreen(write_to_disk, show_pointer, path) => {
                 let active = self.niri.layout.active_output().cloned();
                 if let Some(active) = active {
-                    self.backend.with_primary_renderer(|renderer| {
-                        if let Err(err) = self.niri.screenshot(
+                    let res = self.backend.with_primary_renderer(|renderer| {
+                        self.niri.screenshot(
                             renderer,
                             &active,
                             write_to_disk,
                             show_pointer,
-                            path,
-                        ) {
-                            warn!("error taking screenshot: {err:?}");
-                        }
+                            path.clone(),
+                        )
                     });
+                    if let Some(Err(err)) = res {
+                        warn!("error taking screenshot: {err:?}");
+
+                        // Offscreen rendering isn't available everywhere on macOS, so fall back
+                        // to reading back what the window shows.
+                        if let Backend::Winit(winit) = &mut self.backend {
+                            let res = winit
+                                .capture(&mut self.niri, &active, show_pointer)
+                                .and_then(|(size, pixels)| {
+                                    self.niri.save_screenshot(size, pixels, write_to_disk, path)
+                                });
+                            if let Err(err) = res {
+                                warn!("error capturing window: {err:?}");
+                            }
+                        }
+                    }
                 }
             }
             Action::ConfirmScreenshot { write_to_disk } => {
@@ -3405,6 +3431,7 @@ impl State {
             let config = self.niri.config.borrow();
             match source {
                 AxisSource::Wheel => config.input.mouse.scroll_factor,
+                AxisSource::Finger if event.device().applies_touchpad_scroll_factor() => None,
                 AxisSource::Finger => config.input.touchpad.scroll_factor,
                 _ => None,
             }
@@ -4444,7 +4471,7 @@ fn find_configured_switch_action(
         .map(|switch_action| Action::Spawn(switch_action.spawn.clone()))
 }
 
-fn modifiers_from_state(mods: ModifiersState) -> Modifiers {
+pub fn modifiers_from_state(mods: ModifiersState) -> Modifiers {
     let mut modifiers = Modifiers::empty();
     if mods.ctrl {
         modifiers |= Modifiers::CTRL;
@@ -4911,6 +4938,22 @@ pub fn apply_libinput_settings(config: &niri_config::Input, device: &mut input::
     }
 }
 
+/// Returns the key repeat delay in milliseconds and rate in characters per second.
+///
+/// With `repeat-follow-system`, these come from the macOS keyboard settings, and the config only
+/// fills in the ones that the user never changed.
+pub fn key_repeat_info(config: &niri_config::input::Keyboard) -> (u16, u8) {
+    #[cfg(target_os = "macos")]
+    if config.repeat_follow_system {
+        let system = crate::backend::appkit::key_repeat();
+        let delay = system.delay.unwrap_or(config.repeat_delay);
+        let rate = system.rate.unwrap_or(config.repeat_rate);
+        return (delay, rate);
+    }
+
+    (config.repeat_delay, config.repeat_rate)
+}
+
 pub fn mods_with_binds(mod_key: ModKey, binds: &Binds, triggers: &[Trigger]) -> HashSet<Modifiers> {
     let mut rv = HashSet::new();
     for bind in &binds.0 {
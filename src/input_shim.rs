@@ -26,8 +26,11 @@ pub enum AccelProfile {
 }
 
 impl From<niri_config::input::AccelProfile> for AccelProfile {
-    fn from(_: niri_config::input::AccelProfile) -> Self {
-        Self::Adaptive
+    fn from(p: niri_config::input::AccelProfile) -> Self {
+        match p {
+            niri_config::input::AccelProfile::Adaptive => Self::Adaptive,
+            niri_config::input::AccelProfile::Flat => Self::Flat,
+        }
     }
 }
 
@@ -56,8 +59,38 @@ impl From<niri_config::input::ScrollMethod> for ScrollMethod {
     }
 }
 
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
 use smithay::input::keyboard::LedState;
 
+/// Caps Lock LED state, shared by all devices since they are all the same keyboard to macOS.
+static CAPS_LOCK_LED: AtomicBool = AtomicBool::new(false);
+
+#[rustfmt::skip]
+const IDENTITY_MATRIX: [f32; 6] = [
+    1., 0., 0.,
+    0., 1., 0.,
+];
+
+/// Touchscreen calibration matrix, shared by all devices since winit doesn't tell touchscreens
+/// apart.
+static CALIBRATION_MATRIX: Mutex<[f32; 6]> = Mutex::new(IDENTITY_MATRIX);
+
+/// Natural scrolling from the niri config, `None` while the config doesn't set it. Shared by all
+/// devices like the macOS setting that it overrides.
+static NATURAL_SCROLL: Mutex<Option<bool>> = Mutex::new(None);
+
+/// Send events mode of each [`DeviceKind`], in the order of its variants.
+static SEND_EVENTS_MODES: Mutex<[SendEventsMode; 3]> = Mutex::new([SendEventsMode::ENABLED; 3]);
+
+/// Disable-while-typing of the trackpad, the only device that has it.
+static DWT_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Tap-to-click of the trackpad, the only device that has it.
+static TAP_ENABLED: AtomicBool = AtomicBool::new(false);
+static TAP_BUTTON_MAP: Mutex<TapButtonMap> = Mutex::new(TapButtonMap::LeftRightMiddle);
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ClickMethod {
     NoClick,
@@ -77,33 +110,102 @@ impl From<niri_config::input::ClickMethod> for ClickMethod {
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TapButtonMap {
-    Lrm_,
-    Lmr_,
+    LeftRightMiddle,
+    LeftMiddleRight,
 }
 
 impl From<niri_config::input::TapButtonMap> for TapButtonMap {
     fn from(m: niri_config::input::TapButtonMap) -> Self {
         match m {
-            // niri_config::input::TapButtonMap::Lrm => TapButtonMap::Lrm_,
-            // niri_config::input::TapButtonMap::Lmr => TapButtonMap::Lmr_,
-            _ => TapButtonMap::Lrm_, // Keep wildcard here if Lrm/Lmr missing
+            niri_config::input::TapButtonMap::LeftRightMiddle => TapButtonMap::LeftRightMiddle,
+            niri_config::input::TapButtonMap::LeftMiddleRight => TapButtonMap::LeftMiddleRight,
         }
     }
 }
 
+/// Input devices that a shim [`Device`] stands for.
+///
+/// AppKit doesn't tell the devices apart, but it does tell trackpad events and touches from the
+/// rest, so those get their own devices and settings like `off`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeviceKind {
+    /// Mice and everything else that moves the pointer, with the niri mouse settings.
+    Pointer,
+    /// Trackpads, with the niri touchpad settings.
+    Trackpad,
+    /// Touchscreens, with the niri touch settings.
+    Touchscreen,
+}
+
 // Mock Device struct
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct Device;
+pub struct Device {
+    kind: DeviceKind,
+}
 
 // Implement methods used by niri configuration
 impl Device {
-    pub fn config_tap_finger_count(&self) -> u32 { 0 }
-    pub fn config_send_events_set_mode(&mut self, _mode: SendEventsMode) -> Result<(), ()> { Ok(()) }
-    pub fn config_tap_set_enabled(&mut self, _enable: bool) -> Result<(), ()> { Ok(()) }
-    pub fn config_dwt_set_enabled(&mut self, _enable: bool) -> Result<(), ()> { Ok(()) }
+    pub const POINTER: Self = Self {
+        kind: DeviceKind::Pointer,
+    };
+    pub const TRACKPAD: Self = Self {
+        kind: DeviceKind::Trackpad,
+    };
+    pub const TOUCHSCREEN: Self = Self {
+        kind: DeviceKind::Touchscreen,
+    };
+
+    /// Returns one device of every kind.
+    pub fn all() -> [Self; 3] {
+        [Self::POINTER, Self::TRACKPAD, Self::TOUCHSCREEN]
+    }
+
+    pub fn kind(&self) -> DeviceKind {
+        self.kind
+    }
+
+    // Makes apply_libinput_settings() take the trackpad for a touchpad.
+    pub fn config_tap_finger_count(&self) -> u32 {
+        match self.kind {
+            DeviceKind::Trackpad => 3,
+            DeviceKind::Pointer | DeviceKind::Touchscreen => 0,
+        }
+    }
+    pub fn config_send_events_set_mode(&mut self, mode: SendEventsMode) -> Result<(), ()> {
+        SEND_EVENTS_MODES.lock().unwrap()[self.kind as usize] = mode;
+        Ok(())
+    }
+    pub fn config_send_events_mode(&self) -> SendEventsMode {
+        SEND_EVENTS_MODES.lock().unwrap()[self.kind as usize]
+    }
+    pub fn config_tap_set_enabled(&mut self, enable: bool) -> Result<(), ()> {
+        if self.kind == DeviceKind::Trackpad {
+            TAP_ENABLED.store(enable, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+    pub fn config_tap_enabled(&self) -> bool {
+        self.kind == DeviceKind::Trackpad && TAP_ENABLED.load(Ordering::Relaxed)
+    }
+    pub fn config_dwt_set_enabled(&mut self, enable: bool) -> Result<(), ()> {
+        if self.kind == DeviceKind::Trackpad {
+            DWT_ENABLED.store(enable, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+    pub fn config_dwt_enabled(&self) -> bool {
+        self.kind == DeviceKind::Trackpad && DWT_ENABLED.load(Ordering::Relaxed)
+    }
     pub fn config_dwtp_set_enabled(&mut self, _enable: bool) -> Result<(), ()> { Ok(()) }
     pub fn config_tap_set_drag_lock_enabled(&mut self, _enable: bool) -> Result<(), ()> { Ok(()) }
-    pub fn config_scroll_set_natural_scroll_enabled(&mut self, _enable: bool) -> Result<(), ()> { Ok(()) }
+    pub fn config_scroll_set_natural_scroll_enabled(&mut self, enable: bool) -> Result<(), ()> {
+        // Trackpad scrolling keeps following macOS. The config only has a flag to turn natural
+        // scrolling on, so without it mouse wheels follow macOS too.
+        if self.kind == DeviceKind::Pointer {
+            *NATURAL_SCROLL.lock().unwrap() = enable.then_some(true);
+        }
+        Ok(())
+    }
     pub fn config_accel_set_speed(&mut self, _speed: f64) -> Result<(), ()> { Ok(()) }
     pub fn config_left_handed_set(&mut self, _left: bool) -> Result<(), ()> { Ok(()) }
     pub fn config_middle_emulation_set_enabled(&mut self, _enable: bool) -> Result<(), ()> { Ok(()) }
@@ -115,27 +217,97 @@ impl Device {
     pub fn config_scroll_default_method(&self) -> Option<ScrollMethod> { Some(ScrollMethod::TwoFinger) }
     pub fn config_scroll_set_button(&mut self, _button: u32) -> Result<(), ()> { Ok(()) }
     pub fn config_scroll_set_button_lock(&mut self, _state: ScrollButtonLockState) -> Result<(), ()> { Ok(()) }
-    pub fn config_calibration_set_matrix(&mut self, _matrix: [f32; 6]) -> Result<(), ()> { Ok(()) }
-    pub fn config_calibration_default_matrix(&self) -> Option<[f32; 6]> { Some([1.0, 0.0, 0.0, 0.0, 1.0, 0.0]) }
+    pub fn config_calibration_set_matrix(&mut self, matrix: [f32; 6]) -> Result<(), ()> {
+        *CALIBRATION_MATRIX.lock().unwrap() = matrix;
+        Ok(())
+    }
+    pub fn config_calibration_default_matrix(&self) -> Option<[f32; 6]> { Some(IDENTITY_MATRIX) }
+    pub fn config_calibration_matrix(&self) -> [f32; 6] {
+        *CALIBRATION_MATRIX.lock().unwrap()
+    }
     
     // New methods from last check
-    pub fn config_scroll_natural_scroll_enabled(&self) -> bool { false }
-    pub fn config_tap_set_button_map(&mut self, _map: TapButtonMap) -> Result<(), ()> { Ok(()) }
-    pub fn config_tap_default_button_map(&self) -> Option<TapButtonMap> { Some(TapButtonMap::Lrm_) }
+    pub fn config_scroll_natural_scroll_enabled(&self) -> bool {
+        self.configured_natural_scroll()
+            .unwrap_or_else(crate::backend::appkit::natural_scroll_enabled)
+    }
+    pub fn config_tap_set_button_map(&mut self, map: TapButtonMap) -> Result<(), ()> {
+        if self.kind == DeviceKind::Trackpad {
+            *TAP_BUTTON_MAP.lock().unwrap() = map;
+        }
+        Ok(())
+    }
+    pub fn config_tap_default_button_map(&self) -> Option<TapButtonMap> {
+        Some(TapButtonMap::LeftRightMiddle)
+    }
+    pub fn config_tap_button_map(&self) -> TapButtonMap {
+        *TAP_BUTTON_MAP.lock().unwrap()
+    }
     pub fn config_click_set_method(&mut self, _method: ClickMethod) -> Result<(), ()> { Ok(()) }
     pub fn config_click_default_method(&self) -> Option<ClickMethod> { Some(ClickMethod::ButtonAreas) }
     
     // Unsafe udev_device shim
     pub unsafe fn udev_device(&self) -> Option<()> { None }
 
-    pub fn led_update(&mut self, _led_state: LedState) {}
+    pub fn led_update(&mut self, led_state: LedState) {
+        let Some(caps_lock) = led_state.caps else {
+            return;
+        };
+
+        if CAPS_LOCK_LED.swap(caps_lock, Ordering::Relaxed) != caps_lock {
+            crate::backend::hid::set_caps_lock_led(caps_lock);
+        }
+    }
+
+    /// Returns the Caps Lock LED state last set through [`Device::led_update`].
+    pub fn caps_lock_led(&self) -> bool {
+        CAPS_LOCK_LED.load(Ordering::Relaxed)
+    }
     
-    // Capability check shim
-    pub fn has_capability(&self, _cap: DeviceCapability) -> bool { false }
+    /// Returns the natural scrolling set through
+    /// [`Device::config_scroll_set_natural_scroll_enabled`], if any.
+    pub fn configured_natural_scroll(&self) -> Option<bool> {
+        *NATURAL_SCROLL.lock().unwrap()
+    }
+
+    /// Returns whether events from the devices go to niri, going by the send events mode.
+    ///
+    /// With [`SendEventsMode::DISABLED_ON_EXTERNAL_MOUSE`], that depends on whether a mouse is
+    /// plugged in, see [`hid::external_mouse_connected`](crate::backend::hid).
+    pub fn sends_events(&self) -> bool {
+        let mode = self.config_send_events_mode();
+        mode_sends_events(mode, crate::backend::hid::external_mouse_connected)
+    }
+
+    /// Applies the calibration matrix to a touch position normalized to `0..1`.
+    pub fn calibrate_touch(&self, x: f64, y: f64) -> (f64, f64) {
+        calibrate(&self.config_calibration_matrix(), x, y)
+    }
+
+    // Capability check shim. Touch makes apply_libinput_settings() set the calibration matrix,
+    // Pointer the mouse settings, or the touchpad ones together with the tap finger count.
+    pub fn has_capability(&self, cap: DeviceCapability) -> bool {
+        match self.kind {
+            DeviceKind::Pointer | DeviceKind::Trackpad => cap == DeviceCapability::Pointer,
+            DeviceKind::Touchscreen => cap == DeviceCapability::Touch,
+        }
+    }
     
     // Smithay Device trait implementation methods stubs
-    pub fn id(&self) -> String { "macos-stub".into() }
-    pub fn name(&self) -> String { "macOS Stub Device".into() }
+    pub fn id(&self) -> String {
+        match self.kind {
+            DeviceKind::Pointer => "macos-stub".into(),
+            DeviceKind::Trackpad => "macos-stub-trackpad".into(),
+            DeviceKind::Touchscreen => "macos-stub-touchscreen".into(),
+        }
+    }
+    pub fn name(&self) -> String {
+        match self.kind {
+            DeviceKind::Pointer => "macOS Stub Device".into(),
+            DeviceKind::Trackpad => "macOS Stub Trackpad".into(),
+            DeviceKind::Touchscreen => "macOS Stub Touchscreen".into(),
+        }
+    }
     pub fn usb_id(&self) -> Option<(u32, u32)> { None }
     pub fn syspath(&self) -> Option<std::path::PathBuf> { None }
 }
@@ -171,3 +343,61 @@ pub mod event {
         pub struct GestureHoldEndEvent;
     }
 }
+
+/// Returns whether a device in `mode` sends events, checking for an external mouse only when the
+/// mode depends on it.
+fn mode_sends_events(
+    mode: SendEventsMode,
+    external_mouse_connected: impl FnOnce() -> bool,
+) -> bool {
+    match mode {
+        SendEventsMode::ENABLED => true,
+        SendEventsMode::DISABLED => false,
+        SendEventsMode::DISABLED_ON_EXTERNAL_MOUSE => !external_mouse_connected(),
+    }
+}
+
+/// Applies a libinput calibration matrix to a normalized position.
+///
+/// The matrix is the top two rows of a 3×3 affine transform, same as
+/// `LIBINPUT_CALIBRATION_MATRIX`.
+fn calibrate(m: &[f32; 6], x: f64, y: f64) -> (f64, f64) {
+    let m = m.map(f64::from);
+    (
+        m[0] * x + m[1] * y + m[2],
+        m[3] * x + m[4] * y + m[5],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calibration_matrix() {
+        assert_eq!(calibrate(&IDENTITY_MATRIX, 0.25, 0.75), (0.25, 0.75));
+
+        #[rustfmt::skip]
+        let rotate_180 = [
+            -1., 0., 1.,
+            0., -1., 1.,
+        ];
+        assert_eq!(calibrate(&rotate_180, 0.25, 0.75), (0.75, 0.25));
+        assert_eq!(calibrate(&rotate_180, 0., 0.), (1., 1.));
+
+        #[rustfmt::skip]
+        let left_half = [
+            0.5, 0., 0.,
+            0., 1., 0.,
+        ];
+        assert_eq!(calibrate(&left_half, 1., 0.5), (0.5, 0.5));
+    }
+
+    #[test]
+    fn send_events_modes() {
+        assert!(mode_sends_events(SendEventsMode::ENABLED, || true));
+        assert!(!mode_sends_events(SendEventsMode::DISABLED, || false));
+        assert!(mode_sends_events(SendEventsMode::DISABLED_ON_EXTERNAL_MOUSE, || false));
+        assert!(!mode_sends_events(SendEventsMode::DISABLED_ON_EXTERNAL_MOUSE, || true));
+    }
+}
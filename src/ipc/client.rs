@@ -48,6 +48,8 @@ pub fn handle_msg(mut msg: Msg, json: bool) -> anyhow::Result<()> {
         Msg::EventStream => Request::EventStream,
         Msg::RequestError => Request::ReturnError,
         Msg::OverviewState => Request::OverviewState,
+        #[cfg(feature = "inject-input")]
+        Msg::InjectInput { input } => Request::InjectInput(*input),
     };
 
     let mut socket = Socket::connect().context("error connecting to the niri socket")?;
@@ -321,6 +323,12 @@ pub fn handle_msg(mut msg: Msg, json: bool) -> anyhow::Result<()> {
                 bail!("unexpected response: expected Handled, got {response:?}");
             };
         }
+        #[cfg(feature = "inject-input")]
+        Msg::InjectInput { .. } => {
+            let Response::Handled = response else {
+                bail!("unexpected response: expected Handled, got {response:?}");
+            };
+        }
         Msg::Output { output, .. } => {
             let Response::OutputConfigChanged(response) = response else {
                 bail!("unexpected response: expected OutputConfigChanged, got {response:?}");
@@ -487,6 +495,11 @@ pub fn handle_msg(mut msg: Msg, json: bool) -> anyhow::Result<()> {
                         };
                         println!("Config loaded {status}");
                     }
+                    Event::OutputsChanged { outputs } => {
+                        let mut names: Vec<_> = outputs.keys().collect();
+                        names.sort_unstable();
+                        println!("Outputs changed: {names:?}");
+                    }
                     Event::ScreenshotCaptured { path } => {
                         let mut parts = vec![];
                         parts.push("copied to clipboard".to_string());
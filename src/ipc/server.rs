@@ -1,5 +1,5 @@
 use std::cell::RefCell;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::ffi::OsStr;
 use std::os::unix::net::{UnixListener, UnixStream};
 use std::path::{Path, PathBuf};
@@ -112,6 +112,18 @@ impl IpcServer {
         })
     }
 
+    /// Subscribes to the events like an event stream client, without going through the socket.
+    #[cfg(test)]
+    pub fn subscribe(&self) -> Receiver<Event> {
+        let (events_tx, events_rx) = async_channel::bounded(EVENT_STREAM_BUFFER_SIZE);
+        let (disconnect_tx, _) = async_channel::bounded(1);
+        self.event_streams.borrow_mut().push(EventStreamSender {
+            events: events_tx,
+            disconnect: disconnect_tx,
+        });
+        events_rx
+    }
+
     fn send_event(&self, event: Event) {
         let mut streams = self.event_streams.borrow_mut();
         let mut to_remove = Vec::new();
@@ -450,6 +462,18 @@ async fn process(ctx: &ClientCtx, request: Request) -> Reply {
             let is_open = state.overview.is_open;
             Response::OverviewState(Overview { is_open })
         }
+        #[cfg(feature = "inject-input")]
+        Request::InjectInput(input) => {
+            let (tx, rx) = async_channel::bounded(1);
+            ctx.event_loop.insert_idle(move |state| {
+                state.inject_input(input);
+                let _ = tx.send_blocking(());
+            });
+
+            // Wait for the event to be processed, so that a following request sees its effects.
+            let _ = rx.recv().await;
+            Response::Handled
+        }
     };
 
     Ok(response)
@@ -804,6 +828,34 @@ impl State {
         server.send_event(event);
     }
 
+    /// Sends the outputs to the event streams, unless they are the same as the last ones sent.
+    pub fn ipc_refresh_outputs(&mut self) {
+        let Some(server) = &self.niri.ipc_server else {
+            return;
+        };
+
+        let _span = tracy_client::span!("State::ipc_refresh_outputs");
+
+        let ipc_outputs = self.backend.ipc_outputs();
+        let outputs: HashMap<_, _> = ipc_outputs
+            .lock()
+            .unwrap()
+            .values()
+            .map(|output| (output.name.clone(), output.clone()))
+            .collect();
+
+        let mut state = server.event_stream_state.borrow_mut();
+        let state = &mut state.outputs;
+
+        if state.outputs == outputs {
+            return;
+        }
+
+        let event = Event::OutputsChanged { outputs };
+        state.apply(event.clone());
+        server.send_event(event);
+    }
+
     pub fn ipc_screenshot_taken(&mut self, path: Option<String>) {
         let Some(server) = &self.niri.ipc_server else {
             return;
@@ -156,6 +156,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     });
     let config_includes = config_load_result.includes;
 
+    if let Some(frames) = cli.bench {
+        return run_benchmark(config, frames);
+    }
+
     let spawn_at_startup = mem::take(&mut config.spawn_at_startup);
     let spawn_sh_at_startup = mem::take(&mut config.spawn_sh_at_startup);
     *CHILD_ENV.write().unwrap() = mem::take(&mut config.environment);
@@ -287,6 +291,28 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Renders frames on the headless backend and prints their timings.
+fn run_benchmark(config: Config, frames: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let event_loop = EventLoop::<State>::try_new().unwrap();
+    let display = Display::new().unwrap();
+    let mut state = State::new(
+        config,
+        event_loop.handle(),
+        event_loop.get_signal(),
+        display,
+        true,
+        false,
+        false,
+    )?;
+
+    let report = state
+        .backend
+        .headless()
+        .benchmark(&mut state.niri, frames)?;
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
+
 fn import_environment() {
     let variables = [
         "WAYLAND_DISPLAY",
@@ -23,7 +23,7 @@ use smithay::wayland::relative_pointer::RelativePointerManagerState;
 use crate::protocols::foreign_toplevel;
 use crate::protocols::foreign_toplevel::ForeignToplevelManagerState;
 use niri_config::{
-    Config, FloatOrInt, Key, Modifiers, OutputName, TrackLayout, WarpMouseToFocusMode,
+    Color, Config, FloatOrInt, Key, Modifiers, OutputName, TrackLayout, WarpMouseToFocusMode,
     WorkspaceReference, Xkb,
 };
 use smithay::backend::allocator::Fourcc;
@@ -123,7 +123,8 @@ use smithay::wayland::xdg_foreign::XdgForeignState;
 use crate::a11y::A11y;
 use crate::animation::Clock;
 // use crate::backend::tty::SurfaceDmabufFeedback;
-use crate::backend::{Backend, Headless, RenderResult, Winit};
+use crate::backend::winit::winit_output_transform;
+use crate::backend::{configured_clear_color, configured_transparency, Backend, RenderResult};
 use crate::cursor::{CursorManager, CursorTextureCache, RenderCursor, XCursor};
 #[cfg(feature = "dbus")]
 use crate::dbus::freedesktop_locale1::Locale1ToNiri;
@@ -136,12 +137,12 @@ use crate::dbus::gnome_shell_screenshot::{NiriToScreenshot, ScreenshotToNiri};
 #[cfg(feature = "xdp-gnome-screencast")]
 use crate::dbus::mutter_screen_cast::{self, ScreenCastToNiri};
 use crate::frame_clock::FrameClock;
-use crate::handlers::{configure_lock_surface, XDG_ACTIVATION_TOKEN_TIMEOUT};
+use crate::handlers::{configure_lock_surface, SelectionData, XDG_ACTIVATION_TOKEN_TIMEOUT};
 use crate::input::pick_color_grab::PickColorGrab;
 use crate::input::scroll_swipe_gesture::ScrollSwipeGesture;
 use crate::input::scroll_tracker::ScrollTracker;
 use crate::input::{
-    apply_libinput_settings, mods_with_finger_scroll_binds, mods_with_mouse_binds,
+    apply_libinput_settings, key_repeat_info, mods_with_finger_scroll_binds, mods_with_mouse_binds,
     mods_with_wheel_binds, TabletData,
 };
 use crate::ipc::server::IpcServer;
@@ -158,9 +159,9 @@ use crate::protocols::mutter_x11_interop::MutterX11InteropManagerState;
 use crate::protocols::output_management::OutputManagementManagerState;
 use crate::protocols::screencopy::{Screencopy, ScreencopyBuffer, ScreencopyManagerState};
 use crate::protocols::virtual_pointer::VirtualPointerManagerState;
-use crate::pw_utils::{Cast, PipeWire};
+use crate::pw_utils::{Cast, CastSizeChange, PipeWire};
 #[cfg(feature = "xdp-gnome-screencast")]
-use crate::pw_utils::{CastSizeChange, PwToNiri};
+use crate::pw_utils::PwToNiri;
 use crate::render_helpers::debug::draw_opaque_regions;
 use crate::render_helpers::primary_gpu_texture::PrimaryGpuTextureRenderElement;
 use crate::render_helpers::renderer::NiriRenderer;
@@ -176,7 +177,7 @@ use crate::ui::hotkey_overlay::HotkeyOverlay;
 use crate::ui::mru::{MruCloseRequest, WindowMruUi, WindowMruUiRenderElement};
 use crate::ui::screen_transition::{self, ScreenTransition};
 use crate::ui::screenshot_ui::{OutputScreenshot, ScreenshotUi, ScreenshotUiRenderElement};
-use crate::utils::scale::{closest_representable_scale, guess_monitor_scale};
+use crate::utils::scale::{closest_representable_scale, default_output_scale};
 use crate::utils::spawning::{CHILD_DISPLAY, CHILD_ENV};
 use crate::utils::vblank_throttle::VBlankThrottle;
 use crate::utils::watcher::Watcher;
@@ -184,7 +185,7 @@ use crate::utils::xwayland::satellite::Satellite;
 use crate::utils::{
     center, center_f64, expand_home, get_monotonic_time, ipc_transform_to_smithay, is_mapped,
     logical_output, make_screenshot_path, output_matches_name, output_size, panel_orientation,
-    send_scale_transform, write_png_rgba8, xwayland,
+    send_scale_transform, write_png_rgba8, xwayland, LoopHandleExt as _,
 };
 use crate::window::mapped::MappedId;
 use crate::window::{InitialConfigureState, Mapped, ResolvedWindowRules, Unmapped, WindowRef};
@@ -211,6 +212,8 @@ pub struct Niri {
     pub event_loop: LoopHandle<'static, State>,
     pub scheduler: Scheduler<()>,
     pub stop_signal: LoopSignal,
+    /// Whether the compositor is quitting, see [`Niri::stop`].
+    pub stopping: bool,
     pub display_handle: DisplayHandle,
 
     /// Whether niri was run with `--session`
@@ -412,6 +415,8 @@ pub struct Niri {
     pub inhibit_power_key_fd: Option<zbus::zvariant::OwnedFd>,
 
     pub ipc_server: Option<IpcServer>,
+    /// Set whenever the backend changes its IPC outputs, so that the next refresh sends them to
+    /// the event streams and the other listeners, once for all the changes in between.
     pub ipc_outputs_changed: bool,
 
     pub satellite: Option<Satellite>,
@@ -686,21 +691,31 @@ impl State {
 
         let config = Rc::new(RefCell::new(config));
 
-        let has_display = env::var_os("WAYLAND_DISPLAY").is_some()
-            || env::var_os("WAYLAND_SOCKET").is_some()
-            || env::var_os("DISPLAY").is_some();
+        let backend = Backend::new(config.clone(), event_loop.clone(), headless)?;
 
-        let mut backend = if headless {
-            let headless = Headless::new();
-            Backend::Headless(headless)
-        } else if has_display {
-            let winit = Winit::new(config.clone(), event_loop.clone())?;
-            Backend::Winit(winit)
-        } else {
-             // Tty backend removed for macOS port
-             panic!("TTY backend not supported on macOS. Please run inside a window.");
-        };
+        Self::with_backend(
+            config,
+            event_loop,
+            stop_signal,
+            display,
+            backend,
+            create_wayland_socket,
+            is_session_instance,
+        )
+    }
 
+    /// Creates the compositor state on top of an existing backend.
+    ///
+    /// This lets tests pick a backend, see [`Backend::new_headless`].
+    pub fn with_backend(
+        config: Rc<RefCell<Config>>,
+        event_loop: LoopHandle<'static, State>,
+        stop_signal: LoopSignal,
+        display: Display<State>,
+        mut backend: Backend,
+        create_wayland_socket: bool,
+        is_session_instance: bool,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
         let mut niri = Niri::new(
             config.clone(),
             event_loop,
@@ -785,7 +800,8 @@ impl State {
         self.niri.cursor_manager.check_cursor_image_surface_alive();
         self.niri.refresh_pointer_outputs();
         self.niri.global_space.refresh();
-        self.niri.refresh_idle_inhibit();
+        let idle_inhibited = self.niri.refresh_idle_inhibit();
+        self.backend.set_idle_inhibited(idle_inhibited);
         self.refresh_pointer_contents();
         foreign_toplevel::refresh(self);
         ext_workspace::refresh(self);
@@ -834,6 +850,9 @@ impl State {
 
         self.niri.pointer_contents.clone_from(&under);
 
+        // Take the system cursor along, otherwise the next mouse motion moves the pointer back.
+        self.backend.warp_pointer(&self.niri, location);
+
         let pointer = &self.niri.seat.get_pointer().unwrap();
         pointer.motion(
             self,
@@ -1273,7 +1292,7 @@ impl State {
                         let focus_token = self
                             .niri
                             .event_loop
-                            .insert_source(timer, move |_, _, state| {
+                            .insert_timer(timer, move |_, _, state| {
                                 state.niri.mru_apply_keyboard_commit();
                                 TimeoutAction::Drop
                             })
@@ -1469,12 +1488,12 @@ impl State {
         // Reload the repeat info.
         if config.input.keyboard.repeat_rate != old_config.input.keyboard.repeat_rate
             || config.input.keyboard.repeat_delay != old_config.input.keyboard.repeat_delay
+            || config.input.keyboard.repeat_follow_system
+                != old_config.input.keyboard.repeat_follow_system
         {
+            let (repeat_delay, repeat_rate) = key_repeat_info(&config.input.keyboard);
             let keyboard = self.niri.seat.get_keyboard().unwrap();
-            keyboard.change_repeat_info(
-                config.input.keyboard.repeat_rate.into(),
-                config.input.keyboard.repeat_delay.into(),
-            );
+            keyboard.change_repeat_info(repeat_rate.into(), repeat_delay.into());
         }
 
         if config.input.touchpad != old_config.input.touchpad
@@ -1490,6 +1509,18 @@ impl State {
         let ignored_nodes_changed =
             config.debug.ignored_drm_devices != old_config.debug.ignored_drm_devices;
 
+        let vsync_changed =
+            config.debug.winit_disable_vsync != old_config.debug.winit_disable_vsync;
+        let system_shortcuts_changed =
+            config.debug.winit_system_shortcuts != old_config.debug.winit_system_shortcuts;
+        let borderless_changed =
+            config.debug.winit_borderless != old_config.debug.winit_borderless;
+        let movable_background_changed =
+            config.debug.winit_movable_background != old_config.debug.winit_movable_background;
+        #[cfg(not(feature = "xdp-gnome-screencast"))]
+        let capture_path_changed =
+            config.debug.screencast_capture_path != old_config.debug.screencast_capture_path;
+
         if config.outputs != self.niri.config_file_output_config {
             output_config_changed = true;
             self.niri
@@ -1572,6 +1603,11 @@ impl State {
         if config.layout.background_color != old_config.layout.background_color {
             output_config_changed = true;
         }
+        if config.debug.clear_color != old_config.debug.clear_color
+            || config.debug.winit_transparent != old_config.debug.winit_transparent
+        {
+            output_config_changed = true;
+        }
 
         if config.recent_windows != old_config.recent_windows {
             recent_windows_changed = true;
@@ -1629,6 +1665,31 @@ impl State {
             self.backend.update_ignored_nodes_config(&mut self.niri);
         }
 
+        if vsync_changed {
+            let vsync = !self.niri.config.borrow().debug.winit_disable_vsync;
+            self.backend.set_vsync(vsync);
+        }
+
+        if system_shortcuts_changed {
+            let shortcuts = self.niri.config.borrow().debug.winit_system_shortcuts.clone();
+            self.backend.set_system_shortcuts(shortcuts.as_deref());
+        }
+
+        if borderless_changed {
+            let borderless = self.niri.config.borrow().debug.winit_borderless;
+            self.backend.set_borderless(borderless);
+        }
+
+        if movable_background_changed {
+            let movable = self.niri.config.borrow().debug.winit_movable_background;
+            self.backend.set_movable_by_background(movable);
+        }
+
+        #[cfg(not(feature = "xdp-gnome-screencast"))]
+        if capture_path_changed {
+            self.restart_capture_cast();
+        }
+
         if output_config_changed {
             self.reload_output_config();
         }
@@ -1703,20 +1764,15 @@ impl State {
             let scale = config
                 .and_then(|c| c.scale)
                 .map(|s| s.0)
-                .unwrap_or_else(|| {
-                    let size_mm = output.physical_properties().size;
-                    let resolution = output.current_mode().unwrap().size;
-                    guess_monitor_scale(size_mm, resolution)
-                });
+                .unwrap_or_else(|| default_output_scale(output));
             let scale = closest_representable_scale(scale.clamp(0.1, 10.));
 
             let mut transform = panel_orientation(output)
                 + config
                     .map(|c| ipc_transform_to_smithay(c.transform))
                     .unwrap_or(Transform::Normal);
-            // FIXME: fix winit damage on other transforms.
-            if name.connector == "winit" {
-                transform = Transform::Flipped180;
+            if name.connector.starts_with("winit") {
+                transform = winit_output_transform(transform);
             }
 
             if output.current_scale().fractional_scale() != scale
@@ -1732,12 +1788,8 @@ impl State {
                 resized_outputs.push(output.clone());
             }
 
-            let mut backdrop_color = config
-                .and_then(|c| c.backdrop_color)
-                .unwrap_or(full_config.overview.backdrop_color)
-                .to_array_unpremul();
-            backdrop_color[3] = 1.;
-            let backdrop_color = Color32F::from(backdrop_color);
+            let backdrop = config.and_then(|c| c.backdrop_color);
+            let backdrop_color = Color32F::from(output_backdrop_color(backdrop, &full_config));
 
             if let Some(state) = self.niri.output_state.get_mut(output) {
                 if state.backdrop_buffer.color() != backdrop_color {
@@ -1917,6 +1969,8 @@ impl State {
             ipc_output.logical = logical;
         }
 
+        self.ipc_refresh_outputs();
+
         #[cfg(feature = "dbus")]
         self.niri.on_ipc_outputs_changed();
 
@@ -2103,7 +2157,50 @@ impl State {
     }
 
     #[cfg(not(feature = "xdp-gnome-screencast"))]
-    pub fn set_dynamic_cast_target(&mut self, _target: CastTarget) {}
+    pub fn set_dynamic_cast_target(&mut self, target: CastTarget) {
+        let _span = tracy_client::span!("State::set_dynamic_cast_target");
+
+        let refresh = match &target {
+            CastTarget::Output(output) => output
+                .upgrade()
+                .map(|output| output.current_mode().unwrap().refresh as u32),
+            _ => None,
+        };
+
+        for cast in &mut self.niri.casts {
+            if !cast.dynamic_target {
+                continue;
+            }
+
+            if let Some(refresh) = refresh {
+                if let Err(err) = cast.set_refresh(refresh) {
+                    warn!("error changing cast FPS: {err:?}");
+                }
+            }
+
+            cast.target = target.clone();
+        }
+
+        // Capture casts render along with the outputs.
+        self.niri.queue_redraw_all();
+    }
+
+    /// Replaces the capture cast after `debug.screencast-capture-path` changed.
+    #[cfg(not(feature = "xdp-gnome-screencast"))]
+    fn restart_capture_cast(&mut self) {
+        // Without PipeWire, the capture cast is the only cast there is.
+        let target = mem::take(&mut self.niri.casts)
+            .into_iter()
+            .next()
+            .map_or(CastTarget::Nothing, |cast| cast.target);
+
+        let config = self.niri.config.borrow();
+        self.niri.casts = start_capture_cast(&config).into_iter().collect();
+        drop(config);
+
+        // Keep recording what the old cast recorded, starting with a fresh frame.
+        self.set_dynamic_cast_target(target);
+    }
 
     #[cfg(feature = "xdp-gnome-screencast")]
     pub fn set_dynamic_cast_target(&mut self, target: CastTarget) {
@@ -2155,7 +2252,7 @@ impl State {
 
     #[cfg(feature = "xdp-gnome-screencast")]
     pub fn on_screen_cast_msg(&mut self, msg: ScreenCastToNiri) {
-        use smithay::reexports::gbm::Modifier;
+        use smithay::backend::allocator::Modifier;
 
         use crate::dbus::mutter_screen_cast::StreamTargetId;
 
@@ -2171,8 +2268,8 @@ impl State {
 
                 debug!(session_id, stream_id, "StartCast");
 
-                let Some(gbm) = self.backend.gbm_device() else {
-                    warn!("error starting screencast: no GBM device available");
+                let Some(allocator) = self.backend.cast_allocator() else {
+                    warn!("error starting screencast: no buffer allocator available");
                     self.niri.stop_cast(session_id);
                     return;
                 };
@@ -2262,7 +2359,7 @@ impl State {
                 }
 
                 let res = pw.start_cast(
-                    gbm,
+                    allocator,
                     render_formats,
                     session_id,
                     stream_id,
@@ -2450,6 +2547,17 @@ impl Niri {
 
         let (blocker_cleared_tx, blocker_cleared_rx) = mpsc::channel();
 
+        // macOS doesn't hand out input devices, so shim devices carry the settings for the kinds
+        // of devices that AppKit tells apart, and config reloads apply to them along with the
+        // others.
+        #[allow(unused_mut)]
+        let mut devices = HashSet::new();
+        #[cfg(target_os = "macos")]
+        for mut device in input::Device::all() {
+            apply_libinput_settings(&config_.input, &mut device);
+            devices.insert(device);
+        }
+
         fn client_is_unrestricted(client: &Client) -> bool {
             !client.get_data::<ClientState>().unwrap().restricted
         }
@@ -2527,6 +2635,8 @@ impl Niri {
             InputMethodManagerState::new::<State, _>(&display_handle, client_is_unrestricted);
         let keyboard_shortcuts_inhibit_state =
             KeyboardShortcutsInhibitState::new::<State>(&display_handle);
+        // Virtual keyboards send their keymap and keys straight to the focused client, past the
+        // backend's key translation, and smithay restores the seat keymap on the next real key.
         let virtual_keyboard_state =
             VirtualKeyboardManagerState::new::<State, _>(&display_handle, client_is_unrestricted);
         let virtual_pointer_state =
@@ -2550,7 +2660,7 @@ impl Niri {
             });
         let activation_state = XdgActivationState::new::<State>(&display_handle);
         event_loop
-            .insert_source(
+            .insert_timer(
                 Timer::from_duration(XDG_ACTIVATION_TOKEN_TIMEOUT),
                 |_, _, state| {
                     state.niri.activation_state.retain_tokens(|_, token_data| {
@@ -2568,10 +2678,11 @@ impl Niri {
         let single_pixel_buffer_state = SinglePixelBufferState::new::<State>(&display_handle);
 
         let mut seat: Seat<State> = seat_state.new_wl_seat(&display_handle, backend.seat_name());
+        let (repeat_delay, repeat_rate) = key_repeat_info(&config_.input.keyboard);
         let keyboard = match seat.add_keyboard(
             config_.input.keyboard.xkb.to_xkb_config(),
-            config_.input.keyboard.repeat_delay.into(),
-            config_.input.keyboard.repeat_rate.into(),
+            repeat_delay.into(),
+            repeat_rate.into(),
         ) {
             Err(err) => {
                 if let smithay::input::keyboard::Error::BadKeymap = err {
@@ -2579,12 +2690,8 @@ impl Niri {
                 } else {
                     warn!("error adding keyboard: {err:?}");
                 }
-                seat.add_keyboard(
-                    Default::default(),
-                    config_.input.keyboard.repeat_delay.into(),
-                    config_.input.keyboard.repeat_rate.into(),
-                )
-                .unwrap()
+                seat.add_keyboard(Default::default(), repeat_delay.into(), repeat_rate.into())
+                    .unwrap()
             }
             Ok(keyboard) => keyboard,
         };
@@ -2620,7 +2727,7 @@ impl Niri {
         let a11y = A11y::new(event_loop.clone());
 
         event_loop
-            .insert_source(
+            .insert_timer(
                 Timer::from_duration(Duration::from_secs(1)),
                 |_, _, state| {
                     state.niri.send_frame_callbacks_on_fallback_timer();
@@ -2676,7 +2783,7 @@ impl Niri {
             .unwrap();
 
         event_loop
-            .insert_source(
+            .insert_timer(
                 Timer::from_duration(Duration::from_secs(60)),
                 |_, _, state| {
                     let _span = tracy_client::span!("startup timeout");
@@ -2688,6 +2795,11 @@ impl Niri {
             )
             .unwrap();
 
+        #[cfg(not(feature = "xdp-gnome-screencast"))]
+        let casts = start_capture_cast(&config_).into_iter().collect();
+        #[cfg(feature = "xdp-gnome-screencast")]
+        let casts = vec![];
+
         drop(config_);
         let mut niri = Self {
             config,
@@ -2697,6 +2809,7 @@ impl Niri {
             event_loop,
             scheduler,
             stop_signal,
+            stopping: false,
             socket_name,
             display_handle,
             is_session_instance,
@@ -2718,7 +2831,7 @@ impl Niri {
             monitors_active: true,
             is_lid_closed: false,
 
-            devices: HashSet::new(),
+            devices,
             tablets: HashMap::new(),
             touch: HashSet::new(),
 
@@ -2830,7 +2943,7 @@ impl Niri {
             satellite: None,
 
             pipewire: None,
-            casts: vec![],
+            casts,
             #[cfg(feature = "xdp-gnome-screencast")]
             pw_to_niri,
 
@@ -3025,26 +3138,20 @@ impl Niri {
 
         let config = self.config.borrow();
         let c = config.outputs.find(name);
-        let scale = c.and_then(|c| c.scale).map(|s| s.0).unwrap_or_else(|| {
-            let size_mm = output.physical_properties().size;
-            let resolution = output.current_mode().unwrap().size;
-            guess_monitor_scale(size_mm, resolution)
-        });
+        let scale = c
+            .and_then(|c| c.scale)
+            .map(|s| s.0)
+            .unwrap_or_else(|| default_output_scale(&output));
         let scale = closest_representable_scale(scale.clamp(0.1, 10.));
 
         let mut transform = panel_orientation(&output)
             + c.map(|c| ipc_transform_to_smithay(c.transform))
                 .unwrap_or(Transform::Normal);
 
-        let mut backdrop_color = c
-            .and_then(|c| c.backdrop_color)
-            .unwrap_or(config.overview.backdrop_color)
-            .to_array_unpremul();
-        backdrop_color[3] = 1.;
+        let backdrop_color = output_backdrop_color(c.and_then(|c| c.backdrop_color), &config);
 
-        // FIXME: fix winit damage on other transforms.
-        if name.connector == "winit" {
-            transform = Transform::Flipped180;
+        if name.connector.starts_with("winit") {
+            transform = winit_output_transform(transform);
         }
 
         let mut layout_config = c.and_then(|c| c.layout.clone());
@@ -3117,7 +3224,6 @@ impl Niri {
             RedrawState::WaitingForEstimatedVBlankAndQueued(token) => self.event_loop.remove(token),
         }
 
-        #[cfg(feature = "xdp-gnome-screencast")]
         self.stop_casts_for_target(CastTarget::Output(output.downgrade()));
 
         self.remove_screencopy_output(output);
@@ -3127,7 +3233,7 @@ impl Niri {
         let global = state.global;
         self.display_handle.disable_global::<State>(global.clone());
         self.event_loop
-            .insert_source(
+            .insert_timer(
                 Timer::from_duration(Duration::from_secs(10)),
                 move |_, _, state| {
                     state
@@ -3853,6 +3959,15 @@ impl Niri {
         state.lock_surface.as_ref().map(|s| s.wl_surface()).cloned()
     }
 
+    /// Asks the event loop to quit.
+    ///
+    /// Besides the calloop loop, this also stops the CFRunLoop integration on macOS, which checks
+    /// [`Niri::stopping`] after dispatching events.
+    pub fn stop(&mut self) {
+        self.stopping = true;
+        self.stop_signal.stop();
+    }
+
     /// Schedules an immediate redraw on all outputs if one is not already scheduled.
     pub fn queue_redraw_all(&mut self) {
         for state in self.output_state.values_mut() {
@@ -3928,7 +4043,7 @@ impl Niri {
                 cursor,
             } => {
                 let (idx, frame) = cursor.frame(self.start_time.elapsed().as_millis() as u32);
-                let hotspot = XCursor::hotspot(frame).to_logical(scale);
+                let hotspot = XCursor::hotspot(frame).to_logical(cursor.scale());
                 let pointer_pos =
                     (pointer_pos - hotspot.to_f64()).to_physical_precise_round(output_scale);
 
@@ -4090,7 +4205,7 @@ impl Niri {
 
                     // For simplicity, we always use frame 0 for this computation. Let's hope the
                     // hotspot doesn't change between frames.
-                    let hotspot = XCursor::hotspot(&cursor.frames()[0]).to_logical(output_scale);
+                    let hotspot = XCursor::hotspot(&cursor.frames()[0]).to_logical(cursor.scale());
 
                     let surface_pos = pointer_pos.to_i32_round() - hotspot;
                     let bbox = bbox_from_surface_tree(surface, surface_pos);
@@ -4139,7 +4254,8 @@ impl Niri {
         self.layout.refresh(layout_is_active);
     }
 
-    pub fn refresh_idle_inhibit(&mut self) {
+    /// Updates whether idle is inhibited and returns it.
+    pub fn refresh_idle_inhibit(&mut self) -> bool {
         let _span = tracy_client::span!("Niri::refresh_idle_inhibit");
 
         self.idle_inhibiting_surfaces.retain(|s| s.is_alive());
@@ -4151,6 +4267,7 @@ impl Niri {
                 })
             });
         self.idle_notifier_state.set_is_inhibited(is_inhibited);
+        is_inhibited
     }
 
     pub fn refresh_window_states(&mut self) {
@@ -4713,17 +4830,14 @@ impl Niri {
         // to err on the safe side.
         self.send_frame_callbacks(output);
         backend.with_primary_renderer(|renderer| {
-            #[cfg(feature = "xdp-gnome-screencast")]
-            {
-                // Render and send to PipeWire screencast streams.
-                self.render_for_screen_cast(renderer, output, target_presentation_time);
+            // Render and send to PipeWire screencast streams.
+            self.render_for_screen_cast(renderer, output, target_presentation_time);
 
-                // FIXME: when a window is hidden, it should probably still receive frame callbacks
-                // and get rendered for screen cast. This is currently
-                // unimplemented, but happens to work by chance, since output
-                // redrawing is more eager than it should be.
-                self.render_windows_for_screen_cast(renderer, output, target_presentation_time);
-            }
+            // FIXME: when a window is hidden, it should probably still receive frame callbacks
+            // and get rendered for screen cast. This is currently
+            // unimplemented, but happens to work by chance, since output
+            // redrawing is more eager than it should be.
+            self.render_windows_for_screen_cast(renderer, output, target_presentation_time);
 
             self.render_for_screencopy_with_damage(renderer, output);
         });
@@ -5206,7 +5320,6 @@ impl Niri {
         feedback
     }
 
-    #[cfg(feature = "xdp-gnome-screencast")]
     fn render_for_screen_cast(
         &mut self,
         renderer: &mut GlesRenderer,
@@ -5265,7 +5378,6 @@ impl Niri {
         }
     }
 
-    #[cfg(feature = "xdp-gnome-screencast")]
     fn render_windows_for_screen_cast(
         &mut self,
         renderer: &mut GlesRenderer,
@@ -5497,6 +5609,15 @@ impl Niri {
         Ok((sync, damages))
     }
 
+    #[cfg(not(feature = "xdp-gnome-screencast"))]
+    fn stop_cast(&mut self, session_id: usize) {
+        let _span = tracy_client::span!("Niri::stop_cast");
+
+        debug!(session_id, "StopCast");
+
+        self.casts.retain(|cast| cast.session_id != session_id);
+    }
+
     #[cfg(feature = "xdp-gnome-screencast")]
     fn stop_cast(&mut self, session_id: usize) {
         let _span = tracy_client::span!("Niri::stop_cast");
@@ -5531,7 +5652,15 @@ impl Niri {
     }
 
     #[cfg(not(feature = "xdp-gnome-screencast"))]
-    pub fn stop_casts_for_target(&mut self, _target: CastTarget) {}
+    pub fn stop_casts_for_target(&mut self, target: CastTarget) {
+        let _span = tracy_client::span!("Niri::stop_casts_for_target");
+
+        // Capture casts are all dynamic, so they switch to Nothing rather than stop.
+        if self.casts.iter().any(|cast| cast.target == target) {
+            self.event_loop
+                .insert_idle(|state| state.set_dynamic_cast_target(CastTarget::Nothing));
+        }
+    }
 
     #[cfg(feature = "xdp-gnome-screencast")]
     pub fn stop_casts_for_target(&mut self, target: CastTarget) {
@@ -5762,7 +5891,7 @@ impl Niri {
                         &state.niri.display_handle,
                         &state.niri.seat,
                         vec![String::from("image/png")],
-                        buf.clone(),
+                        SelectionData::Bytes(buf.clone()),
                     );
                 }
                 calloop::channel::Event::Closed => (),
@@ -5965,7 +6094,7 @@ impl Niri {
             let timer = Timer::from_duration(Duration::from_millis(1000));
             let deadline_token = self
                 .event_loop
-                .insert_source(timer, |_, _, state| {
+                .insert_timer(timer, |_, _, state| {
                     trace!("lock deadline expired, continuing");
                     state.niri.continue_to_locking();
                     TimeoutAction::Drop
@@ -6473,7 +6602,7 @@ impl Niri {
         let timer = Timer::from_duration(duration);
         let token = self
             .event_loop
-            .insert_source(timer, move |_, _, state| {
+            .insert_timer(timer, move |_, _, state| {
                 state.niri.pointer_inactivity_timer = None;
 
                 // If the pointer is already invisible, don't reset it back to Hidden causing one
@@ -6565,6 +6694,48 @@ impl ClientData for ClientState {
     fn disconnected(&self, _client_id: ClientId, _reason: DisconnectReason) {}
 }
 
+/// Returns the color of the backdrop behind the workspaces of an output.
+///
+/// The backdrop covers the whole output, so the configured clear color goes here rather than to
+/// the renderer's clear. It is opaque, unless the outputs are transparent, where it must not hide
+/// the desktop.
+fn output_backdrop_color(output_backdrop: Option<Color>, config: &Config) -> [f32; 4] {
+    if configured_transparency(config) {
+        // Fully zero, since premultiplied blending would still add a color with zero alpha.
+        return [0.; 4];
+    }
+
+    let mut color = output_backdrop
+        .or_else(|| configured_clear_color(config))
+        .unwrap_or(config.overview.backdrop_color)
+        .to_array_unpremul();
+    color[3] = 1.;
+    color
+}
+
+/// Creates the dynamic cast that records into `debug.screencast-capture-path`, if it's set.
+#[cfg(not(feature = "xdp-gnome-screencast"))]
+fn start_capture_cast(config: &Config) -> Option<Cast> {
+    let path = config.debug.screencast_capture_path.as_ref()?;
+    let path = match expand_home(path) {
+        Ok(Some(expanded)) => expanded,
+        Ok(None) => path.clone(),
+        Err(err) => {
+            warn!("error expanding ~ in the screencast capture path: {err:?}");
+            return None;
+        }
+    };
+
+    // Like a portal dynamic cast, it starts out as Nothing until a target is picked.
+    match Cast::new_capture(0, 0, CastTarget::Nothing, true, &path, 60_000) {
+        Ok(cast) => Some(cast),
+        Err(err) => {
+            warn!("error starting the screencast capture: {err:?}");
+            None
+        }
+    }
+}
+
 fn scale_relocate_crop<E: Element>(
     elem: E,
     output_scale: Scale<f64>,
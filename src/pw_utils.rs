@@ -49,7 +49,7 @@ use zbus::object_server::SignalEmitter;
 use crate::dbus::mutter_screen_cast::{self, CursorMode};
 use crate::niri::{CastTarget, State};
 use crate::render_helpers::{clear_dmabuf, render_to_dmabuf};
-use crate::utils::get_monotonic_time;
+use crate::utils::{get_monotonic_time, LoopHandleExt as _};
 
 // Give a 0.1 ms allowance for presentation time errors.
 const CAST_DELAY_ALLOWANCE: Duration = Duration::from_micros(100);
@@ -841,7 +841,7 @@ impl Cast {
         let timer = Timer::from_duration(duration);
         let token = self
             .event_loop
-            .insert_source(timer, move |_, _, state| {
+            .insert_timer(timer, move |_, _, state| {
                 // Guard against output disconnecting before the timer has a chance to run.
                 if state.niri.output_state.contains_key(&output) {
                     state.niri.queue_redraw(&output);
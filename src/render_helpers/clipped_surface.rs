@@ -87,8 +87,8 @@ impl<R: NiriRenderer> ClippedSurfaceRenderElement<R> {
         }
     }
 
-    pub fn shader(renderer: &mut R) -> Option<&GlesTexProgram> {
-        Shaders::get(renderer).clipped_surface.as_ref()
+    pub fn shader(renderer: &mut R) -> Option<GlesTexProgram> {
+        Shaders::get(renderer).clipped_surface.borrow().clone()
     }
 
     pub fn will_clip(
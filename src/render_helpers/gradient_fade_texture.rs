@@ -43,7 +43,7 @@ impl GradientFadeTextureRenderElement {
     }
 
     pub fn shader(renderer: &mut GlesRenderer) -> Option<GradientFadeShader> {
-        let program = Shaders::get(renderer).gradient_fade.clone();
+        let program = Shaders::get(renderer).gradient_fade.borrow().clone();
         program.map(GradientFadeShader)
     }
 }
@@ -1,5 +1,9 @@
+use std::borrow::Cow;
 use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::{env, fs, io};
 
+use anyhow::{bail, Context as _};
 use glam::Mat3;
 use smithay::backend::renderer::gles::{
     GlesError, GlesFrame, GlesRenderer, GlesTexProgram, Uniform, UniformName, UniformType,
@@ -10,11 +14,11 @@ use super::renderer::NiriRenderer;
 use super::shader_element::ShaderProgram;
 
 pub struct Shaders {
-    pub border: Option<ShaderProgram>,
-    pub shadow: Option<ShaderProgram>,
-    pub clipped_surface: Option<GlesTexProgram>,
-    pub resize: Option<ShaderProgram>,
-    pub gradient_fade: Option<GlesTexProgram>,
+    pub border: RefCell<Option<ShaderProgram>>,
+    pub shadow: RefCell<Option<ShaderProgram>>,
+    pub clipped_surface: RefCell<Option<GlesTexProgram>>,
+    pub resize: RefCell<Option<ShaderProgram>>,
+    pub gradient_fade: RefCell<Option<GlesTexProgram>>,
     pub custom_resize: RefCell<Option<ShaderProgram>>,
     pub custom_close: RefCell<Option<ShaderProgram>>,
     pub custom_open: RefCell<Option<ShaderProgram>>,
@@ -29,13 +33,64 @@ pub enum ProgramType {
     Open,
 }
 
-impl Shaders {
-    fn compile(renderer: &mut GlesRenderer) -> Self {
-        let _span = tracy_client::span!("Shaders::compile");
+/// Sources of the built-in shaders.
+struct BuiltinSources {
+    border: Cow<'static, str>,
+    shadow: Cow<'static, str>,
+    clipped_surface: Cow<'static, str>,
+    resize: Cow<'static, str>,
+    gradient_fade: Cow<'static, str>,
+}
+
+/// Built-in programs, which [`reload`] swaps out all at once.
+struct BuiltinPrograms {
+    border: Option<ShaderProgram>,
+    shadow: Option<ShaderProgram>,
+    clipped_surface: Option<GlesTexProgram>,
+    resize: Option<ShaderProgram>,
+    gradient_fade: Option<GlesTexProgram>,
+}
+
+impl BuiltinSources {
+    fn embedded() -> Self {
+        Self {
+            border: Cow::Borrowed(include_str!("border.frag")),
+            shadow: Cow::Borrowed(include_str!("shadow.frag")),
+            clipped_surface: Cow::Borrowed(include_str!("clipped_surface.frag")),
+            resize: Cow::Borrowed(include_str!("resize.frag")),
+            gradient_fade: Cow::Borrowed(include_str!("gradient_fade.frag")),
+        }
+    }
+
+    /// Reads the sources from `dir`, keeping the embedded ones for files that aren't there.
+    fn read(dir: &Path) -> anyhow::Result<Self> {
+        let read = |name: &str, embedded: Cow<'static, str>| -> anyhow::Result<_> {
+            let path = dir.join(name);
+            match fs::read_to_string(&path) {
+                Ok(src) => Ok(Cow::Owned(src)),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(embedded),
+                Err(err) => Err(err).with_context(|| format!("error reading {path:?}")),
+            }
+        };
+
+        let embedded = Self::embedded();
+        Ok(Self {
+            border: read("border.frag", embedded.border)?,
+            shadow: read("shadow.frag", embedded.shadow)?,
+            clipped_surface: read("clipped_surface.frag", embedded.clipped_surface)?,
+            resize: read("resize.frag", embedded.resize)?,
+            gradient_fade: read("gradient_fade.frag", embedded.gradient_fade)?,
+        })
+    }
+}
+
+impl BuiltinPrograms {
+    fn compile(renderer: &mut GlesRenderer, sources: &BuiltinSources) -> Self {
+        let _span = tracy_client::span!("BuiltinPrograms::compile");
 
         let border = ShaderProgram::compile(
             renderer,
-            include_str!("border.frag"),
+            &sources.border,
             &[
                 UniformName::new("colorspace", UniformType::_1f),
                 UniformName::new("hue_interpolation", UniformType::_1f),
@@ -58,7 +113,7 @@ impl Shaders {
 
         let shadow = ShaderProgram::compile(
             renderer,
-            include_str!("shadow.frag"),
+            &sources.shadow,
             &[
                 UniformName::new("shadow_color", UniformType::_4f),
                 UniformName::new("sigma", UniformType::_1f),
@@ -78,7 +133,7 @@ impl Shaders {
 
         let clipped_surface = renderer
             .compile_custom_texture_shader(
-                include_str!("clipped_surface.frag"),
+                &sources.clipped_surface,
                 &[
                     UniformName::new("niri_scale", UniformType::_1f),
                     UniformName::new("geo_size", UniformType::_2f),
@@ -91,7 +146,7 @@ impl Shaders {
             })
             .ok();
 
-        let resize = compile_resize_program(renderer, include_str!("resize.frag"))
+        let resize = compile_resize_program(renderer, &sources.resize)
             .map_err(|err| {
                 warn!("error compiling resize shader: {err:?}");
             })
@@ -99,7 +154,7 @@ impl Shaders {
 
         let gradient_fade = renderer
             .compile_custom_texture_shader(
-                include_str!("gradient_fade.frag"),
+                &sources.gradient_fade,
                 &[UniformName::new("cutoff", UniformType::_2f)],
             )
             .map_err(|err| {
@@ -113,12 +168,52 @@ impl Shaders {
             clipped_surface,
             resize,
             gradient_fade,
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        self.border.is_some()
+            && self.shadow.is_some()
+            && self.clipped_surface.is_some()
+            && self.resize.is_some()
+            && self.gradient_fade.is_some()
+    }
+
+    fn destroy(self, renderer: &mut GlesRenderer) {
+        // The texture programs clean up after themselves when dropped.
+        for program in [self.border, self.shadow, self.resize].into_iter().flatten() {
+            if let Err(err) = program.destroy(renderer) {
+                warn!("error destroying shader: {err:?}");
+            }
+        }
+    }
+}
+
+impl Shaders {
+    fn new(programs: BuiltinPrograms) -> Self {
+        Self {
+            border: RefCell::new(programs.border),
+            shadow: RefCell::new(programs.shadow),
+            clipped_surface: RefCell::new(programs.clipped_surface),
+            resize: RefCell::new(programs.resize),
+            gradient_fade: RefCell::new(programs.gradient_fade),
             custom_resize: RefCell::new(None),
             custom_close: RefCell::new(None),
             custom_open: RefCell::new(None),
         }
     }
 
+    /// Puts in new built-in programs and returns the previous ones.
+    fn replace_builtin_programs(&self, programs: BuiltinPrograms) -> BuiltinPrograms {
+        BuiltinPrograms {
+            border: self.border.replace(programs.border),
+            shadow: self.shadow.replace(programs.shadow),
+            clipped_surface: self.clipped_surface.replace(programs.clipped_surface),
+            resize: self.resize.replace(programs.resize),
+            gradient_fade: self.gradient_fade.replace(programs.gradient_fade),
+        }
+    }
+
     pub fn get_from_frame<'a>(frame: &'a mut GlesFrame<'_, '_>) -> &'a Self {
         let data = frame.egl_context().user_data();
         data.get()
@@ -155,13 +250,13 @@ impl Shaders {
 
     pub fn program(&self, program: ProgramType) -> Option<ShaderProgram> {
         match program {
-            ProgramType::Border => self.border.clone(),
-            ProgramType::Shadow => self.shadow.clone(),
+            ProgramType::Border => self.border.borrow().clone(),
+            ProgramType::Shadow => self.shadow.borrow().clone(),
             ProgramType::Resize => self
                 .custom_resize
                 .borrow()
                 .clone()
-                .or_else(|| self.resize.clone()),
+                .or_else(|| self.resize.borrow().clone()),
             ProgramType::Close => self.custom_close.borrow().clone(),
             ProgramType::Open => self.custom_open.borrow().clone(),
         }
@@ -169,13 +264,43 @@ impl Shaders {
 }
 
 pub fn init(renderer: &mut GlesRenderer) {
-    let shaders = Shaders::compile(renderer);
+    let programs = BuiltinPrograms::compile(renderer, &BuiltinSources::embedded());
+    let shaders = Shaders::new(programs);
     let data = renderer.egl_context().user_data();
     if !data.insert_if_missing(|| shaders) {
         error!("shaders were already compiled");
     }
 }
 
+/// Returns the directory that [`reload`] reads the shaders from.
+///
+/// That's `MACNIRI_SHADER_DIR` if set, or the shader directory of the source tree that niri was
+/// built from, where they can be edited right away.
+pub fn source_dir() -> PathBuf {
+    env::var_os("MACNIRI_SHADER_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(env!("CARGO_MANIFEST_DIR")).join("src/render_helpers/shaders"))
+}
+
+/// Recompiles the built-in shaders from the `.frag` files in `dir`, using the embedded source for
+/// files that aren't there.
+///
+/// If any of them fails to compile, the renderer keeps all of its current programs, so a typo
+/// doesn't leave it with a mix of old and new ones, or without borders. Custom shaders from the
+/// config stay as they are.
+pub fn reload(renderer: &mut GlesRenderer, dir: &Path) -> anyhow::Result<()> {
+    let sources = BuiltinSources::read(dir)?;
+    let programs = BuiltinPrograms::compile(renderer, &sources);
+    if !programs.is_complete() {
+        programs.destroy(renderer);
+        bail!("some shaders failed to compile, see the warnings above");
+    }
+
+    let prev = Shaders::get(renderer).replace_builtin_programs(programs);
+    prev.destroy(renderer);
+    Ok(())
+}
+
 fn compile_resize_program(
     renderer: &mut GlesRenderer,
     src: &str,
@@ -318,3 +443,31 @@ pub fn mat3_uniform(name: &str, mat: Mat3) -> Uniform<'_> {
         },
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reload_reads_sources_that_exist() {
+        let sh = xshell::Shell::new().unwrap();
+        let temp_dir = sh.create_temp_dir().unwrap();
+        fs::write(temp_dir.path().join("border.frag"), "void main() {}").unwrap();
+
+        let sources = BuiltinSources::read(temp_dir.path()).unwrap();
+        assert_eq!(sources.border, "void main() {}");
+        assert_eq!(sources.shadow, include_str!("shadow.frag"));
+        assert_eq!(sources.resize, include_str!("resize.frag"));
+    }
+
+    #[test]
+    fn source_tree_has_all_shaders() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/render_helpers/shaders");
+        let sources = BuiltinSources::read(&dir).unwrap();
+        assert!(matches!(sources.border, Cow::Owned(_)));
+        assert!(matches!(sources.shadow, Cow::Owned(_)));
+        assert!(matches!(sources.clipped_surface, Cow::Owned(_)));
+        assert!(matches!(sources.resize, Cow::Owned(_)));
+        assert!(matches!(sources.gradient_fade, Cow::Owned(_)));
+    }
+}
@@ -0,0 +1,63 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use std::{fs, thread};
+
+use niri_config::Config;
+
+use super::*;
+use crate::niri::{CastTarget, RedrawState};
+
+fn capture_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("niri-capture-{name}-{}.png", std::process::id()))
+}
+
+fn config(path: &Path) -> Config {
+    let mut config = Config::default();
+    config.debug.screencast_capture_path = Some(path.to_owned());
+    config
+}
+
+/// Waits for the encoder thread to write out `path`.
+fn wait_for_frame(path: &Path) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !path.exists() {
+        assert!(Instant::now() < deadline, "{path:?} was never written");
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn capture_cast_follows_reload() {
+    let first = capture_path("first");
+    let second = capture_path("second");
+
+    let mut f = Fixture::with_config(config(&first));
+    f.niri_state().backend.headless().add_renderer().unwrap();
+    f.add_output(1, (64, 32));
+
+    let output = f.niri_output(1);
+    let target = CastTarget::Output(output.downgrade());
+    let state = f.niri_state();
+    state.set_dynamic_cast_target(target.clone());
+    state.niri.redraw_queued_outputs(&mut state.backend);
+    wait_for_frame(&first);
+
+    let state = f.niri_state();
+    state.reload_config(Ok(config(&second)));
+
+    // The new cast records the same output, and the next frame comes without new damage.
+    assert_eq!(state.niri.casts.len(), 1);
+    assert!(state.niri.casts[0].target == target);
+    let redraw_state = &state.niri.output_state[&output].redraw_state;
+    assert!(matches!(redraw_state, RedrawState::Queued));
+    state.niri.redraw_queued_outputs(&mut state.backend);
+    wait_for_frame(&second);
+
+    // Unsetting the path stops the capture.
+    let state = f.niri_state();
+    state.reload_config(Ok(Config::default()));
+    assert!(state.niri.casts.is_empty());
+
+    fs::remove_file(first).unwrap();
+    fs::remove_file(second).unwrap();
+}
@@ -2,6 +2,10 @@ use std::cmp::min;
 use std::collections::HashMap;
 use std::fmt;
 use std::fmt::Write as _;
+use std::fs::File;
+use std::io::Write as _;
+use std::os::fd::{AsFd as _, OwnedFd};
+use std::os::unix::fs::FileExt as _;
 use std::os::unix::net::UnixStream;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
@@ -9,7 +13,17 @@ use std::time::Duration;
 
 use calloop::EventLoop;
 use calloop_wayland_source::WaylandSource;
+use fractional_scale::v1::client::wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1;
+use fractional_scale::v1::client::wp_fractional_scale_v1::{self, WpFractionalScaleV1};
 use single_pixel_buffer::v1::client::wp_single_pixel_buffer_manager_v1::WpSinglePixelBufferManagerV1;
+use smithay::reexports::rustix::pipe::pipe;
+use smithay::reexports::wayland_protocols::wp::fractional_scale;
+use smithay::reexports::wayland_protocols::wp::idle_inhibit::zv1::client::zwp_idle_inhibit_manager_v1::ZwpIdleInhibitManagerV1;
+use smithay::reexports::wayland_protocols::wp::idle_inhibit::zv1::client::zwp_idle_inhibitor_v1::ZwpIdleInhibitorV1;
+use smithay::reexports::wayland_protocols::wp::primary_selection::zv1::client::zwp_primary_selection_device_manager_v1::ZwpPrimarySelectionDeviceManagerV1;
+use smithay::reexports::wayland_protocols::wp::primary_selection::zv1::client::zwp_primary_selection_device_v1::{self, ZwpPrimarySelectionDeviceV1};
+use smithay::reexports::wayland_protocols::wp::primary_selection::zv1::client::zwp_primary_selection_offer_v1::{self, ZwpPrimarySelectionOfferV1};
+use smithay::reexports::wayland_protocols::wp::primary_selection::zv1::client::zwp_primary_selection_source_v1::{self, ZwpPrimarySelectionSourceV1};
 use smithay::reexports::wayland_protocols::wp::single_pixel_buffer;
 use smithay::reexports::wayland_protocols::wp::viewporter::client::wp_viewport::WpViewport;
 use smithay::reexports::wayland_protocols::wp::viewporter::client::wp_viewporter::WpViewporter;
@@ -28,10 +42,14 @@ use wayland_client::protocol::wl_buffer::{self, WlBuffer};
 use wayland_client::protocol::wl_callback::{self, WlCallback};
 use wayland_client::protocol::wl_compositor::WlCompositor;
 use wayland_client::protocol::wl_display::WlDisplay;
+use wayland_client::protocol::wl_keyboard::{self, WlKeyboard};
 use wayland_client::protocol::wl_output::{self, WlOutput};
 use wayland_client::protocol::wl_registry::{self, WlRegistry};
+use wayland_client::protocol::wl_seat::{self, WlSeat};
 use wayland_client::protocol::wl_surface::{self, WlSurface};
-use wayland_client::{Connection, Dispatch, Proxy as _, QueueHandle};
+use wayland_client::{Connection, Dispatch, Proxy as _, QueueHandle, WEnum};
+use wayland_protocols_misc::zwp_virtual_keyboard_v1::client::zwp_virtual_keyboard_manager_v1::ZwpVirtualKeyboardManagerV1;
+use wayland_protocols_misc::zwp_virtual_keyboard_v1::client::zwp_virtual_keyboard_v1::ZwpVirtualKeyboardV1;
 
 use crate::utils::id::IdCounter;
 
@@ -49,15 +67,46 @@ pub struct State {
 
     pub globals: Vec<Global>,
     pub outputs: HashMap<WlOutput, String>,
+    /// Last integer scale of the outputs.
+    pub output_scales: HashMap<WlOutput, i32>,
 
     pub compositor: Option<WlCompositor>,
     pub xdg_wm_base: Option<XdgWmBase>,
     pub layer_shell: Option<ZwlrLayerShellV1>,
     pub spbm: Option<WpSinglePixelBufferManagerV1>,
     pub viewporter: Option<WpViewporter>,
+    pub fractional_scale_manager: Option<WpFractionalScaleManagerV1>,
+    pub idle_inhibit_manager: Option<ZwpIdleInhibitManagerV1>,
+    pub primary_selection_manager: Option<ZwpPrimarySelectionDeviceManagerV1>,
+    pub seat: Option<WlSeat>,
+    pub virtual_keyboard_manager: Option<ZwpVirtualKeyboardManagerV1>,
 
     pub windows: Vec<Window>,
     pub layers: Vec<LayerSurface>,
+    pub keyboard: Keyboard,
+    pub primary_selection: PrimarySelection,
+    /// Last preferred fractional scale of the surfaces, in 120ths.
+    pub preferred_scales: HashMap<WlSurface, u32>,
+}
+
+/// What the `wl_keyboard` of the seat received.
+#[derive(Debug, Default)]
+pub struct Keyboard {
+    pub focus: Option<WlSurface>,
+    /// Serial of the last enter.
+    pub serial: u32,
+    /// Text of every keymap sent, oldest first.
+    pub keymaps: Vec<String>,
+    pub keys: Vec<(u32, wl_keyboard::KeyState)>,
+}
+
+/// Primary selection device of the seat and what it received.
+#[derive(Debug, Default)]
+pub struct PrimarySelection {
+    pub device: Option<ZwpPrimarySelectionDeviceV1>,
+    /// Mime types of every offer.
+    pub offers: HashMap<ZwpPrimarySelectionOfferV1, Vec<String>>,
+    pub selection: Option<ZwpPrimarySelectionOfferV1>,
 }
 
 pub struct Window {
@@ -176,13 +225,22 @@ impl Client {
             qh: qh.clone(),
             globals: Vec::new(),
             outputs: HashMap::new(),
+            output_scales: HashMap::new(),
             compositor: None,
             xdg_wm_base: None,
             layer_shell: None,
             spbm: None,
             viewporter: None,
+            fractional_scale_manager: None,
+            idle_inhibit_manager: None,
+            primary_selection_manager: None,
+            seat: None,
+            virtual_keyboard_manager: None,
             windows: Vec::new(),
             layers: Vec::new(),
+            keyboard: Keyboard::default(),
+            primary_selection: PrimarySelection::default(),
+            preferred_scales: HashMap::new(),
         };
 
         Self {
@@ -233,6 +291,74 @@ impl Client {
         self.state.layer(surface)
     }
 
+    /// Asks for the preferred fractional scale of the surface, see [`State::preferred_scales`].
+    pub fn get_fractional_scale(&mut self, surface: &WlSurface) -> WpFractionalScaleV1 {
+        let manager = self.state.fractional_scale_manager.as_ref().unwrap();
+        manager.get_fractional_scale(surface, &self.qh, surface.clone())
+    }
+
+    pub fn create_idle_inhibitor(&mut self, surface: &WlSurface) -> ZwpIdleInhibitorV1 {
+        let manager = self.state.idle_inhibit_manager.as_ref().unwrap();
+        manager.create_inhibitor(surface, &self.qh, ())
+    }
+
+    /// Creates the primary selection device, which receives offers while the client has the
+    /// keyboard focus.
+    pub fn get_primary_selection_device(&mut self) {
+        let manager = self.state.primary_selection_manager.as_ref().unwrap();
+        let seat = self.state.seat.as_ref().unwrap();
+        let device = manager.get_device(seat, &self.qh, ());
+        self.state.primary_selection.device = Some(device);
+    }
+
+    /// Sets the primary selection to `text`, like when selecting it.
+    pub fn set_primary_selection(&mut self, text: &str) -> ZwpPrimarySelectionSourceV1 {
+        let manager = self.state.primary_selection_manager.as_ref().unwrap();
+        let source = manager.create_source(&self.qh, text.to_owned());
+        source.offer(String::from("text/plain;charset=utf-8"));
+
+        let device = self.state.primary_selection.device.as_ref().unwrap();
+        device.set_selection(Some(&source), self.state.keyboard.serial);
+        source
+    }
+
+    /// Asks for the current primary selection as `mime_type`, returning the fd to read it from.
+    pub fn receive_primary_selection(&mut self, mime_type: &str) -> OwnedFd {
+        let offer = self.state.primary_selection.selection.as_ref().unwrap();
+        let (read_fd, write_fd) = pipe().unwrap();
+        offer.receive(mime_type.to_owned(), write_fd.as_fd());
+        read_fd
+    }
+
+    /// Creates a virtual keyboard with this xkb keymap.
+    pub fn create_virtual_keyboard(&mut self, keymap: &str) -> ZwpVirtualKeyboardV1 {
+        let manager = self.state.virtual_keyboard_manager.as_ref().unwrap();
+        let seat = self.state.seat.as_ref().unwrap();
+        let keyboard = manager.create_virtual_keyboard(seat, &self.qh, ());
+
+        // The keymap has to be a file that the compositor can map.
+        let path = std::env::temp_dir().join(format!(
+            "niri-test-keymap-{}-{}",
+            std::process::id(),
+            self.id.0
+        ));
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut text = keymap.as_bytes().to_vec();
+        text.push(0);
+        file.write_all_at(&text, 0).unwrap();
+
+        let format = wl_keyboard::KeymapFormat::XkbV1 as u32;
+        keyboard.keymap(format, file.as_fd(), text.len() as u32);
+        keyboard
+    }
+
     pub fn output(&mut self, name: &str) -> WlOutput {
         self.state
             .outputs
@@ -446,6 +572,12 @@ impl LayerSurface {
         self.surface.attach(Some(&buffer), 0, 0);
     }
 
+    /// Attaches a single-pixel buffer of this color, every channel goes up to `u32::MAX`.
+    pub fn attach_new_color_buffer(&self, r: u32, g: u32, b: u32, a: u32) {
+        let buffer = self.spbm.create_u32_rgba_buffer(r, g, b, a, &self.qh, ());
+        self.surface.attach(Some(&buffer), 0, 0);
+    }
+
     pub fn attach_null(&self) {
         self.surface.attach(None, 0, 0);
     }
@@ -518,6 +650,24 @@ impl Dispatch<WlRegistry, ()> for State {
                 } else if interface == WpViewporter::interface().name {
                     let version = min(version, WpViewporter::interface().version);
                     state.viewporter = Some(registry.bind(name, version, qh, ()));
+                } else if interface == WpFractionalScaleManagerV1::interface().name {
+                    let version = min(version, WpFractionalScaleManagerV1::interface().version);
+                    state.fractional_scale_manager = Some(registry.bind(name, version, qh, ()));
+                } else if interface == ZwpIdleInhibitManagerV1::interface().name {
+                    let version = min(version, ZwpIdleInhibitManagerV1::interface().version);
+                    state.idle_inhibit_manager = Some(registry.bind(name, version, qh, ()));
+                } else if interface == ZwpPrimarySelectionDeviceManagerV1::interface().name {
+                    let version =
+                        min(version, ZwpPrimarySelectionDeviceManagerV1::interface().version);
+                    state.primary_selection_manager = Some(registry.bind(name, version, qh, ()));
+                } else if interface == WlSeat::interface().name {
+                    let version = min(version, WlSeat::interface().version);
+                    let seat: WlSeat = registry.bind(name, version, qh, ());
+                    seat.get_keyboard(qh, ());
+                    state.seat = Some(seat);
+                } else if interface == ZwpVirtualKeyboardManagerV1::interface().name {
+                    let version = min(version, ZwpVirtualKeyboardManagerV1::interface().version);
+                    state.virtual_keyboard_manager = Some(registry.bind(name, version, qh, ()));
                 } else if interface == WlOutput::interface().name {
                     let version = min(version, WlOutput::interface().version);
                     let output = registry.bind(name, version, qh, ());
@@ -550,7 +700,9 @@ impl Dispatch<WlOutput, ()> for State {
             wl_output::Event::Geometry { .. } => (),
             wl_output::Event::Mode { .. } => (),
             wl_output::Event::Done => (),
-            wl_output::Event::Scale { .. } => (),
+            wl_output::Event::Scale { factor } => {
+                state.output_scales.insert(output.clone(), factor);
+            }
             wl_output::Event::Name { name } => {
                 *state.outputs.get_mut(output).unwrap() = name;
             }
@@ -775,3 +927,221 @@ impl Dispatch<WpViewport, ()> for State {
         unreachable!()
     }
 }
+
+impl Dispatch<ZwpIdleInhibitManagerV1, ()> for State {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ZwpIdleInhibitManagerV1,
+        _event: <ZwpIdleInhibitManagerV1 as wayland_client::Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        unreachable!()
+    }
+}
+
+impl Dispatch<ZwpIdleInhibitorV1, ()> for State {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ZwpIdleInhibitorV1,
+        _event: <ZwpIdleInhibitorV1 as wayland_client::Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        unreachable!()
+    }
+}
+
+impl Dispatch<WpFractionalScaleManagerV1, ()> for State {
+    fn event(
+        _state: &mut Self,
+        _proxy: &WpFractionalScaleManagerV1,
+        _event: <WpFractionalScaleManagerV1 as wayland_client::Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        unreachable!()
+    }
+}
+
+impl Dispatch<WpFractionalScaleV1, WlSurface> for State {
+    fn event(
+        state: &mut Self,
+        _proxy: &WpFractionalScaleV1,
+        event: <WpFractionalScaleV1 as wayland_client::Proxy>::Event,
+        surface: &WlSurface,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        match event {
+            wp_fractional_scale_v1::Event::PreferredScale { scale } => {
+                state.preferred_scales.insert(surface.clone(), scale);
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Dispatch<WlSeat, ()> for State {
+    fn event(
+        _state: &mut Self,
+        _proxy: &WlSeat,
+        event: <WlSeat as wayland_client::Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_seat::Event::Capabilities { .. } => (),
+            wl_seat::Event::Name { .. } => (),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Dispatch<WlKeyboard, ()> for State {
+    fn event(
+        state: &mut Self,
+        _proxy: &WlKeyboard,
+        event: <WlKeyboard as wayland_client::Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        let keyboard = &mut state.keyboard;
+        match event {
+            wl_keyboard::Event::Keymap { fd, size, .. } => {
+                keyboard.keymaps.push(read_keymap(fd, size));
+            }
+            wl_keyboard::Event::Enter { serial, surface, .. } => {
+                keyboard.focus = Some(surface);
+                keyboard.serial = serial;
+            }
+            wl_keyboard::Event::Leave { .. } => keyboard.focus = None,
+            wl_keyboard::Event::Key { key, state, .. } => {
+                let WEnum::Value(state) = state else {
+                    unreachable!()
+                };
+                keyboard.keys.push((key, state));
+            }
+            wl_keyboard::Event::Modifiers { .. } => (),
+            wl_keyboard::Event::RepeatInfo { .. } => (),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Dispatch<ZwpPrimarySelectionDeviceManagerV1, ()> for State {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ZwpPrimarySelectionDeviceManagerV1,
+        _event: <ZwpPrimarySelectionDeviceManagerV1 as wayland_client::Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        unreachable!()
+    }
+}
+
+impl Dispatch<ZwpPrimarySelectionDeviceV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _proxy: &ZwpPrimarySelectionDeviceV1,
+        event: <ZwpPrimarySelectionDeviceV1 as wayland_client::Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        let primary_selection = &mut state.primary_selection;
+        match event {
+            zwp_primary_selection_device_v1::Event::DataOffer { offer } => {
+                primary_selection.offers.insert(offer, Vec::new());
+            }
+            zwp_primary_selection_device_v1::Event::Selection { id } => {
+                primary_selection.selection = id;
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    wayland_client::event_created_child!(State, ZwpPrimarySelectionDeviceV1, [
+        zwp_primary_selection_device_v1::EVT_DATA_OFFER_OPCODE => (ZwpPrimarySelectionOfferV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwpPrimarySelectionOfferV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        offer: &ZwpPrimarySelectionOfferV1,
+        event: <ZwpPrimarySelectionOfferV1 as wayland_client::Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwp_primary_selection_offer_v1::Event::Offer { mime_type } => {
+                let offers = &mut state.primary_selection.offers;
+                offers.entry(offer.clone()).or_default().push(mime_type);
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Dispatch<ZwpPrimarySelectionSourceV1, String> for State {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ZwpPrimarySelectionSourceV1,
+        event: <ZwpPrimarySelectionSourceV1 as wayland_client::Proxy>::Event,
+        text: &String,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwp_primary_selection_source_v1::Event::Send { fd, .. } => {
+                File::from(fd).write_all(text.as_bytes()).unwrap();
+            }
+            zwp_primary_selection_source_v1::Event::Cancelled => (),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Dispatch<ZwpVirtualKeyboardManagerV1, ()> for State {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ZwpVirtualKeyboardManagerV1,
+        _event: <ZwpVirtualKeyboardManagerV1 as wayland_client::Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        unreachable!()
+    }
+}
+
+impl Dispatch<ZwpVirtualKeyboardV1, ()> for State {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ZwpVirtualKeyboardV1,
+        _event: <ZwpVirtualKeyboardV1 as wayland_client::Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {
+        unreachable!()
+    }
+}
+
+/// Reads a keymap from the `wl_keyboard.keymap` event, without the trailing NUL.
+fn read_keymap(fd: OwnedFd, size: u32) -> String {
+    // Compositors may share one file between keymap events, so don't move its offset.
+    let mut buf = vec![0; size as usize];
+    File::from(fd).read_exact_at(&mut buf, 0).unwrap();
+    let text = String::from_utf8(buf).unwrap();
+    text.trim_end_matches('\0').to_owned()
+}
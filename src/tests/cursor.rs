@@ -0,0 +1,59 @@
+use niri_config::Config;
+use smithay::utils::Point;
+
+use super::render_snapshot::{render_frame, Frame};
+use super::*;
+
+/// Returns the width and height of what differs from the bottom right pixel.
+fn drawn_size(frame: &Frame) -> (u32, u32) {
+    let pixel = |x: u32, y: u32| {
+        let idx = ((y * frame.width + x) * 4) as usize;
+        &frame.pixels[idx..idx + 4]
+    };
+    let background = pixel(frame.width - 1, frame.height - 1);
+
+    let (mut min, mut max) = ((u32::MAX, u32::MAX), (0, 0));
+    for y in 0..frame.height {
+        for x in 0..frame.width {
+            if pixel(x, y) != background {
+                min = (min.0.min(x), min.1.min(y));
+                max = (max.0.max(x), max.1.max(y));
+            }
+        }
+    }
+    assert!(min.0 <= max.0, "nothing was drawn");
+    (max.0 - min.0 + 1, max.1 - min.1 + 1)
+}
+
+#[test]
+fn cursor_scales_with_output() {
+    // A missing theme makes for the fallback cursor, which has a single 64 pixel image.
+    let config = r##"
+cursor {
+    xcursor-theme "niri-test-missing-theme"
+    xcursor-size 32
+}
+
+output "headless-1" {
+    scale 2
+}
+"##;
+    let mut f = Fixture::with_config(Config::parse_mem(config).unwrap());
+    f.niri_state().backend.headless().add_renderer().unwrap();
+    f.add_output(1, (128, 128));
+    f.add_output(2, (64, 64));
+
+    // Both outputs are 64 logical pixels wide, side by side.
+    f.niri_state().move_cursor(Point::from((8., 8.)));
+    let output = f.niri_output(1);
+    let (w2, h2) = drawn_size(&render_frame(f.niri_state(), &output));
+
+    f.niri_state().move_cursor(Point::from((72., 8.)));
+    let output = f.niri_output(2);
+    let (w1, h1) = drawn_size(&render_frame(f.niri_state(), &output));
+
+    // The cursor is the same logical size on both, so twice as many pixels at scale 2.
+    assert!(w1 <= 32 && h1 <= 32, "{w1}×{h1} at scale 1");
+    assert!(w2.abs_diff(w1 * 2) <= 2, "{w2}×{h2} at scale 2, {w1}×{h1} at scale 1");
+    assert!(h2.abs_diff(h1 * 2) <= 2, "{w2}×{h2} at scale 2, {w1}×{h1} at scale 1");
+}
@@ -7,8 +7,11 @@ use calloop::generic::Generic;
 use calloop::{EventLoop, Interest, LoopHandle, Mode, PostAction};
 use niri_config::Config;
 use smithay::output::Output;
+use smithay::reexports::wayland_protocols_wlr::layer_shell::v1::client::zwlr_layer_shell_v1::Layer;
+use smithay::reexports::wayland_protocols_wlr::layer_shell::v1::client::zwlr_layer_surface_v1::Anchor;
+use wayland_client::protocol::wl_surface::WlSurface;
 
-use super::client::{Client, ClientId};
+use super::client::{Client, ClientId, LayerConfigureProps};
 use super::server::Server;
 use crate::niri::{NewClient, Niri};
 
@@ -132,6 +135,34 @@ impl Fixture {
         }
     }
 
+    /// Adds a client with a red bar along the top of the output, 8 logical pixels tall.
+    ///
+    /// The bar is an overlay layer surface with a buffer `width` pixels wide, for render tests.
+    pub fn add_red_bar(&mut self, width: u16) -> (ClientId, WlSurface) {
+        let id = self.add_client();
+        let layer = self.client(id).create_layer(None, Layer::Overlay, "");
+        let surface = layer.surface.clone();
+        layer.set_configure_props(LayerConfigureProps {
+            anchor: Some(Anchor::Left | Anchor::Right | Anchor::Top),
+            size: Some((0, 8)),
+            ..Default::default()
+        });
+        layer.commit();
+        self.roundtrip(id);
+
+        self.paint_red_bar(id, &surface, width);
+        (id, surface)
+    }
+
+    /// Attaches a new red buffer `width` pixels wide to the bar from [`Fixture::add_red_bar`].
+    pub fn paint_red_bar(&mut self, id: ClientId, surface: &WlSurface, width: u16) {
+        let layer = self.client(id).layer(surface);
+        layer.attach_new_color_buffer(u32::MAX, 0, 0, u32::MAX);
+        layer.set_size(width, 8);
+        layer.ack_last_and_commit();
+        self.double_roundtrip(id);
+    }
+
     /// Roundtrip twice in a row.
     ///
     /// For some reason, when running tests on many threads at once, a single roundtrip is
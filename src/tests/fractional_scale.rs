@@ -0,0 +1,92 @@
+use niri_config::Config;
+use smithay::output::Scale;
+
+use super::render_snapshot::render_frame;
+use super::*;
+
+fn scale_config(scale: &str) -> Config {
+    let config = format!(
+        r##"
+output "headless-1" {{
+    scale {scale}
+}}
+"##
+    );
+    Config::parse_mem(&config).unwrap()
+}
+
+#[test]
+fn preferred_scale_is_exact() {
+    let mut f = Fixture::with_config(scale_config("1.5"));
+    f.add_output(1, (1920, 1080));
+
+    let id = f.add_client();
+    let window = f.client(id).create_window();
+    let surface = window.surface.clone();
+    f.client(id).get_fractional_scale(&surface);
+    f.client(id).window(&surface).commit();
+    f.roundtrip(id);
+
+    let window = f.client(id).window(&surface);
+    window.attach_new_buffer();
+    window.ack_last_and_commit();
+    f.double_roundtrip(id);
+
+    // 1.5 in 120ths, rather than rounded up to 2.
+    let scales = &f.client(id).state.preferred_scales;
+    assert_eq!(scales.get(&surface), Some(&180));
+
+    // Like when the window moves to a display with another scale.
+    let outputs = scale_config("1.25").outputs;
+    f.niri().config.borrow_mut().outputs = outputs;
+    f.niri_state().reload_output_config();
+    f.double_roundtrip(id);
+
+    let scales = &f.client(id).state.preferred_scales;
+    assert_eq!(scales.get(&surface), Some(&150));
+}
+
+#[test]
+fn fractional_scale_renders_crisp_edges() {
+    let mut f = Fixture::with_config(scale_config("1.5"));
+    f.niri_state().backend.headless().add_renderer().unwrap();
+    f.add_output(1, (96, 48));
+
+    // Draw a red bar, 8 logical pixels tall, along the top of the output.
+    f.add_red_bar(64);
+
+    let output = f.niri_output(1);
+    let frame = render_frame(f.niri_state(), &output);
+    let pixel = |x: u32, y: u32| {
+        let idx = ((y * frame.width + x) * 4) as usize;
+        &frame.pixels[idx..idx + 4]
+    };
+
+    // At 1.5, the bar covers exactly 12 rows, with no blending into the row below.
+    for x in [0, 47, 95] {
+        assert_eq!(pixel(x, 0), [255, 0, 0, 255]);
+        assert_eq!(pixel(x, 11), [255, 0, 0, 255]);
+        assert_eq!(pixel(x, 12), pixel(x, 47));
+    }
+}
+
+#[test]
+fn wl_output_scale_follows_output() {
+    let mut f = Fixture::new();
+    f.add_output(1, (1920, 1080));
+
+    let id = f.add_client();
+    f.roundtrip(id);
+    let wl_output = f.client(id).output("headless-1");
+    let scales = &f.client(id).state.output_scales;
+    assert_eq!(scales.get(&wl_output), Some(&1));
+
+    // Like the winit backend when the window moves to a Retina display.
+    let output = f.niri_output(1);
+    output.change_current_state(None, None, Some(Scale::Fractional(2.)), None);
+    f.niri().output_resized(&output);
+    f.double_roundtrip(id);
+
+    let scales = &f.client(id).state.output_scales;
+    assert_eq!(scales.get(&wl_output), Some(&2));
+}
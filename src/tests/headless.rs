@@ -0,0 +1,303 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use calloop::EventLoop;
+use niri_config::{Action, Config};
+use niri_ipc::Event;
+use smithay::output::Mode;
+use smithay::reexports::wayland_server::Display;
+use smithay::utils::Size;
+
+use super::render_snapshot::render_frame;
+use super::*;
+use crate::backend::{Backend, Headless, RenderResult};
+use crate::niri::{RedrawState, State};
+
+#[test]
+fn egl_headless_renders_frame() {
+    let mut f = Fixture::new();
+    f.niri_state().backend.headless().add_renderer().unwrap();
+    f.add_output(1, (1280, 720));
+
+    let output = f.niri_output(1);
+    f.niri().queue_redraw(&output);
+    f.dispatch();
+
+    let bytes = f
+        .niri_state()
+        .backend
+        .headless()
+        .copy_framebuffer(&output)
+        .unwrap();
+    assert!(!bytes.is_empty());
+    assert_eq!(bytes.len(), 1280 * 720 * 4);
+}
+
+#[test]
+fn suspend_pauses_redraw() {
+    let mut f = Fixture::new();
+    f.niri_state().backend.headless().add_renderer().unwrap();
+    f.add_output(1, (1280, 720));
+
+    let output = f.niri_output(1);
+    let state = f.niri_state();
+    state.do_action(Action::Suspend, false);
+    assert!(!state.niri.monitors_active);
+
+    // Nothing gets rendered, and no frame stays queued.
+    state.niri.queue_redraw(&output);
+    state.niri.redraw_queued_outputs(&mut state.backend);
+    let redraw_state = &state.niri.output_state[&output].redraw_state;
+    assert!(matches!(redraw_state, RedrawState::Idle));
+    let headless = state.backend.headless();
+    assert!(headless.copy_framebuffer(&output).is_err());
+
+    // Waking up renders everything again.
+    state.niri.activate_monitors(&mut state.backend);
+    state.niri.redraw_queued_outputs(&mut state.backend);
+    let headless = state.backend.headless();
+    assert!(headless.copy_framebuffer(&output).is_ok());
+}
+
+#[test]
+fn headless_with_size_adds_output() {
+    let event_loop = EventLoop::try_new().unwrap();
+    let backend = Backend::new_headless(Size::from((1920, 1080)), 120_000).unwrap();
+    let mut state = State::with_backend(
+        Rc::new(RefCell::new(Config::default())),
+        event_loop.handle(),
+        event_loop.get_signal(),
+        Display::new().unwrap(),
+        backend,
+        false,
+        false,
+    )
+    .unwrap();
+
+    let output = state.niri.global_space.outputs().next().unwrap().clone();
+    let mode = output.current_mode().unwrap();
+    assert_eq!(mode.size, Size::from((1920, 1080)));
+    assert_eq!(mode.refresh, 120_000);
+
+    let ipc_outputs = state.backend.ipc_outputs();
+    let ipc_outputs = ipc_outputs.lock().unwrap();
+    let ipc_output = ipc_outputs.values().next().unwrap();
+    assert_eq!(ipc_outputs.len(), 1);
+    assert_eq!(ipc_output.name, output.name());
+    assert_eq!(ipc_output.modes[0].refresh_rate, 120_000);
+    drop(ipc_outputs);
+
+    state.niri.queue_redraw(&output);
+    let res = state
+        .backend
+        .render(&mut state.niri, &output, Duration::ZERO);
+    assert!(res == RenderResult::Submitted);
+}
+
+#[test]
+fn rotated_output_renders_rotated() {
+    let config = r##"
+output "headless-1" {
+    transform "90"
+}
+"##;
+    let config = Config::parse_mem(config).unwrap();
+    let mut f = Fixture::with_config(config);
+    f.niri_state().backend.headless().add_renderer().unwrap();
+    f.add_output(1, (64, 32));
+
+    // The output is rotated, so it's taller than the framebuffer.
+    let output = f.niri_output(1);
+    let geo = f.niri().global_space.output_geometry(&output).unwrap();
+    assert_eq!(geo.size, Size::from((32, 64)));
+
+    // Draw a red bar along the top of the output.
+    f.add_red_bar(32);
+
+    f.niri().queue_redraw(&output);
+    f.dispatch();
+
+    let bytes = f
+        .niri_state()
+        .backend
+        .headless()
+        .copy_framebuffer(&output)
+        .unwrap();
+    assert_eq!(bytes.len(), 64 * 32 * 4);
+
+    let is_red = |x: usize, y: usize| {
+        let idx = (y * 64 + x) * 4;
+        bytes[idx..idx + 4] == [255, 0, 0, 255]
+    };
+    let column_is_red = |x: usize| (0..32).all(|y| is_red(x, y));
+
+    // Rotated by 90 degrees, the bar runs down one side of the framebuffer instead of across it.
+    assert!(column_is_red(0) != column_is_red(63));
+    assert!(!is_red(32, 0) && !is_red(32, 31));
+}
+
+#[test]
+fn resized_output_redraws_everything() {
+    let mut f = Fixture::new();
+    f.niri_state().backend.headless().add_renderer().unwrap();
+    f.add_output(1, (64, 32));
+
+    // Draw a red bar along the top of the output.
+    let (id, surface) = f.add_red_bar(64);
+
+    let output = f.niri_output(1);
+    render_frame(f.niri_state(), &output);
+
+    // Grow the output, like a resized window, and follow with the bar.
+    let mode = Mode {
+        size: Size::from((96, 48)),
+        refresh: 60_000,
+    };
+    output.change_current_state(Some(mode), None, None, None);
+    f.niri().output_resized(&output);
+    f.double_roundtrip(id);
+
+    f.paint_red_bar(id, &surface, 96);
+
+    let frame = render_frame(f.niri_state(), &output);
+    assert_eq!((frame.width, frame.height), (96, 48));
+
+    // Nothing is left over from before the resize: every pixel is painted, and the bar spans the
+    // new width.
+    let pixels: Vec<_> = frame.pixels.chunks_exact(4).collect();
+    assert!(pixels.iter().all(|pixel| pixel[3] == 255));
+    assert!(pixels[..96 * 8]
+        .iter()
+        .all(|pixel| *pixel == [255, 0, 0, 255]));
+    assert!(pixels[96 * 8..]
+        .iter()
+        .all(|pixel| *pixel != [255, 0, 0, 255]));
+}
+
+#[test]
+fn resizing_output_sends_outputs_changed_once() {
+    let mut f = Fixture::new();
+    let events = f.niri().ipc_server.as_ref().unwrap().subscribe();
+    let outputs_changed = || {
+        let mut sizes = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let Event::OutputsChanged { outputs } = event {
+                let mode = outputs["headless-1"].modes[0];
+                sizes.push((mode.width, mode.height));
+            }
+        }
+        sizes
+    };
+
+    f.add_output(1, (64, 32));
+    f.dispatch();
+    assert_eq!(outputs_changed(), [(64, 32)]);
+
+    // Nothing changed, so nothing goes out.
+    f.niri().ipc_outputs_changed = true;
+    f.dispatch();
+    assert_eq!(outputs_changed(), []);
+
+    let output = f.niri_output(1);
+    let state = f.niri_state();
+    let headless = state.backend.headless();
+    headless.resize_output(&mut state.niri, &output, Size::from((96, 48)));
+    f.dispatch();
+    assert_eq!(outputs_changed(), [(96, 48)]);
+
+    // Changes in between two refreshes go out together.
+    let state = f.niri_state();
+    let headless = state.backend.headless();
+    headless.resize_output(&mut state.niri, &output, Size::from((80, 40)));
+    headless.resize_output(&mut state.niri, &output, Size::from((128, 64)));
+    f.dispatch();
+    assert_eq!(outputs_changed(), [(128, 64)]);
+}
+
+#[test]
+fn headless_without_renderer_still_presents() {
+    let event_loop = EventLoop::try_new().unwrap();
+    let backend = Backend::Headless(Headless::without_renderer(Size::from((640, 480)), 60_000));
+    let mut state = State::with_backend(
+        Rc::new(RefCell::new(Config::default())),
+        event_loop.handle(),
+        event_loop.get_signal(),
+        Display::new().unwrap(),
+        backend,
+        false,
+        false,
+    )
+    .unwrap();
+
+    let output = state.niri.global_space.outputs().next().unwrap().clone();
+    assert_eq!(output.current_mode().unwrap().size, Size::from((640, 480)));
+
+    state.niri.queue_redraw(&output);
+    let res = state
+        .backend
+        .render(&mut state.niri, &output, Duration::ZERO);
+    assert!(res == RenderResult::Submitted);
+    assert!(state.backend.headless().copy_framebuffer(&output).is_err());
+}
+
+#[test]
+fn benchmark_times_every_frame() {
+    let mut f = Fixture::new();
+    let state = f.niri_state();
+    let report = state
+        .backend
+        .headless()
+        .benchmark(&mut state.niri, 5)
+        .unwrap();
+
+    // Without outputs, the benchmark adds one.
+    assert_eq!(report.frames, 5);
+    assert_eq!((report.width, report.height), (1920, 1080));
+    assert!(report.mean_ms > 0.);
+    assert!(report.median_ms <= report.p99_ms);
+    assert!(report.cpu_ms + report.gpu_wait_ms <= report.total_ms);
+}
+
+#[test]
+fn clear_color_fills_the_backdrop() {
+    // The transparent workspace background lets the backdrop show.
+    let config = r##"
+layout {
+    background-color "transparent"
+}
+
+debug {
+    clear-color "#ff0000"
+}
+"##;
+    let mut f = Fixture::with_config(Config::parse_mem(config).unwrap());
+    f.niri_state().backend.headless().add_renderer().unwrap();
+    f.add_output(1, (64, 32));
+
+    let output = f.niri_output(1);
+    let frame = render_frame(f.niri_state(), &output);
+    let last = frame.pixels.len() - 4;
+    assert_eq!(frame.pixels[last..], [255, 0, 0, 255]);
+}
+
+#[test]
+fn transparent_mode_leaves_empty_areas_transparent() {
+    let config = r##"
+layout {
+    background-color "transparent"
+}
+
+debug {
+    winit-transparent
+}
+"##;
+    let mut f = Fixture::with_config(Config::parse_mem(config).unwrap());
+    f.niri_state().backend.headless().add_renderer().unwrap();
+    f.add_output(1, (64, 32));
+
+    let output = f.niri_output(1);
+    let frame = render_frame(f.niri_state(), &output);
+    let last = frame.pixels.len() - 4;
+    assert_eq!(frame.pixels[last + 3], 0);
+}
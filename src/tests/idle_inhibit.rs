@@ -0,0 +1,37 @@
+use super::render_snapshot::render_frame;
+use super::*;
+
+#[test]
+fn idle_inhibitor_follows_surface() {
+    let mut f = Fixture::new();
+    f.niri_state().backend.headless().add_renderer().unwrap();
+    f.add_output(1, (64, 32));
+
+    let id = f.add_client();
+    let window = f.client(id).create_window();
+    let surface = window.surface.clone();
+    window.commit();
+    f.roundtrip(id);
+
+    let window = f.client(id).window(&surface);
+    window.attach_new_buffer();
+    window.ack_last_and_commit();
+    f.double_roundtrip(id);
+
+    f.client(id).create_idle_inhibitor(&surface);
+    f.roundtrip(id);
+
+    // Inhibitors only count once their surface is on an output.
+    let output = f.niri_output(1);
+    render_frame(f.niri_state(), &output);
+    assert!(f.niri().refresh_idle_inhibit());
+
+    // Like a client that crashed, the surface goes away without destroying the inhibitor.
+    let window = f.client(id).window(&surface);
+    window.xdg_toplevel.destroy();
+    window.xdg_surface.destroy();
+    window.surface.destroy();
+    f.double_roundtrip(id);
+
+    assert!(!f.niri().refresh_idle_inhibit());
+}
@@ -0,0 +1,32 @@
+use smithay::utils::Point;
+
+use super::*;
+
+#[test]
+fn injected_pointer_motion_moves_pointer() {
+    let mut f = Fixture::new();
+    f.add_output(1, (1920, 1080));
+    f.add_output(2, (1280, 720));
+
+    // The second output is to the right of the first one.
+    let state = f.niri_state();
+    state.inject_pointer_motion_abs(2000., 540.);
+
+    let pointer = state.niri.seat.get_pointer().unwrap();
+    assert_eq!(pointer.current_location(), Point::from((2000., 540.)));
+}
+
+#[test]
+fn injected_key_is_held_until_released() {
+    let mut f = Fixture::new();
+    f.add_output(1, (1920, 1080));
+
+    // KEY_A, which is 38 with the xkb offset.
+    let state = f.niri_state();
+    let keyboard = state.niri.seat.get_keyboard().unwrap();
+    state.inject_key(30, true);
+    assert!(keyboard.pressed_keys().contains(&38.into()));
+
+    state.inject_key(30, false);
+    assert!(keyboard.pressed_keys().is_empty());
+}
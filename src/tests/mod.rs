@@ -5,8 +5,18 @@ mod fixture;
 mod server;
 
 mod animations;
+#[cfg(not(feature = "xdp-gnome-screencast"))]
+mod capture_cast;
+mod cursor;
 mod floating;
+mod fractional_scale;
 mod fullscreen;
+mod headless;
+mod idle_inhibit;
+mod inject;
 mod layer_shell;
+mod primary_selection;
+mod render_snapshot;
 mod transactions;
+mod virtual_keyboard;
 mod window_opening;
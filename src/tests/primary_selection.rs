@@ -0,0 +1,55 @@
+use std::fs::File;
+use std::io::Read as _;
+
+use super::*;
+
+#[test]
+fn primary_selection_pastes_between_clients() {
+    let mut f = Fixture::new();
+    f.add_output(1, (1920, 1080));
+
+    // Text gets selected in a window that has the keyboard focus.
+    let source_id = f.add_client();
+    f.client(source_id).get_primary_selection_device();
+    let window = f.client(source_id).create_window();
+    let surface = window.surface.clone();
+    window.commit();
+    f.roundtrip(source_id);
+
+    let window = f.client(source_id).window(&surface);
+    window.attach_new_buffer();
+    window.ack_last_and_commit();
+    f.double_roundtrip(source_id);
+
+    let _source = f.client(source_id).set_primary_selection("selected text");
+    f.roundtrip(source_id);
+
+    // Another window opens and takes the focus, so the selection is offered there.
+    let target_id = f.add_client();
+    f.client(target_id).get_primary_selection_device();
+    let window = f.client(target_id).create_window();
+    let surface = window.surface.clone();
+    window.commit();
+    f.roundtrip(target_id);
+
+    let window = f.client(target_id).window(&surface);
+    window.attach_new_buffer();
+    window.ack_last_and_commit();
+    f.double_roundtrip(target_id);
+
+    let primary_selection = &f.client(target_id).state.primary_selection;
+    let offer = primary_selection.selection.as_ref().unwrap();
+    let mime_types = &primary_selection.offers[offer];
+    assert_eq!(mime_types, &["text/plain;charset=utf-8"]);
+
+    // Like a middle click in the window, which pastes.
+    let fd = f
+        .client(target_id)
+        .receive_primary_selection("text/plain;charset=utf-8");
+    f.roundtrip(target_id);
+    f.roundtrip(source_id);
+
+    let mut text = String::new();
+    File::from(fd).read_to_string(&mut text).unwrap();
+    assert_eq!(text, "selected text");
+}
@@ -0,0 +1,212 @@
+//! Golden-image helpers for render regression tests on the headless backend.
+//!
+//! [`render_frame`] renders one frame of an output into its offscreen framebuffer and reads it
+//! back. Frames compare against PNG snapshots with a per-channel threshold, so that rounding
+//! differences between GL drivers don't fail the tests.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use smithay::output::Output;
+
+use super::*;
+use crate::niri::State;
+use crate::utils::write_png_rgba8;
+
+/// Per-channel difference that [`Frame::assert_matches_snapshot`] tolerates by default.
+pub const DEFAULT_THRESHOLD: u8 = 2;
+
+/// One rendered frame as tightly packed RGBA rows, top row first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// Renders one frame of `output` and reads it back.
+///
+/// The headless backend needs a renderer, see
+/// [`Headless::add_renderer`](crate::backend::Headless::add_renderer).
+pub fn render_frame(state: &mut State, output: &Output) -> Frame {
+    state.niri.queue_redraw(output);
+    state.niri.redraw_queued_outputs(&mut state.backend);
+
+    let size = output.current_mode().unwrap().size;
+    let pixels = state.backend.headless().copy_framebuffer(output).unwrap();
+
+    // Unlike window framebuffers, offscreen ones render without the extra flip, so glReadPixels
+    // already returns the top row first, same as for screenshots.
+    Frame {
+        width: size.w as u32,
+        height: size.h as u32,
+        pixels,
+    }
+}
+
+/// Renders one frame of the first output and returns the hash of its pixels.
+pub fn render_and_hash(state: &mut State) -> u64 {
+    let output = state.niri.global_space.outputs().next().unwrap().clone();
+    render_frame(state, &output).hash()
+}
+
+impl Frame {
+    /// Returns an exact hash of the size and the pixels.
+    ///
+    /// This is 64-bit FNV-1a, which unlike the std hashers stays the same across Rust versions,
+    /// so hashes can be written down in tests.
+    pub fn hash(&self) -> u64 {
+        const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        const PRIME: u64 = 0x0100_0000_01b3;
+
+        let size = [self.width.to_le_bytes(), self.height.to_le_bytes()];
+        let bytes = size.iter().flatten().chain(&self.pixels);
+        bytes.fold(OFFSET_BASIS, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(PRIME)
+        })
+    }
+
+    /// Returns the largest difference of any channel of any pixel, or `None` if the sizes differ.
+    pub fn max_difference(&self, other: &Frame) -> Option<u8> {
+        if self.width != other.width || self.height != other.height {
+            return None;
+        }
+
+        let diff = self
+            .pixels
+            .iter()
+            .zip(&other.pixels)
+            .map(|(a, b)| a.abs_diff(*b))
+            .max()
+            .unwrap_or(0);
+        Some(diff)
+    }
+
+    pub fn save_snapshot(&self, path: &Path) {
+        let file = File::create(path).unwrap();
+        write_png_rgba8(BufWriter::new(file), self.width, self.height, &self.pixels).unwrap();
+    }
+
+    pub fn load_snapshot(path: &Path) -> Frame {
+        let decoder = png::Decoder::new(std::io::BufReader::new(File::open(path).unwrap()));
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size().unwrap()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!(
+            info.color_type,
+            png::ColorType::Rgba,
+            "{path:?} is not RGBA"
+        );
+        assert_eq!(
+            info.bit_depth,
+            png::BitDepth::Eight,
+            "{path:?} is not 8-bit"
+        );
+        pixels.truncate(info.buffer_size());
+
+        Frame {
+            width: info.width,
+            height: info.height,
+            pixels,
+        }
+    }
+
+    /// Checks that no channel differs from the snapshot at `path` by more than `threshold`.
+    ///
+    /// With `NIRI_UPDATE_SNAPSHOTS` set, this frame replaces the snapshot instead. A missing
+    /// snapshot fails the test, so that a golden image that never got committed doesn't pass
+    /// silently; run with `NIRI_UPDATE_SNAPSHOTS` once to record it.
+    pub fn assert_matches_snapshot(&self, path: &Path, threshold: u8) {
+        if std::env::var_os("NIRI_UPDATE_SNAPSHOTS").is_some() {
+            self.save_snapshot(path);
+            return;
+        }
+
+        assert!(
+            path.exists(),
+            "snapshot {path:?} is missing, run with NIRI_UPDATE_SNAPSHOTS=1 to record it"
+        );
+
+        let expected = Frame::load_snapshot(path);
+        match self.max_difference(&expected) {
+            Some(diff) => assert!(
+                diff <= threshold,
+                "frame differs from {path:?} by {diff}, more than {threshold}"
+            ),
+            None => panic!(
+                "frame is {}×{}, but {path:?} is {}×{}",
+                self.width, self.height, expected.width, expected.height
+            ),
+        }
+    }
+}
+
+fn row_frame(pixels: &[[u8; 4]]) -> Frame {
+    Frame {
+        width: pixels.len() as u32,
+        height: 1,
+        pixels: pixels.concat(),
+    }
+}
+
+#[test]
+fn max_difference_tolerates_rounding() {
+    let a = row_frame(&[[10, 20, 30, 255], [0, 0, 0, 255]]);
+    let b = row_frame(&[[11, 18, 30, 255], [0, 0, 0, 255]]);
+    assert_eq!(a.max_difference(&a), Some(0));
+    assert_eq!(a.max_difference(&b), Some(2));
+    assert_ne!(a.hash(), b.hash());
+
+    let c = row_frame(&[[10, 20, 30, 255]]);
+    assert_eq!(a.max_difference(&c), None);
+}
+
+#[test]
+fn render_hash_is_stable_and_sees_changes() {
+    let mut f = Fixture::new();
+    f.niri_state().backend.headless().add_renderer().unwrap();
+    f.add_output(1, (64, 32));
+
+    let empty = render_and_hash(f.niri_state());
+    assert_eq!(render_and_hash(f.niri_state()), empty);
+
+    f.add_red_bar(64);
+
+    let output = f.niri_output(1);
+    let frame = render_frame(f.niri_state(), &output);
+    assert_ne!(frame.hash(), empty);
+
+    // The bar is at the top, so the rows must come out top first.
+    assert_eq!(frame.pixels[..4], [255, 0, 0, 255]);
+    let last_row = ((frame.height - 1) * frame.width * 4) as usize;
+    assert_ne!(frame.pixels[last_row..last_row + 4], [255, 0, 0, 255]);
+}
+
+#[test]
+fn snapshot_round_trip() {
+    let mut f = Fixture::new();
+    f.niri_state().backend.headless().add_renderer().unwrap();
+    f.add_output(1, (32, 16));
+
+    let output = f.niri_output(1);
+    let frame = render_frame(f.niri_state(), &output);
+
+    let path =
+        std::env::temp_dir().join(format!("niri-render-snapshot-{}.png", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    // Without a snapshot there's nothing to match, unless this run records them.
+    if std::env::var_os("NIRI_UPDATE_SNAPSHOTS").is_none() {
+        let missing = std::panic::catch_unwind(|| {
+            frame.assert_matches_snapshot(&path, DEFAULT_THRESHOLD);
+        });
+        assert!(missing.is_err());
+    }
+
+    frame.save_snapshot(&path);
+    assert_eq!(Frame::load_snapshot(&path), frame);
+    render_frame(f.niri_state(), &output).assert_matches_snapshot(&path, DEFAULT_THRESHOLD);
+
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,68 @@
+use smithay::input::keyboard::xkb;
+use wayland_client::protocol::wl_keyboard::KeyState;
+
+use super::*;
+
+/// Returns a keymap unlike the default one, so that it's clear whose keymap the client got.
+fn dvorak_keymap() -> String {
+    let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+    let keymap = xkb::Keymap::new_from_names(
+        &context,
+        "",
+        "",
+        "us",
+        "dvorak",
+        None,
+        xkb::KEYMAP_COMPILE_NO_FLAGS,
+    )
+    .unwrap();
+    keymap.get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1)
+}
+
+#[test]
+fn virtual_keyboard_key_reaches_focused_window() {
+    let mut f = Fixture::new();
+    f.add_output(1, (1920, 1080));
+
+    let id = f.add_client();
+    let window = f.client(id).create_window();
+    let surface = window.surface.clone();
+    window.commit();
+    f.roundtrip(id);
+
+    let window = f.client(id).window(&surface);
+    window.attach_new_buffer();
+    window.ack_last_and_commit();
+    f.double_roundtrip(id);
+
+    let keyboard = &f.client(id).state.keyboard;
+    assert_eq!(keyboard.focus.as_ref(), Some(&surface));
+    let seat_keymap = keyboard.keymaps.last().unwrap().clone();
+
+    // KEY_A, sent as is: virtual keyboard keycodes are evdev codes that skip the backend.
+    let keymap = dvorak_keymap();
+    let virtual_keyboard = f.client(id).create_virtual_keyboard(&keymap);
+    virtual_keyboard.key(0, 30, KeyState::Pressed as u32);
+    virtual_keyboard.key(0, 30, KeyState::Released as u32);
+    f.double_roundtrip(id);
+
+    let keyboard = &mut f.client(id).state.keyboard;
+    assert_eq!(keyboard.keymaps.last(), Some(&keymap));
+    assert_eq!(
+        keyboard.keys,
+        [(30, KeyState::Pressed), (30, KeyState::Released)]
+    );
+    keyboard.keys.clear();
+
+    // Keys from the backend bring the seat keymap back.
+    f.niri_state().inject_key(30, true);
+    f.niri_state().inject_key(30, false);
+    f.double_roundtrip(id);
+
+    let keyboard = &f.client(id).state.keyboard;
+    assert_eq!(keyboard.keymaps.last(), Some(&seat_keymap));
+    assert_eq!(
+        keyboard.keys,
+        [(30, KeyState::Pressed), (30, KeyState::Released)]
+    );
+}
@@ -0,0 +1,258 @@
+//! Frame time overlay for the winit backend.
+//!
+//! Shows the current frame rate, the 1% low and whether frames wait for vsync, in the top-left
+//! corner of the window.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use pangocairo::cairo::{self, ImageSurface};
+use pangocairo::pango::FontDescription;
+use smithay::backend::allocator::Fourcc;
+use smithay::backend::renderer::element::Kind;
+use smithay::backend::renderer::gles::{GlesRenderer, GlesTexture};
+use smithay::utils::{Point, Transform};
+
+use crate::render_helpers::primary_gpu_texture::PrimaryGpuTextureRenderElement;
+use crate::render_helpers::texture::{TextureBuffer, TextureRenderElement};
+use crate::utils::to_physical_precise_round;
+
+const PADDING: i32 = 6;
+const FONT: &str = "monospace 12px";
+
+/// How many frame timestamps to keep; a few seconds at common refresh rates.
+const HISTORY_LEN: usize = 480;
+
+/// How often the text is redrawn, so that it stays readable.
+const TEXT_UPDATE_INTERVAL: Duration = Duration::from_millis(250);
+
+pub struct FpsOverlay {
+    /// Presentation timestamps of the most recent frames, oldest first.
+    frames: VecDeque<Instant>,
+    last_text_update: Option<Instant>,
+    text: String,
+    buffer: Option<(f64, TextureBuffer<GlesTexture>)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameStats {
+    /// Frame rate from the latest frame interval.
+    pub fps: f64,
+    /// Frame rate over the slowest 1% of the frame intervals in the history.
+    pub low_1: f64,
+}
+
+impl FpsOverlay {
+    pub fn new() -> Self {
+        Self {
+            frames: VecDeque::with_capacity(HISTORY_LEN),
+            last_text_update: None,
+            text: String::new(),
+            buffer: None,
+        }
+    }
+
+    /// Records that a frame was presented at `now`.
+    pub fn record_frame(&mut self, now: Instant) {
+        if self.frames.len() == HISTORY_LEN {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(now);
+    }
+
+    pub fn stats(&self) -> Option<FrameStats> {
+        let intervals: Vec<Duration> = self
+            .frames
+            .iter()
+            .zip(self.frames.iter().skip(1))
+            .map(|(a, b)| b.saturating_duration_since(*a))
+            .collect();
+        frame_stats(&intervals)
+    }
+
+    pub fn render(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        scale: f64,
+        vsync: bool,
+    ) -> Option<PrimaryGpuTextureRenderElement> {
+        let now = self.frames.back().copied().unwrap_or_else(Instant::now);
+        let text_is_stale = self.last_text_update.map_or(true, |last| {
+            now.saturating_duration_since(last) >= TEXT_UPDATE_INTERVAL
+        });
+        if text_is_stale {
+            self.last_text_update = Some(now);
+
+            let text = format_text(self.stats(), vsync);
+            if text != self.text {
+                self.text = text;
+                self.buffer = None;
+            }
+        }
+
+        if self.buffer.as_ref().map_or(true, |(s, _)| *s != scale) {
+            let buffer = match render(renderer, scale, &self.text) {
+                Ok(buffer) => buffer,
+                Err(err) => {
+                    warn!("error rendering the FPS overlay: {err:?}");
+                    return None;
+                }
+            };
+            self.buffer = Some((scale, buffer));
+        }
+        let (_, buffer) = self.buffer.as_ref()?;
+
+        let location = Point::from((f64::from(PADDING), f64::from(PADDING)));
+        let location = location.to_physical_precise_round(scale).to_logical(scale);
+
+        let elem = TextureRenderElement::from_texture_buffer(
+            buffer.clone(),
+            location,
+            1.,
+            None,
+            None,
+            Kind::Unspecified,
+        );
+        Some(PrimaryGpuTextureRenderElement(elem))
+    }
+}
+
+impl Default for FpsOverlay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn frame_stats(intervals: &[Duration]) -> Option<FrameStats> {
+    let last = intervals.last()?;
+    if last.is_zero() {
+        return None;
+    }
+
+    let mut sorted = intervals.to_vec();
+    sorted.sort_unstable_by(|a, b| b.cmp(a));
+    let slowest = &sorted[..(sorted.len() / 100).max(1)];
+    let slowest_mean = slowest.iter().sum::<Duration>() / slowest.len() as u32;
+
+    Some(FrameStats {
+        fps: 1. / last.as_secs_f64(),
+        low_1: 1. / slowest_mean.as_secs_f64(),
+    })
+}
+
+fn format_text(stats: Option<FrameStats>, vsync: bool) -> String {
+    let vsync = if vsync { "on" } else { "off" };
+    match stats {
+        Some(stats) => format!(
+            "{:.0} FPS\n1% low: {:.0}\nvsync: {vsync}",
+            stats.fps, stats.low_1
+        ),
+        None => format!("- FPS\n1% low: -\nvsync: {vsync}"),
+    }
+}
+
+fn render(
+    renderer: &mut GlesRenderer,
+    scale: f64,
+    text: &str,
+) -> anyhow::Result<TextureBuffer<GlesTexture>> {
+    let _span = tracy_client::span!("fps_overlay::render");
+
+    let padding: i32 = to_physical_precise_round(scale, PADDING);
+
+    let mut font = FontDescription::from_string(FONT);
+    font.set_absolute_size(to_physical_precise_round(scale, font.size()));
+
+    let surface = ImageSurface::create(cairo::Format::ARgb32, 0, 0)?;
+    let cr = cairo::Context::new(&surface)?;
+    let layout = pangocairo::functions::create_layout(&cr);
+    layout.context().set_round_glyph_positions(false);
+    layout.set_font_description(Some(&font));
+    layout.set_text(text);
+
+    let (mut width, mut height) = layout.pixel_size();
+    width += padding * 2;
+    height += padding * 2;
+
+    let surface = ImageSurface::create(cairo::Format::ARgb32, width, height)?;
+    let cr = cairo::Context::new(&surface)?;
+    cr.set_source_rgba(0., 0., 0., 0.7);
+    cr.paint()?;
+
+    cr.move_to(padding.into(), padding.into());
+    let layout = pangocairo::functions::create_layout(&cr);
+    layout.context().set_round_glyph_positions(false);
+    layout.set_font_description(Some(&font));
+    layout.set_text(text);
+
+    cr.set_source_rgb(1., 1., 1.);
+    pangocairo::functions::show_layout(&cr, &layout);
+    drop(cr);
+
+    let data = surface.take_data().unwrap();
+    let buffer = TextureBuffer::from_memory(
+        renderer,
+        &data,
+        Fourcc::Argb8888,
+        (width, height),
+        false,
+        scale,
+        Transform::Normal,
+        Vec::new(),
+    )?;
+
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn frame_stats_need_an_interval() {
+        assert_eq!(frame_stats(&[]), None);
+        assert_eq!(frame_stats(&[Duration::ZERO]), None);
+    }
+
+    #[test]
+    fn frame_stats_fps_and_one_percent_low() {
+        // 198 frames at 100 FPS and two 50 ms hitches.
+        let mut intervals = vec![ms(10); 198];
+        intervals.insert(50, ms(50));
+        intervals.insert(150, ms(50));
+
+        let stats = frame_stats(&intervals).unwrap();
+        assert!((stats.fps - 100.).abs() < 1e-9);
+        assert!((stats.low_1 - 20.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn overlay_keeps_a_bounded_history() {
+        let mut overlay = FpsOverlay::new();
+        let start = Instant::now();
+        for i in 0..HISTORY_LEN as u64 * 2 {
+            overlay.record_frame(start + ms(16) * i as u32);
+        }
+        assert_eq!(overlay.frames.len(), HISTORY_LEN);
+
+        let stats = overlay.stats().unwrap();
+        assert!((stats.fps - 62.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn text_shows_vsync_state() {
+        assert_eq!(format_text(None, true), "- FPS\n1% low: -\nvsync: on");
+        let stats = FrameStats {
+            fps: 59.6,
+            low_1: 30.2,
+        };
+        assert_eq!(
+            format_text(Some(stats), false),
+            "60 FPS\n1% low: 30\nvsync: off"
+        );
+    }
+}
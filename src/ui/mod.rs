@@ -1,5 +1,6 @@
 pub mod config_error_notification;
 pub mod exit_confirm_dialog;
+pub mod fps_overlay;
 pub mod hotkey_overlay;
 pub mod mru;
 pub mod screen_transition;
@@ -382,7 +382,7 @@ impl Thumbnail {
         .unwrap_or_default();
 
         let has_border_shader = BorderRenderElement::has_shader(renderer);
-        let clip_shader = ClippedSurfaceRenderElement::shader(renderer).cloned();
+        let clip_shader = ClippedSurfaceRenderElement::shader(renderer);
         let geo = Rectangle::from_size(self.size.to_f64());
         // FIXME: deduplicate code with Tile::render_inner()
         let elems = elems.map(move |elem| match elem {
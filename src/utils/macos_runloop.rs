@@ -1,71 +1,423 @@
 // CFRunLoop integration for macOS
-// This module uses CFRunLoopTimer to wake up calloop periodically
-// since we can't easily get the underlying kqueue fd from calloop.
+// The CFRunLoop sleeps until calloop's kqueue fd (through a CFFileDescriptor), the next calloop
+// timer, AppKit or the display link wake it up, so a static screen costs no CPU. With vsync,
+// buffer swaps pace the frames, and the display link or a timer only throttle rendering when
+// vsync is unavailable.
 
-use std::time::Duration;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::ffi::c_void;
+use std::os::fd::{AsFd, AsRawFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
-use core_foundation::runloop::{
-    CFRunLoop, kCFRunLoopDefaultMode, CFRunLoopRunInMode,
-    kCFRunLoopRunTimedOut, kCFRunLoopRunHandledSource,
-};
+use core_foundation::filedescriptor::{kCFFileDescriptorReadCallBack, CFFileDescriptor};
+use core_foundation::runloop::{kCFRunLoopDefaultMode, CFRunLoop, CFRunLoopRunInMode};
+use core_foundation_sys::base::CFOptionFlags;
+use core_foundation_sys::filedescriptor::{CFFileDescriptorEnableCallBacks, CFFileDescriptorRef};
+use core_foundation_sys::runloop::{CFRunLoopGetMain, CFRunLoopRef, CFRunLoopStop};
 
 use calloop::EventLoop;
-use crate::niri::State;
+use crate::backend::winit::DisplayTiming;
+use crate::niri::{RedrawState, State};
+use crate::utils::get_monotonic_time;
+
+/// Sleep when nothing is due, the same as `CFRunLoopRun()` uses.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10_000_000_000);
+
+/// Deadlines of the calloop timers, earliest first.
+///
+/// calloop timers don't show up on its fd, so the run loop wakes up for them on its own.
+static TIMER_DEADLINES: Mutex<BinaryHeap<Reverse<Instant>>> = Mutex::new(BinaryHeap::new());
+
+/// Makes the run loop wake up at `deadline` to dispatch the calloop timer due then.
+///
+/// Timers inserted through [`LoopHandleExt`](super::LoopHandleExt) call this on their own.
+/// Removed timers stay in here and cost one extra wakeup.
+pub fn wake_at(deadline: Instant) {
+    TIMER_DEADLINES.lock().unwrap().push(Reverse(deadline));
+}
+
+/// Forgets the timers that the dispatch at `dispatched_at` fired and returns the next deadline.
+fn next_timer_deadline(dispatched_at: Instant) -> Option<Instant> {
+    let mut deadlines = TIMER_DEADLINES.lock().unwrap();
+    while deadlines
+        .peek()
+        .is_some_and(|Reverse(deadline)| *deadline <= dispatched_at)
+    {
+        deadlines.pop();
+    }
+    deadlines.peek().map(|Reverse(deadline)| *deadline)
+}
 
 /// Run the event loop using CFRunLoop on macOS
-/// This properly integrates calloop with the native macOS run loop by using
-/// a polling approach where CFRunLoop handles the Cocoa events and we periodically
-/// dispatch calloop.
+/// Cocoa events are handled by the CFRunLoop, calloop is dispatched whenever its fd becomes
+/// readable, and rendering waits for the next display refresh. Returns once
+/// [`Niri::stop`](crate::niri::Niri::stop) was called.
 pub fn run_with_cfrunloop(
     event_loop: &mut EventLoop<'static, State>,
     state: &mut State,
 ) -> Result<(), Box<dyn std::error::Error>> {
     tracing::info!("CFRunLoop integration initialized");
-    
+
     // Safety: we are on macOS where objc is available.
     use objc::rc::autoreleasepool;
-    
-    let mut last_frame_time = std::time::Instant::now();
-    let target_frame_time = Duration::from_micros(16666); // ~60 FPS (safe mode)
+
+    // Keep these alive for the whole loop, dropping them removes the wakeups.
+    let _calloop_source = add_calloop_fd_source(event_loop)?;
+    let display_link = DisplayLink::new();
+    if display_link.is_none() {
+        tracing::warn!("error creating display link, pacing rendering with a timer");
+    }
+
+    let mut last_frame_time = Instant::now();
 
     loop {
-        autoreleasepool(|| {
-            // 1. Pump Winit events (Input) - Always run this to catch input instantly
+        let stopped = autoreleasepool(|| {
+            // Pace rendering to the refresh rate of the display showing the window.
+            let target_frame_time = match &state.backend {
+                crate::backend::Backend::Winit(winit) => winit.refresh_interval(),
+                crate::backend::Backend::Headless(_) => Duration::from_micros(16_666),
+            };
+
+            // 1. Pump Winit events (Input) - Always run this to catch input instantly, and pick
+            // up what macOS apps copied before clients ask for the clipboard.
             if let crate::backend::Backend::Winit(winit) = &mut state.backend {
                 winit.pump();
+                winit.sync_pasteboard(&mut state.niri);
+                winit.sync_outputs(&mut state.niri);
+            }
+
+            // Presentation feedback reports the refreshes that the display link saw.
+            if let (crate::backend::Backend::Winit(winit), Some(display_link)) =
+                (&mut state.backend, &display_link)
+            {
+                winit.set_display_timing(display_link.timing());
             }
 
             // 2. Dispatch Wayland events
-            if let Err(e) = event_loop.dispatch(Duration::ZERO, state) {
+            let dispatched_at = Instant::now();
+            if let Err(e) = event_loop.dispatch(Some(Duration::ZERO), state) {
                 tracing::error!("Calloop dispatch error: {:?}", e);
             }
 
-            // 3. Render - Throttle to 60 FPS
-            let now = std::time::Instant::now();
-            let elapsed = now.duration_since(last_frame_time);
+            // Closing the window, quit actions and signals all land here through the dispatch.
+            if state.niri.stopping {
+                return true;
+            }
 
-            if elapsed >= target_frame_time {
-                // tracing::info!("tick - render");
-                // tracing::info!("tick - render");
+            // 3. Render - Throttle to the display refresh rate
+            let now = Instant::now();
+            // With VRR, a frame goes out as soon as it's ready, the display ramps its refresh
+            // rate to match. Only the maximum rate still limits it.
+            let vrr = match &state.backend {
+                crate::backend::Backend::Winit(winit) => winit.is_vrr_enabled(),
+                crate::backend::Backend::Headless(_) => false,
+            };
+            // With vsync, buffer swaps block until the next display refresh and do the pacing.
+            // Covered windows don't block, so they keep waiting for the display link.
+            let vsync = match &state.backend {
+                crate::backend::Backend::Winit(winit) => winit.is_vsync_active(),
+                crate::backend::Backend::Headless(_) => false,
+            };
+            let frame_due = match &display_link {
+                _ if vsync => true,
+                Some(display_link) if !vrr => display_link.take_frame_due(),
+                _ => now.duration_since(last_frame_time) >= target_frame_time,
+            };
+
+            let redraw_pending = is_redraw_pending(state);
+            if !redraw_pending || frame_due {
+                // With nothing to redraw, this only refreshes and flushes the clients.
                 state.refresh_and_flush_clients();
-                last_frame_time = now;
+                if redraw_pending {
+                    last_frame_time = now;
+                }
             } else {
-               // tracing::trace!("tick - skip render");
+                // Send replies out right away, the frame comes with the next refresh.
+                state.niri.display_handle.flush_clients().unwrap();
+            }
+
+            // Rendering may have queued the next frame, e.g. for an animation. While the displays
+            // sleep or are powered off nothing gets rendered, so there's no frame to wait for.
+            let paused = match &state.backend {
+                crate::backend::Backend::Winit(winit) => winit.is_rendering_paused(),
+                crate::backend::Backend::Headless(_) => false,
+            };
+            let redraw_pending = !paused && is_redraw_pending(state);
+            // With vsync, the display link only keeps the refresh timing up to date.
+            if let Some(display_link) = &display_link {
+                display_link.set_running(redraw_pending && !vrr);
             }
 
-            // 4. Smart Sleep
-            // Calculate time until next *Render* frame
-            let next_render_due = last_frame_time + target_frame_time;
-            let sleep_duration = next_render_due.saturating_duration_since(std::time::Instant::now());
+            // 4. Sleep until calloop, AppKit or the display link have something for us
+            let timeout = if paused {
+                // Waking up comes with input or a notification through AppKit.
+                IDLE_TIMEOUT
+            } else if redraw_pending && vsync {
+                Duration::ZERO
+            } else if redraw_pending {
+                let next_render_due = last_frame_time + target_frame_time;
+                match &display_link {
+                    // The display link wakes us up, the timeout only guards against stalls.
+                    Some(_) if !vrr => target_frame_time * 2,
+                    _ => next_render_due.saturating_duration_since(Instant::now()),
+                }
+            } else {
+                IDLE_TIMEOUT
+            };
+            // Timers like key repeat keep running while the displays sleep.
+            let timeout = match next_timer_deadline(dispatched_at) {
+                Some(deadline) => timeout.min(deadline.saturating_duration_since(Instant::now())),
+                None => timeout,
+            };
 
-            // Render-throttled sleep (16ms)
-            // We use standard blocking mode to prevent high CPU usage/leaks.
-            // Latency is capped at ~16ms.
             unsafe {
-                 CFRunLoopRunInMode(kCFRunLoopDefaultMode, sleep_duration.as_secs_f64(), false as u8);
+                CFRunLoopRunInMode(kCFRunLoopDefaultMode, timeout.as_secs_f64(), true as u8);
             }
+
+            false
         });
+
+        if stopped {
+            break;
+        }
+    }
+
+    tracing::info!("quitting the CFRunLoop integration");
+
+    // Send out what is still queued, like the replies to the client that asked us to quit. The
+    // windows and GL contexts go away with the backend when the state is dropped.
+    if let Err(err) = state.niri.display_handle.flush_clients() {
+        tracing::warn!("error flushing clients before quitting: {err:?}");
     }
 
+    // A logout waits for this before going on.
+    crate::backend::appkit::reply_to_quit();
+
     Ok(())
 }
+
+fn is_redraw_pending(state: &State) -> bool {
+    state.niri.output_state.values().any(|output_state| {
+        matches!(
+            output_state.redraw_state,
+            RedrawState::Queued | RedrawState::WaitingForEstimatedVBlankAndQueued(_)
+        )
+    })
+}
+
+/// Wakes up the run loop whenever calloop's kqueue fd becomes readable.
+fn add_calloop_fd_source(
+    event_loop: &EventLoop<'static, State>,
+) -> Result<CFFileDescriptor, Box<dyn std::error::Error>> {
+    extern "C" fn on_readable(fd: CFFileDescriptorRef, _types: CFOptionFlags, _info: *mut c_void) {
+        // Callbacks are one-shot, so ask for the next one. The run loop returns after handling
+        // this source and calloop gets dispatched.
+        unsafe { CFFileDescriptorEnableCallBacks(fd, kCFFileDescriptorReadCallBack) };
+    }
+
+    let fd = event_loop.as_fd().as_raw_fd();
+    let cf_fd = CFFileDescriptor::new(fd, false, on_readable, None)
+        .ok_or("error creating CFFileDescriptor for the calloop fd")?;
+    cf_fd.enable_callbacks(kCFFileDescriptorReadCallBack);
+
+    let source = cf_fd
+        .to_run_loop_source(0)
+        .ok_or("error creating run loop source for the calloop fd")?;
+    CFRunLoop::get_current().add_source(&source, unsafe { kCFRunLoopDefaultMode });
+
+    Ok(cf_fd)
+}
+
+type CVDisplayLinkRef = *mut c_void;
+type CVReturn = i32;
+
+/// `CVTimeStamp` from CoreVideo.
+#[repr(C)]
+struct CVTimeStamp {
+    version: u32,
+    video_time_scale: i32,
+    video_time: i64,
+    host_time: u64,
+    rate_scalar: f64,
+    video_refresh_period: i64,
+    smpte_time: [u8; 24],
+    flags: u64,
+    reserved: u64,
+}
+
+const K_CV_TIME_STAMP_VIDEO_TIME_VALID: u64 = 1 << 0;
+const K_CV_TIME_STAMP_HOST_TIME_VALID: u64 = 1 << 1;
+
+#[repr(C)]
+#[derive(Default)]
+struct MachTimebaseInfo {
+    numer: u32,
+    denom: u32,
+}
+
+extern "C" {
+    fn mach_absolute_time() -> u64;
+    fn mach_timebase_info(info: *mut MachTimebaseInfo) -> i32;
+}
+type CVDisplayLinkOutputCallback = extern "C" fn(
+    link: CVDisplayLinkRef,
+    now: *const CVTimeStamp,
+    output_time: *const CVTimeStamp,
+    flags_in: u64,
+    flags_out: *mut u64,
+    user_info: *mut c_void,
+) -> CVReturn;
+
+#[link(name = "CoreVideo", kind = "framework")]
+extern "C" {
+    fn CVDisplayLinkCreateWithActiveCGDisplays(link_out: *mut CVDisplayLinkRef) -> CVReturn;
+    fn CVDisplayLinkSetOutputCallback(
+        link: CVDisplayLinkRef,
+        callback: CVDisplayLinkOutputCallback,
+        user_info: *mut c_void,
+    ) -> CVReturn;
+    fn CVDisplayLinkStart(link: CVDisplayLinkRef) -> CVReturn;
+    fn CVDisplayLinkStop(link: CVDisplayLinkRef) -> CVReturn;
+    fn CVDisplayLinkRelease(link: CVDisplayLinkRef);
+}
+
+/// State shared with the display link thread.
+struct DisplayLinkShared {
+    frame_due: AtomicBool,
+    /// Refresh that the next frame shows up with, as of the last tick.
+    timing: Mutex<Option<DisplayTiming>>,
+    run_loop: CFRunLoopRef,
+}
+
+// CFRunLoopStop() can be called from any thread.
+unsafe impl Send for DisplayLinkShared {}
+unsafe impl Sync for DisplayLinkShared {}
+
+/// A `CVDisplayLink` that wakes up the main run loop on every display refresh.
+///
+/// It's only kept running while a redraw is pending.
+struct DisplayLink {
+    link: CVDisplayLinkRef,
+    shared: *mut DisplayLinkShared,
+    running: std::cell::Cell<bool>,
+}
+
+impl DisplayLink {
+    fn new() -> Option<Self> {
+        extern "C" fn on_refresh(
+            _link: CVDisplayLinkRef,
+            _now: *const CVTimeStamp,
+            output_time: *const CVTimeStamp,
+            _flags_in: u64,
+            _flags_out: *mut u64,
+            user_info: *mut c_void,
+        ) -> CVReturn {
+            let shared = unsafe { &*(user_info as *const DisplayLinkShared) };
+            if let Some(timing) = unsafe { output_time.as_ref() }.and_then(display_timing) {
+                *shared.timing.lock().unwrap() = Some(timing);
+            }
+            shared.frame_due.store(true, Ordering::Release);
+            // If the run loop isn't sleeping right now, its next run returns right away.
+            unsafe { CFRunLoopStop(shared.run_loop) };
+            0
+        }
+
+        unsafe {
+            let mut link = std::ptr::null_mut();
+            if CVDisplayLinkCreateWithActiveCGDisplays(&mut link) != 0 || link.is_null() {
+                return None;
+            }
+
+            let shared = Box::into_raw(Box::new(DisplayLinkShared {
+                frame_due: AtomicBool::new(false),
+                timing: Mutex::new(None),
+                run_loop: CFRunLoopGetMain(),
+            }));
+            if CVDisplayLinkSetOutputCallback(link, on_refresh, shared.cast()) != 0 {
+                CVDisplayLinkRelease(link);
+                drop(Box::from_raw(shared));
+                return None;
+            }
+
+            Some(Self {
+                link,
+                shared,
+                running: std::cell::Cell::new(false),
+            })
+        }
+    }
+
+    /// Returns whether the display refreshed since the last call.
+    fn take_frame_due(&self) -> bool {
+        let shared = unsafe { &*self.shared };
+        shared.frame_due.swap(false, Ordering::AcqRel)
+    }
+
+    /// Returns the refresh timing as of the last display refresh, if the link ticked yet.
+    fn timing(&self) -> Option<DisplayTiming> {
+        let shared = unsafe { &*self.shared };
+        *shared.timing.lock().unwrap()
+    }
+
+    fn set_running(&self, running: bool) {
+        if self.running.get() == running {
+            return;
+        }
+
+        unsafe {
+            if running {
+                CVDisplayLinkStart(self.link);
+            } else {
+                CVDisplayLinkStop(self.link);
+            }
+        }
+        self.running.set(running);
+    }
+}
+
+impl Drop for DisplayLink {
+    fn drop(&mut self) {
+        unsafe {
+            // Stopping waits for the callback, so nothing uses the shared state after this.
+            CVDisplayLinkStop(self.link);
+            CVDisplayLinkRelease(self.link);
+            drop(Box::from_raw(self.shared));
+        }
+    }
+}
+
+/// Converts a display link time stamp to the monotonic clock of presentation feedback.
+fn display_timing(stamp: &CVTimeStamp) -> Option<DisplayTiming> {
+    let valid = K_CV_TIME_STAMP_VIDEO_TIME_VALID | K_CV_TIME_STAMP_HOST_TIME_VALID;
+    if stamp.flags & valid != valid
+        || stamp.video_refresh_period <= 0
+        || stamp.video_time_scale <= 0
+    {
+        return None;
+    }
+
+    let mut timebase = MachTimebaseInfo::default();
+    if unsafe { mach_timebase_info(&mut timebase) } != 0 || timebase.denom == 0 {
+        return None;
+    }
+    let host_to_nanos =
+        |host: u64| u128::from(host) * u128::from(timebase.numer) / u128::from(timebase.denom);
+
+    // Host time counts mach_absolute_time(), which stops during sleep unlike the monotonic
+    // clock, so go through the current time of both.
+    let host_now = host_to_nanos(unsafe { mach_absolute_time() }) as i128;
+    let host_vblank = host_to_nanos(stamp.host_time) as i128;
+    let now = get_monotonic_time().as_nanos() as i128;
+    let vblank = u64::try_from(now + host_vblank - host_now).ok()?;
+
+    let refresh = stamp.video_refresh_period as u128 * 1_000_000_000
+        / stamp.video_time_scale as u128;
+    Some(DisplayTiming {
+        vblank: Duration::from_nanos(vblank),
+        seq: (stamp.video_time / stamp.video_refresh_period) as u64,
+        refresh: Duration::from_nanos(refresh as u64),
+    })
+}
@@ -6,10 +6,12 @@ use std::os::unix::prelude::OsStrExt;
 use std::path::{Path, PathBuf};
 use std::ptr::null_mut;
 use std::sync::atomic::AtomicBool;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use anyhow::{ensure, Context};
 use bitflags::bitflags;
+use calloop::timer::{TimeoutAction, Timer};
+use calloop::{InsertError, LoopHandle, RegistrationToken};
 use directories::UserDirs;
 use git_version::git_version;
 use niri_config::{Config, OutputName};
@@ -113,6 +115,49 @@ pub fn get_monotonic_time() -> Duration {
     Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
 }
 
+/// Inserts calloop timers so that they wake up the event loop.
+///
+/// On macOS, the CFRunLoop only wakes up for calloop's fd, which timers don't show up on, so it
+/// needs to know their deadlines, see [`macos_runloop::wake_at`].
+pub trait LoopHandleExt<'l, Data> {
+    fn insert_timer<F>(
+        &self,
+        timer: Timer,
+        callback: F,
+    ) -> Result<RegistrationToken, InsertError<Timer>>
+    where
+        F: FnMut(Instant, &mut (), &mut Data) -> TimeoutAction + 'l;
+}
+
+impl<'l, Data> LoopHandleExt<'l, Data> for LoopHandle<'l, Data> {
+    fn insert_timer<F>(
+        &self,
+        timer: Timer,
+        mut callback: F,
+    ) -> Result<RegistrationToken, InsertError<Timer>>
+    where
+        F: FnMut(Instant, &mut (), &mut Data) -> TimeoutAction + 'l,
+    {
+        #[cfg(target_os = "macos")]
+        if let Some(deadline) = timer.current_deadline() {
+            macos_runloop::wake_at(deadline);
+        }
+
+        self.insert_source(timer, move |deadline, meta, data| {
+            let action = callback(deadline, meta, data);
+            #[cfg(target_os = "macos")]
+            match &action {
+                TimeoutAction::ToDuration(duration) => {
+                    macos_runloop::wake_at(Instant::now() + *duration)
+                }
+                TimeoutAction::ToInstant(instant) => macos_runloop::wake_at(*instant),
+                TimeoutAction::Drop => (),
+            }
+            action
+        })
+    }
+}
+
 pub fn center(rect: Rectangle<i32, Logical>) -> Point<i32, Logical> {
     rect.loc + rect.size.downscale(2).to_point()
 }
@@ -3,6 +3,9 @@
 //! This module follows logic and tests from Mutter:
 //! <https://gitlab.gnome.org/GNOME/mutter/-/blob/gnome-46/src/backends/meta-monitor.c>
 
+use std::cell::Cell;
+
+use smithay::output::Output;
 use smithay::utils::{Physical, Raw, Size};
 
 const MIN_SCALE: i32 = 1;
@@ -14,6 +17,23 @@ const MOBILE_TARGET_DPI: f64 = 135.;
 const LARGE_TARGET_DPI: f64 = 110.;
 const LARGE_MIN_SIZE_INCHES: f64 = 20.;
 
+/// Scale that the platform recommends for an output.
+///
+/// Backends that know it, like winit with the display backing scale factor, store this in the
+/// output user data. It's used instead of guessing when the config doesn't set a scale.
+pub struct PreferredScale(pub Cell<f64>);
+
+/// Returns the scale for an output that has none set in the config.
+pub fn default_output_scale(output: &Output) -> f64 {
+    if let Some(preferred) = output.user_data().get::<PreferredScale>() {
+        return preferred.0.get();
+    }
+
+    let size_mm = output.physical_properties().size;
+    let resolution = output.current_mode().unwrap().size;
+    guess_monitor_scale(size_mm, resolution)
+}
+
 /// Calculates the ideal scale for a monitor.
 pub fn guess_monitor_scale(size_mm: Size<i32, Raw>, resolution: Size<i32, Physical>) -> f64 {
     if size_mm.w == 0 || size_mm.h == 0 {
@@ -12,6 +12,8 @@ use calloop::LoopHandle;
 use smithay::reexports::wayland_server::Client;
 use smithay::wayland::compositor::{Blocker, BlockerState};
 
+use crate::utils::LoopHandleExt as _;
+
 /// Default time limit, after which the transaction completes.
 ///
 /// Serves to avoid hanging when a client fails to respond to a configure promptly.
@@ -89,7 +91,7 @@ impl Transaction {
             let timer = Timer::from_deadline(deadline);
             let inner = Arc::downgrade(&self.inner);
             let token = event_loop
-                .insert_source(timer, move |_, _, _| {
+                .insert_timer(timer, move |_, _, _| {
                     let _span = trace_span!("deadline timer", transaction = ?Weak::as_ptr(&inner))
                         .entered();
 
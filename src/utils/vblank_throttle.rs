@@ -9,6 +9,7 @@ use calloop::timer::{TimeoutAction, Timer};
 use calloop::{LoopHandle, RegistrationToken};
 
 use crate::niri::State;
+use crate::utils::LoopHandleExt as _;
 
 #[derive(Debug)]
 pub struct VBlankThrottle {
@@ -55,7 +56,7 @@ impl VBlankThrottle {
                 let remaining = refresh - passed;
                 let token = self
                     .event_loop
-                    .insert_source(Timer::from_duration(remaining), move |_, _, state| {
+                    .insert_timer(Timer::from_duration(remaining), move |_, _, state| {
                         call_vblank(state);
                         TimeoutAction::Drop
                     })
@@ -0,0 +1,246 @@
+//! Feeds winit events through the event handler of the winit backend.
+//!
+//! winit only runs on the main thread on macOS, while libtest runs every test on a thread of its
+//! own, so this test target comes with its own `main()`. winit also opens real windows, which
+//! needs a GUI session, and a single event loop per process, so all tests share one compositor
+//! and leave it as they found it.
+
+#[cfg(target_os = "macos")]
+fn main() {
+    macos::main();
+}
+
+#[cfg(not(target_os = "macos"))]
+fn main() {}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::Instant;
+
+    use calloop::EventLoop;
+    use niri::backend::winit::{handle_event, Winit, WinitEventMsg};
+    use niri::backend::Backend;
+    use niri::input_shim::{Device, SendEventsMode};
+    use niri::niri::{RedrawState, State};
+    use niri_config::Config;
+    use smithay::input::keyboard::Keycode;
+    use smithay::reexports::wayland_server::Display;
+    use smithay::utils::{Logical, Point};
+    use winit::dpi::PhysicalPosition;
+    use winit::event::{ElementState, MouseButton};
+    use winit::keyboard::{KeyCode, PhysicalKey};
+    use winit::window::WindowId;
+
+    type Test = fn(&mut State);
+
+    macro_rules! tests {
+        ($($test:ident),* $(,)?) => {
+            &[$((stringify!($test), $test as Test)),*]
+        };
+    }
+
+    const TESTS: &[(&str, Test)] = tests![
+        key_press_reaches_the_keyboard,
+        cursor_moved_moves_the_pointer,
+        typing_holds_back_the_trackpad,
+        disabled_trackpad_sends_nothing,
+        uncovered_window_redraws,
+        click_lands_where_the_motion_went,
+    ];
+
+    pub fn main() {
+        let event_loop = EventLoop::try_new().unwrap();
+        let config = Rc::new(RefCell::new(Config::default()));
+        let winit = match Winit::new(config.clone(), event_loop.handle()) {
+            Ok(winit) => winit,
+            Err(err) => {
+                println!("skipping the winit tests, error opening the windows: {err}");
+                return;
+            }
+        };
+        let mut state = State::with_backend(
+            config,
+            event_loop.handle(),
+            event_loop.get_signal(),
+            Display::new().unwrap(),
+            Backend::Winit(winit),
+            false,
+            false,
+        )
+        .unwrap();
+
+        println!("\nrunning {} tests", TESTS.len());
+        for (name, test) in TESTS {
+            test(&mut state);
+            println!("test {name} ... ok");
+        }
+    }
+
+    /// Returns the window of the first output.
+    fn window_id(state: &mut State) -> WindowId {
+        let output = state.niri.global_space.outputs().next().unwrap().clone();
+        state.backend.winit().output_window_id(&output).unwrap()
+    }
+
+    fn pointer_location(state: &State) -> Point<f64, Logical> {
+        state.niri.seat.get_pointer().unwrap().current_location()
+    }
+
+    fn assert_pointer_at(state: &State, expected: Point<f64, Logical>) {
+        let location = pointer_location(state);
+        let diff = location - expected;
+        assert!(
+            diff.x.abs() < 0.01 && diff.y.abs() < 0.01,
+            "{location:?} != {expected:?}"
+        );
+    }
+
+    fn key(state: &mut State, code: KeyCode, key_state: ElementState) {
+        let msg = WinitEventMsg::KeyboardInput {
+            key: PhysicalKey::Code(code),
+            state: key_state,
+            time: Instant::now(),
+            event_time: None,
+        };
+        handle_event(state, msg);
+    }
+
+    fn cursor_moved(state: &mut State, x: f64, y: f64) {
+        moved(state, x, y, 0);
+    }
+
+    fn trackpad_moved(state: &mut State, x: f64, y: f64) {
+        moved(state, x, y, 1);
+    }
+
+    fn moved(state: &mut State, x: f64, y: f64, touch_count: u32) {
+        let msg = WinitEventMsg::CursorMoved {
+            window_id: window_id(state),
+            position: PhysicalPosition::new(x, y),
+            touch_count,
+            event_time: None,
+            tablet: None,
+        };
+        handle_event(state, msg);
+    }
+
+    /// Clicks at `x`, `y` in physical pixels from the top left of the first window.
+    fn click(state: &mut State, x: f64, y: f64, button_state: ElementState) {
+        // AppKit reports clicks in points from the bottom left of the window.
+        let output = state.niri.global_space.outputs().next().unwrap().clone();
+        let height = f64::from(output.current_mode().unwrap().size.h);
+        let scale = output.current_scale().fractional_scale();
+        let msg = WinitEventMsg::MouseButton {
+            window_id: window_id(state),
+            button: MouseButton::Left,
+            state: button_state,
+            location: Some((x / scale, (height - y) / scale)),
+            touch_count: 0,
+            event_time: None,
+            tablet: None,
+        };
+        handle_event(state, msg);
+    }
+
+    fn key_press_reaches_the_keyboard(state: &mut State) {
+        // evdev KEY_A, plus 8 for xkb.
+        let keycode = Keycode::new(30 + 8);
+        let keyboard = state.niri.seat.get_keyboard().unwrap();
+
+        key(state, KeyCode::KeyA, ElementState::Pressed);
+        assert!(keyboard.pressed_keys().contains(&keycode));
+
+        key(state, KeyCode::KeyA, ElementState::Released);
+        assert!(!keyboard.pressed_keys().contains(&keycode));
+    }
+
+    fn cursor_moved_moves_the_pointer(state: &mut State) {
+        cursor_moved(state, 10., 10.);
+        let start = pointer_location(state);
+
+        cursor_moved(state, 50., 30.);
+        let end = pointer_location(state);
+        assert!(end.x > start.x, "{start:?} -> {end:?}");
+        assert!(end.y > start.y, "{start:?} -> {end:?}");
+    }
+
+    fn typing_holds_back_the_trackpad(state: &mut State) {
+        let mut trackpad = Device::TRACKPAD;
+        trackpad.config_dwt_set_enabled(true).unwrap();
+        trackpad_moved(state, 10., 10.);
+
+        // Modifiers don't count as typing, so that Cmd+click works.
+        key(state, KeyCode::ShiftLeft, ElementState::Pressed);
+        let start = pointer_location(state);
+        trackpad_moved(state, 20., 20.);
+        assert_ne!(pointer_location(state), start);
+        key(state, KeyCode::ShiftLeft, ElementState::Released);
+
+        key(state, KeyCode::KeyA, ElementState::Pressed);
+        key(state, KeyCode::KeyA, ElementState::Released);
+        let start = pointer_location(state);
+        trackpad_moved(state, 50., 30.);
+        assert_eq!(pointer_location(state), start);
+
+        // A mouse keeps working.
+        cursor_moved(state, 50., 30.);
+        assert_ne!(pointer_location(state), start);
+
+        trackpad.config_dwt_set_enabled(false).unwrap();
+    }
+
+    fn disabled_trackpad_sends_nothing(state: &mut State) {
+        let mut trackpad = Device::TRACKPAD;
+        trackpad
+            .config_send_events_set_mode(SendEventsMode::DISABLED)
+            .unwrap();
+        cursor_moved(state, 10., 10.);
+
+        let start = pointer_location(state);
+        trackpad_moved(state, 50., 30.);
+        assert_eq!(pointer_location(state), start);
+
+        // The mouse is a device of its own.
+        cursor_moved(state, 50., 30.);
+        assert_ne!(pointer_location(state), start);
+
+        trackpad
+            .config_send_events_set_mode(SendEventsMode::ENABLED)
+            .unwrap();
+        let start = pointer_location(state);
+        trackpad_moved(state, 10., 10.);
+        assert_ne!(pointer_location(state), start);
+    }
+
+    fn uncovered_window_redraws(state: &mut State) {
+        let output = state.niri.global_space.outputs().next().unwrap().clone();
+        let window_id = window_id(state);
+        state.niri.redraw_queued_outputs(&mut state.backend);
+
+        handle_event(state, WinitEventMsg::Covered { window_id });
+        handle_event(state, WinitEventMsg::Uncovered { window_id });
+        let queued = match &state.niri.output_state[&output].redraw_state {
+            RedrawState::Queued | RedrawState::WaitingForEstimatedVBlankAndQueued(_) => true,
+            RedrawState::WaitingForVBlank { redraw_needed } => *redraw_needed,
+            _ => false,
+        };
+        assert!(queued);
+    }
+
+    fn click_lands_where_the_motion_went(state: &mut State) {
+        // Motion and click in the same batch.
+        cursor_moved(state, 200., 100.);
+        let moved = pointer_location(state);
+        click(state, 200., 100., ElementState::Pressed);
+        assert_pointer_at(state, moved);
+        click(state, 200., 100., ElementState::Released);
+
+        // The motion up to the click didn't come.
+        cursor_moved(state, 10., 10.);
+        click(state, 200., 100., ElementState::Pressed);
+        assert_pointer_at(state, moved);
+        click(state, 200., 100., ElementState::Released);
+    }
+}